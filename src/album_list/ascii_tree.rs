@@ -5,10 +5,11 @@ use ascii_tree::Tree;
 use ascii_tree::Tree::{Leaf, Node};
 use colored::Colorize;
 
+use crate::album_list::AlbumSort;
 use crate::model::album::Album;
 use crate::model::album::Kind;
 
-pub fn build_tree(albums: &Vec<Album>) -> Tree {
+pub fn build_tree(albums: &[Album], asset_counts: &HashMap<i32, i64>, sort: AlbumSort) -> Tree {
     let root = match albums.iter().find(|a| a.kind == Kind::Root) {
         None => panic!("Library does not contain a root album!"),
         Some(album) => album
@@ -18,25 +19,79 @@ pub fn build_tree(albums: &Vec<Album>) -> Tree {
     albums.iter().for_each(|a| {
         albums_by_parent
             .entry(a.parent_id.unwrap_or(-1))
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(a);
     });
 
-    build_tree_recursively(root, &albums_by_parent)
+    let mut total_counts = HashMap::new();
+    count_recursively(root, &albums_by_parent, asset_counts, &mut total_counts);
+
+    for children in albums_by_parent.values_mut() {
+        sort_albums(children, &total_counts, sort);
+    }
+
+    build_tree_recursively(root, &albums_by_parent, &total_counts)
+}
+
+/// Sums up the assets of each album and of all albums nested in it.
+fn count_recursively(
+    album: &Album,
+    albums_by_parent: &HashMap<i32, Vec<&Album>>,
+    asset_counts: &HashMap<i32, i64>,
+    total_counts: &mut HashMap<i32, i64>
+) -> i64 {
+    let nested: i64 = albums_by_parent
+        .get(&album.id)
+        .map(|children| {
+            children
+                .iter()
+                .map(|a| count_recursively(a, albums_by_parent, asset_counts, total_counts))
+                .sum()
+        })
+        .unwrap_or(0);
+
+    let total = asset_counts.get(&album.id).copied().unwrap_or(0) + nested;
+    total_counts.insert(album.id, total);
+    total
 }
 
-fn build_tree_recursively(album: &Album, albums_by_parent: &HashMap<i32, Vec<&Album>>) -> Tree {
+fn sort_albums(albums: &mut [&Album], total_counts: &HashMap<i32, i64>, sort: AlbumSort) {
+    match sort {
+        AlbumSort::Name => albums.sort_by_key(|a| a.name.clone().unwrap_or_default().to_lowercase()),
+        AlbumSort::Date => albums.sort_by_key(|a| (a.start_date.is_none(), a.start_date)),
+        AlbumSort::Count => albums.sort_by_key(|a| std::cmp::Reverse(total_counts.get(&a.id).copied())),
+    }
+}
+
+fn build_tree_recursively(
+    album: &Album,
+    albums_by_parent: &HashMap<i32, Vec<&Album>>,
+    total_counts: &HashMap<i32, i64>
+) -> Tree {
+    let label = label(album, total_counts);
     let children = match albums_by_parent.get(&album.id) {
-        None => return Leaf(vec![format!("{album}")]),
+        None => return Leaf(vec![label]),
         Some(c) => c
     };
 
     let child_nodes = children
         .iter()
-        .map(|a| build_tree_recursively(a, albums_by_parent))
+        .map(|a| build_tree_recursively(a, albums_by_parent, total_counts))
         .collect();
 
-    Node(format!("{album}"), child_nodes)
+    Node(label, child_nodes)
+}
+
+fn label(album: &Album, total_counts: &HashMap<i32, i64>) -> String {
+    let count = match album.kind {
+        Kind::SmartAlbum => "smart album".to_string(),
+        _ => match total_counts.get(&album.id).copied().unwrap_or(0) {
+            1 => "1 asset".to_string(),
+            n => format!("{} assets", n),
+        },
+    };
+
+    format!("{album} {}", format!("({})", count).cyan())
 }
 
 impl Display for Album {
@@ -57,6 +112,11 @@ impl Display for Album {
             self.name.clone().unwrap_or(String::from("<no name>"))
         };
 
-        write!(f, "{}", format!("{} {} {}", id, date, name))
+        let uuid = match &self.uuid {
+            None => String::new(),
+            Some(uuid) => format!(" [{}]", uuid).dimmed().to_string()
+        };
+
+        write!(f, "{} {} {}{}", id, date, name, uuid)
     }
 }
\ No newline at end of file
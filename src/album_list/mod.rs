@@ -1,26 +1,61 @@
 use ::ascii_tree::write_tree;
+use clap::ValueEnum;
 
 use crate::album_list::ascii_tree::build_tree;
+use crate::db::cache::QueryCache;
 use crate::db::repo::album::AlbumRepository;
-use crate::model::album::Album;
+use crate::db::model::album::AlbumDto;
+use crate::model::album::{Album, Kind};
 use crate::model::FromDbModel;
 use crate::result::PhotosExportResult;
 
 mod ascii_tree;
 
-pub fn print_album_tree(db_path: String) -> PhotosExportResult<()> {
-    let album_repository = AlbumRepository::new(db_path);
+/// Order of albums within the same folder
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum AlbumSort {
+    /// Alphabetically by name
+    Name,
+    /// By start date, albums without a date last
+    Date,
+    /// By number of assets, largest first
+    Count,
+}
 
-    let db_albums = album_repository.get_all()?;
+/// Kinds of albums to list
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum AlbumKindFilter {
+    /// Albums and folders created by the user
+    User,
+    /// Smart albums along with the folders containing them
+    Smart,
+    /// Both user and smart albums
+    All,
+}
+
+pub fn print_album_tree(
+    db_path: String,
+    cache: Option<QueryCache>,
+    sort: AlbumSort,
+    kind_filter: AlbumKindFilter
+) -> PhotosExportResult<()> {
+    let album_repository = AlbumRepository::new(db_path, cache);
+
+    let db_albums = select_albums(
+        album_repository.get_all()?,
+        album_repository.get_smart()?,
+        kind_filter
+    );
+    let asset_counts = album_repository.get_asset_counts()?;
 
     let albums: Vec<Album> = db_albums
         .iter()
         .map(|a| {
-            Album::from_db_model(&a)
+            Album::from_db_model(a)
         })
         .collect::<Result<Vec<Album>, String>>()?;
 
-    let tree = build_tree(&albums);
+    let tree = build_tree(&albums, &asset_counts, sort);
 
     let mut ascii_tree = String::new();
     let _ = write_tree(&mut ascii_tree, &tree);
@@ -28,4 +63,66 @@ pub fn print_album_tree(db_path: String) -> PhotosExportResult<()> {
     println!("{}", ascii_tree);
 
     Ok(())
+}
+
+/// Returns the albums of the given kind. Folders are only kept if they contain albums of the
+/// given kind, so that the smart albums can be listed in their place in the folder hierarchy.
+fn select_albums(
+    user_albums: Vec<AlbumDto>,
+    smart_albums: Vec<AlbumDto>,
+    kind_filter: AlbumKindFilter
+) -> Vec<AlbumDto> {
+    match kind_filter {
+        AlbumKindFilter::User => user_albums,
+        AlbumKindFilter::All => [user_albums, smart_albums].concat(),
+        AlbumKindFilter::Smart => {
+            let mut folder_ids: Vec<i32> = vec![];
+            let mut pending: Vec<i32> = smart_albums.iter().filter_map(|a| a.parent_id).collect();
+            while let Some(id) = pending.pop() {
+                if folder_ids.contains(&id) {
+                    continue;
+                }
+                folder_ids.push(id);
+                if let Some(parent_id) = user_albums.iter().find(|a| a.id == id).and_then(|a| a.parent_id) {
+                    pending.push(parent_id);
+                }
+            }
+
+            user_albums
+                .into_iter()
+                .filter(|a| a.kind == Kind::Root as i32 || folder_ids.contains(&a.id))
+                .chain(smart_albums)
+                .collect()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::library::{SyntheticLibrary, TestAlbum};
+
+    #[test]
+    fn keeps_folders_containing_smart_albums() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum::folder(3, "2024").in_folder(2));
+        library.add_album(&TestAlbum { kind: 1507, ..TestAlbum::album(4, "Favorites").in_folder(3) });
+        library.add_album(&TestAlbum::album(5, "Summer").in_folder(2));
+        library.add_album(&TestAlbum::folder(6, "Family"));
+        let repo = AlbumRepository::new(library.db_path(), None);
+        let ids = |kind_filter| {
+            let mut ids: Vec<i32> = select_albums(repo.get_all().unwrap(), repo.get_smart().unwrap(), kind_filter)
+                .iter()
+                .map(|a| a.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids(AlbumKindFilter::User), [1, 2, 3, 5, 6]);
+        assert_eq!(ids(AlbumKindFilter::Smart), [1, 2, 3, 4]);
+        assert_eq!(ids(AlbumKindFilter::All), [1, 2, 3, 4, 5, 6]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,140 @@
+use std::fs::{create_dir_all, read_dir, remove_file, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use diesel::QueryResult;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::util::checksum::fnv1a_64;
+use crate::util::dirs::cache_dir;
+
+/// On-disk cache for query results of a specific Photos database.
+///
+/// Entries are keyed by the modification times of the database and its write-ahead log, so any
+/// change to the library invalidates them.
+#[derive(Clone)]
+pub struct QueryCache {
+    dir: PathBuf,
+    /// Identifies the database regardless of its state
+    database_key: String,
+    /// Identifies the current state of the database
+    state_key: String,
+}
+
+impl QueryCache {
+
+    /// Creates a cache for the given database, returning `None` if no cache directory is
+    /// available or the state of the database can not be determined.
+    pub fn new(db_path: &str) -> Option<QueryCache> {
+        let dir = cache_dir()?;
+
+        let canonical_path = std::fs::canonicalize(db_path).ok()?;
+        let database_key = format!("{:016x}", fnv1a_64(canonical_path.as_os_str().as_encoded_bytes()));
+        let [database_modified, wal_modified] = get_database_state(db_path)?;
+        let state = format!(
+            "{} {} {}",
+            env!("CARGO_PKG_VERSION"),
+            nanos_since_epoch(database_modified),
+            nanos_since_epoch(wal_modified)
+        );
+        let state_key = format!("{:016x}", fnv1a_64(state.as_bytes()));
+
+        Some(QueryCache { dir, database_key, state_key })
+    }
+
+    fn entry_path(&self, query_key: &str) -> PathBuf {
+        self.dir.join(
+            format!("{}-{}-{:016x}.json", self.database_key, self.state_key, fnv1a_64(query_key.as_bytes()))
+        )
+    }
+
+    fn read<T: DeserializeOwned>(&self, query_key: &str) -> Option<T> {
+        let file = File::open(self.entry_path(query_key)).ok()?;
+        serde_json::from_reader(BufReader::new(file)).ok()
+    }
+
+    /// Writes the given value to the cache and removes outdated entries of the same database.
+    ///
+    /// Failing to write the cache is not an error as it is only an optimization.
+    fn write<T: Serialize>(&self, query_key: &str, value: &T) {
+        if create_dir_all(&self.dir).is_err() {
+            return;
+        }
+
+        self.remove_outdated_entries();
+
+        if let Ok(file) = File::create(self.entry_path(query_key)) {
+            let _ = serde_json::to_writer(BufWriter::new(file), value);
+        }
+    }
+
+    fn remove_outdated_entries(&self) {
+        let Ok(entries) = read_dir(&self.dir) else { return };
+
+        let database_prefix = format!("{}-", self.database_key);
+        let current_prefix = format!("{}-{}-", self.database_key, self.state_key);
+
+        entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with(&database_prefix) && !name.starts_with(&current_prefix))
+            .for_each(|name| {
+                let _ = remove_file(self.dir.join(name));
+            });
+    }
+}
+
+/// Returns the cached result for the given query key if available, otherwise loads and caches it.
+pub fn cached<T, F>(cache: &Option<QueryCache>, query_key: &str, load: F) -> QueryResult<T>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> QueryResult<T>,
+{
+    let Some(cache) = cache else {
+        return load();
+    };
+
+    if let Some(value) = cache.read(query_key) {
+        return Ok(value);
+    }
+
+    let value = load()?;
+    cache.write(query_key, &value);
+    Ok(value)
+}
+
+/// Returns the modification times of the given database and its write-ahead log, which change
+/// with any change to the library, or `None` if the database does not exist.
+pub fn get_database_state(db_path: &str) -> Option<[SystemTime; 2]> {
+    Some([
+        modification_time(Path::new(db_path))?,
+        modification_time(Path::new(&format!("{}-wal", db_path))).unwrap_or(SystemTime::UNIX_EPOCH),
+    ])
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+
+fn nanos_since_epoch(time: SystemTime) -> u128 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_entries_by_a_hash_that_is_stable_across_builds() {
+        let cache = QueryCache {
+            dir: PathBuf::from("/cache"),
+            database_key: String::from("database"),
+            state_key: String::from("state"),
+        };
+
+        assert_eq!(cache.entry_path("assets"), PathBuf::from("/cache/database-state-f88a72bdb42bac38.json"));
+    }
+}
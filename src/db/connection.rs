@@ -1,6 +1,95 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
 use diesel::{Connection, SqliteConnection};
 
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Snapshots to query instead of the databases they have been taken of, keyed by the path of the
+/// original database
+static SNAPSHOTS: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// Makes all connections established afterward read-only, so that SQLite itself rejects any
+/// attempt to write to the database.
+pub fn enable_read_only_connections() {
+    READ_ONLY.store(true, Ordering::SeqCst);
+}
+
+/// Makes all connections to the given database established afterward query the given snapshot
+/// instead, which is opened as immutable.
+pub fn redirect_to_snapshot(database_path: &str, snapshot_path: &Path) {
+    SNAPSHOTS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(database_path.to_string(), snapshot_path.to_string_lossy().to_string());
+}
+
 pub fn establish_connection(database_url: &String) -> SqliteConnection {
-    SqliteConnection::establish(&database_url)
+    let snapshot = SNAPSHOTS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|snapshots| snapshots.get(database_url).cloned());
+
+    let url = if let Some(snapshot_path) = snapshot {
+        to_immutable_uri(&snapshot_path)
+    } else if READ_ONLY.load(Ordering::SeqCst) {
+        to_read_only_uri(database_url)
+    } else {
+        database_url.clone()
+    };
+
+    SqliteConnection::establish(&url)
         .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+}
+
+/// Converts a database path to an SQLite URI opening the database in read-only mode.
+pub fn to_read_only_uri(database_path: &str) -> String {
+    to_uri(database_path, "mode=ro")
+}
+
+/// Converts a database path to an SQLite URI opening the database as immutable, i.e. without any
+/// locking or reading of its write-ahead log.
+pub fn to_immutable_uri(database_path: &str) -> String {
+    to_uri(database_path, "immutable=1")
+}
+
+/// Converts a database path to an SQLite URI with the given query parameters. Characters with a
+/// special meaning in URIs are percent-encoded.
+fn to_uri(database_path: &str, parameters: &str) -> String {
+    let encoded = database_path
+        .replace('%', "%25")
+        .replace('?', "%3F")
+        .replace('#', "%23");
+    format!("file:{}?{}", encoded, parameters)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use diesel::connection::SimpleConnection;
+
+    use super::*;
+    use crate::test_util::library::SyntheticLibrary;
+
+    #[test]
+    fn encodes_special_characters_of_read_only_uris() {
+        assert_eq!(
+            to_read_only_uri("/Users/me/100% #1?.photoslibrary/database/Photos.sqlite"),
+            "file:/Users/me/100%25 %231%3F.photoslibrary/database/Photos.sqlite?mode=ro"
+        );
+    }
+
+    #[test]
+    fn rejects_writes_to_read_only_connections() {
+        let library = SyntheticLibrary::new();
+
+        let mut conn = SqliteConnection::establish(&to_read_only_uri(&library.db_path())).unwrap();
+
+        assert!(conn.batch_execute("SELECT COUNT(*) FROM ZASSET").is_ok());
+        assert!(conn.batch_execute("DELETE FROM ZASSET").is_err());
+    }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
+pub mod cache;
 pub mod schema;
 pub mod connection;
 pub mod model;
 pub mod repo;
-pub mod version;
\ No newline at end of file
+pub mod version;
+pub mod snapshot;
\ No newline at end of file
@@ -1,13 +1,15 @@
 use diesel::{Queryable, Selectable};
+use serde::{Deserialize, Serialize};
 
 use crate::foundation::cocoa;
 use crate::model::album::Kind;
 use crate::model::FromDbModel;
 
-#[derive(Clone, Queryable, Selectable)]
+#[derive(Clone, Queryable, Selectable, Serialize, Deserialize)]
 #[diesel(table_name = crate::db::schema::albums)]
 pub struct AlbumDto {
     pub id: i32,
+    pub uuid: Option<String>,
     pub kind: i32,
     pub parent_id: Option<i32>,
     pub name: Option<String>,
@@ -19,6 +21,7 @@ impl FromDbModel<AlbumDto> for crate::model::album::Album {
     fn from_db_model(model: &AlbumDto) -> Result<Self, String> {
         Ok(crate::model::album::Album {
             id: model.id,
+            uuid: model.uuid.clone(),
             kind: Kind::try_from(model.kind)?,
             name: model.name.clone(),
             parent_id: model.parent_id,
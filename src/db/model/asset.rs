@@ -1,8 +1,11 @@
-use diesel::{Identifiable, Queryable, Selectable};
+use std::path::PathBuf;
+
+use diesel::{Identifiable, Queryable, QueryableByName, Selectable};
+use diesel::sql_types::{Integer, Text};
 
 use crate::db::repo::asset::ExportAssetDto;
 use crate::foundation::cocoa;
-use crate::model::asset::ExportAsset;
+use crate::model::asset::{Dimensions, ExportAsset, Location, Orientation, Subtype};
 use crate::model::FromDbModel;
 use crate::model::uti::Uti;
 
@@ -21,6 +24,13 @@ pub struct AssetDto {
     pub visibility_state: i32,
     pub duplicate_asset_visibility_state: i32,
     pub adjustments_state: i32,
+    pub kind_subtype: i32,
+    pub orientation: i32,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration: Option<f64>,
 }
 
 #[derive(Clone, Queryable, Selectable, Identifiable)]
@@ -29,6 +39,27 @@ pub struct AssetAttributesDto {
     pub id: i32,
     pub asset_id: i32,
     pub original_filename: String,
+    pub title: Option<String>,
+}
+
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::extended_attributes)]
+pub struct ExtendedAttributesDto {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+}
+
+/// A keyword assigned to an asset.
+///
+/// Keywords are loaded using a raw query as the name of the join table's keyword column differs
+/// between versions of the database.
+#[derive(QueryableByName)]
+pub struct AssetKeywordDto {
+    #[diesel(sql_type = Integer)]
+    pub asset_id: i32,
+    #[diesel(sql_type = Text)]
+    pub keyword: String,
 }
 
 #[allow(dead_code)]
@@ -37,6 +68,7 @@ pub struct AssetAttributesDto {
 pub struct AlbumAssetDto {
     pub asset_id: i32,
     pub album_id: i32,
+    pub sort_order: Option<i64>,
 }
 
 
@@ -57,12 +89,28 @@ impl FromDbModel<ExportAssetDto> for ExportAsset {
             datetime: cocoa::parse_cocoa_timestamp(model.timestamp)?,
             favorite: model.favorite,
             hidden: model.hidden,
+            trashed: model.trashed,
+            visibility_state: model.visibility_state,
             original_filename: model.original_filename.clone(),
             has_adjustments: model.has_adjustments,
+            subtype: Subtype::from(model.kind_subtype),
+            orientation: Orientation::from(model.orientation),
+            location: Location::from_coordinates(model.latitude, model.longitude),
+            dimensions: Dimensions::from_pixels(model.width, model.height),
+            duration: model.duration.filter(|duration| *duration > 0.0),
             album: match &model.album {
                 Some(a) => Some(crate::model::album::Album::from_db_model(a)?),
                 None => None,
-            }
+            },
+            album_position: model.album_position,
+            title: model.title.clone(),
+            description: model.description.clone(),
+            keywords: model.keywords.clone(),
+            camera_make: model.camera_make.clone(),
+            camera_model: model.camera_model.clone(),
+            lens_model: model.lens_model.clone(),
+            contributor: model.contributor.clone(),
+            referenced_path: model.referenced_path.as_ref().map(PathBuf::from),
         })
     }
 }
\ No newline at end of file
@@ -1,31 +1,366 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
 use derive_new::new;
+use diesel::dsl::count_star;
 use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
 
+use crate::db::cache::{cached, QueryCache};
 use crate::db::connection::establish_connection;
 use crate::db::model::album::AlbumDto;
 use crate::db::schema::albums::{kind, start_date, trashed};
 use crate::db::schema::albums::dsl::albums;
+use crate::db::schema::{album_assets, assets};
+use crate::foundation::cocoa::parse_cocoa_timestamp;
 use crate::model::album::Kind;
 
 #[derive(new)]
 pub struct AlbumRepository {
-    db_path: String
+    db_path: String,
+    cache: Option<QueryCache>
 }
 
 impl AlbumRepository {
 
     pub fn get_all(&self) -> QueryResult<Vec<AlbumDto>> {
+        cached(&self.cache, "albums", || self.load_all())
+    }
+
+    fn load_all(&self) -> QueryResult<Vec<AlbumDto>> {
         let mut conn = establish_connection(&self.db_path);
 
-        let album_types = [Kind::Root, Kind::UserAlbum, Kind::UserFolder]
-            .map(|k| k as i32);
+        let result = albums
+            .filter(kind.eq_any(Kind::int_values()))
+            .filter(trashed.eq(false))
+            .order_by(start_date.asc())
+            .load::<AlbumDto>(&mut conn)?;
+
+        Ok(result)
+    }
+
+    pub fn get_smart(&self) -> QueryResult<Vec<AlbumDto>> {
+        cached(&self.cache, "smart-albums", || self.load_smart())
+    }
+
+    fn load_smart(&self) -> QueryResult<Vec<AlbumDto>> {
+        let mut conn = establish_connection(&self.db_path);
 
         let result = albums
-            .filter(kind.eq_any(&album_types))
+            .filter(kind.eq(Kind::SmartAlbum as i32))
             .filter(trashed.eq(false))
             .order_by(start_date.asc())
             .load::<AlbumDto>(&mut conn)?;
 
         Ok(result)
     }
+
+    /// Returns the number of assets that are not in the trash for each album containing any. Smart
+    /// albums do not contain any assets themselves.
+    pub fn get_asset_counts(&self) -> QueryResult<HashMap<i32, i64>> {
+        cached(&self.cache, "album-asset-counts", || self.load_asset_counts())
+    }
+
+    fn load_asset_counts(&self) -> QueryResult<HashMap<i32, i64>> {
+        let mut conn = establish_connection(&self.db_path);
+
+        let result = album_assets::table
+            .inner_join(assets::table)
+            .filter(assets::trashed.eq(false))
+            .group_by(album_assets::album_id)
+            .select((album_assets::album_id, count_star()))
+            .load::<(i32, i64)>(&mut conn)?;
+
+        Ok(result.into_iter().collect())
+    }
+
+    /// Returns albums of unsupported kinds placed in a user folder, which are therefore visible
+    /// in Photos but not exported.
+    ///
+    /// Albums of unsupported kinds outside of user folders are internal albums of Photos, e.g.
+    /// smart albums or import sessions, and are not returned.
+    pub fn get_unsupported(&self) -> QueryResult<Vec<AlbumDto>> {
+        cached(&self.cache, "unsupported-albums", || self.load_unsupported())
+    }
+
+    fn load_unsupported(&self) -> QueryResult<Vec<AlbumDto>> {
+        let mut conn = establish_connection(&self.db_path);
+
+        let folder_kinds = [Kind::Root, Kind::UserFolder].map(|k| k as i32);
+        let all = albums
+            .filter(trashed.eq(false))
+            .load::<AlbumDto>(&mut conn)?;
+
+        let folder_ids: Vec<i32> = all
+            .iter()
+            .filter(|a| folder_kinds.contains(&a.kind))
+            .map(|a| a.id)
+            .collect();
+
+        Ok(
+            all.into_iter()
+                .filter(|a| !Kind::int_values().contains(&a.kind))
+                .filter(|a| a.parent_id.is_some_and(|id| folder_ids.contains(&id)))
+                .collect()
+        )
+    }
+}
+
+/// Returns the given album ids along with the ids of all albums and folders nested in them.
+pub fn with_descendants(all_albums: &[AlbumDto], ids: &[i32]) -> Vec<i32> {
+    let mut result = ids.to_vec();
+    let mut index = 0;
+
+    while index < result.len() {
+        let parent_id = result[index];
+        for album in all_albums {
+            if album.parent_id == Some(parent_id) && !result.contains(&album.id) {
+                result.push(album.id);
+            }
+        }
+        index += 1;
+    }
+
+    result
+}
+
+
+/// Returns the ids of the albums and folders whose name starts with any of the given prefixes,
+/// along with the ids of all albums and folders nested in them.
+pub fn with_name_prefix(all_albums: &[AlbumDto], prefixes: &[String]) -> Vec<i32> {
+    let ids: Vec<i32> = all_albums
+        .iter()
+        .filter(|a| {
+            a.name
+                .as_ref()
+                .is_some_and(|name| prefixes.iter().any(|prefix| name.starts_with(prefix.as_str())))
+        })
+        .map(|a| a.id)
+        .collect();
+
+    with_descendants(all_albums, &ids)
+}
+
+/// Returns the ids of the albums containing fewer than `min` or more than `max` assets, given the
+/// number of assets per album. Folders are never returned as they do not contain assets
+/// themselves.
+pub fn with_asset_count_outside(
+    all_albums: &[AlbumDto],
+    asset_counts: &HashMap<i32, i64>,
+    min: Option<i64>,
+    max: Option<i64>
+) -> Vec<i32> {
+    all_albums
+        .iter()
+        .filter(|a| !matches!(Kind::try_from(a.kind), Ok(Kind::Root | Kind::UserFolder)))
+        .filter(|a| {
+            let count = asset_counts.get(&a.id).copied().unwrap_or(0);
+            min.is_some_and(|min| count < min) || max.is_some_and(|max| count > max)
+        })
+        .map(|a| a.id)
+        .collect()
+}
+
+/// Finds the album or folder matching the given id, UUID or name. Names are compared exactly
+/// first and ignoring case second. Ambiguous names are rejected, listing the matching albums.
+pub fn find_album<'a>(all_albums: &'a [AlbumDto], query: &str) -> Result<&'a AlbumDto, String> {
+    let candidates: Vec<&AlbumDto> = all_albums
+        .iter()
+        .filter(|a| a.kind != Kind::Root as i32)
+        .collect();
+
+    let by_reference = candidates
+        .iter()
+        .find(|a| query.parse::<i32>() == Ok(a.id) || a.uuid.as_deref() == Some(query));
+    if let Some(album) = by_reference {
+        return Ok(album);
+    }
+
+    let mut matches: Vec<&AlbumDto> = candidates
+        .iter()
+        .filter(|a| a.name.as_deref() == Some(query))
+        .copied()
+        .collect();
+    if matches.is_empty() {
+        matches = candidates
+            .iter()
+            .filter(|a| a.name.as_ref().is_some_and(|name| name.to_lowercase() == query.to_lowercase()))
+            .copied()
+            .collect();
+    }
+
+    match matches.as_slice() {
+        [] => Err(format!("No album with the id, UUID or name '{}' found", query)),
+        [album] => Ok(album),
+        _ => Err(
+            format!(
+                "Multiple albums are named '{}', please use one of their ids instead: {}",
+                query,
+                matches.iter().map(|a| a.id.to_string()).collect::<Vec<_>>().join(", ")
+            )
+        ),
+    }
+}
+
+/// Returns the ids of the albums and folders whose start date falls within the given range of days
+/// (both inclusive), along with the ids of all albums and folders nested in them. Albums without a
+/// start date are never selected by themselves.
+pub fn with_start_date_between(
+    all_albums: &[AlbumDto],
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>
+) -> Result<Vec<i32>, String> {
+    let mut ids = vec![];
+    for album in all_albums {
+        let Some(timestamp) = album.start_date else {
+            continue;
+        };
+
+        let date = parse_cocoa_timestamp(timestamp)?.date();
+        if from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to) {
+            ids.push(album.id);
+        }
+    }
+
+    Ok(with_descendants(all_albums, &ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{find_album, with_asset_count_outside, with_descendants, with_name_prefix, with_start_date_between, AlbumRepository};
+    use crate::test_util::library::{SyntheticLibrary, TestAlbum, TestAsset};
+
+    #[test]
+    fn resolves_descendants_of_folders() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum::folder(3, "2024").in_folder(2));
+        library.add_album(&TestAlbum::album(4, "Summer").in_folder(3));
+        library.add_album(&TestAlbum::album(5, "Family"));
+        let all_albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+
+        let mut ids = with_descendants(&all_albums, &[2]);
+        ids.sort();
+
+        assert_eq!(ids, [2, 3, 4]);
+        assert_eq!(with_descendants(&all_albums, &[5]), [5]);
+    }
+
+    #[test]
+    fn resolves_albums_by_name_prefix() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "zz_Archive"));
+        library.add_album(&TestAlbum::album(3, "Receipts").in_folder(2));
+        library.add_album(&TestAlbum::album(4, "zz_Scans"));
+        library.add_album(&TestAlbum::album(5, "Summer zz_"));
+        let all_albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+
+        let mut ids = with_name_prefix(&all_albums, &["zz_".to_string()]);
+        ids.sort();
+
+        assert_eq!(ids, [2, 3, 4]);
+        assert!(with_name_prefix(&all_albums, &[]).is_empty());
+    }
+
+    #[test]
+    fn finds_albums_by_id_uuid_or_name() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum::album(3, "Summer 2024").in_folder(2));
+        library.add_album(&TestAlbum::album(4, "Family"));
+        library.add_album(&TestAlbum::album(5, "Family").in_folder(2));
+        let all_albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let find = |query| find_album(&all_albums, query).map(|a| a.id);
+        let uuid = all_albums.iter().find(|a| a.id == 3).unwrap().uuid.clone().unwrap();
+
+        assert_eq!(find("3"), Ok(3));
+        assert_eq!(find(&uuid), Ok(3));
+        assert_eq!(find("Summer 2024"), Ok(3));
+        assert_eq!(find("summer 2024"), Ok(3));
+        assert_eq!(find("Trips"), Ok(2));
+        assert!(find("Family").unwrap_err().contains("4, 5"));
+        assert!(find("Winter").is_err());
+        assert!(find("1").is_err());
+    }
+
+    #[test]
+    fn resolves_albums_by_start_date() {
+        // Noon UTC, so that the local UTC offset does not change the day
+        const MARCH_8_2022: f32 = 668_433_600.0;
+        const MARCH_8_2023: f32 = 699_969_600.0;
+
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips").starting_at(MARCH_8_2022));
+        library.add_album(&TestAlbum::album(3, "Rome").in_folder(2).starting_at(MARCH_8_2023));
+        library.add_album(&TestAlbum::album(4, "Summer").starting_at(MARCH_8_2023));
+        library.add_album(&TestAlbum::album(5, "Undated"));
+        let all_albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+
+        let mut from_2022 = with_start_date_between(&all_albums, date(2022, 1, 1), None).unwrap();
+        from_2022.sort();
+        let mut in_2023 = with_start_date_between(&all_albums, date(2023, 1, 1), date(2023, 3, 8)).unwrap();
+        in_2023.sort();
+
+        assert_eq!(from_2022, [2, 3, 4]);
+        assert_eq!(in_2023, [3, 4]);
+        assert!(with_start_date_between(&all_albums, None, date(2021, 12, 31)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn finds_unsupported_albums_in_user_folders() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum { kind: 1508, ..TestAlbum::album(3, "Book") });
+        library.add_album(&TestAlbum { kind: 1234, ..TestAlbum::album(4, "Legacy").in_folder(2) });
+        library.add_album(&TestAlbum { kind: 1505, parent_id: None, ..TestAlbum::album(5, "Smart") });
+        let repo = AlbumRepository::new(library.db_path(), None);
+
+        let mut supported: Vec<i32> = repo.get_all().unwrap().iter().map(|a| a.id).collect();
+        supported.sort();
+        let unsupported: Vec<i32> = repo.get_unsupported().unwrap().iter().map(|a| a.id).collect();
+
+        assert_eq!(supported, [1, 2, 3]);
+        assert_eq!(unsupported, [4]);
+    }
+
+    #[test]
+    fn counts_assets_not_in_the_trash() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_album(&TestAlbum::album(3, "Winter"));
+        library.add_album(&TestAlbum::album(4, "Empty"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2, 3]));
+        library.add_asset(&TestAsset::jpeg(2).in_albums(&[2]));
+        library.add_asset(&TestAsset::jpeg(3).in_albums(&[3]).trashed());
+
+        let counts = AlbumRepository::new(library.db_path(), None).get_asset_counts().unwrap();
+
+        assert_eq!(counts.get(&2), Some(&2));
+        assert_eq!(counts.get(&3), Some(&1));
+        assert_eq!(counts.get(&4), None);
+    }
+
+    #[test]
+    fn resolves_albums_by_asset_count() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum::album(3, "Summer").in_folder(2));
+        library.add_album(&TestAlbum::album(4, "Winter"));
+        library.add_album(&TestAlbum::album(5, "Empty"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[3, 4]));
+        library.add_asset(&TestAsset::jpeg(2).in_albums(&[3]));
+        library.add_asset(&TestAsset::jpeg(3).in_albums(&[3]));
+        let repo = AlbumRepository::new(library.db_path(), None);
+        let all_albums = repo.get_all().unwrap();
+        let counts = repo.get_asset_counts().unwrap();
+
+        let mut below_two = with_asset_count_outside(&all_albums, &counts, Some(2), None);
+        below_two.sort();
+
+        assert_eq!(below_two, [4, 5]);
+        assert_eq!(with_asset_count_outside(&all_albums, &counts, None, Some(2)), [3]);
+        assert!(with_asset_count_outside(&all_albums, &counts, None, None).is_empty());
+    }
 }
\ No newline at end of file
@@ -1,16 +1,32 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use clap::ValueEnum;
 use derive_new::new;
 use diesel::dsl;
-use diesel::dsl::count;
+use diesel::dsl::{count, sql};
 use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Text};
+use diesel::sqlite::Sqlite;
+use serde::{Deserialize, Serialize};
 
+use crate::db::cache::{cached, QueryCache};
 use crate::db::connection::establish_connection;
 use crate::db::model::album::AlbumDto;
-use crate::db::model::asset::{AlbumAssetDto, AssetAttributesDto, AssetDto};
+use crate::db::model::asset::{AlbumAssetDto, AssetAttributesDto, AssetDto, AssetKeywordDto, ExtendedAttributesDto};
 use crate::db::model::internal_resource::InternalResource;
-use crate::db::repo::asset::LocalAvailabilityFilter::Offloaded;
+use crate::db::repo::asset::LocalAvailabilityFilter::{Any, Local, Offloaded};
 use crate::db::schema::*;
+use crate::foundation::cocoa::{local_utc_offset, UNIX_COCOA_DELTA_MILLIS};
 use crate::model::album::Kind;
+use crate::model::asset::Subtype;
+
+/// Directory macOS mounts volumes at, including the startup volume.
+const VOLUMES_DIR: &str = "/Volumes";
 
+/// Whether to export hidden assets along with the visible ones, exclusively or not at all.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
 pub enum HiddenAssetsFilter {
     Include,
     Only,
@@ -42,19 +58,46 @@ fn filter_visible(hidden_assets: &HiddenAssetsFilter) -> AssetVisibilityFilter {
 }
 
 
+#[derive(Debug)]
 pub enum LocalAvailabilityFilter {
     Any,
+    /// Assets whose original is stored in the library, including all assets of libraries without
+    /// iCloud Photos
+    Local,
     Offloaded
 }
 
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum AlbumFilter {
     Include(Vec<i32>),
     Exclude(Vec<i32>),
+    /// Only assets that are not part of any album
+    Unalbumed,
     None
 }
 
-#[derive(new)]
+impl AlbumFilter {
+
+    /// Returns a filter additionally excluding the assets of the given albums.
+    pub fn excluding(self, album_ids: &[i32]) -> AlbumFilter {
+        if album_ids.is_empty() {
+            return self;
+        }
+
+        match self {
+            AlbumFilter::Include(ids) => AlbumFilter::Include(
+                ids.into_iter().filter(|id| !album_ids.contains(id)).collect()
+            ),
+            AlbumFilter::Exclude(ids) => AlbumFilter::Exclude([ids, album_ids.to_vec()].concat()),
+            AlbumFilter::Unalbumed => AlbumFilter::Unalbumed,
+            AlbumFilter::None => AlbumFilter::Exclude(album_ids.to_vec()),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[derive(new, Serialize, Deserialize)]
 pub struct ExportAssetDto {
     pub id: i32,
     pub uuid: String,
@@ -65,21 +108,170 @@ pub struct ExportAssetDto {
     pub timestamp: f32,
     pub favorite: bool,
     pub hidden: bool,
+    pub trashed: bool,
+    pub visibility_state: i32,
     pub original_filename: String,
     pub has_adjustments: bool,
-    pub album: Option<AlbumDto>
+    pub kind_subtype: i32,
+    pub orientation: i32,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub album: Option<AlbumDto>,
+    /// 1-based position of the asset in the manual sort order of its album
+    pub album_position: Option<usize>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    #[new(default)]
+    pub keywords: Vec<String>,
+    /// Name of the person who contributed the asset to a shared album or library
+    #[new(default)]
+    pub contributor: Option<String>,
+    /// Absolute path of the original if it is referenced, i.e. stored outside the library
+    #[new(default)]
+    pub referenced_path: Option<String>,
+    #[new(default)]
+    pub width: Option<i32>,
+    #[new(default)]
+    pub height: Option<i32>,
+    #[new(default)]
+    pub duration: Option<f64>,
+}
+
+/// Number of assets in the library, broken down by their state.
+#[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LibraryCounts {
+    /// Assets not in the trash, including hidden ones
+    pub visible: i64,
+    pub hidden: i64,
+    /// Assets in the "Recently Deleted" album
+    pub trashed: i64,
+    /// Visible assets whose original is only available in iCloud
+    pub offloaded: i64,
+}
+
+impl LibraryCounts {
+
+    pub fn merge(self, other: LibraryCounts) -> LibraryCounts {
+        LibraryCounts {
+            visible: self.visible + other.visible,
+            hidden: self.hidden + other.hidden,
+            trashed: self.trashed + other.trashed,
+            offloaded: self.offloaded + other.offloaded,
+        }
+    }
+}
+
+/// Length of the periods of a timeline of the library.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TimelineResolution {
+    Year,
+    Month
+}
+
+/// Number and total original size of the assets created within a year or month.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimelinePeriod {
+    /// Year or month formatted as `YYYY` or `YYYY-MM`
+    pub period: String,
+    pub count: i64,
+    pub bytes: i64,
+}
+
+/// Number of assets that would be exportable but are excluded by the filters of the repository.
+/// Assets excluded by multiple filters are attributed to the first filter excluding them, in the
+/// order of the fields.
+#[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExclusionCounts {
+    /// Hidden assets excluded as hidden assets are not included
+    pub hidden: i64,
+    /// Visible assets excluded as only hidden assets are exported
+    pub not_hidden: i64,
+    pub albums: i64,
+    pub subtypes: i64,
+    pub date: i64,
+    /// Assets excluded as they belong to another shard
+    pub shard: i64,
+}
+
+impl ExclusionCounts {
+
+    pub fn total(&self) -> i64 {
+        self.hidden + self.not_hidden + self.albums + self.subtypes + self.date + self.shard
+    }
+
+    pub fn merge(self, other: ExclusionCounts) -> ExclusionCounts {
+        ExclusionCounts {
+            hidden: self.hidden + other.hidden,
+            not_hidden: self.not_hidden + other.not_hidden,
+            albums: self.albums + other.albums,
+            subtypes: self.subtypes + other.subtypes,
+            date: self.date + other.date,
+            shard: self.shard + other.shard,
+        }
+    }
+}
+
+/// Deterministic part of the assets of a library, e.g. to split a large export across multiple runs.
+/// Assets are assigned to shards by their id, so that each asset stays in the same shard when
+/// assets are added to the library.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shard {
+    /// 0-based index of the shard
+    pub index: u32,
+    pub count: u32,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    /// Parses a 1-based shard like `3/8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid shard '{}', expected e.g. 3/8 for the third of eight shards", s);
+
+        let (number, count) = s.split_once('/').ok_or_else(invalid)?;
+        let number: u32 = number.trim().parse().map_err(|_| invalid())?;
+        let count: u32 = count.trim().parse().map_err(|_| invalid())?;
+        if number == 0 || number > count || count > i32::MAX as u32 {
+            return Err(invalid());
+        }
+
+        Ok(Shard { index: number - 1, count })
+    }
 }
 
 #[derive(new)]
 pub struct AssetRepository {
     db_path: String,
     hidden_assets: HiddenAssetsFilter,
-    album_filter: AlbumFilter
+    album_filter: AlbumFilter,
+    excluded_subtypes: Vec<Subtype>,
+    cache: Option<QueryCache>,
+    /// Only include assets created after the given cocoa timestamp
+    #[new(default)]
+    created_after: Option<f32>,
+    #[new(default)]
+    shard: Option<Shard>,
 }
 
 impl AssetRepository {
 
+    pub fn created_after(self, cocoa_timestamp: f32) -> Self {
+        AssetRepository { created_after: Some(cocoa_timestamp), ..self }
+    }
+
+    pub fn in_shard(self, shard: Shard) -> Self {
+        AssetRepository { shard: Some(shard), ..self }
+    }
+
     pub fn get_visible_count(&self, availability: LocalAvailabilityFilter) -> QueryResult<i64> {
+        let key = format!("visible-count-{:?}", availability);
+        cached(&self.cache, &key, || self.load_visible_count(availability))
+    }
+
+    fn load_visible_count(&self, availability: LocalAvailabilityFilter) -> QueryResult<i64> {
         let mut conn = establish_connection(&self.db_path);
         let mut boxed_select = assets::table
             .inner_join(asset_attributes::table)
@@ -94,38 +286,246 @@ impl AssetRepository {
             .select(count(assets::id))
             .into_boxed();
 
-        if let Offloaded = availability {
-            boxed_select = boxed_select
-                .filter(internal_resources::local_availability.ne(1));
+        boxed_select = match availability {
+            Any => boxed_select,
+            Local => boxed_select.filter(
+                internal_resources::local_availability.eq(1)
+                    .or(internal_resources::local_availability.is_null())
+            ),
+            Offloaded => boxed_select.filter(internal_resources::local_availability.ne(1)),
+        };
+
+        boxed_select.first(&mut conn)
+    }
+
+    pub fn get_library_counts(&self) -> QueryResult<LibraryCounts> {
+        Ok(
+            LibraryCounts {
+                visible: self.get_visible_count(LocalAvailabilityFilter::Any)?,
+                hidden: cached(&self.cache, "hidden-count", || self.load_hidden_count())?,
+                trashed: cached(&self.cache, "trashed-count", || self.load_trashed_count())?,
+                offloaded: self.get_visible_count(LocalAvailabilityFilter::Offloaded)?,
+            }
+        )
+    }
+
+    /// Returns the number and size of the visible assets per year or month they have been created
+    /// in, in chronological order. Periods without assets are omitted.
+    pub fn get_timeline(&self, resolution: TimelineResolution) -> QueryResult<Vec<TimelinePeriod>> {
+        let utc_offset_secs = local_utc_offset().local_minus_utc();
+        let key = format!("timeline-{:?}-{:?}-{}", resolution, self.hidden_assets, utc_offset_secs);
+        cached(&self.cache, &key, || self.load_timeline(resolution, utc_offset_secs))
+    }
+
+    fn load_timeline(&self, resolution: TimelineResolution, utc_offset_secs: i32) -> QueryResult<Vec<TimelinePeriod>> {
+        let mut conn = establish_connection(&self.db_path);
+
+        // Dates are shifted by the current UTC offset instead of using SQLite's 'localtime', which
+        // respects daylight saving time, to group them by the same dates they are exported with
+        let period = format!(
+            "strftime('{}', ZASSET.ZDATECREATED + {} + {}, 'unixepoch')",
+            match resolution {
+                TimelineResolution::Year => "%Y",
+                TimelineResolution::Month => "%Y-%m",
+            },
+            UNIX_COCOA_DELTA_MILLIS / 1000,
+            utc_offset_secs
+        );
+
+        let rows: Vec<(String, i64, i64)> = assets::table
+            .inner_join(asset_attributes::table)
+            .filter(filter_visible(&self.hidden_assets))
+            .group_by(sql::<Text>(&period))
+            .select((
+                sql::<Text>(&period),
+                count(assets::id),
+                sql::<BigInt>("COALESCE(SUM(ZADDITIONALASSETATTRIBUTES.ZORIGINALFILESIZE), 0)"),
+            ))
+            .order_by(sql::<Text>(&period))
+            .load(&mut conn)?;
+
+        Ok(
+            rows.into_iter()
+                .map(|(period, count, bytes)| TimelinePeriod { period, count, bytes })
+                .collect()
+        )
+    }
+
+    fn load_hidden_count(&self) -> QueryResult<i64> {
+        let mut conn = establish_connection(&self.db_path);
+        assets::table
+            .filter(filter_visible(&HiddenAssetsFilter::Only))
+            .select(count(assets::id))
+            .first(&mut conn)
+    }
+
+    fn load_trashed_count(&self) -> QueryResult<i64> {
+        let mut conn = establish_connection(&self.db_path);
+        assets::table
+            .filter(
+                assets::trashed.eq(true)
+                    .and(assets::visibility_state.eq(0))
+                    .and(assets::duplicate_asset_visibility_state.eq(0))
+            )
+            .select(count(assets::id))
+            .first(&mut conn)
+    }
+
+    pub fn get_exclusion_counts(&self) -> QueryResult<ExclusionCounts> {
+        let key = format!(
+            "exclusion-counts-{:?}-{:?}-{:?}-{:?}-{:?}",
+            self.hidden_assets,
+            self.album_filter,
+            self.excluded_subtypes,
+            self.created_after,
+            self.shard
+        );
+        cached(&self.cache, &key, || self.load_exclusion_counts())
+    }
+
+    /// Counts the exportable assets while applying the filters of the repository one after another,
+    /// in the order of the fields of [ExclusionCounts], attributing the assets each filter removes
+    /// to it.
+    fn load_exclusion_counts(&self) -> QueryResult<ExclusionCounts> {
+        let mut repo = AssetRepository::new(
+            self.db_path.clone(),
+            HiddenAssetsFilter::Include,
+            AlbumFilter::None,
+            vec![],
+            None
+        );
+        let mut remaining = repo.load_exportable_count()?;
+        let mut excluded = |repo: &AssetRepository| -> QueryResult<i64> {
+            let count = repo.load_exportable_count()?;
+            let excluded = remaining - count;
+            remaining = count;
+            Ok(excluded)
+        };
+
+        let mut counts = ExclusionCounts::default();
+
+        repo.hidden_assets = self.hidden_assets;
+        match self.hidden_assets {
+            HiddenAssetsFilter::Include => {},
+            HiddenAssetsFilter::Only => counts.not_hidden = excluded(&repo)?,
+            HiddenAssetsFilter::Exclude => counts.hidden = excluded(&repo)?,
         }
 
-        Ok(boxed_select.first(&mut conn)?)
+        repo.album_filter = self.album_filter.clone();
+        counts.albums = excluded(&repo)?;
+
+        repo.excluded_subtypes = self.excluded_subtypes.clone();
+        counts.subtypes = excluded(&repo)?;
+
+        repo.created_after = self.created_after;
+        counts.date = excluded(&repo)?;
+
+        repo.shard = self.shard;
+        counts.shard = excluded(&repo)?;
+
+        Ok(counts)
+    }
+
+    /// Counts the distinct locally available assets matching the filters of the repository.
+    fn load_exportable_count(&self) -> QueryResult<i64> {
+        let mut conn = establish_connection(&self.db_path);
+        self.exportable_query(Local)
+            .select(dsl::count_distinct(assets::id))
+            .first(&mut conn)
     }
 
     pub fn get_exportable(&self) -> QueryResult<Vec<ExportAssetDto>> {
+        let key = format!(
+            "exportable-{:?}-{:?}-{:?}-{:?}-{:?}",
+            self.hidden_assets,
+            self.album_filter,
+            self.excluded_subtypes,
+            self.created_after,
+            self.shard
+        );
+        cached(&self.cache, &key, || self.load_assets(Local))
+    }
+
+    /// Returns the assets that would be exportable but whose originals are not locally available,
+    /// e.g. as they have been offloaded to iCloud.
+    pub fn get_offloaded(&self) -> QueryResult<Vec<ExportAssetDto>> {
+        let key = format!(
+            "offloaded-{:?}-{:?}-{:?}-{:?}-{:?}",
+            self.hidden_assets,
+            self.album_filter,
+            self.excluded_subtypes,
+            self.created_after,
+            self.shard
+        );
+        cached(&self.cache, &key, || self.load_assets(Offloaded))
+    }
+
+    fn load_assets(&self, availability: LocalAvailabilityFilter) -> QueryResult<Vec<ExportAssetDto>> {
         let mut conn = establish_connection(&self.db_path);
 
-        let mut query = assets::table
-            .inner_join(
-                asset_attributes::table.left_join(
-                    internal_resources::table.on(
-                        internal_resources::asset_id
-                            .eq(asset_attributes::asset_id)
-                            .and(internal_resources::data_store_subtype.eq(1))
-                    )
-                )
-            )
-            .left_join(
-                album_assets::table.inner_join(albums::table)
-            )
+        let result = self.exportable_query(availability)
+            .select((
+                AssetDto::as_select(), AssetAttributesDto::as_select(), Option::<InternalResource>::as_select(),
+                Option::<AlbumAssetDto>::as_select(), Option::<AlbumDto>::as_select(),
+                asset_descriptions::long_description.nullable(),
+                Option::<ExtendedAttributesDto>::as_select()
+            ))
+            .load::<(AssetDto, AssetAttributesDto, Option<InternalResource>, Option<AlbumAssetDto>, Option<AlbumDto>, Option<String>, Option<ExtendedAttributesDto>)>(&mut conn)?;
+
+        let keywords = load_keywords(&mut conn)?;
+        let contributors = load_contributors(&mut conn)?;
+        let referenced_paths = load_referenced_paths(&mut conn)?;
+
+        let mut assets = result
+            .iter()
+            .map(|(asset, attributes, internal_resources, album_asset, albums, description, extended_attributes)| {
+                let mut dto = ExportAssetDto::new(
+                    asset.id,
+                    asset.uuid.clone(),
+                    asset.dir.clone(),
+                    asset.filename.clone(),
+                    internal_resources.clone().map(|ir| ir.compact_uti),
+                    asset.uniform_type_identifier.clone(),
+                    asset.date,
+                    asset.favorite,
+                    asset.hidden,
+                    asset.trashed,
+                    asset.visibility_state,
+                    attributes.original_filename.clone(),
+                    asset.adjustments_state > 0,
+                    asset.kind_subtype,
+                    asset.orientation,
+                    asset.latitude,
+                    asset.longitude,
+                    albums.clone(),
+                    None,
+                    attributes.title.clone(),
+                    description.clone(),
+                    extended_attributes.as_ref().and_then(|e| e.camera_make.clone()),
+                    extended_attributes.as_ref().and_then(|e| e.camera_model.clone()),
+                    extended_attributes.as_ref().and_then(|e| e.lens_model.clone())
+                );
+                dto.keywords = keywords.get(&asset.id).cloned().unwrap_or_default();
+                dto.contributor = contributors.get(&asset.id).cloned();
+                dto.referenced_path = referenced_paths.get(&asset.id).cloned();
+                dto.width = asset.width;
+                dto.height = asset.height;
+                dto.duration = asset.duration;
+                (dto, album_asset.as_ref().and_then(|a| a.sort_order))
+            })
+            .collect::<Vec<(ExportAssetDto, Option<i64>)>>();
+
+        assign_album_positions(&mut assets);
+
+        Ok(assets.into_iter().map(|(dto, _)| dto).collect())
+    }
+
+    /// Returns the query of the assets matching the filters of the repository and the given
+    /// availability, once per album they are part of.
+    fn exportable_query(&self, availability: LocalAvailabilityFilter) -> ExportableQuery {
+        let mut query = exportable_source()
             .filter(
                 filter_visible(&self.hidden_assets)
-                    .and(
-                        internal_resources::local_availability.eq(1)
-                            // second case is true if the library is offline-only, iCloud enabled
-                            // libraries seem to have local_availability set to a non-null value
-                            .or(internal_resources::local_availability.is_null())
-                    )
                     .and(
                         albums::kind.is_null()
                             .or(
@@ -134,45 +534,474 @@ impl AssetRepository {
                             )
                     )
             )
-            .select((
-                AssetDto::as_select(), AssetAttributesDto::as_select(), Option::<InternalResource>::as_select(),
-                Option::<AlbumAssetDto>::as_select(), Option::<AlbumDto>::as_select()
-            ))
             .into_boxed();
 
         query = match &self.album_filter {
             AlbumFilter::Include(ids) => query.filter(
-                albums::id.eq_any(ids)
+                albums::id.eq_any(ids.clone())
             ),
             AlbumFilter::Exclude(ids) => query.filter(
-                albums::id.ne_all(ids).or(albums::id.is_null())
+                albums::id.ne_all(ids.clone()).or(albums::id.is_null())
+            ),
+            AlbumFilter::Unalbumed => query.filter(
+                albums::id.is_null()
             ),
             AlbumFilter::None => query
         };
 
-        let result = query
-            .load::<(AssetDto, AssetAttributesDto, Option<InternalResource>, Option<AlbumAssetDto>, Option<AlbumDto>)>(&mut conn)?;
+        query = match availability {
+            Any => query,
+            Local => query.filter(
+                internal_resources::local_availability.eq(1)
+                    // second case is true if the library is offline-only, iCloud enabled
+                    // libraries seem to have local_availability set to a non-null value
+                    .or(internal_resources::local_availability.is_null())
+            ),
+            Offloaded => query.filter(internal_resources::local_availability.ne(1)),
+        };
 
-        Ok(
-            result
+        if !self.excluded_subtypes.is_empty() {
+            let subtypes: Vec<i32> = self.excluded_subtypes
                 .iter()
-                .map(|(asset, attributes, internal_resources, _, albums)| {
-                    ExportAssetDto::new(
-                        asset.id,
-                        asset.uuid.clone(),
-                        asset.dir.clone(),
-                        asset.filename.clone(),
-                        internal_resources.clone().map(|ir| ir.compact_uti),
-                        asset.uniform_type_identifier.clone(),
-                        asset.date,
-                        asset.favorite,
-                        asset.hidden,
-                        attributes.original_filename.clone(),
-                        asset.adjustments_state > 0,
-                        albums.clone()
-                    )
-                })
-                .collect::<Vec<ExportAssetDto>>()
+                .map(|s| i32::from(*s))
+                .collect();
+            query = query.filter(assets::kind_subtype.ne_all(subtypes));
+        }
+
+        if let Some(timestamp) = self.created_after {
+            query = query.filter(assets::date.gt(timestamp));
+        }
+
+        if let Some(shard) = self.shard {
+            // Diesel does not support the modulo operator
+            query = query.filter(sql::<Bool>(&format!("ZASSET.Z_PK % {} = {}", shard.count, shard.index)));
+        }
+
+        query
+    }
+}
+
+type ExportableQuery = dsl::IntoBoxed<'static, exportable_source, Sqlite>;
+
+/// Joins the assets with their attributes, original resource, description, albums and extended
+/// attributes.
+#[dsl::auto_type]
+fn exportable_source() -> _ {
+    assets::table
+        .inner_join(
+            asset_attributes::table.left_join(
+                internal_resources::table.on(
+                    internal_resources::asset_id
+                        .eq(asset_attributes::asset_id)
+                        .and(internal_resources::data_store_subtype.eq(1i32))
+                )
+            ).left_join(asset_descriptions::table)
+        )
+        .left_join(
+            album_assets::table.inner_join(albums::table)
+        )
+        .left_join(extended_attributes::table)
+}
+
+/// Loads the names of the people who contributed assets to shared albums or libraries, grouped by
+/// the id of the contributed asset.
+fn load_contributors(conn: &mut SqliteConnection) -> QueryResult<HashMap<i32, String>> {
+    let rows = assets::table
+        .inner_join(
+            persons::table.on(persons::person_uri.eq(assets::cloud_owner_hashed_person_id))
+        )
+        .filter(persons::full_name.is_not_null())
+        .select((assets::id, persons::full_name))
+        .load::<(i32, Option<String>)>(conn)?;
+
+    let mut contributors = HashMap::new();
+    for (asset_id, full_name) in rows {
+        if let Some(full_name) = full_name {
+            contributors.entry(asset_id).or_insert(full_name);
+        }
+    }
+    Ok(contributors)
+}
+
+/// Loads the absolute paths of referenced originals, grouped by the id of their asset. Volumes are
+/// expected to be mounted where macOS mounts them.
+fn load_referenced_paths(conn: &mut SqliteConnection) -> QueryResult<HashMap<i32, String>> {
+    let rows = internal_resources::table
+        .inner_join(
+            file_system_bookmarks::table.on(
+                file_system_bookmarks::resource_fingerprint.eq(internal_resources::fingerprint.nullable())
+            )
         )
+        .inner_join(
+            file_system_volumes::table.on(
+                file_system_volumes::id.nullable().eq(internal_resources::file_system_volume_id)
+            )
+        )
+        .select((internal_resources::asset_id, file_system_volumes::name, file_system_bookmarks::path_relative_to_volume))
+        .load::<(i32, Option<String>, Option<String>)>(conn)?;
+
+    let mut paths = HashMap::new();
+    for (asset_id, volume, relative_path) in rows {
+        if let (Some(volume), Some(relative_path)) = (volume, relative_path) {
+            let path = Path::new(VOLUMES_DIR).join(volume).join(relative_path);
+            paths.entry(asset_id).or_insert(path.to_string_lossy().to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// Loads the keywords of all assets, grouped by the id of the asset they are assigned to.
+///
+/// The join table links the asset attributes to the keywords using a column whose name contains
+/// the entity number of the keyword table, e.g. `Z_52KEYWORDS`. As this number changes between
+/// versions of the database, the column is looked up at runtime.
+fn load_keywords(conn: &mut SqliteConnection) -> QueryResult<HashMap<i32, Vec<String>>> {
+    #[derive(QueryableByName)]
+    struct TableColumn {
+        #[diesel(sql_type = diesel::sql_types::Text)]
+        name: String,
+    }
+
+    let keyword_column: Option<String> = diesel::sql_query(
+        "SELECT name FROM pragma_table_info('Z_1KEYWORDS') \
+         WHERE name LIKE 'Z!_%KEYWORDS' ESCAPE '!'"
+    )
+        .load::<TableColumn>(conn)?
+        .into_iter()
+        .map(|column| column.name)
+        .next();
+
+    let Some(keyword_column) = keyword_column else {
+        return Ok(HashMap::new())
+    };
+
+    let rows = diesel::sql_query(format!(
+        "SELECT attributes.ZASSET AS asset_id, keyword.ZTITLE AS keyword \
+         FROM Z_1KEYWORDS AS assignment \
+         INNER JOIN ZADDITIONALASSETATTRIBUTES AS attributes \
+             ON attributes.Z_PK = assignment.Z_1ASSETATTRIBUTES \
+         INNER JOIN ZKEYWORD AS keyword ON keyword.Z_PK = assignment.{} \
+         WHERE keyword.ZTITLE IS NOT NULL \
+         ORDER BY keyword.ZTITLE",
+        keyword_column
+    ))
+        .load::<AssetKeywordDto>(conn)?;
+
+    let mut keywords: HashMap<i32, Vec<String>> = HashMap::new();
+    for row in rows {
+        keywords.entry(row.asset_id).or_default().push(row.keyword);
+    }
+    Ok(keywords)
+}
+
+/// Sets the position of each asset in the manual sort order of its album, based on the sort keys
+/// the assets are paired with. Assets without sort key are placed at the end of their album.
+fn assign_album_positions(assets: &mut [(ExportAssetDto, Option<i64>)]) {
+    let mut ordered: Vec<&mut (ExportAssetDto, Option<i64>)> = assets
+        .iter_mut()
+        .filter(|(dto, _)| dto.album.is_some())
+        .collect();
+
+    ordered.sort_by_key(|(dto, sort_order)| {
+        (dto.album.as_ref().map(|a| a.id), sort_order.is_none(), *sort_order, dto.id)
+    });
+
+    let mut previous_album = None;
+    let mut position = 0;
+    for (dto, _) in ordered {
+        let album = dto.album.as_ref().map(|a| a.id);
+        position = if album == previous_album { position + 1 } else { 1 };
+        previous_album = album;
+        dto.album_position = Some(position);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    use crate::foundation::cocoa::to_cocoa_timestamp;
+    use crate::model::asset::ExportAsset;
+    use crate::test_util::library::{load_exportable, SyntheticLibrary, TestAlbum, TestAsset};
+
+    fn repo(library: &SyntheticLibrary, hidden: HiddenAssetsFilter, albums: AlbumFilter) -> AssetRepository {
+        AssetRepository::new(library.db_path(), hidden, albums, vec![], None)
+    }
+
+    fn uuids(assets: Vec<ExportAssetDto>) -> Vec<String> {
+        let mut uuids: Vec<String> = assets.into_iter().map(|a| a.uuid).collect();
+        uuids.sort();
+        uuids
+    }
+
+    fn library() -> SyntheticLibrary {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_album(&TestAlbum::album(3, "Winter"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2]));
+        library.add_asset(&TestAsset::jpeg(2).in_albums(&[3]));
+        library.add_asset(&TestAsset::jpeg(3).hidden());
+        library.add_asset(&TestAsset::jpeg(4).trashed());
+        library.add_asset(&TestAsset::mov(5).with_subtype(101));
+        library
+    }
+
+    #[test]
+    fn excludes_trashed_assets() {
+        let library = library();
+        let assets = repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None)
+            .get_exportable()
+            .unwrap();
+
+        assert_eq!(uuids(assets), ["ASSET-0001", "ASSET-0002", "ASSET-0003", "ASSET-0005"]);
+    }
+
+    #[test]
+    fn groups_timeline_by_the_dates_assets_are_exported_with() {
+        let library = SyntheticLibrary::new();
+        // Close to midnight in UTC, in winter, so that the month depends on the UTC offset used
+        let winter_night = Utc.with_ymd_and_hms(2024, 1, 31, 22, 30, 0).unwrap();
+        library.add_asset(&TestAsset::jpeg(1).taken_at(to_cocoa_timestamp(winter_night)));
+        let repo = repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None);
+
+        let exported_month = load_exportable(&repo)[0].datetime.format("%Y-%m").to_string();
+        let timeline = repo.get_timeline(TimelineResolution::Month).unwrap();
+
+        assert_eq!(timeline.iter().map(|p| &p.period).collect::<Vec<_>>(), [&exported_month]);
+    }
+
+    #[test]
+    fn filters_hidden_assets() {
+        let library = library();
+
+        let excluded = repo(&library, HiddenAssetsFilter::Exclude, AlbumFilter::None)
+            .get_exportable()
+            .unwrap();
+        let only = repo(&library, HiddenAssetsFilter::Only, AlbumFilter::None)
+            .get_exportable()
+            .unwrap();
+
+        assert_eq!(uuids(excluded), ["ASSET-0001", "ASSET-0002", "ASSET-0005"]);
+        assert_eq!(uuids(only), ["ASSET-0003"]);
+    }
+
+    #[test]
+    fn counts_assets_by_state() {
+        let library = library();
+        library.add_asset(&TestAsset::jpeg(6).offloaded());
+
+        assert_eq!(
+            repo(&library, HiddenAssetsFilter::Exclude, AlbumFilter::None).get_library_counts().unwrap(),
+            LibraryCounts { visible: 5, hidden: 1, trashed: 1, offloaded: 1 }
+        );
+    }
+
+    #[test]
+    fn counts_excluded_assets_per_filter() {
+        let library = library();
+        let repo = AssetRepository::new(
+            library.db_path(),
+            HiddenAssetsFilter::Exclude,
+            AlbumFilter::Exclude(vec![2]),
+            vec![Subtype::from(101)],
+            None
+        );
+
+        assert_eq!(
+            repo.get_exclusion_counts().unwrap(),
+            ExclusionCounts { hidden: 1, not_hidden: 0, albums: 1, subtypes: 1, date: 0, shard: 0 }
+        );
+        assert_eq!(uuids(repo.get_exportable().unwrap()), ["ASSET-0002"]);
+    }
+
+    #[test]
+    fn resolves_referenced_originals() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::jpeg(2).referenced_at("Photos", "2010/IMG_0002.JPG"));
+        let mut assets = load_exportable(&repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None));
+        assets.sort_by_key(|a| a.id);
+
+        assert_eq!(assets[0].get_path(), TestAsset::jpeg(1).original_path());
+        assert_eq!(assets[1].get_path(), Path::new("/Volumes/Photos/2010/IMG_0002.JPG"));
+    }
+
+    #[test]
+    fn filters_albums() {
+        let library = library();
+
+        let included = repo(&library, HiddenAssetsFilter::Include, AlbumFilter::Include(vec![2]))
+            .get_exportable()
+            .unwrap();
+        let excluded = repo(&library, HiddenAssetsFilter::Include, AlbumFilter::Exclude(vec![2]))
+            .get_exportable()
+            .unwrap();
+
+        assert_eq!(uuids(included), ["ASSET-0001"]);
+        assert_eq!(uuids(excluded), ["ASSET-0002", "ASSET-0003", "ASSET-0005"]);
+    }
+
+    #[test]
+    fn filters_shards() {
+        let library = SyntheticLibrary::new();
+        for id in 1..=5 {
+            library.add_asset(&TestAsset::jpeg(id));
+        }
+        let shard = |s: &str| repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None)
+            .in_shard(s.parse().unwrap());
+
+        assert_eq!(uuids(shard("1/2").get_exportable().unwrap()), ["ASSET-0002", "ASSET-0004"]);
+        assert_eq!(uuids(shard("2/2").get_exportable().unwrap()), ["ASSET-0001", "ASSET-0003", "ASSET-0005"]);
+        assert_eq!(shard("1/2").get_exclusion_counts().unwrap().shard, 3);
+        assert!("0/2".parse::<Shard>().is_err());
+        assert!("3/2".parse::<Shard>().is_err());
+        assert!("1/0".parse::<Shard>().is_err());
+        assert!("1".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn excludes_albums_from_album_filters() {
+        assert_eq!(AlbumFilter::Include(vec![2, 3]).excluding(&[3]), AlbumFilter::Include(vec![2]));
+        assert_eq!(AlbumFilter::Exclude(vec![2]).excluding(&[3]), AlbumFilter::Exclude(vec![2, 3]));
+        assert_eq!(AlbumFilter::None.excluding(&[3]), AlbumFilter::Exclude(vec![3]));
+        assert_eq!(AlbumFilter::None.excluding(&[]), AlbumFilter::None);
+        assert_eq!(AlbumFilter::Unalbumed.excluding(&[3]), AlbumFilter::Unalbumed);
+    }
+
+    #[test]
+    fn filters_assets_not_in_any_album() {
+        let library = library();
+        let repo = repo(&library, HiddenAssetsFilter::Exclude, AlbumFilter::Unalbumed);
+
+        assert_eq!(uuids(repo.get_exportable().unwrap()), ["ASSET-0005"]);
+        assert_eq!(
+            repo.get_exclusion_counts().unwrap(),
+            ExclusionCounts { hidden: 1, not_hidden: 0, albums: 2, subtypes: 0, date: 0, shard: 0 }
+        );
+    }
+
+    #[test]
+    fn exports_assets_once_per_album() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_album(&TestAlbum::album(3, "Winter"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2, 3]));
+
+        let assets = repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None)
+            .get_exportable()
+            .unwrap();
+
+        let mut albums: Vec<i32> = assets.iter().filter_map(|a| a.album.as_ref()).map(|a| a.id).collect();
+        albums.sort();
+        assert_eq!(albums, [2, 3]);
+    }
+
+    #[test]
+    fn excludes_subtypes() {
+        let library = library();
+        let assets = AssetRepository::new(
+            library.db_path(),
+            HiddenAssetsFilter::Include,
+            AlbumFilter::None,
+            vec![Subtype::SlowMotion],
+            None
+        ).get_exportable().unwrap();
+
+        assert_eq!(uuids(assets), ["ASSET-0001", "ASSET-0002", "ASSET-0003"]);
+    }
+
+    #[test]
+    fn skips_and_counts_offloaded_assets() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::jpeg(2).offloaded());
+        let repo = repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None);
+
+        assert_eq!(uuids(repo.get_exportable().unwrap()), ["ASSET-0001"]);
+        assert_eq!(uuids(repo.get_offloaded().unwrap()), ["ASSET-0002"]);
+        assert_eq!(repo.get_visible_count(LocalAvailabilityFilter::Any), Ok(2));
+        assert_eq!(repo.get_visible_count(LocalAvailabilityFilter::Local), Ok(1));
+        assert_eq!(repo.get_visible_count(LocalAvailabilityFilter::Offloaded), Ok(1));
+    }
+
+    #[test]
+    fn exports_assets_of_offline_libraries() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).offline_library());
+
+        let assets = repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None)
+            .get_exportable()
+            .unwrap();
+
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].compact_uti, None);
+    }
+
+    #[test]
+    fn assigns_positions_in_manual_album_order() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_album(&TestAlbum::album(3, "Winter"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2, 3]).sorted_at(2048));
+        library.add_asset(&TestAsset::jpeg(2).in_albums(&[2]).sorted_at(1024));
+        library.add_asset(&TestAsset::jpeg(3));
+
+        let mut positions: Vec<(String, Option<i32>, Option<usize>)> =
+            repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None)
+                .get_exportable()
+                .unwrap()
+                .into_iter()
+                .map(|a| (a.uuid, a.album.map(|album| album.id), a.album_position))
+                .collect();
+        positions.sort();
+
+        assert_eq!(
+            positions,
+            [
+                ("ASSET-0001".to_string(), Some(2), Some(2)),
+                ("ASSET-0001".to_string(), Some(3), Some(1)),
+                ("ASSET-0002".to_string(), Some(2), Some(1)),
+                ("ASSET-0003".to_string(), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn exposes_asset_flags() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).favorite().hidden());
+        library.add_asset(&TestAsset::mov(2));
+
+        let assets: Vec<ExportAsset> = load_exportable(
+            &repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None)
+        );
+        let flags: Vec<(bool, bool, bool, i32, &str)> = assets
+            .iter()
+            .map(|a| (a.favorite, a.hidden, a.trashed, a.visibility_state, a.original_uti.uti))
+            .collect();
+
+        assert_eq!(
+            flags,
+            [
+                (true, true, false, 0, "public.jpeg"),
+                (false, false, false, 0, "com.apple.quicktime-movie"),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_by_creation_date() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).taken_at(700_000_000.0));
+        library.add_asset(&TestAsset::jpeg(2).taken_at(740_000_000.0));
+
+        let assets = repo(&library, HiddenAssetsFilter::Include, AlbumFilter::None)
+            .created_after(720_000_000.0)
+            .get_exportable()
+            .unwrap();
+
+        assert_eq!(uuids(assets), ["ASSET-0002"]);
     }
 }
\ No newline at end of file
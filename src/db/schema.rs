@@ -30,6 +30,15 @@ diesel::table! {
         #[sql_name = "Z_PK"]
         id -> Integer,
 
+        /// Unique identifier of the album.
+        ///
+        /// Unlike the primary key, this identifier is stable across library rebuilds.
+        ///
+        /// - `Photos.db` name: `ZUUID`
+        /// - Type: `VARCHAR (nullable)`
+        #[sql_name = "ZUUID"]
+        uuid -> Nullable<VarChar>,
+
         /// Type of the album.
         ///
         /// - `Photos.db` name: `ZKIND`
@@ -190,6 +199,113 @@ diesel::table! {
         //  - Values `> 0`: Has adjustments
         #[sql_name = "ZADJUSTMENTSSTATE"]
         adjustments_state -> Integer,
+
+        /// Subtype of the asset, e.g. whether a video is a slow-motion video.
+        ///
+        /// - `Photos.db` name: `ZKINDSUBTYPE`
+        /// - Type: `INTEGER`
+        ///
+        /// Known possible values:
+        /// - `0`: Regular photo or video
+        /// - `1`: Panorama
+        /// - `2`: Live photo
+        /// - `10`: Screenshot
+        /// - `101`: Slow-motion video
+        /// - `102`: Time-lapse video
+        /// - `103`: Screen recording
+        #[sql_name = "ZKINDSUBTYPE"]
+        kind_subtype -> Integer,
+
+        /// EXIF orientation of the original, i.e. how its pixels have to be transformed to be
+        /// displayed upright.
+        ///
+        /// - `Photos.db` name: `ZORIENTATION`
+        /// - Type: `INTEGER`
+        ///
+        /// Known possible values:
+        /// - `1`: Upright
+        /// - `2` to `8`: Mirrored and/or rotated as defined by the EXIF specification
+        #[sql_name = "ZORIENTATION"]
+        orientation -> Integer,
+
+        /// Latitude of the location where the asset was captured.
+        ///
+        /// Assets without location information have a value of `-180.0`.
+        ///
+        /// - `Photos.db` name: `ZLATITUDE`
+        /// - Type: `FLOAT`
+        #[sql_name = "ZLATITUDE"]
+        latitude -> Double,
+
+        /// Longitude of the location where the asset was captured.
+        ///
+        /// Assets without location information have a value of `-180.0`.
+        ///
+        /// - `Photos.db` name: `ZLONGITUDE`
+        /// - Type: `FLOAT`
+        #[sql_name = "ZLONGITUDE"]
+        longitude -> Double,
+
+        /// Width of the original in pixels, before applying its orientation.
+        ///
+        /// - `Photos.db` name: `ZWIDTH`
+        /// - Type: `INTEGER`
+        #[sql_name = "ZWIDTH"]
+        width -> Nullable<Integer>,
+
+        /// Height of the original in pixels, before applying its orientation.
+        ///
+        /// - `Photos.db` name: `ZHEIGHT`
+        /// - Type: `INTEGER`
+        #[sql_name = "ZHEIGHT"]
+        height -> Nullable<Integer>,
+
+        /// Duration of videos in seconds, `0` for photos.
+        ///
+        /// - `Photos.db` name: `ZDURATION`
+        /// - Type: `FLOAT`
+        #[sql_name = "ZDURATION"]
+        duration -> Nullable<Double>,
+
+        /// Hashed identifier of the person who contributed the asset to a shared album or
+        /// library. This references the `person_uri` of a person.
+        ///
+        /// - `Photos.db` name: `ZCLOUDOWNERHASHEDPERSONID`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZCLOUDOWNERHASHEDPERSONID"]
+        cloud_owner_hashed_person_id -> Nullable<VarChar>,
+    }
+}
+
+diesel::table! {
+
+    /// This table contains the people known to the Photos library, e.g. from faces or contacts
+    /// sharing assets.
+    ///
+    /// - `Photos.db` name: `ZPERSON`
+    #[sql_name = "ZPERSON"]
+    persons (id) {
+
+        /// The primary key of the person.
+        ///
+        /// - `Photos.db` name: `Z_PK`
+        /// - Type: `INTEGER`
+        #[sql_name = "Z_PK"]
+        id -> Integer,
+
+        /// Full name of the person.
+        ///
+        /// - `Photos.db` name: `ZFULLNAME`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZFULLNAME"]
+        full_name -> Nullable<VarChar>,
+
+        /// Identifier of the person, referenced by the assets the person has shared.
+        ///
+        /// - `Photos.db` name: `ZPERSONURI`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZPERSONURI"]
+        person_uri -> Nullable<VarChar>,
     }
 }
 
@@ -232,6 +348,96 @@ diesel::table! {
         /// - Type: `VARCHAR`
         #[sql_name = "ZMASTERFINGERPRINT"]
         master_fingerprint -> VarChar,
+
+        /// Title of the asset as entered in the info panel of Photos.
+        ///
+        /// - `Photos.db` name: `ZTITLE`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZTITLE"]
+        title -> Nullable<VarChar>,
+
+        /// Size of the original file in bytes.
+        ///
+        /// - `Photos.db` name: `ZORIGINALFILESIZE`
+        /// - Type: `INTEGER`
+        #[sql_name = "ZORIGINALFILESIZE"]
+        original_file_size -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+
+    /// This table contains attributes of assets read from their EXIF data, e.g. the camera used.
+    ///
+    /// - `Photos.db` name: `ZEXTENDEDATTRIBUTES`
+    #[sql_name = "ZEXTENDEDATTRIBUTES"]
+    extended_attributes (id) {
+
+        /// The primary key of the extended attributes.
+        ///
+        /// - `Photos.db` name: `Z_PK`
+        /// - Type: `INTEGER`
+        #[sql_name = "Z_PK"]
+        id -> Integer,
+
+        /// ID of the asset the attributes belong to.
+        ///
+        /// - `Photos.db` name: `ZASSET`
+        /// - Type: `INTEGER`
+        #[sql_name = "ZASSET"]
+        asset_id -> Integer,
+
+        /// Manufacturer of the camera the asset has been taken with, e.g. `Apple`.
+        ///
+        /// - `Photos.db` name: `ZCAMERAMAKE`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZCAMERAMAKE"]
+        camera_make -> Nullable<VarChar>,
+
+        /// Model of the camera the asset has been taken with, e.g. `iPhone 15 Pro`.
+        ///
+        /// - `Photos.db` name: `ZCAMERAMODEL`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZCAMERAMODEL"]
+        camera_model -> Nullable<VarChar>,
+
+        /// Model of the lens the asset has been taken with.
+        ///
+        /// - `Photos.db` name: `ZLENSMODEL`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZLENSMODEL"]
+        lens_model -> Nullable<VarChar>,
+    }
+}
+
+diesel::table! {
+
+    /// This table contains the captions of assets in the Photos database.
+    ///
+    /// - `Photos.db` name: `ZASSETDESCRIPTION`
+    #[sql_name = "ZASSETDESCRIPTION"]
+    asset_descriptions (id) {
+
+        /// The primary key of the asset description.
+        ///
+        /// - `Photos.db` name: `Z_PK`
+        /// - Type: `INTEGER`
+        #[sql_name = "Z_PK"]
+        id -> Integer,
+
+        /// ID of the asset attributes the description belongs to.
+        ///
+        /// - `Photos.db` name: `ZASSETATTRIBUTES`
+        /// - Type: `INTEGER`
+        #[sql_name = "ZASSETATTRIBUTES"]
+        asset_attributes_id -> Integer,
+
+        /// Caption of the asset as entered in the info panel of Photos.
+        ///
+        /// - `Photos.db` name: `ZLONGDESCRIPTION`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZLONGDESCRIPTION"]
+        long_description -> Nullable<VarChar>,
     }
 }
 
@@ -304,6 +510,70 @@ diesel::table! {
         /// - Type: `VARCHAR`
         #[sql_name = "ZCOMPACTUTI"]
         compact_uti -> VarChar,
+
+        /// Volume a referenced original is stored on, i.e. an original that has not been copied
+        /// into the library upon import. `NULL` for originals stored in the library.
+        ///
+        /// - `Photos.db` name: `ZFILESYSTEMVOLUME`
+        /// - Type: `INTEGER`
+        #[sql_name = "ZFILESYSTEMVOLUME"]
+        file_system_volume_id -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+
+    /// This table contains the locations of referenced originals, which are stored outside the
+    /// library, relative to the volume they are stored on.
+    ///
+    /// - `Photos.db` name: `ZFILESYSTEMBOOKMARK`
+    #[sql_name = "ZFILESYSTEMBOOKMARK"]
+    file_system_bookmarks (id) {
+
+        /// The primary key of the bookmark.
+        ///
+        /// - `Photos.db` name: `Z_PK`
+        /// - Type: `INTEGER`
+        #[sql_name = "Z_PK"]
+        id -> Integer,
+
+        /// Fingerprint of the internal resource of the referenced original.
+        ///
+        /// - `Photos.db` name: `ZRESOURCEFINGERPRINT`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZRESOURCEFINGERPRINT"]
+        resource_fingerprint -> Nullable<VarChar>,
+
+        /// Path of the referenced original relative to the root of its volume.
+        ///
+        /// - `Photos.db` name: `ZPATHRELATIVETOVOLUME`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZPATHRELATIVETOVOLUME"]
+        path_relative_to_volume -> Nullable<VarChar>,
+    }
+}
+
+diesel::table! {
+
+    /// This table contains the volumes referenced originals are stored on.
+    ///
+    /// - `Photos.db` name: `ZFILESYSTEMVOLUME`
+    #[sql_name = "ZFILESYSTEMVOLUME"]
+    file_system_volumes (id) {
+
+        /// The primary key of the volume.
+        ///
+        /// - `Photos.db` name: `Z_PK`
+        /// - Type: `INTEGER`
+        #[sql_name = "Z_PK"]
+        id -> Integer,
+
+        /// Name of the volume, which is mounted at `/Volumes/<name>` on macOS.
+        ///
+        /// - `Photos.db` name: `ZNAME`
+        /// - Type: `VARCHAR`
+        #[sql_name = "ZNAME"]
+        name -> Nullable<VarChar>,
     }
 }
 
@@ -329,10 +599,22 @@ diesel::table! {
         /// - Type: `INTEGER`
         #[sql_name = "Z_30ALBUMS"]
         album_id -> Integer,
+
+        /// Key defining the manual sort order of the assets in the album.
+        ///
+        /// Assets are sorted by this key in ascending order. Photos spaces the keys out so that
+        /// reordering an asset does not require updating all other keys of the album.
+        ///
+        /// - `Photos.db` name: `Z_FOK_3ASSETS`
+        /// - Type: `INTEGER`
+        #[sql_name = "Z_FOK_3ASSETS"]
+        sort_order -> Nullable<BigInt>,
     }
 }
 
 diesel::joinable!(asset_attributes -> assets (asset_id));
+diesel::joinable!(extended_attributes -> assets (asset_id));
+diesel::joinable!(asset_descriptions -> asset_attributes (asset_attributes_id));
 diesel::joinable!(album_assets -> assets (asset_id));
 diesel::joinable!(album_assets -> albums (album_id));
 
@@ -340,6 +622,11 @@ diesel::allow_tables_to_appear_in_same_query!(
     albums,
     assets,
     asset_attributes,
+    asset_descriptions,
+    extended_attributes,
     internal_resources,
-    album_assets
+    file_system_bookmarks,
+    file_system_volumes,
+    album_assets,
+    persons
 );
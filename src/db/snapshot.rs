@@ -0,0 +1,177 @@
+use std::fs::{copy, create_dir_all, remove_dir_all};
+use std::path::{Path, PathBuf};
+
+use diesel::connection::SimpleConnection;
+use diesel::{Connection, SqliteConnection};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use crate::db::connection::{to_immutable_uri, to_read_only_uri};
+
+const SNAPSHOT_FILENAME: &str = "Photos.sqlite";
+
+/// Copy of a Photos database in a temporary directory, which is removed when the snapshot is
+/// dropped.
+pub struct DatabaseSnapshot {
+    dir: PathBuf,
+}
+
+impl DatabaseSnapshot {
+
+    /// Copies the database along with its write-ahead log and merges the log into the copy, so
+    /// that the copy contains all committed changes and can be opened as immutable.
+    pub fn copy_with_wal(database_path: &str) -> Result<DatabaseSnapshot, String> {
+        let snapshot = DatabaseSnapshot::create_dir()?;
+        let path = snapshot.path();
+
+        copy_file(Path::new(database_path), &path)?;
+        let wal_path = format!("{}-wal", database_path);
+        if Path::new(&wal_path).is_file() {
+            copy_file(Path::new(&wal_path), Path::new(&format!("{}-wal", path.to_string_lossy())))?;
+        }
+
+        let mut conn = SqliteConnection::establish(&path.to_string_lossy())
+            .map_err(|e| format!("Unable to open snapshot of '{}': {}", database_path, e))?;
+        conn.batch_execute("PRAGMA wal_checkpoint(TRUNCATE); PRAGMA journal_mode = DELETE;")
+            .map_err(|e| format!("Unable to merge the write-ahead log of '{}': {}", database_path, e))?;
+
+        Ok(snapshot)
+    }
+
+    /// Writes a consistent copy of the database, including all changes committed to its write-ahead
+    /// log, using `VACUUM INTO`. Unlike copying the files, this does not race with Photos writing to
+    /// the database while the copy is being taken.
+    ///
+    /// The database is opened read-only, so that the library is never written to. Without a
+    /// write-ahead log, e.g. if Photos is not running, it is opened as immutable instead, as SQLite
+    /// would otherwise create the log and its index next to the database.
+    pub fn backup(database_path: &str) -> Result<DatabaseSnapshot, String> {
+        let snapshot = DatabaseSnapshot::create_dir()?;
+        let path = snapshot.path().to_string_lossy().replace('\'', "''");
+
+        let uri = if Path::new(&format!("{}-wal", database_path)).is_file() {
+            to_read_only_uri(database_path)
+        } else {
+            to_immutable_uri(database_path)
+        };
+        let mut conn = SqliteConnection::establish(&uri)
+            .map_err(|e| format!("Unable to open '{}': {}", database_path, e))?;
+        conn.batch_execute(&format!("VACUUM INTO '{}';", path))
+            .map_err(|e| format!("Unable to take a snapshot of '{}': {}", database_path, e))?;
+
+        Ok(snapshot)
+    }
+
+    /// Path of the database copy.
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(SNAPSHOT_FILENAME)
+    }
+
+    fn create_dir() -> Result<DatabaseSnapshot, String> {
+        let suffix: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+        let dir = std::env::temp_dir().join(format!("apple-photos-export-{}", suffix));
+
+        create_dir_all(&dir)
+            .map_err(|e| format!("Unable to create '{}': {}", dir.to_string_lossy(), e))?;
+
+        Ok(DatabaseSnapshot { dir })
+    }
+}
+
+impl Drop for DatabaseSnapshot {
+
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.dir);
+    }
+}
+
+fn copy_file(source: &Path, dest: &Path) -> Result<(), String> {
+    copy(source, dest)
+        .map(|_| ())
+        .map_err(|e| format!("Unable to copy '{}': {}", source.to_string_lossy(), e))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use diesel::dsl::count_star;
+    use diesel::prelude::*;
+
+    use super::*;
+    use crate::db::schema::assets;
+    use crate::test_util::library::{SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn includes_changes_of_the_write_ahead_log() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::jpeg(2));
+
+        // The log is merged into the database once the connection writing to it is closed
+        let mut writer = SqliteConnection::establish(&library.db_path()).unwrap();
+        writer.batch_execute("PRAGMA journal_mode = WAL; DELETE FROM ZASSET WHERE Z_PK = 2;").unwrap();
+        assert!(Path::new(&format!("{}-wal", library.db_path())).is_file());
+
+        let snapshot = DatabaseSnapshot::copy_with_wal(&library.db_path()).unwrap();
+        let dir = snapshot.dir.clone();
+        let mut conn = SqliteConnection::establish(
+            &format!("file:{}?immutable=1", snapshot.path().to_string_lossy())
+        ).unwrap();
+        let count: i64 = assets::table.select(count_star()).first(&mut conn).unwrap();
+
+        assert_eq!(count, 1);
+
+        drop(conn);
+        drop(snapshot);
+        assert!(!dir.exists());
+        drop(writer);
+    }
+
+    #[test]
+    fn backup_contains_all_committed_changes() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::jpeg(2));
+
+        let mut writer = SqliteConnection::establish(&library.db_path()).unwrap();
+        writer.batch_execute("PRAGMA journal_mode = WAL; DELETE FROM ZASSET WHERE Z_PK = 2;").unwrap();
+
+        let snapshot = DatabaseSnapshot::backup(&library.db_path()).unwrap();
+        let mut conn = SqliteConnection::establish(
+            &format!("file:{}?immutable=1", snapshot.path().to_string_lossy())
+        ).unwrap();
+        let count: i64 = assets::table.select(count_star()).first(&mut conn).unwrap();
+
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn backup_leaves_the_library_unchanged() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let database_dir = Path::new(&library.db_path()).parent().unwrap().to_path_buf();
+        SqliteConnection::establish(&library.db_path())
+            .unwrap()
+            .batch_execute("PRAGMA journal_mode = WAL;")
+            .unwrap();
+        let state = || {
+            let mut entries: Vec<_> = std::fs::read_dir(&database_dir)
+                .unwrap()
+                .map(|e| e.unwrap())
+                .map(|e| (e.file_name(), e.metadata().unwrap().modified().unwrap()))
+                .collect();
+            entries.sort();
+            (entries, database_dir.metadata().unwrap().modified().unwrap())
+        };
+        let before = state();
+
+        let snapshot = DatabaseSnapshot::backup(&library.db_path()).unwrap();
+
+        assert!(snapshot.path().is_file());
+        assert_eq!(state(), before);
+    }
+}
\ No newline at end of file
@@ -13,7 +13,7 @@ const MIN_SUPPORTED: u64 = 18000;
 const MAX_SUPPORTED: u64 = 18999;
 
 fn is_supported(model_version: u64) -> bool {
-    model_version >= MIN_SUPPORTED && model_version <= MAX_SUPPORTED
+    (MIN_SUPPORTED..=MAX_SUPPORTED).contains(&model_version)
 }
 
 
@@ -32,25 +32,41 @@ fn get_version_info(model_version: u64) -> VersionInfo {
 }
 
 
-pub fn check_library_version(database_path: &String) -> PhotosExportResult<()> {
+/// Checks that the library has been created by a supported version of Photos. Unsupported
+/// versions are only reported as a warning if they are explicitly allowed.
+pub fn check_library_version(database_path: &String, allow_unsupported: bool) -> PhotosExportResult<()> {
     let model_number: u64 = get_library_version(database_path)?;
 
     let library_version = get_version_info(model_number);
     let minimum_version = get_version_info(MIN_SUPPORTED);
 
     if is_supported(model_number) {
+        return Ok(());
+    }
+
+    let message = format!(
+        "Unsupported library version!\n\
+        - Your version is: {} (model version {})\n\
+        - The minimum supported version is: {}\n\
+        - See the project's README for more version information.",
+        library_version.name.to_string().italic(),
+        model_number,
+        minimum_version.name.to_string().italic()
+    );
+
+    if allow_unsupported {
+        eprintln!(
+            "{} {}\n\
+            - Proceeding anyway as --allow-unsupported-version is given. Queries may fail or assets may\n  \
+            be missing from the export, so please check the result carefully.",
+            "Warning:".yellow().bold(),
+            message
+        );
         Ok(())
     } else {
         Err(
             PhotosExportError::from(
-                format!(
-                    "Unsupported library version!\n\
-                    - Your version is: {}\n\
-                    - The minimum supported version is: {}\n\
-                    - See the project's README for more version information.",
-                    format!("{}", library_version.name).italic(),
-                    format!("{}", minimum_version.name).italic()
-                )
+                format!("{}\n- Pass --allow-unsupported-version to proceed at your own risk.", message)
             )
         )
     }
@@ -68,11 +84,45 @@ fn get_library_version(database_path: &String) -> Result<u64, String> {
     let cursor = Cursor::new(result.plist);
 
     let version = Value::from_reader(cursor)
-        .map_err(|e| format!("Unable to parse binary version plist: {}", e.to_string()))?
+        .map_err(|e| format!("Unable to parse binary version plist: {}", e))?
         .as_dictionary()
         .and_then(|dict| dict.get("PLModelVersion"))
         .and_then(|version| version.as_unsigned_integer())
         .ok_or("Unable to read model version from plist")?;
 
     Ok(version)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::library::SyntheticLibrary;
+
+    #[test]
+    fn reads_model_version() {
+        let library = SyntheticLibrary::with_model_version(18123);
+
+        assert_eq!(get_library_version(&library.db_path()), Ok(18123));
+    }
+
+    #[test]
+    fn accepts_supported_versions() {
+        let library = SyntheticLibrary::new();
+
+        assert!(check_library_version(&library.db_path(), false).is_ok());
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        let library = SyntheticLibrary::with_model_version(17000);
+
+        assert!(check_library_version(&library.db_path(), false).is_err());
+    }
+
+    #[test]
+    fn allows_unsupported_versions_on_request() {
+        let library = SyntheticLibrary::with_model_version(19000);
+
+        assert!(check_library_version(&library.db_path(), true).is_ok());
+    }
+}
@@ -0,0 +1,110 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use derive_new::new;
+
+use crate::export::copying::{CopyOperation, CopyOperationFactory};
+use crate::model::asset::ExportAsset;
+use crate::util::interaction::UserInteraction;
+
+/// Durations of the stages of planning an export, e.g. querying the database or each decorator
+/// building the copy operations, to measure performance regressions on real libraries.
+///
+/// Stages may be nested, in which case only the time not spent in nested stages is attributed to
+/// the outer one.
+#[derive(Default)]
+pub struct PlanningBenchmark {
+    /// Total durations of the stages in the order they first finished
+    stages: RefCell<Vec<(String, Duration)>>,
+    /// Time spent in nested stages, one entry per currently running stage
+    nested: RefCell<Vec<Duration>>,
+}
+
+impl PlanningBenchmark {
+
+    /// Runs the given function as part of the given stage.
+    pub fn time<T>(&self, stage: &str, f: impl FnOnce() -> T) -> T {
+        self.nested.borrow_mut().push(Duration::ZERO);
+        let started = Instant::now();
+        let result = f();
+        let elapsed = started.elapsed();
+
+        let mut nested = self.nested.borrow_mut();
+        let nested_time = nested.pop().unwrap_or_default();
+        if let Some(outer) = nested.last_mut() {
+            *outer += elapsed;
+        }
+        drop(nested);
+
+        self.record(stage, elapsed.saturating_sub(nested_time));
+        result
+    }
+
+    fn record(&self, stage: &str, duration: Duration) {
+        let mut stages = self.stages.borrow_mut();
+        match stages.iter_mut().find(|(name, _)| name == stage) {
+            Some((_, total)) => *total += duration,
+            None => stages.push((stage.to_string(), duration)),
+        }
+    }
+
+    /// Returns the stages along with their total durations in the order they first finished.
+    pub fn get_all(&self) -> Vec<(String, Duration)> {
+        self.stages.borrow().clone()
+    }
+
+    pub fn print(&self, interaction: &dyn UserInteraction) {
+        let stages = self.get_all();
+        let width = stages.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+        let total: Duration = stages.iter().map(|(_, duration)| *duration).sum();
+
+        interaction.info(&format!("\n{}", "Planning benchmark:".blue()));
+        for (name, duration) in &stages {
+            interaction.info(&format!(
+                "  {:width$}  {:>10.3}ms  {:>5.1}%",
+                name,
+                duration.as_secs_f64() * 1000.0,
+                100.0 * duration.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON),
+                width = width
+            ));
+        }
+        interaction.info(&format!("  {:width$}  {:>10.3}ms", "Total", total.as_secs_f64() * 1000.0, width = width));
+    }
+}
+
+/// Attributes the time the inner factory takes to build the copy operations of an asset to the
+/// given stage of the benchmark, excluding the time of inner factories timed themselves.
+#[derive(new)]
+pub struct TimingCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    stage: String,
+    benchmark: Rc<PlanningBenchmark>,
+}
+impl CopyOperationFactory for TimingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        self.benchmark.time(&self.stage, || self.inner.build(asset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    #[test]
+    fn excludes_nested_stages_from_outer_ones() {
+        let benchmark = PlanningBenchmark::default();
+
+        benchmark.time("outer", || {
+            benchmark.time("inner", || sleep(Duration::from_millis(20)));
+            benchmark.time("inner", || sleep(Duration::from_millis(20)));
+        });
+
+        let stages = benchmark.get_all();
+        assert_eq!(stages.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["inner", "outer"]);
+        assert!(stages[0].1 >= Duration::from_millis(40));
+        assert!(stages[1].1 < Duration::from_millis(20));
+    }
+}
\ No newline at end of file
@@ -1,25 +1,62 @@
-use std::fs::{copy, create_dir_all};
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::{copy, create_dir_all, metadata, read_dir, read_to_string, remove_file, rename, set_permissions, File, OpenOptions, Permissions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::process::Command;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::time::UNIX_EPOCH;
 
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
 use derive_new::new;
+use serde::{Deserialize, Serialize};
 
 use crate::export::structure::OutputStrategy;
-use crate::model::asset::ExportAsset;
-use crate::model::uti::Uti;
+use crate::model::asset::{ExportAsset, Orientation};
+use crate::model::uti::{MediaType, Uti};
+use crate::util::checksum::{fnv1a_32, sha256_file};
+use crate::util::interaction::UserInteraction;
+use crate::util::interrupt::is_interrupted;
+use crate::util::paths::{is_inside_library, to_folder_name};
 
-#[derive(new)]
+#[derive(new, Clone)]
 pub struct CopyOperation {
     pub source_path: PathBuf,
     pub uti: &'static Uti,
     pub output_filename: String,
     pub output_filename_suffix: Option<String>,
     pub output_folder: Option<PathBuf>,
+    /// UTI of the source file if it is converted to [Self::uti] while copying
+    #[new(default)]
+    pub converted_from: Option<&'static Uti>,
+    /// Orientation of the source file, applied to the pixels if it is converted
+    #[new(default)]
+    pub orientation: Orientation,
+    /// Folder of the album the file is exported to, if any, to date the folder after the export
+    #[new(default)]
+    pub album_folder: Option<AlbumFolder>,
+}
+
+/// Folder an album is exported to, given by the number of leading components of the output folder
+/// relative to the export root that make up the album folder, so that it is kept by decorators
+/// adding subfolders or shortening folder names.
+#[derive(new, Clone, Copy, Debug, PartialEq)]
+pub struct AlbumFolder {
+    pub depth: usize,
+    pub start_date: NaiveDateTime,
 }
 
 impl CopyOperation {
+
+    pub fn with_orientation(self, orientation: Orientation) -> Self {
+        CopyOperation { orientation, ..self }
+    }
+
     pub fn get_output_path(&self) -> PathBuf {
         PathBuf::new()
-            .join(self.output_folder.clone().unwrap_or(PathBuf::new()))
+            .join(self.output_folder.clone().unwrap_or_default())
             .join(
                 format!(
                     "{}{}.{}",
@@ -46,7 +83,7 @@ impl CopyOperationFactory for OriginalsCopyOperationFactory {
             asset.uuid.clone(),
             None,
             None,
-        );
+        ).with_orientation(asset.orientation);
         Ok(vec![operation])
     }
 }
@@ -72,6 +109,160 @@ impl CopyOperationFactory for DerivatesCopyOperationFactory {
     }
 }
 
+/// Derivates of a library that are missing on disk and have been replaced by their originals.
+#[derive(Default)]
+pub struct MissingDerivateRegistry {
+    derivate_paths: RefCell<BTreeSet<PathBuf>>,
+}
+
+impl MissingDerivateRegistry {
+
+    /// Returns the paths of the missing derivates relative to the library root.
+    pub fn get_all(&self) -> Vec<PathBuf> {
+        self.derivate_paths.borrow().iter().cloned().collect()
+    }
+}
+
+/// Replaces copy operations of derivates that are missing on disk, e.g. because Photos has not
+/// rendered the edit yet, with the copy operations of the asset's original so that the asset is
+/// still exported. Replaced derivates are recorded in the given registry.
+///
+/// This decorator expects the source paths to still be relative to the library root.
+#[derive(new)]
+pub struct OriginalFallbackCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    library_path: PathBuf,
+    registry: Rc<MissingDerivateRegistry>,
+}
+impl CopyOperationFactory for OriginalFallbackCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let derivate_path = asset.get_derivate_path();
+        let mut operations = Vec::new();
+
+        for op in self.inner.build(asset)? {
+            let is_missing_derivate = Some(&op.source_path) == derivate_path.as_ref()
+                && !self.library_path.join(&op.source_path).exists();
+
+            if is_missing_derivate {
+                self.registry.derivate_paths.borrow_mut().insert(op.source_path);
+                operations.extend(OriginalsCopyOperationFactory::new().build(asset)?);
+            } else {
+                operations.push(op);
+            }
+        }
+
+        Ok(operations)
+    }
+}
+
+/// Originals that are missing at their default location and have been found at an alternative one.
+#[derive(Default)]
+pub struct RelocatedOriginalRegistry {
+    originals: RefCell<BTreeMap<PathBuf, PathBuf>>,
+}
+
+impl RelocatedOriginalRegistry {
+
+    /// Returns the default paths of the relocated originals along with the paths they have been
+    /// found at, both relative to the library root.
+    pub fn get_all(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.originals.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+}
+
+/// Replaces the source paths of originals missing at their default location with the first of the
+/// asset's alternative locations they exist at, recording the relocation in the given registry.
+/// Originals missing at all locations are left unchanged, so that copying them fails as before.
+///
+/// This decorator expects the source paths to still be relative to the library root.
+#[derive(new)]
+pub struct OriginalResolvingCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    library_path: PathBuf,
+    registry: Rc<RelocatedOriginalRegistry>,
+}
+impl CopyOperationFactory for OriginalResolvingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let original_path = asset.get_path();
+
+        Ok(
+            self.inner
+                .build(asset)?
+                .into_iter()
+                .map(|op| {
+                    if op.source_path != original_path || self.library_path.join(&op.source_path).exists() {
+                        return op;
+                    }
+
+                    let relocated = asset
+                        .get_path_candidates()
+                        .into_iter()
+                        .skip(1)
+                        .find(|candidate| self.library_path.join(candidate).exists());
+
+                    match relocated {
+                        Some(source_path) => {
+                            self.registry.originals.borrow_mut().insert(original_path.clone(), source_path.clone());
+                            CopyOperation { source_path, ..op }
+                        },
+                        None => op,
+                    }
+                })
+                .collect()
+        )
+    }
+}
+
+/// Builds copy operations for the companion resources of an asset's original, e.g. HDR gain maps
+/// or depth data, which Photos stores next to the original as `<uuid>_<suffix>.<extension>`.
+///
+/// Companion resources are exported with the output filename suffixed by `_companion` and the
+/// internal suffix of the resource. Resources with unknown file types are skipped.
+#[derive(new)]
+pub struct CompanionResourcesCopyOperationFactory {
+    library_path: PathBuf,
+}
+impl CopyOperationFactory for CompanionResourcesCopyOperationFactory {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let original_dir = asset.get_path()
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        let prefix = format!("{}_", asset.uuid);
+
+        let entries = match read_dir(self.library_path.join(&original_dir)) {
+            Ok(entries) => entries,
+            // Missing originals are reported when copying the original itself
+            Err(_) => return Ok(vec![]),
+        };
+
+        let mut operations: Vec<CopyOperation> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with(&prefix))
+            .filter_map(|name| {
+                let uti = Uti::from_filename(&name).ok()?;
+                let stem = PathBuf::from(&name).file_stem()?.to_string_lossy().to_string();
+                let internal_suffix = stem.strip_prefix(&asset.uuid)?.to_string();
+
+                Some(
+                    CopyOperation::new(
+                        original_dir.join(&name),
+                        uti,
+                        asset.uuid.clone(),
+                        Some(format!("_companion{}", internal_suffix)),
+                        None,
+                    )
+                )
+            })
+            .collect();
+
+        operations.sort_by_key(|op| op.source_path.clone());
+
+        Ok(operations)
+    }
+}
+
 #[derive(new)]
 pub struct CombiningCopyOperationFactory {
     factories: Vec<Box<dyn CopyOperationFactory>>,
@@ -93,6 +284,85 @@ impl CopyOperationFactory for CombiningCopyOperationFactory {
     }
 }
 
+pub type AssetPredicateFunc = Box<dyn Fn(&ExportAsset) -> bool>;
+
+/// Only delegates to the inner factory if the given predicate matches the asset.
+#[derive(new)]
+pub struct FilteringCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    predicate: AssetPredicateFunc,
+}
+impl CopyOperationFactory for FilteringCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        if (self.predicate)(asset) {
+            self.inner.build(asset)
+        } else {
+            Ok(vec![])
+        }
+    }
+}
+
+/// File types copy operations are filtered by.
+pub enum UtiFilter {
+    Include(Vec<&'static Uti>),
+    Exclude(Vec<&'static Uti>),
+}
+
+impl UtiFilter {
+    fn matches(&self, uti: &Uti) -> bool {
+        let contains = |utis: &[&'static Uti]| utis.iter().any(|u| u.uti == uti.uti);
+        match self {
+            UtiFilter::Include(utis) => contains(utis),
+            UtiFilter::Exclude(utis) => !contains(utis),
+        }
+    }
+}
+
+/// Drops the copy operations whose source file type does not match the filter. Types are compared
+/// by their UTI, so `jpg` and `jpeg` are equivalent, and originals and derivates of the same asset
+/// are filtered independently.
+///
+/// This decorator expects the copy operations not to be marked for conversion yet.
+#[derive(new)]
+pub struct UtiFilteringCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    filter: UtiFilter,
+}
+impl CopyOperationFactory for UtiFilteringCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        Ok(
+            self.inner
+                .build(asset)?
+                .into_iter()
+                .filter(|op| self.filter.matches(op.uti))
+                .collect()
+        )
+    }
+}
+
+/// Cameras assets are filtered by, given by their name or model, e.g. `FUJIFILM X-T5` or `X-T5`.
+/// Names are compared ignoring case.
+pub enum CameraFilter {
+    Include(Vec<String>),
+    Exclude(Vec<String>),
+}
+
+impl CameraFilter {
+
+    /// Whether the asset has been taken with a camera of the filter. Assets without camera
+    /// information are only matched by exclusions.
+    pub fn matches(&self, asset: &ExportAsset) -> bool {
+        let names = [asset.get_camera_name(), asset.camera_model.as_ref().map(|m| m.trim().to_string())];
+        let contains = |cameras: &[String]| cameras
+            .iter()
+            .any(|camera| names.iter().flatten().any(|name| name.eq_ignore_ascii_case(camera.trim())));
+        match self {
+            CameraFilter::Include(cameras) => contains(cameras),
+            CameraFilter::Exclude(cameras) => !contains(cameras),
+        }
+    }
+}
+
 #[derive(new)]
 pub struct FilenameRestoringCopyOperationFactoryDecorator {
     inner: Box<dyn CopyOperationFactory>,
@@ -103,14 +373,8 @@ impl CopyOperationFactory for FilenameRestoringCopyOperationFactoryDecorator {
             .build(asset)?
             .into_iter()
             .map(|op| {
-                let original_filename_stem = PathBuf::from(&asset.original_filename)
-                    .file_stem()
-                    .ok_or("Failed to get file stem")?
-                    .to_string_lossy()
-                    .to_string();
-
                 Ok(CopyOperation {
-                    output_filename: original_filename_stem,
+                    output_filename: original_filename_stem(asset)?,
                     ..op
                 })
             })
@@ -118,19 +382,80 @@ impl CopyOperationFactory for FilenameRestoringCopyOperationFactoryDecorator {
     }
 }
 
+fn original_filename_stem(asset: &ExportAsset) -> Result<String, String> {
+    Ok(
+        PathBuf::from(&asset.original_filename)
+            .file_stem()
+            .ok_or("Failed to get file stem")?
+            .to_string_lossy()
+            .to_string()
+    )
+}
+
+/// Names output files after the title of their asset, e.g. `Sunset at the beach.jpeg`, falling
+/// back to the filename the asset has been imported with if it has no title. Suffixes like
+/// `_edited` are kept.
 #[derive(new)]
-pub struct OutputStructureCopyOperationFactoryDecorator {
+pub struct TitleNamingCopyOperationFactoryDecorator {
     inner: Box<dyn CopyOperationFactory>,
-    strategy: Box<dyn OutputStrategy>,
 }
-impl CopyOperationFactory for OutputStructureCopyOperationFactoryDecorator {
+impl CopyOperationFactory for TitleNamingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let output_filename = match asset.title.as_deref().map(sanitize_title) {
+            Some(title) if !title.is_empty() => title,
+            _ => original_filename_stem(asset)?,
+        };
+
+        Ok(
+            self.inner
+                .build(asset)?
+                .into_iter()
+                .map(|op| {
+                    CopyOperation {
+                        output_filename: output_filename.clone(),
+                        ..op
+                    }
+                })
+                .collect()
+        )
+    }
+}
+
+/// Replaces the characters that are not allowed in filenames on common file systems, e.g. `/` or
+/// `:`, with an underscore. Surrounding whitespace and dots are removed so that titles do not
+/// result in hidden files.
+fn sanitize_title(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect::<String>()
+        .trim_matches(|c: char| c.is_whitespace() || c == '.')
+        .to_string()
+}
+
+/// Names output files after the UUID of their asset, optionally prefixed with the date of the
+/// asset, e.g. `2024-06-14_<uuid>.jpeg`. As UUIDs are unique and never change, the same asset is
+/// exported to the same filename in every run, which keeps successive exports comparable by tools
+/// like rsync. Suffixes like `_edited` are kept.
+#[derive(new)]
+pub struct StableNamingCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    date_prefix: bool,
+}
+impl CopyOperationFactory for StableNamingCopyOperationFactoryDecorator {
     fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let output_filename = if self.date_prefix {
+            format!("{}_{}", asset.datetime.format("%Y-%m-%d"), asset.uuid)
+        } else {
+            asset.uuid.clone()
+        };
+
         let operations = self.inner
             .build(asset)?
             .into_iter()
             .map(|op| {
                 CopyOperation {
-                    output_folder: self.strategy.get_relative_output_dir(asset).ok(),
+                    output_filename: output_filename.clone(),
                     ..op
                 }
             })
@@ -140,25 +465,24 @@ impl CopyOperationFactory for OutputStructureCopyOperationFactoryDecorator {
     }
 }
 
+/// Prefixes the output filenames of assets in an album with their position in the album's manual
+/// sort order, e.g. `0001_IMG_1234.jpeg`, so that sorting the files by name restores the order.
 #[derive(new)]
-pub struct AbsolutePathBuildingCopyOperationFactoryDecorator {
-    library_path: PathBuf,
-    output_folder: PathBuf,
+pub struct AlbumOrderPrefixingCopyOperationFactoryDecorator {
     inner: Box<dyn CopyOperationFactory>,
 }
-impl CopyOperationFactory for AbsolutePathBuildingCopyOperationFactoryDecorator {
+impl CopyOperationFactory for AlbumOrderPrefixingCopyOperationFactoryDecorator {
     fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
         let operations = self.inner
             .build(asset)?
             .into_iter()
             .map(|op| {
-                CopyOperation {
-                    source_path: self.library_path.join(&op.source_path),
-                    output_folder: Some(
-                        self.output_folder.clone()
-                            .join(&op.output_folder.unwrap_or(PathBuf::new()))
-                    ),
-                    ..op
+                match asset.album_position {
+                    Some(position) => CopyOperation {
+                        output_filename: format!("{:04}_{}", position, op.output_filename),
+                        ..op
+                    },
+                    None => op
                 }
             })
             .collect();
@@ -167,20 +491,64 @@ impl CopyOperationFactory for AbsolutePathBuildingCopyOperationFactoryDecorator
     }
 }
 
+pub const FAVORITES_FOLDER_NAME: &str = "_favorites";
+
+/// Additionally exports favorite assets to a `_favorites` folder in the output directory, next to
+/// their regular location. Assets part of multiple albums are only added to the folder once.
+///
+/// This decorator expects the output folder to still be relative to the export root.
+#[derive(new)]
+pub struct FavoritesFolderCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    #[new(default)]
+    exported_sources: RefCell<HashSet<PathBuf>>,
+}
+impl CopyOperationFactory for FavoritesFolderCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let operations = self.inner.build(asset)?;
+
+        if !asset.favorite {
+            return Ok(operations);
+        }
+
+        let favorites: Vec<CopyOperation> = operations
+            .iter()
+            .filter(|op| self.exported_sources.borrow_mut().insert(op.source_path.clone()))
+            .map(|op| {
+                CopyOperation {
+                    output_folder: Some(PathBuf::from(FAVORITES_FOLDER_NAME)),
+                    album_folder: None,
+                    ..op.clone()
+                }
+            })
+            .collect();
+
+        Ok([operations, favorites].concat())
+    }
+}
+
+/// Moves RAW files into a subfolder of their output folder, e.g. to store the RAW files of RAW+JPEG
+/// pairs apart from the JPEGs while keeping them in the same album or date folder.
+///
+/// This decorator expects the output folder to still be relative to the export root.
 #[derive(new)]
-pub struct SuffixSettingCopyOperationFactoryDecorator {
+pub struct RawSubfolderCopyOperationFactoryDecorator {
     inner: Box<dyn CopyOperationFactory>,
-    suffix: String,
+    subfolder: String,
 }
-impl CopyOperationFactory for SuffixSettingCopyOperationFactoryDecorator {
+impl CopyOperationFactory for RawSubfolderCopyOperationFactoryDecorator {
     fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
         let operations = self.inner
             .build(asset)?
             .into_iter()
             .map(|op| {
-                CopyOperation {
-                    output_filename_suffix: Some(self.suffix.clone()),
-                    ..op
+                if op.uti.is_raw() {
+                    CopyOperation {
+                        output_folder: Some(op.output_folder.clone().unwrap_or_default().join(&self.subfolder)),
+                        ..op
+                    }
+                } else {
+                    op
                 }
             })
             .collect();
@@ -189,32 +557,1773 @@ impl CopyOperationFactory for SuffixSettingCopyOperationFactoryDecorator {
     }
 }
 
+/// Output filenames and source files already used by a flat export, shared between the factories
+/// of all exported libraries.
+#[derive(Default)]
+pub struct FlatOutputRegistry {
+    /// Lowercase output filenames, as most file systems of picture frames are case-insensitive
+    filenames: RefCell<HashSet<String>>,
+    sources: RefCell<HashSet<PathBuf>>,
+}
+
+/// Places all assets directly in the output directory for use on digital picture frames.
+///
+/// Output filenames are prefixed with the date of the asset so that sorting them by name sorts
+/// them chronologically, and characters that are not safe on all file systems are replaced.
+/// Assets part of multiple albums are only exported once. Remaining name conflicts are resolved
+/// by appending a counter to the filename.
+#[derive(new)]
+pub struct FlatFrameCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    library_path: PathBuf,
+    registry: Rc<FlatOutputRegistry>,
+}
+
+impl FlatFrameCopyOperationFactoryDecorator {
 
-pub trait AssetCopyStrategy {
+    fn make_unique(&self, op: CopyOperation) -> CopyOperation {
+        let mut filenames = self.registry.filenames.borrow_mut();
 
-    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error>;
+        let mut candidate = op;
+        let base_filename = candidate.output_filename.clone();
+        let mut counter = 1;
+        while !filenames.insert(candidate.get_output_path().to_string_lossy().to_lowercase()) {
+            counter += 1;
+            candidate = CopyOperation {
+                output_filename: format!("{}_{}", base_filename, counter),
+                ..candidate
+            };
+        }
+
+        candidate
+    }
+}
+
+impl CopyOperationFactory for FlatFrameCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let date_prefix = asset.datetime.format("%Y-%m-%d_%H%M%S");
+
+        let operations = self.inner
+            .build(asset)?
+            .into_iter()
+            .filter(|op| {
+                self.registry.sources
+                    .borrow_mut()
+                    .insert(self.library_path.join(&op.source_path))
+            })
+            .map(|op| {
+                let op = CopyOperation {
+                    output_filename: format!(
+                        "{}_{}",
+                        date_prefix,
+                        sanitize_filename(&op.output_filename)
+                    ),
+                    output_folder: None,
+                    album_folder: None,
+                    ..op
+                };
+                self.make_unique(op)
+            })
+            .collect();
+
+        Ok(operations)
+    }
+}
+
+/// Replaces all characters except ASCII letters, digits, `-` and `_` with an underscore.
+fn sanitize_filename(filename: &str) -> String {
+    filename
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
 }
 
 #[derive(new)]
-pub struct DryRunAssetCopyStrategy;
-impl AssetCopyStrategy for DryRunAssetCopyStrategy {
+pub struct OutputStructureCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    strategy: Box<dyn OutputStrategy>,
+}
+impl CopyOperationFactory for OutputStructureCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let operations = self.inner
+            .build(asset)?
+            .into_iter()
+            .map(|op| {
+                let output_folder = self.strategy.get_relative_output_dir(asset).ok();
+                let album_folder = match (&output_folder, asset.album.as_ref().and_then(|a| a.start_date)) {
+                    (Some(folder), Some(start_date)) if self.strategy.is_album_folder(asset) => {
+                        Some(AlbumFolder::new(folder.components().count(), start_date))
+                    },
+                    _ => None,
+                };
+                CopyOperation { output_folder, album_folder, ..op }
+            })
+            .collect();
 
-    fn copy_asset(&self, _: &CopyOperation) -> Result<u64, std::io::Error> {
-        // do nothing - dry run
-        Ok(0)
+        Ok(operations)
     }
 }
 
+pub const DATE_TREE_FOLDER_NAME: &str = "_by_date";
+
+/// Additionally exports assets to a date tree in a `_by_date` folder in the output directory, so
+/// that an export grouped by album also contains a view of all assets independent of albums.
+///
+/// Each asset is placed in the date tree once, even if it is part of multiple albums. Assets not
+/// part of any album are only placed in the date tree.
+///
+/// This decorator expects the output folder to still be relative to the export root.
 #[derive(new)]
-pub struct DefaultAssetCopyStrategy;
-impl AssetCopyStrategy for DefaultAssetCopyStrategy {
+pub struct DateTreeCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    strategy: Box<dyn OutputStrategy>,
+    #[new(default)]
+    exported_sources: RefCell<HashSet<PathBuf>>,
+}
+impl CopyOperationFactory for DateTreeCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let operations = self.inner.build(asset)?;
+        let date_folder = PathBuf::from(DATE_TREE_FOLDER_NAME).join(self.strategy.get_relative_output_dir(asset)?);
 
-    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error> {
-        let dest = copy_operation.get_output_path();
+        let dated: Vec<CopyOperation> = operations
+            .iter()
+            .filter(|op| self.exported_sources.borrow_mut().insert(op.source_path.clone()))
+            .map(|op| {
+                CopyOperation {
+                    output_folder: Some(date_folder.clone()),
+                    album_folder: None,
+                    ..op.clone()
+                }
+            })
+            .collect();
+
+        if asset.album.is_none() {
+            Ok(dated)
+        } else {
+            Ok([operations, dated].concat())
+        }
+    }
+}
 
-        if let Some(parent) = dest.parent() {
-            create_dir_all(parent)?
+pub const NO_KEYWORDS_FOLDER_NAME: &str = "No keywords";
+
+/// Exports assets to one folder per keyword assigned to them, e.g. `Beach/`, so that assets with
+/// multiple keywords are exported multiple times. Assets without keywords are placed in a
+/// `No keywords` folder.
+///
+/// This decorator expects the output folder to still be relative to the export root.
+#[derive(new)]
+pub struct KeywordGroupingCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+}
+impl CopyOperationFactory for KeywordGroupingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let mut folders: Vec<String> = vec![];
+        for keyword in &asset.keywords {
+            let folder = to_folder_name(keyword.trim());
+            if !folder.is_empty() && !folders.iter().any(|f| f.eq_ignore_ascii_case(&folder)) {
+                folders.push(folder);
+            }
+        }
+        if folders.is_empty() {
+            folders.push(NO_KEYWORDS_FOLDER_NAME.to_string());
         }
-        copy(&copy_operation.source_path, &dest)
+
+        let operations = self.inner
+            .build(asset)?
+            .into_iter()
+            .flat_map(|op| {
+                folders
+                    .iter()
+                    .map(|folder| {
+                        CopyOperation {
+                            output_folder: Some(op.output_folder.clone().unwrap_or_default().join(folder)),
+                            ..op.clone()
+                        }
+                    })
+                    .collect::<Vec<CopyOperation>>()
+            })
+            .collect();
+
+        Ok(operations)
+    }
+}
+
+const REFERENCED_FOLDER_NAME: &str = "referenced";
+
+/// Exports files to the same path relative to the output directory as they have relative to the
+/// library, e.g. `originals/A/<uuid>.jpeg`, so that the export can be compared to the library
+/// directly. Referenced originals, which are stored outside the library, are exported to their
+/// absolute path below `referenced`, e.g. `referenced/Volumes/Photos/2010/IMG_0001.jpeg`.
+///
+/// This decorator expects the source paths to still be relative to the library root.
+#[derive(new)]
+pub struct LibraryStructureCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+}
+impl CopyOperationFactory for LibraryStructureCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        self.inner
+            .build(asset)?
+            .into_iter()
+            .map(|op| {
+                let stem = op.source_path
+                    .file_stem()
+                    .ok_or("Failed to get file stem")?
+                    .to_string_lossy()
+                    .to_string();
+
+                let output_folder = op.source_path.parent().map(|parent| match parent.strip_prefix("/") {
+                    Ok(referenced) => Path::new(REFERENCED_FOLDER_NAME).join(referenced),
+                    Err(_) => parent.to_path_buf(),
+                });
+
+                Ok(CopyOperation {
+                    output_filename: stem,
+                    output_filename_suffix: None,
+                    output_folder,
+                    album_folder: None,
+                    ..op
+                })
+            })
+            .collect::<Result<Vec<CopyOperation>, String>>()
+    }
+}
+
+#[derive(new)]
+pub struct AbsolutePathBuildingCopyOperationFactoryDecorator {
+    library_path: PathBuf,
+    output_folder: PathBuf,
+    inner: Box<dyn CopyOperationFactory>,
+}
+impl CopyOperationFactory for AbsolutePathBuildingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let operations = self.inner
+            .build(asset)?
+            .into_iter()
+            .map(|op| {
+                CopyOperation {
+                    source_path: self.library_path.join(&op.source_path),
+                    output_folder: Some(
+                        self.output_folder.clone()
+                            .join(op.output_folder.unwrap_or(PathBuf::new()))
+                    ),
+                    ..op
+                }
+            })
+            .collect();
+
+        Ok(operations)
+    }
+}
+
+#[derive(new)]
+pub struct SuffixSettingCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    suffix: String,
+}
+impl CopyOperationFactory for SuffixSettingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let operations = self.inner
+            .build(asset)?
+            .into_iter()
+            .map(|op| {
+                CopyOperation {
+                    output_filename_suffix: Some(self.suffix.clone()),
+                    ..op
+                }
+            })
+            .collect();
+
+        Ok(operations)
+    }
+}
+
+
+/// Marks copy operations of proprietary RAW files to be converted to DNG while copying, which
+/// also changes the extension of their output path.
+#[derive(new)]
+pub struct RawConvertingCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+}
+impl CopyOperationFactory for RawConvertingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let operations = self.inner
+            .build(asset)?
+            .into_iter()
+            .map(|op| {
+                if op.uti.is_proprietary_raw() {
+                    CopyOperation {
+                        uti: Uti::dng(),
+                        converted_from: Some(op.uti),
+                        ..op
+                    }
+                } else {
+                    op
+                }
+            })
+            .collect();
+
+        Ok(operations)
+    }
+}
+
+/// Marks copy operations of videos to be transcoded while copying. The container and therefore the
+/// extension of the output path is kept, so that a video can still be copied unchanged to the same
+/// path if transcoding fails.
+#[derive(new)]
+pub struct VideoTranscodingCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+}
+impl CopyOperationFactory for VideoTranscodingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let operations = self.inner
+            .build(asset)?
+            .into_iter()
+            .map(|op| {
+                if op.uti.media_type == MediaType::Video && op.converted_from.is_none() {
+                    CopyOperation {
+                        converted_from: Some(op.uti),
+                        ..op
+                    }
+                } else {
+                    op
+                }
+            })
+            .collect();
+
+        Ok(operations)
+    }
+}
+
+/// Number of characters reserved for the filename when shortening the output folder.
+const RESERVED_FILENAME_LENGTH: usize = 32;
+const SHORTENED_HASH_SEPARATOR: char = '~';
+
+/// Shortens the output paths of copy operations that exceed a maximum number of characters.
+///
+/// The output folder is shortened independently of the filename so that all assets of a folder
+/// are still placed in the same folder afterward. Components are shortened by truncating them and
+/// appending a hash of the full name to keep them unique.
+///
+/// This decorator expects the output folder to still be relative to the export root.
+#[derive(new)]
+pub struct PathLengthLimitingCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    max_length: usize,
+    /// Interaction to warn about each path that exceeds the maximum length on, if any
+    warnings: Option<Rc<dyn UserInteraction>>,
+}
+
+impl PathLengthLimitingCopyOperationFactoryDecorator {
+
+    fn limit_path_length(&self, op: CopyOperation) -> CopyOperation {
+        let original_path = op.get_output_path();
+        let original_folder = op.output_folder.clone().unwrap_or_default();
+
+        // The folder is shortened regardless of the filename so that all of its assets are
+        // placed in the same shortened folder
+        let folder = self.shorten_folder(original_folder.clone());
+
+        let folder_length = path_length(&folder);
+        let separator_length = if folder_length > 0 { 1 } else { 0 };
+        let filename_length = original_path
+            .file_name()
+            .map(|name| name.to_string_lossy().chars().count())
+            .unwrap_or(0);
+
+        let available = self.max_length.saturating_sub(folder_length + separator_length);
+        let output_filename = if filename_length > available {
+            let excess = filename_length - available;
+            let stem_length = op.output_filename.chars().count();
+            shorten_component(&op.output_filename, stem_length.saturating_sub(excess))
+        } else {
+            op.output_filename.clone()
+        };
+
+        if folder == original_folder && output_filename == op.output_filename {
+            return op;
+        }
+
+        let shortened = CopyOperation {
+            output_folder: Some(folder),
+            output_filename,
+            ..op
+        };
+
+        if let Some(warnings) = &self.warnings {
+            let shortened_path = shortened.get_output_path();
+            let length = path_length(&shortened_path);
+            if length > self.max_length {
+                warnings.warn(&format!(
+                    "Output path '{}' exceeds {} characters and can not be shortened any further",
+                    shortened_path.to_string_lossy(),
+                    self.max_length
+                ));
+            } else {
+                warnings.warn(&format!(
+                    "Output path '{}' is shortened to '{}' to stay within {} characters",
+                    original_path.to_string_lossy(),
+                    shortened_path.to_string_lossy(),
+                    self.max_length
+                ));
+            }
+        }
+
+        shortened
+    }
+
+    /// Shortens the components of the given folder to a common maximum length so that it leaves
+    /// enough room for a filename.
+    fn shorten_folder(&self, folder: PathBuf) -> PathBuf {
+        let max_folder_length = self.max_length.saturating_sub(RESERVED_FILENAME_LENGTH + 1);
+
+        let components: Vec<String> = folder
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                _ => None
+            })
+            .collect();
+
+        let shorten_all = |max_component_length: usize| -> Vec<String> {
+            components
+                .iter()
+                .map(|c| shorten_component(c, max_component_length))
+                .collect()
+        };
+        let joined_length = |components: &Vec<String>| {
+            components.iter().map(|c| c.chars().count()).sum::<usize>()
+                + components.len().saturating_sub(1)
+        };
+
+        let longest = components.iter().map(|c| c.chars().count()).max().unwrap_or(0);
+
+        // Find the largest common component length for which the folder fits
+        let shortened = (0..=longest)
+            .rev()
+            .map(shorten_all)
+            .find(|shortened| joined_length(shortened) <= max_folder_length)
+            .unwrap_or_else(|| shorten_all(0));
+
+        shortened.iter().collect()
+    }
+}
+
+impl CopyOperationFactory for PathLengthLimitingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let operations = self.inner
+            .build(asset)?
+            .into_iter()
+            .map(|op| self.limit_path_length(op))
+            .collect();
+
+        Ok(operations)
+    }
+}
+
+fn path_length(path: &std::path::Path) -> usize {
+    path.to_string_lossy().chars().count()
+}
+
+/// Truncates the given name to at most `max_length` characters, appending a hash of the full name.
+///
+/// Names that already fit are returned unchanged. The result may be longer than `max_length` if
+/// it is too small to contain the hash.
+fn shorten_component(name: &str, max_length: usize) -> String {
+    if name.chars().count() <= max_length {
+        return name.to_string();
+    }
+
+    let hash = format!("{:08x}", fnv1a_32(name.as_bytes()));
+    let prefix_length = max_length.saturating_sub(hash.len() + 1);
+
+    if prefix_length == 0 {
+        hash
+    } else {
+        let prefix: String = name.chars().take(prefix_length).collect();
+        format!("{}{}{}", prefix.trim_end(), SHORTENED_HASH_SEPARATOR, hash)
+    }
+}
+
+
+
+const PARTIAL_FILE_EXTENSION: &str = ".part";
+
+pub trait AssetCopyStrategy {
+
+    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error>;
+}
+
+#[derive(new)]
+pub struct DryRunAssetCopyStrategy;
+impl AssetCopyStrategy for DryRunAssetCopyStrategy {
+
+    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error> {
+        // Nothing is copied, but the size of the source is reported as it would have been copied
+        Ok(metadata(&copy_operation.source_path).map(|m| m.len()).unwrap_or(0))
+    }
+}
+
+#[derive(new)]
+pub struct DefaultAssetCopyStrategy;
+impl AssetCopyStrategy for DefaultAssetCopyStrategy {
+
+    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error> {
+        write_through_partial_file(&copy_operation.get_output_path(), |partial| {
+            copy(&copy_operation.source_path, partial)
+        })
+    }
+}
+
+/// Writes the destination file using the given function, creating its parent directories.
+///
+/// The file is written to a temporary name first so that an interrupted copy never leaves a
+/// truncated file under the final name.
+fn write_through_partial_file<F>(dest: &Path, write: F) -> Result<u64, std::io::Error>
+where
+    F: FnOnce(&Path) -> Result<u64, std::io::Error>
+{
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent)?
+    }
+
+    let partial = with_name_suffix(dest, PARTIAL_FILE_EXTENSION);
+
+    let bytes = write(&partial)
+        .and_then(|bytes| rename(&partial, dest).map(|_| bytes));
+
+    if bytes.is_err() {
+        let _ = remove_file(&partial);
+    }
+    bytes
+}
+
+/// Minimum size of the files copied in chunks by [ResumableAssetCopyStrategyDecorator].
+pub const RESUMABLE_COPY_MIN_SIZE: u64 = 64 * 1024 * 1024;
+const RESUMABLE_COPY_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+const COPY_PROGRESS_FILE_EXTENSION: &str = ".offset";
+
+/// Progress of a chunked copy, stored next to its partial file.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+struct CopyProgress {
+    source_size: u64,
+    /// Modification time of the source in seconds since the epoch, to detect changed sources
+    source_modified: u64,
+    /// Number of bytes copied to the partial file so far
+    offset: u64,
+}
+
+/// Copies files of at least the given size in chunks, recording the number of bytes copied next
+/// to the partial file after each chunk. A copy stopped by an interruption, or by the process
+/// being killed, is continued from there by the next run, as long as the source is unchanged.
+///
+/// The copy is validated by comparing its checksum to the source before it is moved to its final
+/// name. Smaller files are copied by the inner strategy.
+#[derive(new)]
+pub struct ResumableAssetCopyStrategyDecorator {
+    inner: Box<dyn AssetCopyStrategy>,
+    min_size: u64,
+}
+
+impl AssetCopyStrategy for ResumableAssetCopyStrategyDecorator {
+
+    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error> {
+        let source = metadata(&copy_operation.source_path)?;
+        if source.len() < self.min_size {
+            return self.inner.copy_asset(copy_operation);
+        }
+
+        let source_modified = source
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|modified| modified.as_secs())
+            .unwrap_or_default();
+
+        copy_resumably(
+            &copy_operation.source_path,
+            &copy_operation.get_output_path(),
+            CopyProgress { source_size: source.len(), source_modified, offset: 0 }
+        )
+    }
+}
+
+fn copy_resumably(source_path: &Path, dest: &Path, initial: CopyProgress) -> Result<u64, std::io::Error> {
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent)?
+    }
+
+    let partial = with_name_suffix(dest, PARTIAL_FILE_EXTENSION);
+    let progress_path = with_name_suffix(&partial, COPY_PROGRESS_FILE_EXTENSION);
+
+    // Progress of a copy of a different version of the source is discarded
+    let recorded_offset = read_to_string(&progress_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<CopyProgress>(&content).ok())
+        .filter(|progress| CopyProgress { offset: 0, ..*progress } == initial)
+        .map(|progress| progress.offset)
+        .unwrap_or(0);
+
+    let mut partial_file = OpenOptions::new().create(true).truncate(false).write(true).open(&partial)?;
+    let mut offset = recorded_offset.min(partial_file.metadata()?.len());
+    partial_file.set_len(offset)?;
+    partial_file.seek(SeekFrom::Start(offset))?;
+
+    let mut source = File::open(source_path)?;
+    source.seek(SeekFrom::Start(offset))?;
+
+    let mut chunk = Vec::new();
+    loop {
+        if is_interrupted() {
+            return Err(
+                std::io::Error::new(
+                    ErrorKind::Interrupted,
+                    format!("Copy stopped after {} of {} bytes, the next run continues it", offset, initial.source_size)
+                )
+            );
+        }
+
+        chunk.clear();
+        let read = (&mut source).take(RESUMABLE_COPY_CHUNK_SIZE).read_to_end(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        partial_file.write_all(&chunk)?;
+        partial_file.sync_data()?;
+
+        offset += read as u64;
+        let progress = serde_json::to_string(&CopyProgress { offset, ..initial }).map_err(std::io::Error::other)?;
+        std::fs::write(&progress_path, progress)?;
+    }
+
+    if sha256_file(source_path)? != sha256_file(&partial)? {
+        let _ = remove_file(&partial);
+        let _ = remove_file(&progress_path);
+        return Err(std::io::Error::new(ErrorKind::InvalidData, "The checksum of the copy differs from its source"));
+    }
+
+    rename(&partial, dest)?;
+    remove_file(&progress_path)?;
+
+    Ok(offset)
+}
+
+fn with_name_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Sets the mode bits of copied files and of the directories created for them.
+#[derive(new)]
+pub struct PermissionSettingAssetCopyStrategyDecorator {
+    inner: Box<dyn AssetCopyStrategy>,
+    file_mode: Option<u32>,
+    dir_mode: Option<u32>,
+}
+
+impl AssetCopyStrategy for PermissionSettingAssetCopyStrategyDecorator {
+
+    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error> {
+        let dest = copy_operation.get_output_path();
+
+        // Only directories created for this file get the mode, existing ones are left untouched
+        let created_dirs: Vec<PathBuf> = dest
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+            .map(PathBuf::from)
+            .collect();
+
+        let bytes = self.inner.copy_asset(copy_operation)?;
+
+        if let Some(mode) = self.file_mode {
+            set_permissions(&dest, Permissions::from_mode(mode))?;
+        }
+        if let Some(mode) = self.dir_mode {
+            for dir in created_dirs {
+                set_permissions(&dir, Permissions::from_mode(mode))?;
+            }
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// Flushes each copied file and its directory to disk before reporting it as copied, so that a
+/// file is only counted as exported once it has been persisted, e.g. on unreliable network shares.
+#[derive(new)]
+pub struct SyncingAssetCopyStrategyDecorator {
+    inner: Box<dyn AssetCopyStrategy>,
+}
+
+impl AssetCopyStrategy for SyncingAssetCopyStrategyDecorator {
+
+    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error> {
+        let bytes = self.inner.copy_asset(copy_operation)?;
+
+        let dest = copy_operation.get_output_path();
+        File::open(&dest)?.sync_all()?;
+        // Persists the directory entry of the file, which has been renamed into place
+        if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+            File::open(parent)?.sync_all()?;
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// Kind of conversion performed by a [FileConverter].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConversionKind {
+    RawToDng,
+    VideoTranscoding,
+}
+
+impl ConversionKind {
+
+    /// Describes files that could not be converted this way, e.g. "videos could not be transcoded".
+    pub fn failure_description(&self) -> &'static str {
+        match self {
+            ConversionKind::RawToDng => "RAW files could not be converted to DNG",
+            ConversionKind::VideoTranscoding => "videos could not be transcoded",
+        }
+    }
+}
+
+/// Converts a file to a different format while it is exported.
+pub trait FileConverter {
+
+    /// The kind of conversion, e.g. to describe the files it has failed for.
+    fn kind(&self) -> ConversionKind;
+
+    /// Whether files of the given type are converted by this converter.
+    fn converts(&self, uti: &Uti) -> bool;
+
+    /// Converts the source file, rotating its pixels upright according to the given orientation
+    /// if the target format supports it.
+    fn convert(&self, source: &Path, dest: &Path, orientation: Orientation) -> Result<(), std::io::Error>;
+}
+
+/// Runs an external command, returning its error output if it fails.
+fn run_external_command(command: &mut Command, program: &str) -> Result<(), std::io::Error> {
+    let output = command
+        .output()
+        .map_err(|e| std::io::Error::new(e.kind(), format!("Unable to run '{}': {}", program, e)))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(
+            std::io::Error::other(
+                format!(
+                    "'{}' failed with {}: {}",
+                    program,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )
+            )
+        )
+    }
+}
+
+/// Converts RAW files to DNG by invoking an external converter with the source and destination
+/// path, e.g. `dnglab convert <source> <dest>`. The sensor data of RAW files can not be rotated, so
+/// the orientation is left to the orientation tag the converter carries over.
+#[derive(new)]
+pub struct ExternalDngConverter {
+    /// The converter executable, followed by the arguments passed before the paths
+    command: Vec<String>,
+}
+
+impl FileConverter for ExternalDngConverter {
+
+    fn kind(&self) -> ConversionKind {
+        ConversionKind::RawToDng
+    }
+
+    fn converts(&self, uti: &Uti) -> bool {
+        uti.is_proprietary_raw()
+    }
+
+    fn convert(&self, source: &Path, dest: &Path, _orientation: Orientation) -> Result<(), std::io::Error> {
+        let (program, args) = self.command
+            .split_first()
+            .ok_or(std::io::Error::new(ErrorKind::InvalidInput, "No DNG converter given"))?;
+
+        run_external_command(Command::new(program).args(args).arg(source).arg(dest), program)
+    }
+}
+
+/// Video codecs videos can be transcoded to
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum VideoCodec {
+    /// H.264 (AVC), which is supported by virtually all TVs and digital photo frames
+    H264,
+}
+
+impl VideoCodec {
+    fn ffmpeg_encoder(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "libx264",
+        }
+    }
+}
+
+/// Transcodes videos by invoking ffmpeg, keeping their container format and metadata. The audio
+/// is transcoded to AAC and the video to 8 bit color, which is what most players are limited to.
+///
+/// Videos are rotated upright and their rotation tag is reset, so that players ignoring the tag
+/// show them correctly. The orientation from the library takes precedence over the rotation tag,
+/// which ffmpeg applies otherwise.
+#[derive(new)]
+pub struct FfmpegVideoTranscoder {
+    /// The ffmpeg executable, followed by any additional arguments passed before the input
+    command: Vec<String>,
+    codec: VideoCodec,
+}
+
+impl FileConverter for FfmpegVideoTranscoder {
+
+    fn kind(&self) -> ConversionKind {
+        ConversionKind::VideoTranscoding
+    }
+
+    fn converts(&self, uti: &Uti) -> bool {
+        uti.media_type == MediaType::Video
+    }
+
+    fn convert(&self, source: &Path, dest: &Path, orientation: Orientation) -> Result<(), std::io::Error> {
+        let (program, args) = self.command
+            .split_first()
+            .ok_or(std::io::Error::new(ErrorKind::InvalidInput, "No ffmpeg command given"))?;
+
+        // The destination is a partial file, so the container format can not be inferred from it
+        let is_quicktime = source
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("mov"));
+        let format = if is_quicktime { "mov" } else { "mp4" };
+
+        let mut command = Command::new(program);
+        command.args(args).args(["-nostdin", "-y", "-loglevel", "error"]);
+        let filter = orientation.ffmpeg_filter();
+        if filter.is_some() {
+            command.arg("-noautorotate");
+        }
+        command.arg("-i").arg(source);
+        if let Some(filter) = filter {
+            command.args(["-vf", filter]);
+        }
+        command
+            .args(["-map_metadata", "0", "-metadata:s:v:0", "rotate=0"])
+            .args(["-c:v", self.codec.ffmpeg_encoder(), "-pix_fmt", "yuv420p"])
+            .args(["-c:a", "aac", "-f", format])
+            .arg(dest);
+
+        run_external_command(&mut command, program)
+    }
+}
+
+/// Files that could not be converted and have been copied unchanged instead.
+#[derive(Default)]
+pub struct ConversionFallbackRegistry {
+    fallbacks: RefCell<Vec<(ConversionKind, PathBuf, String)>>,
+}
+
+impl ConversionFallbackRegistry {
+
+    /// Returns the output paths of the copied files along with the kind of conversion that has
+    /// failed and the reason it failed.
+    pub fn get_all(&self) -> Vec<(ConversionKind, PathBuf, String)> {
+        self.fallbacks.borrow().clone()
+    }
+}
+
+/// Converts the files of copy operations marked for conversion instead of copying them, if they
+/// are of a type handled by the converter. The source file is never modified, and no file is left
+/// at the destination if the conversion fails.
+///
+/// With a fallback registry, files that could not be converted are copied unchanged to the same
+/// destination instead and recorded in the registry.
+#[derive(new)]
+pub struct ConvertingAssetCopyStrategyDecorator {
+    inner: Box<dyn AssetCopyStrategy>,
+    converter: Box<dyn FileConverter>,
+    #[new(default)]
+    fallbacks: Option<Rc<ConversionFallbackRegistry>>,
+}
+
+impl ConvertingAssetCopyStrategyDecorator {
+
+    pub fn with_fallback(self, registry: Rc<ConversionFallbackRegistry>) -> Self {
+        ConvertingAssetCopyStrategyDecorator { fallbacks: Some(registry), ..self }
+    }
+}
+
+impl AssetCopyStrategy for ConvertingAssetCopyStrategyDecorator {
+
+    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error> {
+        let is_converted = copy_operation.converted_from.is_some_and(|uti| self.converter.converts(uti));
+        if !is_converted {
+            return self.inner.copy_asset(copy_operation);
+        }
+
+        let dest = copy_operation.get_output_path();
+        let result = write_through_partial_file(&dest, |partial| {
+            self.converter.convert(&copy_operation.source_path, partial, copy_operation.orientation)?;
+            metadata(partial).map(|m| m.len())
+        });
+
+        match (result, &self.fallbacks) {
+            (Err(e), Some(registry)) => {
+                registry.fallbacks.borrow_mut().push((self.converter.kind(), dest, e.to_string()));
+                self.inner.copy_asset(&CopyOperation { converted_from: None, ..copy_operation.clone() })
+            },
+            (result, _) => result,
+        }
+    }
+}
+
+/// Verifies that no file is ever written into one of the exported libraries by refusing to copy
+/// assets to destinations inside a library bundle.
+#[derive(new)]
+pub struct LibraryProtectingAssetCopyStrategyDecorator {
+    inner: Box<dyn AssetCopyStrategy>,
+    library_paths: Vec<String>,
+}
+
+impl AssetCopyStrategy for LibraryProtectingAssetCopyStrategyDecorator {
+
+    fn copy_asset(&self, copy_operation: &CopyOperation) -> Result<u64, std::io::Error> {
+        let dest = copy_operation.get_output_path();
+
+        if let Some(library) = self.library_paths.iter().find(|l| is_inside_library(&dest, Path::new(l))) {
+            return Err(
+                std::io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("Refusing to write '{}' into the library '{}'", dest.to_string_lossy(), library)
+                )
+            );
+        }
+
+        self.inner.copy_asset(copy_operation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::db::repo::album::AlbumRepository;
+    use crate::db::repo::asset::{AlbumFilter, AssetRepository, HiddenAssetsFilter};
+    use crate::export::structure::{AlbumOutputStrategy, DateGranularity, DateOutputStrategy, NestingOutputStrategyDecorator};
+    use crate::test_util::library::{load_exportable, load_operations, SyntheticLibrary, TestAlbum, TestAsset};
+
+    fn load_assets(library: &SyntheticLibrary) -> Vec<ExportAsset> {
+        let repo = AssetRepository::new(
+            library.db_path(),
+            HiddenAssetsFilter::Include,
+            AlbumFilter::None,
+            vec![],
+            None
+        );
+        let mut assets = load_exportable(&repo);
+        assets.sort_by_key(|a| a.id);
+        assets
+    }
+
+    fn output_paths(factory: &dyn CopyOperationFactory, assets: &[ExportAsset]) -> Vec<String> {
+        assets
+            .iter()
+            .flat_map(|a| factory.build(a).unwrap())
+            .map(|op| op.get_output_path().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn builds_originals_and_derivates() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::mov(2).edited());
+        let assets = load_assets(&library);
+
+        let factory = CombiningCopyOperationFactory::new(
+            vec![
+                Box::new(OriginalsCopyOperationFactory::new()),
+                Box::new(DerivatesCopyOperationFactory::new()),
+            ]
+        );
+
+        assert_eq!(
+            output_paths(&factory, &assets),
+            ["ASSET-0001.jpeg", "ASSET-0002.mov", "ASSET-0002_edited.mov"]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_originals_of_missing_derivates() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).edited());
+        library.add_asset(&TestAsset::jpeg(2).edited());
+        std::fs::remove_file(library.path().join(TestAsset::jpeg(2).edited().render_path())).unwrap();
+        let assets = load_assets(&library);
+
+        let registry = Rc::new(MissingDerivateRegistry::default());
+        let factory = OriginalFallbackCopyOperationFactoryDecorator::new(
+            Box::new(DerivatesCopyOperationFactory::new()),
+            library.path().to_path_buf(),
+            registry.clone()
+        );
+
+        assert_eq!(
+            output_paths(&factory, &assets),
+            ["ASSET-0001_edited.jpeg", "ASSET-0002.jpeg"]
+        );
+        assert_eq!(registry.get_all(), [TestAsset::jpeg(2).edited().render_path()]);
+    }
+
+    #[test]
+    fn resolves_relocated_originals() {
+        let library = SyntheticLibrary::new();
+        let relocated = TestAsset::jpeg(1);
+        library.add_asset(&relocated);
+        library.add_asset(&TestAsset::jpeg(2));
+        library.add_asset(&TestAsset::jpeg(3));
+        let masters_path = Path::new("Masters").join(relocated.original_path().strip_prefix("originals").unwrap());
+        std::fs::create_dir_all(library.path().join(masters_path.parent().unwrap())).unwrap();
+        std::fs::rename(library.path().join(relocated.original_path()), library.path().join(&masters_path)).unwrap();
+        std::fs::remove_file(library.path().join(TestAsset::jpeg(3).original_path())).unwrap();
+        let assets = load_assets(&library);
+
+        let registry = Rc::new(RelocatedOriginalRegistry::default());
+        let factory = OriginalResolvingCopyOperationFactoryDecorator::new(
+            Box::new(OriginalsCopyOperationFactory::new()),
+            library.path().to_path_buf(),
+            registry.clone()
+        );
+        let source_paths: Vec<PathBuf> = assets
+            .iter()
+            .flat_map(|a| factory.build(a).unwrap())
+            .map(|op| op.source_path)
+            .collect();
+
+        assert_eq!(
+            source_paths,
+            [masters_path.clone(), TestAsset::jpeg(2).original_path(), TestAsset::jpeg(3).original_path()]
+        );
+        assert_eq!(registry.get_all(), [(relocated.original_path(), masters_path)]);
+    }
+
+    #[test]
+    fn keeps_library_structure() {
+        let library = SyntheticLibrary::new();
+        let asset = TestAsset::jpeg(1).edited();
+        library.add_asset(&asset);
+        library.add_asset(&TestAsset::jpeg(2).referenced_at("Photos", "2010/IMG_0002.JPG"));
+        let assets = load_assets(&library);
+
+        let factory = LibraryStructureCopyOperationFactoryDecorator::new(
+            Box::new(
+                CombiningCopyOperationFactory::new(
+                    vec![
+                        Box::new(OriginalsCopyOperationFactory::new()),
+                        Box::new(DerivatesCopyOperationFactory::new()),
+                    ]
+                )
+            )
+        );
+
+        assert_eq!(
+            output_paths(&factory, &assets),
+            [
+                asset.original_path().to_string_lossy().to_string(),
+                asset.render_path().to_string_lossy().to_string(),
+                "referenced/Volumes/Photos/2010/IMG_0002.jpeg".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_utis_of_offline_libraries() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).offline_library());
+        library.add_asset(&TestAsset::mov(2).offline_library());
+        let assets = load_assets(&library);
+
+        assert_eq!(
+            output_paths(&OriginalsCopyOperationFactory::new(), &assets),
+            ["ASSET-0001.jpeg", "ASSET-0002.mov"]
+        );
+    }
+
+    #[test]
+    fn filters_assets() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::jpeg(2).favorite());
+        let assets = load_assets(&library);
+
+        let factory = FilteringCopyOperationFactoryDecorator::new(
+            Box::new(OriginalsCopyOperationFactory::new()),
+            Box::new(|asset| asset.favorite)
+        );
+
+        assert_eq!(output_paths(&factory, &assets), ["ASSET-0002.jpeg"]);
+    }
+
+    #[test]
+    fn filters_copy_operations_by_uti() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::mov(2));
+        // HEIC original with a JPEG render
+        library.add_asset(
+            &TestAsset { extension: "heic".to_string(), compact_uti: Some("3"), ..TestAsset::jpeg(3).edited() }
+        );
+        let assets = load_assets(&library);
+
+        let factory = |filter| UtiFilteringCopyOperationFactoryDecorator::new(
+            Box::new(
+                CombiningCopyOperationFactory::new(
+                    vec![
+                        Box::new(OriginalsCopyOperationFactory::new()),
+                        Box::new(DerivatesCopyOperationFactory::new()),
+                    ]
+                )
+            ),
+            filter
+        );
+        let jpg = Uti::from_extension("jpg").unwrap();
+        let mov = Uti::from_extension("mov").unwrap();
+
+        assert_eq!(
+            output_paths(&factory(UtiFilter::Include(vec![jpg])), &assets),
+            ["ASSET-0001.jpeg", "ASSET-0003_edited.jpeg"]
+        );
+        assert_eq!(
+            output_paths(&factory(UtiFilter::Exclude(vec![jpg, mov])), &assets),
+            ["ASSET-0003.heic"]
+        );
+    }
+
+    #[test]
+    fn builds_companion_resources() {
+        let library = SyntheticLibrary::new();
+        let asset = TestAsset::jpeg(1);
+        library.add_asset(&asset);
+        library.write_file(
+            &asset.original_path().with_file_name("ASSET-0001_3.heic"),
+            "gain map"
+        );
+        library.write_file(
+            &asset.original_path().with_file_name("ASSET-0001_4.unknown"),
+            "unsupported"
+        );
+        let assets = load_assets(&library);
+
+        let factory = CompanionResourcesCopyOperationFactory::new(library.path().to_path_buf());
+
+        assert_eq!(output_paths(&factory, &assets), ["ASSET-0001_companion_3.heic"]);
+    }
+
+    #[test]
+    fn limits_path_length() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, &"Folder".repeat(10)));
+        library.add_album(&TestAlbum::album(3, &"Album".repeat(10)).in_folder(2));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[3]));
+        let assets = load_assets(&library);
+
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let factory = PathLengthLimitingCopyOperationFactoryDecorator::new(
+            Box::new(
+                OutputStructureCopyOperationFactoryDecorator::new(
+                    Box::new(OriginalsCopyOperationFactory::new()),
+                    Box::new(AlbumOutputStrategy::new(false, albums))
+                )
+            ),
+            64,
+            None
+        );
+
+        let paths = output_paths(&factory, &assets);
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].chars().count() <= 64);
+        assert!(paths[0].ends_with("ASSET-0001.jpeg"));
+    }
+
+    #[test]
+    fn copies_derivates_with_restored_filenames() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).edited());
+        let output = tempfile::tempdir().unwrap();
+
+        let planned = load_operations(
+            &library,
+            output.path(),
+            Box::new(
+                FilenameRestoringCopyOperationFactoryDecorator::new(
+                    Box::new(DerivatesCopyOperationFactory::new())
+                )
+            )
+        );
+
+        let (_, op) = planned.get_all()[0];
+        DefaultAssetCopyStrategy::new().copy_asset(op).unwrap();
+
+        assert_eq!(
+            read_to_string(output.path().join("IMG_0001_edited.jpeg")).unwrap(),
+            "render of ASSET-0001"
+        );
+    }
+
+    #[test]
+    fn copies_originals_into_album_folders() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum::album(3, "Summer").in_folder(2));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[3]));
+        let output = tempfile::tempdir().unwrap();
+
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let planned = load_operations(
+            &library,
+            output.path(),
+            Box::new(
+                OutputStructureCopyOperationFactoryDecorator::new(
+                    Box::new(OriginalsCopyOperationFactory::new()),
+                    Box::new(AlbumOutputStrategy::new(false, albums))
+                )
+            )
+        );
+
+        let (_, op) = planned.get_all()[0];
+        let copied = DefaultAssetCopyStrategy::new().copy_asset(op).unwrap();
+
+        let expected = output.path().join("Trips").join("Summer").join("ASSET-0001.jpeg");
+        assert_eq!(copied, "original of ASSET-0001".len() as u64);
+        assert_eq!(read_to_string(expected).unwrap(), "original of ASSET-0001");
+    }
+
+    #[test]
+    fn records_album_folders_below_date_folders() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum::album(3, "Summer").in_folder(2).starting_at(740_000_000.0));
+        library.add_album(&TestAlbum::album(4, "Undated"));
+        library.add_asset(&TestAsset::jpeg(1).favorite().in_albums(&[3]));
+        library.add_asset(&TestAsset::jpeg(2).in_albums(&[4]));
+        library.add_asset(&TestAsset::jpeg(3));
+        let assets = load_assets(&library);
+
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let factory = FavoritesFolderCopyOperationFactoryDecorator::new(
+            Box::new(
+                OutputStructureCopyOperationFactoryDecorator::new(
+                    Box::new(OriginalsCopyOperationFactory::new()),
+                    Box::new(
+                        NestingOutputStrategyDecorator::new(
+                            vec![
+                                Box::new(DateOutputStrategy::album_date_based(DateGranularity::Month)),
+                                Box::new(AlbumOutputStrategy::new(false, albums)),
+                            ]
+                        )
+                    )
+                )
+            )
+        );
+        let album_folders: Vec<Option<AlbumFolder>> = assets
+            .iter()
+            .flat_map(|a| factory.build(a).unwrap())
+            .map(|op| op.album_folder)
+            .collect();
+
+        let start_date = assets[0].album.as_ref().and_then(|album| album.start_date).unwrap();
+        // The favorite's copy in the favorites folder is not in the album folder
+        assert_eq!(album_folders, [Some(AlbumFolder::new(4, start_date)), None, None, None]);
+    }
+
+    #[test]
+    fn exports_flat_with_unique_names() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_album(&TestAlbum::album(3, "Winter"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2, 3]));
+        library.add_asset(
+            &TestAsset { original_filename: "IMG_0001.JPG".to_string(), ..TestAsset::jpeg(2) }
+        );
+        library.add_asset(
+            &TestAsset { original_filename: "Summer Trip (1).JPG".to_string(), ..TestAsset::jpeg(3) }
+        );
+        let assets = load_assets(&library);
+
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let factory = FlatFrameCopyOperationFactoryDecorator::new(
+            Box::new(
+                FilenameRestoringCopyOperationFactoryDecorator::new(
+                    Box::new(
+                        OutputStructureCopyOperationFactoryDecorator::new(
+                            Box::new(OriginalsCopyOperationFactory::new()),
+                            Box::new(AlbumOutputStrategy::new(false, albums))
+                        )
+                    )
+                )
+            ),
+            library.path().to_path_buf(),
+            Rc::new(FlatOutputRegistry::default())
+        );
+
+        let date = assets[0].datetime.format("%Y-%m-%d_%H%M%S");
+        assert_eq!(
+            output_paths(&factory, &assets),
+            [
+                format!("{}_IMG_0001.jpeg", date),
+                format!("{}_IMG_0001_2.jpeg", date),
+                format!("{}_Summer_Trip__1_.jpeg", date),
+            ]
+        );
+    }
+
+    struct FakeDngConverter {
+        fails: bool,
+    }
+
+    impl FileConverter for FakeDngConverter {
+        fn kind(&self) -> ConversionKind {
+            ConversionKind::RawToDng
+        }
+
+        fn converts(&self, uti: &Uti) -> bool {
+            uti.is_proprietary_raw()
+        }
+
+        fn convert(&self, source: &Path, dest: &Path, _orientation: Orientation) -> Result<(), std::io::Error> {
+            std::fs::write(dest, format!("dng of {}", source.to_string_lossy()))?;
+            if self.fails {
+                Err(std::io::Error::other("conversion failed"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn converts_proprietary_raw_files_to_dng() {
+        let library = SyntheticLibrary::new();
+        library.write_file(Path::new("originals/A/ASSET-0001.raf"), "raw");
+        let output = tempfile::tempdir().unwrap();
+
+        let raw = CopyOperation::new(
+            library.path().join("originals/A/ASSET-0001.raf"),
+            Uti::from_name("com.fuji.raw-image").unwrap(),
+            "IMG_0001".to_string(),
+            None,
+            Some(output.path().to_path_buf())
+        );
+        let jpeg = CopyOperation { uti: Uti::from_name("public.jpeg").unwrap(), ..raw.clone() };
+        let converted = CopyOperation { uti: Uti::dng(), converted_from: Some(raw.uti), ..raw.clone() };
+
+        let strategy = |fails| ConvertingAssetCopyStrategyDecorator::new(
+            Box::new(DefaultAssetCopyStrategy::new()),
+            Box::new(FakeDngConverter { fails })
+        );
+
+        assert!(strategy(true).copy_asset(&converted).is_err());
+        assert_eq!(read_dir(output.path()).unwrap().count(), 0);
+
+        strategy(false).copy_asset(&converted).unwrap();
+        strategy(false).copy_asset(&jpeg).unwrap();
+
+        assert!(read_to_string(output.path().join("IMG_0001.dng")).unwrap().starts_with("dng of"));
+        assert_eq!(read_to_string(output.path().join("IMG_0001.jpeg")).unwrap(), "raw");
+        assert_eq!(read_to_string(library.path().join("originals/A/ASSET-0001.raf")).unwrap(), "raw");
+    }
+
+    struct FakeVideoTranscoder;
+
+    impl FileConverter for FakeVideoTranscoder {
+        fn kind(&self) -> ConversionKind {
+            ConversionKind::VideoTranscoding
+        }
+
+        fn converts(&self, uti: &Uti) -> bool {
+            uti.media_type == MediaType::Video
+        }
+
+        fn convert(&self, source: &Path, dest: &Path, orientation: Orientation) -> Result<(), std::io::Error> {
+            if read_to_string(source)?.contains("ASSET-0002") {
+                return Err(std::io::Error::other("unsupported codec"));
+            }
+            std::fs::write(dest, format!("h264 {:?}", orientation))
+        }
+    }
+
+    #[test]
+    fn falls_back_to_copying_videos_that_fail_to_transcode() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::mov(1).oriented(6));
+        library.add_asset(&TestAsset::mov(2));
+        library.add_asset(&TestAsset::jpeg(3));
+        let assets = load_assets(&library);
+        let output = tempfile::tempdir().unwrap();
+
+        let factory = VideoTranscodingCopyOperationFactoryDecorator::new(Box::new(OriginalsCopyOperationFactory::new()));
+        let registry = Rc::new(ConversionFallbackRegistry::default());
+        let strategy = ConvertingAssetCopyStrategyDecorator::new(
+            Box::new(DefaultAssetCopyStrategy::new()),
+            Box::new(FakeVideoTranscoder)
+        ).with_fallback(registry.clone());
+
+        for asset in &assets {
+            for op in factory.build(asset).unwrap() {
+                let op = CopyOperation {
+                    source_path: library.path().join(&op.source_path),
+                    output_folder: Some(output.path().to_path_buf()),
+                    ..op
+                };
+                strategy.copy_asset(&op).unwrap();
+            }
+        }
+
+        let exported = |name: &str| read_to_string(output.path().join(name)).unwrap();
+        assert_eq!(exported("ASSET-0001.mov"), "h264 Rotated90Clockwise");
+        assert_eq!(exported("ASSET-0002.mov"), "original of ASSET-0002");
+        assert_eq!(exported("ASSET-0003.jpeg"), "original of ASSET-0003");
+        assert_eq!(
+            registry.get_all(),
+            [(ConversionKind::VideoTranscoding, output.path().join("ASSET-0002.mov"), "unsupported codec".to_string())]
+        );
+    }
+
+    #[test]
+    fn keeps_orientation_of_originals_only() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).oriented(6).edited());
+        library.add_asset(&TestAsset::jpeg(2).oriented(9));
+        let assets = load_assets(&library);
+
+        let factory = CombiningCopyOperationFactory::new(
+            vec![
+                Box::new(OriginalsCopyOperationFactory::new()),
+                Box::new(DerivatesCopyOperationFactory::new()),
+            ]
+        );
+        let orientations: Vec<Orientation> = assets
+            .iter()
+            .flat_map(|a| factory.build(a).unwrap())
+            .map(|op| op.orientation)
+            .collect();
+
+        assert_eq!(
+            orientations,
+            [Orientation::Rotated90Clockwise, Orientation::Upright, Orientation::Upright]
+        );
+    }
+
+    #[test]
+    fn syncs_copied_files() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let assets = load_assets(&library);
+        let output = tempfile::tempdir().unwrap();
+
+        let op = OriginalsCopyOperationFactory::new().build(&assets[0]).unwrap().remove(0);
+        let op = CopyOperation {
+            source_path: library.path().join(&op.source_path),
+            output_folder: Some(output.path().join("2024")),
+            ..op
+        };
+
+        let bytes = SyncingAssetCopyStrategyDecorator::new(Box::new(DefaultAssetCopyStrategy::new()))
+            .copy_asset(&op)
+            .unwrap();
+
+        assert_eq!(bytes, metadata(op.get_output_path()).unwrap().len());
+    }
+
+    #[test]
+    fn continues_resumable_copies_where_they_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.mov");
+        let content: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&source, &content).unwrap();
+
+        let op = CopyOperation::new(
+            source.clone(),
+            Uti::from_name("com.apple.quicktime-movie").unwrap(),
+            "copy".to_string(),
+            None,
+            Some(dir.path().join("export"))
+        );
+        let dest = op.get_output_path();
+        let partial = dest.with_file_name("copy.mov.part");
+        let progress_path = dest.with_file_name("copy.mov.part.offset");
+
+        // A previous run copied the first 400 bytes, followed by garbage not recorded as copied
+        let source_modified = metadata(&source).unwrap().modified().unwrap().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        create_dir_all(dest.parent().unwrap()).unwrap();
+        std::fs::write(&partial, [&content[..400], b"garbage"].concat()).unwrap();
+        std::fs::write(
+            &progress_path,
+            serde_json::to_string(&CopyProgress { source_size: 1000, source_modified, offset: 400 }).unwrap()
+        ).unwrap();
+
+        let strategy = ResumableAssetCopyStrategyDecorator::new(Box::new(DefaultAssetCopyStrategy::new()), 1);
+        strategy.copy_asset(&op).unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+        assert!(!partial.exists());
+        assert!(!progress_path.exists());
+
+        // Progress of a different version of the source is discarded
+        std::fs::write(&partial, b"garbage").unwrap();
+        std::fs::write(
+            &progress_path,
+            serde_json::to_string(&CopyProgress { source_size: 999, source_modified, offset: 7 }).unwrap()
+        ).unwrap();
+        remove_file(&dest).unwrap();
+
+        strategy.copy_asset(&op).unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+    }
+
+    #[test]
+    fn sets_permissions_of_files_and_created_directories() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let assets = load_assets(&library);
+        let output = tempfile::tempdir().unwrap();
+
+        let op = CopyOperation {
+            output_folder: Some(output.path().join("2024").join("06")),
+            ..OriginalsCopyOperationFactory::new().build(&assets[0]).unwrap().remove(0)
+        };
+        let op = CopyOperation { source_path: library.path().join(&op.source_path), ..op };
+
+        PermissionSettingAssetCopyStrategyDecorator::new(
+            Box::new(DefaultAssetCopyStrategy::new()),
+            Some(0o640),
+            Some(0o750)
+        ).copy_asset(&op).unwrap();
+
+        let mode = |path: PathBuf| metadata(path).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode(op.get_output_path()), 0o640);
+        assert_eq!(mode(output.path().join("2024")), 0o750);
+        assert_eq!(mode(output.path().join("2024").join("06")), 0o750);
+        assert_ne!(mode(output.path().to_path_buf()), 0o750);
+    }
+
+    #[test]
+    fn refuses_to_copy_into_libraries() {
+        let library = SyntheticLibrary::new();
+        let asset = TestAsset::jpeg(1);
+        library.add_asset(&asset);
+        let output = tempfile::tempdir().unwrap();
+
+        let strategy = LibraryProtectingAssetCopyStrategyDecorator::new(
+            Box::new(DefaultAssetCopyStrategy::new()),
+            vec![library.path().to_string_lossy().to_string()]
+        );
+        let op = |folder: PathBuf| CopyOperation::new(
+            library.path().join(asset.original_path()),
+            Uti::from_name(asset.uti).unwrap(),
+            "copy".to_string(),
+            None,
+            Some(folder)
+        );
+
+        assert!(strategy.copy_asset(&op(output.path().to_path_buf())).is_ok());
+        assert_eq!(
+            strategy.copy_asset(&op(library.path().join("originals"))).unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert!(!library.path().join("originals").join("copy.jpeg").exists());
+    }
+
+    #[test]
+    fn adds_favorites_to_favorites_folder_once() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_album(&TestAlbum::album(3, "Winter"));
+        library.add_asset(&TestAsset::jpeg(1).favorite().in_albums(&[2, 3]));
+        library.add_asset(&TestAsset::jpeg(2).in_albums(&[2]));
+        let mut assets = load_assets(&library);
+        assets.sort_by_key(|a| (a.id, a.album.as_ref().map(|album| album.id)));
+
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let factory = FavoritesFolderCopyOperationFactoryDecorator::new(
+            Box::new(
+                OutputStructureCopyOperationFactoryDecorator::new(
+                    Box::new(OriginalsCopyOperationFactory::new()),
+                    Box::new(AlbumOutputStrategy::new(false, albums))
+                )
+            )
+        );
+
+        assert_eq!(
+            output_paths(&factory, &assets),
+            [
+                "Summer/ASSET-0001.jpeg",
+                "_favorites/ASSET-0001.jpeg",
+                "Winter/ASSET-0001.jpeg",
+                "Summer/ASSET-0002.jpeg",
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_raw_files_to_subfolder() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2]));
+        library.write_file(Path::new("originals/1/ASSET-0001_1.raf"), "raw");
+        library.add_asset(&TestAsset {
+            extension: "raf".to_string(),
+            uti: "com.fuji.raw-image",
+            compact_uti: Some("21"),
+            ..TestAsset::jpeg(2)
+        });
+        let assets = load_assets(&library);
+
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let factory = RawSubfolderCopyOperationFactoryDecorator::new(
+            Box::new(
+                OutputStructureCopyOperationFactoryDecorator::new(
+                    Box::new(
+                        CombiningCopyOperationFactory::new(
+                            vec![
+                                Box::new(OriginalsCopyOperationFactory::new()),
+                                Box::new(CompanionResourcesCopyOperationFactory::new(library.path().to_path_buf()))
+                            ]
+                        )
+                    ),
+                    Box::new(AlbumOutputStrategy::new(false, albums))
+                )
+            ),
+            "raw".to_string()
+        );
+
+        assert_eq!(
+            output_paths(&factory, &assets),
+            [
+                "Summer/ASSET-0001.jpeg",
+                "Summer/raw/ASSET-0001_companion_1.raf",
+                "raw/ASSET-0002.raf",
+            ]
+        );
+    }
+
+    #[test]
+    fn adds_assets_to_date_tree_once() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_album(&TestAlbum::album(3, "Winter"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2, 3]));
+        library.add_asset(&TestAsset::jpeg(2));
+        let mut assets = load_assets(&library);
+        assets.sort_by_key(|a| (a.id, a.album.as_ref().map(|album| album.id)));
+
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let factory = DateTreeCopyOperationFactoryDecorator::new(
+            Box::new(
+                OutputStructureCopyOperationFactoryDecorator::new(
+                    Box::new(OriginalsCopyOperationFactory::new()),
+                    Box::new(AlbumOutputStrategy::new(false, albums))
+                )
+            ),
+            Box::new(DateOutputStrategy::asset_date_based(DateGranularity::Month))
+        );
+
+        assert_eq!(
+            output_paths(&factory, &assets),
+            [
+                "Summer/ASSET-0001.jpeg",
+                "_by_date/2024/06/ASSET-0001.jpeg",
+                "Winter/ASSET-0001.jpeg",
+                "_by_date/2024/06/ASSET-0002.jpeg",
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_by_keywords() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).tagged(&["Beach", "Family"]));
+        library.add_asset(&TestAsset::jpeg(2).tagged(&["Beach/Sea"]));
+        library.add_asset(&TestAsset::jpeg(3));
+        let assets = load_assets(&library);
+
+        let factory = KeywordGroupingCopyOperationFactoryDecorator::new(
+            Box::new(OriginalsCopyOperationFactory::new())
+        );
+        let mut paths = output_paths(&factory, &assets);
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            [
+                "Beach-Sea/ASSET-0002.jpeg",
+                "Beach/ASSET-0001.jpeg",
+                "Family/ASSET-0001.jpeg",
+                "No keywords/ASSET-0003.jpeg",
+            ]
+        );
+    }
+
+    #[test]
+    fn names_files_after_uuids() {
+        let library = SyntheticLibrary::new();
+        // Noon UTC, so that the local UTC offset does not change the day
+        library.add_asset(&TestAsset::jpeg(1).edited().taken_at(699_969_600.0));
+        let assets = load_assets(&library);
+
+        let factory = |date_prefix| StableNamingCopyOperationFactoryDecorator::new(
+            Box::new(
+                FilenameRestoringCopyOperationFactoryDecorator::new(
+                    Box::new(
+                        CombiningCopyOperationFactory::new(
+                            vec![
+                                Box::new(OriginalsCopyOperationFactory::new()),
+                                Box::new(DerivatesCopyOperationFactory::new()),
+                            ]
+                        )
+                    )
+                )
+            ),
+            date_prefix
+        );
+
+        assert_eq!(output_paths(&factory(false), &assets), ["ASSET-0001.jpeg", "ASSET-0001_edited.jpeg"]);
+        assert_eq!(
+            output_paths(&factory(true), &assets),
+            ["2023-03-08_ASSET-0001.jpeg", "2023-03-08_ASSET-0001_edited.jpeg"]
+        );
+    }
+
+    #[test]
+    fn names_files_after_titles() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).edited().titled("Dinner: 2/3 "));
+        library.add_asset(&TestAsset::jpeg(2));
+        library.add_asset(&TestAsset::jpeg(3).titled(" .. "));
+        let assets = load_assets(&library);
+
+        let factory = TitleNamingCopyOperationFactoryDecorator::new(
+            Box::new(
+                CombiningCopyOperationFactory::new(
+                    vec![
+                        Box::new(OriginalsCopyOperationFactory::new()),
+                        Box::new(DerivatesCopyOperationFactory::new()),
+                    ]
+                )
+            )
+        );
+
+        assert_eq!(
+            output_paths(&factory, &assets),
+            ["Dinner_ 2_3.jpeg", "Dinner_ 2_3_edited.jpeg", "IMG_0002.jpeg", "IMG_0003.jpeg"]
+        );
     }
 }
\ No newline at end of file
@@ -0,0 +1,141 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::metadata;
+use std::path::{Path, PathBuf};
+
+use crate::export::copying::CopyOperation;
+use crate::export::verification::{collect_files, is_export_metadata, relative_to};
+use crate::model::asset::ExportAsset;
+
+/// Differences between the files an export would produce and the files of an existing export.
+/// All paths are relative to the respective export directory.
+#[derive(Default, Debug, PartialEq)]
+pub struct ExportDiff {
+    /// Files that are not part of the existing export
+    pub added: Vec<PathBuf>,
+    /// Files of the existing export that would be exported to a different path, given as the
+    /// existing path and the new path
+    pub moved: Vec<(PathBuf, PathBuf)>,
+    /// Files of the existing export that would no longer be exported
+    pub removed: Vec<PathBuf>,
+    /// Number of files that would be exported to the same path again
+    pub unchanged: usize,
+}
+
+impl ExportDiff {
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.moved.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Compares the planned copy operations to the files of an existing export.
+///
+/// Files are matched by their path relative to the export directory. Files which are not present
+/// at the same path are considered moved if the existing export contains a file with the same name
+/// and size elsewhere, e.g. because the assets are grouped differently.
+pub fn diff_export(
+    operations: &[(&ExportAsset, CopyOperation)],
+    output_dir: &Path,
+    existing_dir: &Path
+) -> Result<ExportDiff, String> {
+    let mut existing = BTreeSet::new();
+    if existing_dir.exists() {
+        collect_files(existing_dir, &mut existing)?;
+    }
+    let mut remaining: BTreeSet<PathBuf> = existing
+        .iter()
+        .filter(|path| !is_export_metadata(path))
+        .map(|path| relative_to(path, existing_dir))
+        .collect();
+
+    let mut diff = ExportDiff::default();
+    let mut planned = HashSet::new();
+    let mut unmatched = vec![];
+
+    for (_, op) in operations {
+        let path = relative_to(&op.get_output_path(), output_dir);
+        // Assets of multiple albums are exported to the same file unless grouped by album
+        if !planned.insert(path.clone()) {
+            continue;
+        }
+
+        if remaining.remove(&path) {
+            diff.unchanged += 1;
+        } else {
+            unmatched.push((path, metadata(&op.source_path).map(|m| m.len()).ok()));
+        }
+    }
+
+    let mut candidates: HashMap<(String, u64), Vec<PathBuf>> = HashMap::new();
+    for path in &remaining {
+        if let (Some(name), Ok(file)) = (path.file_name(), metadata(existing_dir.join(path))) {
+            candidates
+                .entry((name.to_string_lossy().to_string(), file.len()))
+                .or_default()
+                .push(path.clone());
+        }
+    }
+
+    for (path, size) in unmatched {
+        let previous = path
+            .file_name()
+            .zip(size)
+            .and_then(|(name, size)| candidates.get_mut(&(name.to_string_lossy().to_string(), size)))
+            .and_then(|paths| paths.pop());
+
+        match previous {
+            Some(previous) => {
+                remaining.remove(&previous);
+                diff.moved.push((previous, path));
+            },
+            None => diff.added.push(path),
+        }
+    }
+
+    diff.removed = remaining.into_iter().collect();
+    diff.added.sort();
+    diff.moved.sort();
+
+    Ok(diff)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{copy, create_dir_all, write};
+
+    use super::*;
+    use crate::export::copying::OriginalsCopyOperationFactory;
+    use crate::export::metadata::manifest::MANIFEST_FILENAME;
+    use crate::test_util::library::{load_operations, SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn reports_added_moved_and_removed_files() {
+        let library = SyntheticLibrary::new();
+        for id in 1..=3 {
+            library.add_asset(&TestAsset::jpeg(id));
+        }
+        let output = tempfile::tempdir().unwrap();
+        let existing = tempfile::tempdir().unwrap();
+
+        let planned = load_operations(&library, output.path(), Box::new(OriginalsCopyOperationFactory::new()));
+        let operations = planned.to_planned();
+
+        let source = |id: i32| library.path().join(TestAsset::jpeg(id).original_path());
+        create_dir_all(existing.path().join("2023")).unwrap();
+        copy(source(1), existing.path().join("ASSET-0001.jpeg")).unwrap();
+        copy(source(2), existing.path().join("2023/ASSET-0002.jpeg")).unwrap();
+        write(existing.path().join("2023/IMG_9999.jpeg"), "").unwrap();
+        write(existing.path().join(MANIFEST_FILENAME), "{}").unwrap();
+
+        let diff = diff_export(&operations, output.path(), existing.path()).unwrap();
+
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(
+            diff.moved,
+            [(PathBuf::from("2023/ASSET-0002.jpeg"), PathBuf::from("ASSET-0002.jpeg"))]
+        );
+        assert_eq!(diff.added, [PathBuf::from("ASSET-0003.jpeg")]);
+        assert_eq!(diff.removed, [PathBuf::from("2023/IMG_9999.jpeg")]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::export::copying::CopyOperation;
+use crate::model::asset::ExportAsset;
+use crate::util::checksum::sha256_file;
+
+/// Detects source files whose content has already been exported for a different asset, e.g.
+/// when the same photo has been imported multiple times.
+///
+/// Checksums are computed once per source file. The same asset exported to multiple locations,
+/// e.g. because it is part of multiple albums, is not considered a duplicate.
+#[derive(Default)]
+pub struct DuplicateContentDetector {
+    checksums_by_source: HashMap<PathBuf, String>,
+    /// UUID of the asset and output path each checksum has first been exported with
+    exported_by_checksum: HashMap<String, (String, PathBuf)>,
+}
+
+impl DuplicateContentDetector {
+
+    /// Returns the output path the content of the copy operation's source has already been
+    /// exported to for a different asset, if any.
+    pub fn find_duplicate(
+        &mut self,
+        asset: &ExportAsset,
+        copy_operation: &CopyOperation
+    ) -> Result<Option<PathBuf>, String> {
+        let checksum = self.checksum(copy_operation)?;
+
+        Ok(
+            self.exported_by_checksum
+                .get(&checksum)
+                .filter(|(uuid, _)| *uuid != asset.uuid)
+                .map(|(_, output_path)| output_path.clone())
+        )
+    }
+
+    /// Records the content of the copy operation's source as exported.
+    pub fn record(&mut self, asset: &ExportAsset, copy_operation: &CopyOperation) -> Result<(), String> {
+        let checksum = self.checksum(copy_operation)?;
+
+        self.exported_by_checksum
+            .entry(checksum)
+            .or_insert_with(|| (asset.uuid.clone(), copy_operation.get_output_path()));
+
+        Ok(())
+    }
+
+    fn checksum(&mut self, copy_operation: &CopyOperation) -> Result<String, String> {
+        if let Some(checksum) = self.checksums_by_source.get(&copy_operation.source_path) {
+            return Ok(checksum.clone());
+        }
+
+        let checksum = sha256_file(&copy_operation.source_path)
+            .map_err(|e| {
+                format!(
+                    "Unable to compute checksum of '{}': {}",
+                    copy_operation.source_path.to_string_lossy(),
+                    e
+                )
+            })?;
+
+        self.checksums_by_source.insert(copy_operation.source_path.clone(), checksum.clone());
+        Ok(checksum)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::copying::OriginalsCopyOperationFactory;
+    use crate::test_util::library::{load_operations, SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn detects_identical_content_of_different_assets() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::jpeg(2));
+        library.add_asset(&TestAsset::jpeg(3));
+        library.write_file(&TestAsset::jpeg(2).original_path(), "original of ASSET-0001");
+
+        let planned = load_operations(&library, &PathBuf::from("/out"), Box::new(OriginalsCopyOperationFactory::new()));
+        let (assets, ops): (Vec<&ExportAsset>, Vec<&CopyOperation>) = planned.get_all().into_iter().unzip();
+
+        let mut detector = DuplicateContentDetector::default();
+        assert_eq!(detector.find_duplicate(assets[0], ops[0]), Ok(None));
+        detector.record(assets[0], ops[0]).unwrap();
+
+        assert_eq!(detector.find_duplicate(assets[0], ops[0]), Ok(None));
+        assert_eq!(
+            detector.find_duplicate(assets[1], ops[1]),
+            Ok(Some(PathBuf::from("/out/ASSET-0001.jpeg")))
+        );
+        assert_eq!(detector.find_duplicate(assets[2], ops[2]), Ok(None));
+    }
+}
@@ -1,154 +1,1954 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs::{metadata, remove_dir, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDateTime;
 use colored::Colorize;
 use derive_new::new;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::SeedableRng;
 
-use crate::db::repo::asset::{AssetRepository, LocalAvailabilityFilter};
-use crate::export::copying::{AssetCopyStrategy, CopyOperation, CopyOperationFactory};
+use crate::db::repo::asset::{AssetRepository, ExclusionCounts, ExportAssetDto, LibraryCounts};
+use crate::export::benchmark::PlanningBenchmark;
+use crate::export::copying::{AssetCopyStrategy, CopyOperation, CopyOperationFactory, MissingDerivateRegistry, RelocatedOriginalRegistry};
+use crate::export::diff::{diff_export, ExportDiff};
+use crate::export::duplicates::DuplicateContentDetector;
+use crate::export::journal::ExportJournal;
+use crate::export::structure::{detach_from_albums, RenamedAlbumRegistry, UnknownAlbumRegistry};
+use crate::export::metadata::MetadataWriter;
+use crate::export::pipeline::{PipelineExclusionRegistry, PipelineStage};
+use crate::export::placeholders::{remove_placeholder, write_placeholder};
+use crate::export::plan::ExportPlan;
+use crate::export::run_log::{RunLog, RunLogOutcome};
+use crate::export::statistics::ExportStatistics;
+use crate::export::verification::{audit_export, verify_export, AuditReport, VerificationReport};
 use crate::model::asset::ExportAsset;
 use crate::model::FromDbModel;
-use crate::result::{PhotosExportError, PhotosExportResult};
-use crate::util::confirmation::{Answer, confirmation_prompt};
+use crate::result::{AssetError, AssetErrorStep, PhotosExportError, PhotosExportResult};
+use crate::util::interaction::{Answer, TerminalInteraction, UserInteraction};
+use crate::util::duration::format_duration;
+use crate::util::paths::{find_prunable_dirs, set_modified_time};
+use crate::util::interrupt::is_interrupted;
 
+/// A library to export assets from, along with the factory building its copy operations.
 #[derive(new)]
-pub struct Exporter {
+pub struct ExportSource {
+    /// Name of the library, used to disambiguate assets also present in another library
+    name: String,
+    /// Path of the library, which the source paths of a previously saved plan must lie inside
+    library_path: PathBuf,
     repo: AssetRepository,
     copy_operation_factory: Box<dyn CopyOperationFactory>,
+    /// Derivates the factory replaced by their originals as they are missing on disk
+    #[new(default)]
+    missing_derivates: Rc<MissingDerivateRegistry>,
+    /// Originals the factory found at alternative locations as they are missing at their default one
+    #[new(default)]
+    relocated_originals: Rc<RelocatedOriginalRegistry>,
+    /// Assets the factory exported as if they were in no album as their album is unknown
+    #[new(default)]
+    unknown_albums: Rc<UnknownAlbumRegistry>,
+    /// Albums renamed as they share their name with another album
+    #[new(default)]
+    renamed_albums: Rc<RenamedAlbumRegistry>,
+    /// Assets the filtering stages of the factory have built no copy operations for
+    #[new(default)]
+    pipeline_exclusions: Rc<PipelineExclusionRegistry>,
+    /// Albums whose assets are exported as if they were in no album, e.g. as they are too small
+    #[new(default)]
+    detached_albums: HashSet<i32>,
+}
+
+impl ExportSource {
+
+    pub fn with_missing_derivates(self, registry: Rc<MissingDerivateRegistry>) -> Self {
+        ExportSource { missing_derivates: registry, ..self }
+    }
+
+    pub fn with_relocated_originals(self, registry: Rc<RelocatedOriginalRegistry>) -> Self {
+        ExportSource { relocated_originals: registry, ..self }
+    }
+
+    pub fn with_unknown_albums(self, registry: Rc<UnknownAlbumRegistry>) -> Self {
+        ExportSource { unknown_albums: registry, ..self }
+    }
+
+    pub fn with_renamed_albums(self, registry: Rc<RenamedAlbumRegistry>) -> Self {
+        ExportSource { renamed_albums: registry, ..self }
+    }
+
+    pub fn with_pipeline_exclusions(self, registry: Rc<PipelineExclusionRegistry>) -> Self {
+        ExportSource { pipeline_exclusions: registry, ..self }
+    }
+
+    pub fn with_detached_albums(self, album_ids: HashSet<i32>) -> Self {
+        ExportSource { detached_albums: album_ids, ..self }
+    }
+}
+
+#[derive(new)]
+pub struct Exporter {
+    sources: Vec<ExportSource>,
     copy_strategy: Box<dyn AssetCopyStrategy>,
+    metadata_writers: Vec<Box<dyn MetadataWriter>>,
+    options: ExportOptions,
+    output_dir: PathBuf,
+    /// Journal of copied files to resume interrupted exports, `None` in dry-run mode
+    journal: Option<ExportJournal>,
+    /// Log of the outcome of each processed file
+    #[new(default)]
+    run_log: Option<RunLog>,
+    /// Asks for confirmations and shows progress, warnings and notes
+    #[new(value = "Rc::new(TerminalInteraction)")]
+    interaction: Rc<dyn UserInteraction>,
+}
+
+/// Options for skipping assets while exporting
+#[derive(new, Default)]
+pub struct ExportOptions {
+    /// Whether to skip assets whose source file is empty instead of exporting them
+    skip_empty_sources: bool,
+    /// Whether to skip assets whose content has already been exported for a different asset
+    skip_duplicate_content: bool,
+    /// Whether to only write the metadata files, assuming the assets have already been exported
+    metadata_only: bool,
+    /// Subset of the planned copy operations to perform, e.g. to try out options
+    subset: Option<ExportSubset>,
+    /// Whether the output directory tells apart paths only differing in case, so that such paths
+    /// do not need to be disambiguated
+    case_sensitive_target: bool,
+    /// Whether to annotate each copied file with badges of its asset's state, e.g. to check the
+    /// filters in a dry run
+    show_badges: bool,
+    /// Benchmark to time the stages of planning the export in, printing their breakdown before
+    /// starting the export
+    #[new(default)]
+    planning_benchmark: Option<Rc<PlanningBenchmark>>,
+    /// Previously saved plan to execute instead of planning the copy operations again
+    #[new(default)]
+    plan: Option<ExportPlan>,
+    /// Bounds of the number of planned copy operations, failing the export if it is out of them
+    #[new(default)]
+    expected_count: ExpectedCount,
+    /// Number of planned copy operations below which the export starts without asking for
+    /// confirmation
+    #[new(default)]
+    auto_confirm_below: Option<usize>,
+    /// Duration after which no more files are copied, leaving the rest to a resumed export
+    #[new(default)]
+    max_duration: Option<Duration>,
+    /// Whether to write placeholders for assets that are not locally available, e.g. as they have
+    /// been offloaded to iCloud
+    #[new(default)]
+    write_placeholders: bool,
+    /// Whether to remove the placeholders of files once they have been exported, regardless of
+    /// whether placeholders are written by this export
+    #[new(default)]
+    remove_placeholders: bool,
+    /// Whether to set the modification time of album folders to the start date of their albums
+    /// once the export has been completed, so that file browsers sort them chronologically
+    #[new(default)]
+    date_album_folders: bool,
+    /// Files of the previous export, relative to the output directory, whose directories are
+    /// removed once the export has been completed if they have been left empty
+    #[new(default)]
+    previous_files: Option<Vec<PathBuf>>,
+    /// Number of directories to remove above which a separate confirmation is asked for
+    #[new(default)]
+    confirm_deletions_above: Option<usize>,
+}
+
+/// Compact badges of the state of an asset a file is copied for, e.g. `★ ✎` for the original of an
+/// edited favorite.
+struct AssetBadges {
+    favorite: bool,
+    hidden: bool,
+    edited: bool,
+    raw: bool,
+}
+
+impl AssetBadges {
+
+    fn of(asset: &ExportAsset, copy_operation: &CopyOperation) -> Self {
+        AssetBadges {
+            favorite: asset.favorite,
+            hidden: asset.hidden,
+            edited: asset.has_adjustments,
+            raw: copy_operation.converted_from.unwrap_or(copy_operation.uti).is_raw(),
+        }
+    }
+}
+
+impl Display for AssetBadges {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let badges: Vec<&str> = [
+            (self.favorite, "★"),
+            (self.hidden, "🙈"),
+            (self.edited, "✎"),
+            (self.raw, "RAW"),
+        ]
+            .iter()
+            .filter(|(present, _)| *present)
+            .map(|(_, badge)| *badge)
+            .collect();
+
+        write!(f, "{}", badges.join(" "))
+    }
+}
+
+/// Bounds of the number of copy operations an export is expected to plan, e.g. so that automated
+/// exports fail instead of exporting nothing if a broken filter excludes all assets.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExpectedCount {
+    pub at_least: Option<usize>,
+    pub at_most: Option<usize>,
+}
+
+impl ExpectedCount {
+
+    fn check(&self, count: usize) -> Result<(), String> {
+        if let Some(minimum) = self.at_least.filter(|minimum| count < *minimum) {
+            return Err(format!("The export planned {} files, but at least {} were expected", count, minimum));
+        }
+        if let Some(maximum) = self.at_most.filter(|maximum| count > *maximum) {
+            return Err(format!("The export planned {} files, but at most {} were expected", count, maximum));
+        }
+        Ok(())
+    }
+}
+
+/// Subset of the copy operations to perform instead of all of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportSubset {
+    /// The first N copy operations
+    Limit(usize),
+    /// N copy operations chosen at random, keeping their order. The same operations are chosen
+    /// each time if a seed is given.
+    Sample(usize, Option<u64>),
+}
+
+impl ExportSubset {
+
+    fn select<T>(&self, items: Vec<T>) -> Vec<T> {
+        match *self {
+            ExportSubset::Limit(count) => items.into_iter().take(count).collect(),
+            ExportSubset::Sample(count, seed) => {
+                let amount = count.min(items.len());
+                let selected: HashSet<usize> = match seed {
+                    Some(seed) => sample(&mut StdRng::seed_from_u64(seed), items.len(), amount),
+                    None => sample(&mut rand::thread_rng(), items.len(), amount),
+                }
+                    .into_iter()
+                    .collect();
+                items
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| selected.contains(index))
+                    .map(|(_, item)| item)
+                    .collect()
+            },
+        }
+    }
+}
+
+impl ExportOptions {
+
+    pub fn with_planning_benchmark(self, benchmark: Rc<PlanningBenchmark>) -> Self {
+        ExportOptions { planning_benchmark: Some(benchmark), ..self }
+    }
+
+    pub fn with_plan(self, plan: ExportPlan) -> Self {
+        ExportOptions { plan: Some(plan), ..self }
+    }
+
+    pub fn with_expected_count(self, expected_count: ExpectedCount) -> Self {
+        ExportOptions { expected_count, ..self }
+    }
+
+    pub fn with_auto_confirm_below(self, threshold: Option<usize>) -> Self {
+        ExportOptions { auto_confirm_below: threshold, ..self }
+    }
+
+    pub fn with_max_duration(self, max_duration: Option<Duration>) -> Self {
+        ExportOptions { max_duration, ..self }
+    }
+
+    pub fn with_placeholders(self, write_placeholders: bool) -> Self {
+        ExportOptions { write_placeholders, ..self }
+    }
+
+    pub fn with_placeholder_removal(self, remove_placeholders: bool) -> Self {
+        ExportOptions { remove_placeholders, ..self }
+    }
+
+    pub fn with_album_folder_dates(self, date_album_folders: bool) -> Self {
+        ExportOptions { date_album_folders, ..self }
+    }
+
+    pub fn with_pruning(self, previous_files: Vec<PathBuf>) -> Self {
+        ExportOptions { previous_files: Some(previous_files), ..self }
+    }
+
+    pub fn with_confirm_deletions_above(self, threshold: Option<usize>) -> Self {
+        ExportOptions { confirm_deletions_above: threshold, ..self }
+    }
+
+    pub fn is_metadata_only(&self) -> bool {
+        self.metadata_only
+    }
+}
+
+/// Outcome of an export that has not failed.
+#[derive(Default)]
+pub struct ExportSummary {
+    pub exported: u64,
+    /// Number of empty directories removed from the output directory
+    pub deleted: u64,
+    /// Number of assets exported as originals as their derivates are missing on disk
+    pub original_fallbacks: u64,
+    /// Number of assets exported as if they were in no album as their album is unknown
+    pub unknown_album_fallbacks: u64,
+    /// Number of placeholders written for assets that are not locally available
+    pub placeholders: u64,
+    /// Whether the export has been interrupted before all assets have been exported
+    pub interrupted: bool,
 }
 
 impl Exporter {
 
-    pub fn export(&self) -> PhotosExportResult<u64> {
-        let visible_count = self.get_visible_count()?;
-        let visible_offloaded_count = self.get_visible_offloaded_count()?;
+    pub fn with_run_log(self, run_log: RunLog) -> Self {
+        Exporter { run_log: Some(run_log), ..self }
+    }
 
-        if visible_offloaded_count > 0 {
-            if let Answer::No = self.missing_assets_prompt(visible_count, visible_offloaded_count) {
-                return Ok(0)
-            }
+    pub fn with_interaction(self, interaction: Rc<dyn UserInteraction>) -> Self {
+        Exporter { interaction, ..self }
+    }
+
+    pub fn export(&self) -> PhotosExportResult<ExportSummary> {
+        let export_started = Instant::now();
+        let library_counts = self.get_library_counts()?;
+
+        let assets = self.timed("Querying assets", || self.get_exportable_assets())?;
+        let mut export_assets = self.plan_copy_operations(&assets)?;
+
+        let exclusions = self.timed("Counting exclusions", || self.get_exclusion_counts())?;
+        let pipeline_exclusions = self.get_pipeline_exclusions();
+        if exclusions.total() > 0 || !pipeline_exclusions.is_empty() {
+            self.exclusions_note(&exclusions, &pipeline_exclusions);
+        }
+
+        if let Err(e) = self.options.expected_count.check(export_assets.len()) {
+            self.interaction.error(&e);
+            return Err(e.into());
+        }
+
+        if let Some(subset) = self.options.subset {
+            let planned_count = export_assets.len();
+            export_assets = self.timed("Selecting subset", || subset.select(export_assets));
+            self.subset_note(export_assets.len(), planned_count);
         }
 
-        let export_assets = self.get_copy_operations()?;
         let export_assets_count = export_assets.len() as i64;
 
         if export_assets_count == 0 {
             self.no_matching_assets_warning();
-            return Ok(0);
+            // Placeholders are still written, e.g. if all matching assets are offloaded
+            return Ok(ExportSummary { placeholders: self.write_placeholders()?, ..ExportSummary::default() });
+        }
+
+        let collisions = self.timed("Finding destination collisions", || find_destination_collisions(&export_assets));
+        if !collisions.is_empty() {
+            self.destination_collisions_warning(&collisions);
+        }
+
+        if let Some(benchmark) = &self.options.planning_benchmark {
+            benchmark.print(self.interaction.as_ref());
+        }
+
+        if let Some(journal) = &self.journal {
+            if journal.exists() && !journal.is_resuming() {
+                self.interrupted_export_prompt(journal)?;
+            }
+        }
+
+        // Asked after planning so that the prompt can be skipped for small exports
+        if library_counts.offloaded > 0 {
+            if let Answer::No = self.missing_assets_prompt(library_counts.visible, library_counts.offloaded, export_assets_count) {
+                return Ok(ExportSummary::default())
+            }
         }
 
+        self.library_counts_note(&library_counts);
+
         if let Answer::No = self.start_export_prompt(export_assets_count) {
-            return Ok(0);
+            return Ok(ExportSummary::default());
         }
 
-        let (export_count, error_messages) = export_assets
-            .iter()
-            .enumerate()
-            .fold((0, Vec::<String>::new()), |(cnt, msgs), (index, op)| {
-                let result = self.export_single_asset(index, export_assets_count, op);
-                match result {
-                    Ok(_) => (cnt + 1, msgs),
-                    Err(e) => (cnt, [msgs, vec![e.to_string()]].concat())
+        let completed = match &self.journal {
+            Some(journal) => {
+                journal.start(export_assets.len())?;
+                journal.completed()?
+            },
+            None => HashSet::new(),
+        };
+
+        let mut exported = Vec::new();
+        let mut error_messages = Vec::<String>::new();
+        // Errors concerning single assets, kept apart to only be turned into messages at the end
+        let mut asset_errors = Vec::<AssetError>::new();
+        let mut empty_sources = Vec::new();
+        let mut unreadable_sources = Vec::new();
+        let mut statistics = ExportStatistics::new(self.output_dir.clone());
+        let mut interrupted = false;
+        // Number of files not copied as the maximum duration has been reached
+        let mut remaining_after_timeout = None;
+        let mut duplicate_detector = DuplicateContentDetector::default();
+        let mut duplicates = Vec::new();
+
+        for (index, (asset, op)) in export_assets.iter().enumerate() {
+            if is_interrupted() {
+                interrupted = true;
+                break;
+            }
+            if self.options.max_duration.is_some_and(|max_duration| export_started.elapsed() >= max_duration) {
+                interrupted = true;
+                remaining_after_timeout = Some(export_assets.len() - index);
+                break;
+            }
+
+            let started = Instant::now();
+            let mut log = |outcome, bytes, error: Option<&str>| {
+                if let Err(e) = self.log_outcome(asset, op, outcome, started, bytes, error) {
+                    error_messages.push(e);
+                }
+            };
+
+            if self.options.metadata_only {
+                exported.push((*asset, op));
+                continue;
+            }
+
+            if completed.contains(&op.get_output_path()) {
+                // Copied by a previous or the interrupted run, but still included in the metadata
+                log(RunLogOutcome::AlreadyExported, 0, None);
+                exported.push((*asset, op));
+                continue;
+            }
+
+            match check_source(op) {
+                Some(SourceProblem::Empty) => {
+                    empty_sources.push(op.source_path.to_string_lossy().to_string());
+                    if self.options.skip_empty_sources {
+                        self.skipped_empty_source_warning(index, export_assets_count, op);
+                        log(RunLogOutcome::SkippedEmptySource, 0, None);
+                        continue;
+                    }
+                },
+                Some(SourceProblem::Unreadable(e)) => {
+                    let source_path = op.source_path.to_string_lossy().to_string();
+                    self.interaction.error(&format!("Unable to read '{}': {}", source_path, e));
+                    let e = AssetError::of(
+                        asset,
+                        AssetErrorStep::Copying,
+                        format!("'{}' is unreadable: {}", source_path, e)
+                    );
+                    log(RunLogOutcome::Failed, 0, Some(&e.to_string()));
+                    asset_errors.push(e);
+                    unreadable_sources.push(source_path);
+                    continue;
+                },
+                None => {},
+            }
+
+            if self.options.skip_duplicate_content {
+                match duplicate_detector.find_duplicate(asset, op) {
+                    Ok(Some(original)) => {
+                        self.skipped_duplicate_warning(index, export_assets_count, op, &original);
+                        log(RunLogOutcome::SkippedDuplicate, 0, None);
+                        duplicates.push((op.get_output_path(), original));
+                        continue;
+                    },
+                    Ok(None) => {},
+                    Err(e) => {
+                        let e = AssetError::of(asset, AssetErrorStep::DuplicateDetection, e);
+                        log(RunLogOutcome::Failed, 0, Some(&e.to_string()));
+                        asset_errors.push(e);
+                        continue;
+                    }
+                }
+            }
+
+            let copy_started = Instant::now();
+            match self.export_single_asset(index, export_assets_count, asset, op) {
+                Ok(bytes) => {
+                    statistics.record_copy(&op.get_output_path(), bytes, copy_started.elapsed());
+                    log(RunLogOutcome::Exported, bytes, None);
+                    statistics.record(&op.get_output_path(), bytes);
+                    if let Some(Err(e)) = self.journal.as_ref().map(|j| j.record(&op.get_output_path())) {
+                        error_messages.push(e);
+                    }
+                    if self.options.remove_placeholders {
+                        if let Err(e) = remove_placeholder(&op.get_output_path()) {
+                            error_messages.push(e);
+                        }
+                    }
+                    if self.options.skip_duplicate_content {
+                        if let Err(e) = duplicate_detector.record(asset, op) {
+                            asset_errors.push(AssetError::of(asset, AssetErrorStep::DuplicateDetection, e));
+                        }
+                    }
+                    exported.push((*asset, op))
+                },
+                Err(_) if is_interrupted() => {
+                    // Resumable copies stop at an interruption and are continued by the next run
+                    interrupted = true;
+                    break;
+                },
+                Err(e) => {
+                    log(RunLogOutcome::Failed, 0, Some(&e.to_string()));
+                    asset_errors.push(e)
+                },
+            }
+        }
+        let export_count = exported.len() as u64;
+        let mut placeholders = 0;
+        let mut deleted = 0;
+
+        statistics.print(self.interaction.as_ref());
+
+        if !empty_sources.is_empty() {
+            self.empty_sources_warning(&empty_sources);
+        }
+
+        if !unreadable_sources.is_empty() {
+            self.unreadable_sources_warning(&unreadable_sources);
+        }
+
+        if !duplicates.is_empty() {
+            self.duplicates_warning(&duplicates);
+        }
+
+        let missing_derivates = self.get_missing_derivates();
+        if !missing_derivates.is_empty() {
+            self.missing_derivates_warning(&missing_derivates);
+        }
+
+        let relocated_originals = self.get_relocated_originals();
+        if !relocated_originals.is_empty() {
+            self.relocated_originals_note(&relocated_originals);
+        }
+
+        let unknown_albums = self.get_unknown_albums();
+        if !unknown_albums.is_empty() {
+            self.unknown_albums_warning(&unknown_albums);
+        }
+
+        let renamed_albums = self.get_renamed_albums();
+        if !renamed_albums.is_empty() {
+            self.renamed_albums_note(&renamed_albums);
+        }
+
+        if let Some(remaining) = remaining_after_timeout {
+            self.timed_out_export_warning(remaining, export_assets_count);
+        } else if interrupted {
+            self.interrupted_export_warning(export_count, export_assets_count);
+        } else {
+            for writer in &self.metadata_writers {
+                if let Err(e) = writer.write_with_duplicates(&exported, &duplicates) {
+                    self.interaction.error(&e);
+                    error_messages.push(e);
+                }
+            }
+            match self.write_placeholders() {
+                Ok(count) => placeholders = count,
+                Err(e) => {
+                    self.interaction.error(&e);
+                    error_messages.push(e);
+                }
+            }
+            // Pruned before dating, as removing a directory updates the modification time of its parent
+            match self.prune_empty_dirs() {
+                Ok(count) => deleted = count,
+                Err(e) => {
+                    self.interaction.error(&e);
+                    error_messages.push(e);
                 }
-            });
+            }
+            // Dated last, as writing into a folder updates its modification time
+            if self.options.date_album_folders {
+                if let Err(e) = self.date_album_folders(&exported) {
+                    self.interaction.error(&e);
+                    error_messages.push(e);
+                }
+            }
+        }
+
+        let error_messages: Vec<String> = asset_errors
+            .iter()
+            .map(AssetError::to_string)
+            .chain(error_messages)
+            .collect();
+
+        // The journal is kept after errors so that only the failed files are retried on resume
+        if let Some(journal) = &self.journal {
+            if !interrupted && error_messages.is_empty() {
+                journal.complete()?;
+            }
+        }
 
         if error_messages.is_empty() {
-            Ok(export_count)
+            Ok(
+                ExportSummary {
+                    exported: export_count,
+                    deleted,
+                    original_fallbacks: missing_derivates.len() as u64,
+                    unknown_album_fallbacks: unknown_albums.len() as u64,
+                    placeholders,
+                    interrupted
+                }
+            )
         } else {
             Err(PhotosExportError { messages: error_messages })
         }
     }
 
 
-    fn export_single_asset(&self, index: usize, total: i64, copy_operation: &CopyOperation) -> Result<(), String> {
+    /// Compares the files the export would produce to the files present in the output directory
+    /// without copying anything.
+    pub fn verify(&self, compare_checksums: bool) -> PhotosExportResult<VerificationReport> {
+        let assets = self.get_exportable_assets()?;
+        let operations = self.plan_copy_operations(&assets)?;
+
+        Ok(verify_export(&operations, &self.output_dir, compare_checksums)?)
+    }
+
+    /// Compares the files the export would produce to the files of an existing export in another
+    /// directory without copying anything.
+    pub fn diff(&self, existing_dir: &Path) -> PhotosExportResult<ExportDiff> {
+        let assets = self.get_exportable_assets()?;
+        let operations = self.plan_copy_operations(&assets)?;
+
+        Ok(diff_export(&operations, &self.output_dir, existing_dir)?)
+    }
+
+    /// Spot-checks the exported files of the configured subset of the copy operations against
+    /// their source without copying anything.
+    pub fn audit(&self) -> PhotosExportResult<AuditReport> {
+        let assets = self.get_exportable_assets()?;
+        let mut operations = self.plan_copy_operations(&assets)?;
+
+        // Assets of multiple albums are exported to the same file unless grouped by album
+        let mut seen = HashSet::new();
+        operations.retain(|(_, op)| seen.insert(op.get_output_path()));
+        if let Some(subset) = self.options.subset {
+            operations = subset.select(operations);
+        }
+
+        Ok(audit_export(&operations, &self.output_dir)?)
+    }
+
+    /// Returns the directories the export would copy files to without copying anything, e.g. to
+    /// create them ahead of the export.
+    pub fn plan_directories(&self) -> PhotosExportResult<BTreeSet<PathBuf>> {
+        let assets = self.get_exportable_assets()?;
+        let operations = self.plan_copy_operations(&assets)?;
+
+        Ok(
+            operations
+                .iter()
+                .filter_map(|(_, op)| op.get_output_path().parent().map(PathBuf::from))
+                .collect()
+        )
+    }
+
+    /// Plans the copy operations of the export without copying anything, e.g. to review them
+    /// before executing the plan.
+    pub fn plan(&self) -> PhotosExportResult<ExportPlan> {
+        let assets = self.get_exportable_assets()?;
+        let mut operations = self.plan_copy_operations(&assets)?;
+        if let Some(subset) = self.options.subset {
+            operations = subset.select(operations);
+        }
+
+        let planned: Vec<(&str, &ExportAsset, &CopyOperation)> = operations
+            .iter()
+            .map(|(asset, op)| (self.get_source_name(&assets, asset), *asset, op))
+            .collect();
+
+        Ok(ExportPlan::new(&planned, &self.output_dir)?)
+    }
+
+
+    /// Copies a single asset, returning the number of bytes copied.
+    fn export_single_asset(
+        &self,
+        index: usize,
+        total: i64,
+        asset: &ExportAsset,
+        copy_operation: &CopyOperation
+    ) -> Result<u64, AssetError> {
         let source_path = copy_operation.source_path.to_string_lossy().to_string();
         let output_path = copy_operation.get_output_path().to_string_lossy().to_string();
 
-        println!(
-            "{} Exporting '{}' to '{}'",
+        let badges = if self.options.show_badges {
+            format!(" {}", AssetBadges::of(asset, copy_operation)).trim_end().to_string()
+        } else {
+            String::new()
+        };
+        self.interaction.info(&format!(
+            "{} Exporting '{}' to '{}'{}",
             format!("({}/{})", index + 1, total).yellow(),
             source_path.dimmed(),
-            output_path.dimmed()
-        );
+            output_path.dimmed(),
+            badges.cyan()
+        ));
 
         self.copy_strategy.copy_asset(copy_operation)
-            .map(|_| ())
             .map_err(|e| {
                 // Short error message to print to the console
-                eprintln!("{} {}", "Error:".red(), e.to_string());
+                self.interaction.error(&e.to_string());
                 // Long, more detailed error message to include in the error log
+                AssetError::of(
+                    asset,
+                    AssetErrorStep::Copying,
+                    format!("'{}' to '{}': {}", source_path, output_path, e)
+                )
+            })
+    }
+
+
+    fn log_outcome(
+        &self,
+        asset: &ExportAsset,
+        copy_operation: &CopyOperation,
+        outcome: RunLogOutcome,
+        started: Instant,
+        bytes: u64,
+        error: Option<&str>
+    ) -> Result<(), String> {
+        match &self.run_log {
+            Some(run_log) => run_log.record(asset, copy_operation, outcome, started.elapsed(), bytes, error),
+            None => Ok(()),
+        }
+    }
+
+    fn skipped_empty_source_warning(&self, index: usize, total: i64, copy_operation: &CopyOperation) {
+        self.interaction.info(&format!(
+            "{} Skipping empty source file '{}'",
+            format!("({}/{})", index + 1, total).yellow(),
+            copy_operation.source_path.to_string_lossy().dimmed()
+        ));
+    }
+
+    fn skipped_duplicate_warning(
+        &self,
+        index: usize,
+        total: i64,
+        copy_operation: &CopyOperation,
+        original: &Path
+    ) {
+        self.interaction.info(&format!(
+            "{} Skipping '{}', its content has already been exported to '{}'",
+            format!("({}/{})", index + 1, total).yellow(),
+            copy_operation.source_path.to_string_lossy().dimmed(),
+            original.to_string_lossy().dimmed()
+        ));
+    }
+
+    /// Returns the missing derivates of all sources, prefixed with the name of their library if
+    /// multiple libraries are exported.
+    fn get_missing_derivates(&self) -> Vec<String> {
+        self.sources
+            .iter()
+            .flat_map(|source| {
+                source.missing_derivates
+                    .get_all()
+                    .into_iter()
+                    .map(|path| {
+                        if self.sources.len() > 1 {
+                            format!("{}: {}", source.name, path.to_string_lossy())
+                        } else {
+                            path.to_string_lossy().to_string()
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the relocated originals of all sources along with the paths they have been found
+    /// at, prefixed with the name of their library if multiple libraries are exported.
+    fn get_relocated_originals(&self) -> Vec<(String, String)> {
+        self.sources
+            .iter()
+            .flat_map(|source| {
+                source.relocated_originals
+                    .get_all()
+                    .into_iter()
+                    .map(|(original, relocated)| {
+                        let original = original.to_string_lossy().to_string();
+                        let original = if self.sources.len() > 1 {
+                            format!("{}: {}", source.name, original)
+                        } else {
+                            original
+                        };
+                        (original, relocated.to_string_lossy().to_string())
+                    })
+            })
+            .collect()
+    }
+
+    fn relocated_originals_note(&self, relocated_originals: &[(String, String)]) {
+        let lines: String = relocated_originals
+            .iter()
+            .map(|(original, relocated)| format!("\n  - {} -> {}", original.dimmed(), relocated))
+            .collect();
+        self.interaction.info(&format!(
+            "\n{} {} originals were missing at their default location and have been found elsewhere:{}",
+            "Note:".blue(),
+            relocated_originals.len(),
+            lines
+        ));
+    }
+
+    /// Returns the assets of all sources whose album is unknown along with the id of the album,
+    /// prefixed with the name of their library if multiple libraries are exported.
+    fn get_unknown_albums(&self) -> Vec<(String, i32)> {
+        self.sources
+            .iter()
+            .flat_map(|source| {
+                source.unknown_albums
+                    .get_all()
+                    .into_iter()
+                    .map(|(uuid, album_id)| {
+                        if self.sources.len() > 1 {
+                            (format!("{}: {}", source.name, uuid), album_id)
+                        } else {
+                            (uuid, album_id)
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    fn unknown_albums_warning(&self, unknown_albums: &[(String, i32)]) {
+        let lines: String = unknown_albums
+            .iter()
+            .map(|(uuid, album_id)| format!("\n  - {} (album {})", uuid.dimmed(), album_id))
+            .collect();
+        self.interaction.warn(&format!(
+            "\n{} assets were exported as if they were in no album as their album no longer exists:{}",
+            unknown_albums.len(),
+            lines
+        ));
+    }
+
+    /// Returns a description of each album renamed to tell it apart from albums with the same name,
+    /// prefixed with the name of its library if multiple libraries are exported.
+    fn get_renamed_albums(&self) -> Vec<String> {
+        self.sources
+            .iter()
+            .flat_map(|source| {
+                source.renamed_albums
+                    .get_all()
+                    .into_iter()
+                    .map(|description| {
+                        if self.sources.len() > 1 {
+                            format!("{}: {}", source.name, description)
+                        } else {
+                            description
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    fn renamed_albums_note(&self, renamed_albums: &[String]) {
+        let lines: String = renamed_albums
+            .iter()
+            .map(|description| format!("\n  - {}", description))
+            .collect();
+        self.interaction.info(&format!(
+            "\n{} {} albums share their name with another album and have been exported under a different name:{}",
+            "Note:".blue(),
+            renamed_albums.len(),
+            lines
+        ));
+    }
+
+    fn missing_derivates_warning(&self, missing_derivates: &[String]) {
+        let lines: String = missing_derivates
+            .iter()
+            .map(|path| format!("\n  - {}", path.dimmed()))
+            .collect();
+        self.interaction.warn(&format!(
+            "\n{} assets were exported as originals as their edited versions are missing:{}",
+            missing_derivates.len(),
+            lines
+        ));
+    }
+
+    fn duplicates_warning(&self, duplicates: &[(PathBuf, PathBuf)]) {
+        let lines: String = duplicates
+            .iter()
+            .map(|(skipped, original)| {
                 format!(
-                    "Error exporting '{}' to '{}': {}",
-                    source_path,
-                    output_path,
-                    e.to_string()
+                    "\n  - {} (same as {})",
+                    skipped.to_string_lossy().dimmed(),
+                    original.to_string_lossy().dimmed()
                 )
             })
+            .collect();
+        self.interaction.warn(&format!(
+            "\n{} assets were skipped as their content has already been exported:{}",
+            duplicates.len(),
+            lines
+        ));
+    }
+
+    fn empty_sources_warning(&self, empty_sources: &[String]) {
+        let action = if self.options.skip_empty_sources {
+            "were skipped"
+        } else {
+            "have been exported as empty files (use --skip-empty-sources to skip them)"
+        };
+
+        let lines: String = empty_sources
+            .iter()
+            .map(|source| format!("\n  - {}", source.dimmed()))
+            .collect();
+        self.interaction.warn(&format!(
+            "\n{} source files are empty and {}:{}",
+            empty_sources.len(),
+            action,
+            lines
+        ));
     }
 
 
-    fn get_visible_count(&self) -> Result<i64, String> {
-        self.repo
-            .get_visible_count(LocalAvailabilityFilter::Any)
-            .map_err(|e| e.to_string())
+    fn unreadable_sources_warning(&self, unreadable_sources: &[String]) {
+        let lines: String = unreadable_sources
+            .iter()
+            .map(|source| format!("\n  - {}", source.dimmed()))
+            .collect();
+        self.interaction.warn(&format!(
+            "\n{} source files could not be read and have not been exported:{}",
+            unreadable_sources.len(),
+            lines
+        ));
     }
 
-    fn get_visible_offloaded_count(&self) -> Result<i64, String> {
-        self.repo
-            .get_visible_count(LocalAvailabilityFilter::Offloaded)
-            .map_err(|e| e.to_string())
+    fn destination_collisions_warning(&self, collisions: &[(PathBuf, Vec<String>)]) {
+        let lines: String = collisions
+            .iter()
+            .map(|(path, uuids)| format!("\n  - {} ({})", path.to_string_lossy().dimmed(), uuids.join(", ")))
+            .collect();
+        self.interaction.warn(&format!(
+            "{} output paths are shared by different assets, which will overwrite each other:{}",
+            collisions.len(),
+            lines
+        ));
     }
 
-    fn get_copy_operations(&self) -> Result<Vec<CopyOperation>, String> {
-        let operations = self
-            .get_exportable_assets()?
+    fn exclusions_note(&self, exclusions: &ExclusionCounts, pipeline_exclusions: &[(PipelineStage, usize)]) {
+        let reasons: Vec<String> = [
+            (exclusions.hidden, "hidden"),
+            (exclusions.not_hidden, "not hidden"),
+            (exclusions.albums, "excluded by album filter"),
+            (exclusions.subtypes, "of an excluded subtype"),
+            (exclusions.date, "outside date range"),
+            (exclusions.shard, "in other shards"),
+        ]
             .iter()
-            .map(|a| self.copy_operation_factory.build(a))
-            .collect::<Result<Vec<Vec<_>>, _>>()?
-            .into_iter()
-            .flatten()
-            .collect::<Vec<CopyOperation>>();
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, reason)| format!("{} {}", count, reason))
+            .chain(
+                pipeline_exclusions
+                    .iter()
+                    .map(|(stage, count)| format!("{} {}", count, stage.exclusion_reason()))
+            )
+            .collect();
+
+        self.interaction.info(&format!("{} Assets excluded by filters: {}", "Note:".blue(), reasons.join(", ")));
+    }
+
+    fn case_insensitive_collisions_note(&self, renamed: usize) {
+        self.interaction.info(&format!(
+            "{} {} output paths only differ in case from another one and have a counter appended \
+            to their filename. Pass --case-sensitive-target if the output directory is case-sensitive.",
+            "Note:".blue(),
+            renamed
+        ));
+    }
+
+    /// Reports how far a previous export into the output directory got before it has been
+    /// interrupted, e.g. by a reboot, and offers to resume it if running interactively.
+    fn interrupted_export_prompt(&self, journal: &ExportJournal) -> Result<(), String> {
+        let progress = journal.progress()?;
+        let copied = match progress.planned {
+            Some(planned) => format!("{} of {}", progress.completed, planned),
+            None => progress.completed.to_string(),
+        };
+        self.interaction.info(&format!(
+            "{} A previous export into this directory did not complete after copying {} files.",
+            "Note:".blue(),
+            copied
+        ));
+
+        if !self.interaction.is_interactive() {
+            self.interaction.info("Pass --resume to skip the files it has already copied.");
+            return Ok(());
+        }
+        let prompt = "Resume it, skipping the files it has already copied? Otherwise, all files are copied again.";
+        if let Answer::Yes = self.interaction.prompt_yes_no(prompt) {
+            journal.resume();
+        }
+
+        Ok(())
+    }
+
+    fn interrupted_export_warning(&self, exported: u64, total: i64) {
+        self.interaction.warn(&format!(
+            "\nExport interrupted after {} of {} assets. Run the same command with --resume to \
+            continue.",
+            exported,
+            total
+        ));
+    }
+
+
+    fn timed_out_export_warning(&self, remaining: usize, total: i64) {
+        self.interaction.warn(&format!(
+            "\nExport stopped after reaching the maximum duration of {}, {} of {} assets remain. Run \
+            the same command with --resume to continue.",
+            format_duration(self.options.max_duration.unwrap_or_default()),
+            remaining,
+            total
+        ));
+    }
+
+    /// Runs the given function as the given stage of the planning benchmark, if any.
+    fn timed<T>(&self, stage: &str, f: impl FnOnce() -> T) -> T {
+        match &self.options.planning_benchmark {
+            Some(benchmark) => benchmark.time(stage, f),
+            None => f(),
+        }
+    }
+
+    fn get_library_counts(&self) -> Result<LibraryCounts, String> {
+        self.sources
+            .iter()
+            .map(|s| s.repo.get_library_counts())
+            .try_fold(LibraryCounts::default(), |total, counts| Ok(total.merge(counts?)))
+            .map_err(|e: diesel::result::Error| e.to_string())
+    }
+
+    /// Builds the copy operations for the assets of all sources, disambiguating output paths which
+    /// would collide on case-insensitive file systems unless the target is case-sensitive.
+    ///
+    /// If a previously saved plan is given, its copy operations are used as they are instead.
+    fn plan_copy_operations<'a>(
+        &self,
+        assets_by_source: &'a [Vec<ExportAsset>]
+    ) -> PhotosExportResult<Vec<(&'a ExportAsset, CopyOperation)>> {
+        if let Some(plan) = &self.options.plan {
+            let libraries: Vec<(&str, &Path, &[ExportAsset])> = self.sources
+                .iter()
+                .zip(assets_by_source)
+                .map(|(source, assets)| (source.name.as_str(), source.library_path.as_path(), assets.as_slice()))
+                .collect();
+            return Ok(self.timed("Resolving plan", || plan.resolve(&libraries, &self.output_dir))?);
+        }
+
+        let mut operations = self.timed("Building copy operations", || self.get_copy_operations(assets_by_source))?;
+
+        if !self.options.case_sensitive_target {
+            let renamed = self.timed(
+                "Disambiguating paths",
+                || disambiguate_case_insensitive_collisions(&mut operations)
+            );
+            if renamed > 0 {
+                self.case_insensitive_collisions_note(renamed);
+            }
+        }
 
         Ok(operations)
     }
 
-    fn get_exportable_assets(&self) -> Result<Vec<ExportAsset>, String> {
-        self.repo
-            .get_exportable()
-            .map_err(|e| e.to_string())?
+    /// Builds the copy operations for the assets of all sources.
+    ///
+    /// Assets whose UUID has already been seen in a previous source get the name of their source
+    /// appended to their output filename so that they do not overwrite each other.
+    fn get_copy_operations<'a>(
+        &self,
+        assets_by_source: &'a [Vec<ExportAsset>]
+    ) -> Result<Vec<(&'a ExportAsset, CopyOperation)>, AssetError> {
+        let mut operations = Vec::new();
+        let mut seen_uuids = HashSet::new();
+
+        for (source, assets) in self.sources.iter().zip(assets_by_source) {
+            let mut source_uuids = HashSet::new();
+
+            for asset in assets {
+                let is_collision = seen_uuids.contains(&asset.uuid);
+                source_uuids.insert(asset.uuid.clone());
+
+                let asset_operations = source.copy_operation_factory
+                    .build(asset)
+                    .map_err(|e| AssetError::of(asset, AssetErrorStep::Planning, e))?;
+                for op in asset_operations {
+                    let op = if is_collision {
+                        CopyOperation {
+                            output_filename: format!("{}_{}", op.output_filename, source.name),
+                            ..op
+                        }
+                    } else {
+                        op
+                    };
+                    operations.push((asset, op));
+                }
+            }
+
+            seen_uuids.extend(source_uuids);
+        }
+
+        Ok(operations)
+    }
+
+    /// Returns the name of the source the given asset has been loaded from.
+    fn get_source_name(&self, assets_by_source: &[Vec<ExportAsset>], asset: &ExportAsset) -> &str {
+        self.sources
             .iter()
-            .map(|a| {
-                ExportAsset::from_db_model(a)
-                    .map_err(|e| e.to_string())
-            })
-            .collect::<Result<Vec<ExportAsset>, String>>()
+            .zip(assets_by_source)
+            .find(|(_, assets)| assets.as_ptr_range().contains(&(asset as *const ExportAsset)))
+            .map(|(source, _)| source.name.as_str())
+            .unwrap_or_default()
+    }
+
+    fn get_exclusion_counts(&self) -> Result<ExclusionCounts, String> {
+        self.sources
+            .iter()
+            .map(|s| s.repo.get_exclusion_counts())
+            .try_fold(ExclusionCounts::default(), |total, counts| Ok(total.merge(counts?)))
+            .map_err(|e: diesel::result::Error| e.to_string())
+    }
+
+    /// Returns the number of assets excluded per filtering stage of the pipelines of all sources.
+    fn get_pipeline_exclusions(&self) -> Vec<(PipelineStage, usize)> {
+        let mut counts: BTreeMap<PipelineStage, usize> = BTreeMap::new();
+        for source in &self.sources {
+            for (stage, count) in source.pipeline_exclusions.get_all() {
+                *counts.entry(stage).or_default() += count;
+            }
+        }
+        counts.into_iter().collect()
+    }
+
+    fn get_exportable_assets(&self) -> PhotosExportResult<Vec<Vec<ExportAsset>>> {
+        self.sources
+            .iter()
+            .map(|s| self.get_exportable_assets_of_source(s))
+            .collect()
+    }
+
+    fn get_exportable_assets_of_source(&self, source: &ExportSource) -> PhotosExportResult<Vec<ExportAsset>> {
+        Ok(detach_from_albums(load_assets(&source.repo.get_exportable()?)?, &source.detached_albums))
+    }
+
+    /// Writes placeholders for the assets of all sources that are not locally available, if
+    /// enabled, returning their number. Assets whose file has already been exported, e.g. before
+    /// their original has been offloaded, are skipped.
+    ///
+    /// The copy operations are built after all others, so that the registries of the factories
+    /// only report fallbacks of the exported files.
+    fn write_placeholders(&self) -> Result<u64, String> {
+        if !self.options.write_placeholders {
+            return Ok(0);
+        }
+
+        let mut written_paths = HashSet::new();
+        for source in &self.sources {
+            let assets = load_assets(&source.repo.get_offloaded().map_err(|e| e.to_string())?)
+                .map_err(|e| e.to_string())?;
+            let assets = detach_from_albums(assets, &source.detached_albums);
+            for asset in &assets {
+                let operations = source.copy_operation_factory
+                    .build(asset)
+                    .map_err(|e| AssetError::of(asset, AssetErrorStep::Planning, e).to_string())?;
+                // Assets of multiple albums are exported to the same file unless grouped by album
+                for op in operations.iter().filter(|op| !op.get_output_path().exists()) {
+                    if written_paths.insert(op.get_output_path()) {
+                        write_placeholder(asset, op)?;
+                    }
+                }
+            }
+        }
+
+        Ok(written_paths.len() as u64)
     }
 
+    /// Removes the directories of the previous export's files that have been left empty, if
+    /// enabled, returning their number. More directories than the configured threshold are only
+    /// removed after a separate confirmation.
+    fn prune_empty_dirs(&self) -> Result<u64, String> {
+        let Some(previous_files) = &self.options.previous_files else {
+            return Ok(0);
+        };
+
+        let prunable = find_prunable_dirs(&self.output_dir, previous_files)?;
+        if prunable.is_empty() {
+            return Ok(0);
+        }
 
-    fn missing_assets_prompt(&self, total: i64, missing: i64) -> Answer {
-        println!(
-            "{} {} of {} assets in your library are not locally available and can not be exported.",
-            "Warning:".yellow(),
+        if self.options.confirm_deletions_above.is_some_and(|threshold| prunable.len() > threshold) {
+            let prompt = format!("Remove {} empty directories from the output directory?", prunable.len());
+            if let Answer::No = self.interaction.prompt_yes_no(&prompt) {
+                self.kept_empty_dirs_note(prunable.len());
+                return Ok(0);
+            }
+        }
+
+        let total = prunable.len();
+        for (index, dir) in prunable.iter().enumerate() {
+            self.interaction.info(&format!(
+                "{} {} '{}'",
+                format!("({}/{})", index + 1, total).yellow(),
+                "Removing empty directory".red(),
+                dir.to_string_lossy().dimmed()
+            ));
+            remove_dir(dir).map_err(|e| format!("Unable to remove '{}': {}", dir.to_string_lossy(), e))?;
+        }
+
+        Ok(total as u64)
+    }
+
+    /// Sets the modification time of the album folders of the given exported files to the start
+    /// date of their albums.
+    fn date_album_folders(&self, exported: &[(&ExportAsset, &CopyOperation)]) -> Result<(), String> {
+        let folders: BTreeMap<PathBuf, NaiveDateTime> = exported
+            .iter()
+            .filter_map(|(_, op)| {
+                let album_folder = op.album_folder?;
+                let relative = op.output_folder.as_ref()?.strip_prefix(&self.output_dir).ok()?;
+                let folder: PathBuf = relative.components().take(album_folder.depth).collect();
+                Some((self.output_dir.join(folder), album_folder.start_date))
+            })
+            .collect();
+
+        folders
+            .iter()
+            .filter(|(folder, _)| folder.is_dir())
+            .try_for_each(|(folder, start_date)| set_modified_time(folder, *start_date))
+    }
+
+    fn missing_assets_prompt(&self, total: i64, missing: i64, planned: i64) -> Answer {
+        self.interaction.warn(&format!(
+            "{} of {} assets in your library are not locally available and can not be exported.",
             missing,
             total,
-        );
-        confirmation_prompt("Continue anyway?".to_string())
+        ));
+        self.confirm("Continue anyway?".to_string(), planned)
+    }
+
+    /// Asks the user to confirm the given prompt, unless fewer copy operations than the threshold
+    /// for confirming automatically are planned. The prompt is declined if the export has been
+    /// interrupted before it has been answered.
+    fn confirm(&self, prompt: String, planned: i64) -> Answer {
+        let answer = match self.options.auto_confirm_below {
+            Some(threshold) if planned < threshold as i64 => {
+                self.interaction.info(&format!("{} {}", prompt, "y (confirmed automatically)".dimmed()));
+                Answer::Yes
+            },
+            _ => self.interaction.prompt_yes_no(&prompt),
+        };
+        // Ctrl-C does not abort reading the answer, so an export confirmed afterwards would only
+        // start to stop right away
+        if is_interrupted() {
+            Answer::No
+        } else {
+            answer
+        }
+    }
+
+    fn library_counts_note(&self, counts: &LibraryCounts) {
+        self.interaction.info(&format!(
+            "{} The {} {} assets, of which {} are hidden and {} are offloaded to iCloud. {} more \
+            assets are in Recently Deleted.",
+            "Note:".blue(),
+            if self.sources.len() > 1 { "libraries contain" } else { "library contains" },
+            counts.visible,
+            counts.hidden,
+            counts.offloaded,
+            counts.trashed
+        ));
     }
 
     fn start_export_prompt(&self, total: i64) -> Answer {
-        println!(
+        self.interaction.info(&format!(
             "{} Some assets may be part of multiple albums and will be exported multiple times. \
             Thus, the number of exported assets may be higher than the number of assets in the \
             database.",
             "Note:".blue()
-        );
-        confirmation_prompt(
-            format!(
-                "Export {} assets?",
-                &total,
+        ));
+        if self.options.metadata_only {
+            self.confirm(format!("Write the metadata of {} assets without copying them?", &total), total)
+        } else {
+            self.confirm(format!("Export {} assets?", &total), total)
+        }
+    }
+
+    fn kept_empty_dirs_note(&self, count: usize) {
+        self.interaction.info(&format!(
+            "{} {} empty directories have been kept in the output directory.",
+            "Note:".blue(),
+            count
+        ));
+    }
+
+    fn subset_note(&self, selected: usize, total: usize) {
+        self.interaction.info(&format!(
+            "{} Only {} of {} planned files are exported as a subset has been requested.",
+            "Note:".blue(),
+            selected,
+            total
+        ));
+    }
+
+    fn no_matching_assets_warning(&self) {
+        self.interaction.warn("No available assets match the specified criteria!")
+    }
+}
+
+
+fn load_assets(dtos: &[ExportAssetDto]) -> Result<Vec<ExportAsset>, AssetError> {
+    dtos
+        .iter()
+        .map(|a| {
+            ExportAsset::from_db_model(a)
+                .map_err(|e| AssetError::new(&a.uuid, &a.original_filename, AssetErrorStep::Loading, e))
+        })
+        .collect()
+}
+
+/// Number of bytes read from each source file to check that it is readable
+const SOURCE_PROBE_LENGTH: usize = 16;
+
+/// Problem of a source file that exists, but can not be exported as is
+#[derive(Debug, PartialEq)]
+enum SourceProblem {
+    /// The file has a size of zero bytes, e.g. due to a failed iCloud sync
+    Empty,
+    /// The file can not be opened or read, e.g. as its permissions do not allow it, along with the
+    /// reason
+    Unreadable(String),
+}
+
+/// Checks that the source file of the copy operation is neither empty nor unreadable by reading its
+/// first bytes. Missing files are reported when copying them. Whether the content is valid, e.g.
+/// has an intact header, is not checked.
+fn check_source(copy_operation: &CopyOperation) -> Option<SourceProblem> {
+    let metadata = metadata(&copy_operation.source_path).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    if metadata.len() == 0 {
+        return Some(SourceProblem::Empty);
+    }
+
+    let mut buffer = [0; SOURCE_PROBE_LENGTH];
+    File::open(&copy_operation.source_path)
+        .and_then(|mut file| file.read(&mut buffer))
+        .err()
+        .map(|e| SourceProblem::Unreadable(e.to_string()))
+}
+
+/// Finds output paths that different assets are exported to, returning the UUIDs of the assets
+/// per path. Assets exported to the same path multiple times, e.g. because they are part of
+/// multiple albums, are not considered a collision.
+fn find_destination_collisions(operations: &[(&ExportAsset, CopyOperation)]) -> Vec<(PathBuf, Vec<String>)> {
+    let mut uuids_by_path: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+
+    for (asset, op) in operations {
+        let uuids = uuids_by_path.entry(op.get_output_path()).or_default();
+        if !uuids.contains(&asset.uuid) {
+            uuids.push(asset.uuid.clone());
+        }
+    }
+
+    uuids_by_path
+        .into_iter()
+        .filter(|(_, uuids)| uuids.len() > 1)
+        .collect()
+}
+
+/// Appends a counter to the filename of output paths only differing in case from another output
+/// path, as they overwrite each other on case-insensitive file systems like APFS or exFAT. The
+/// paths are disambiguated in sorted order, so that the same paths are always renamed the same
+/// way. Returns the number of renamed paths.
+fn disambiguate_case_insensitive_collisions(operations: &mut [(&ExportAsset, CopyOperation)]) -> usize {
+    let mut paths_by_folded_path: BTreeMap<String, BTreeSet<PathBuf>> = BTreeMap::new();
+    for (_, op) in operations.iter() {
+        let path = op.get_output_path();
+        paths_by_folded_path
+            .entry(path.to_string_lossy().to_lowercase())
+            .or_default()
+            .insert(path);
+    }
+
+    let counters: HashMap<PathBuf, usize> = paths_by_folded_path
+        .into_values()
+        .flat_map(|paths| paths.into_iter().enumerate().skip(1).map(|(index, path)| (path, index + 1)))
+        .collect();
+
+    for (_, op) in operations.iter_mut() {
+        if let Some(counter) = counters.get(&op.get_output_path()) {
+            op.output_filename = format!("{}_{}", op.output_filename, counter);
+        }
+    }
+
+    counters.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repo::album::{with_asset_count_outside, AlbumRepository};
+    use crate::db::repo::asset::{AlbumFilter, HiddenAssetsFilter};
+    use crate::export::copying::{
+        AbsolutePathBuildingCopyOperationFactoryDecorator, DefaultAssetCopyStrategy, DryRunAssetCopyStrategy,
+        FilenameRestoringCopyOperationFactoryDecorator, OriginalsCopyOperationFactory,
+        OutputStructureCopyOperationFactoryDecorator
+    };
+    use crate::export::placeholders::get_placeholder_path;
+    use crate::export::structure::AlbumOutputStrategy;
+    use crate::util::interaction::NonInteractive;
+    use chrono::{Local, TimeZone};
+    use std::time::SystemTime;
+    use crate::model::uti::Uti;
+    use crate::test_util::library::{SyntheticLibrary, TestAlbum, TestAsset};
+    use std::cell::RefCell;
+
+    /// Answers all prompts with the given answer, recording the prompts, warnings and errors
+    struct RecordingInteraction {
+        answer: Answer,
+        prompts: RefCell<Vec<String>>,
+        warnings: RefCell<Vec<String>>,
+        errors: RefCell<Vec<String>>,
+    }
+    impl RecordingInteraction {
+        fn new(answer: Answer) -> Rc<Self> {
+            Rc::new(
+                RecordingInteraction {
+                    answer,
+                    prompts: RefCell::default(),
+                    warnings: RefCell::default(),
+                    errors: RefCell::default()
+                }
             )
+        }
+    }
+    impl UserInteraction for RecordingInteraction {
+        fn prompt_yes_no(&self, prompt: &str) -> Answer {
+            self.prompts.borrow_mut().push(prompt.to_string());
+            self.answer
+        }
+        fn warn(&self, message: &str) {
+            self.warnings.borrow_mut().push(message.to_string());
+        }
+        fn error(&self, message: &str) {
+            self.errors.borrow_mut().push(message.to_string());
+        }
+        fn info(&self, _: &str) {}
+        fn is_interactive(&self) -> bool {
+            false
+        }
+    }
+
+    fn source(name: &str, library: &SyntheticLibrary) -> ExportSource {
+        ExportSource::new(
+            name.to_string(),
+            library.path().to_path_buf(),
+            AssetRepository::new(
+                library.db_path(),
+                HiddenAssetsFilter::Include,
+                AlbumFilter::None,
+                vec![],
+                None
+            ),
+            Box::new(OriginalsCopyOperationFactory::new())
         )
     }
 
-    fn no_matching_assets_warning(&self) {
-        println!("{} No available assets match the specified criteria!", "Warning:".yellow())
+    /// Returns a source of all assets of the library building their copy operations with the given
+    /// factory, resolved against the library and the output directory.
+    fn resolving_source(library: &SyntheticLibrary, output_dir: &Path, factory: Box<dyn CopyOperationFactory>) -> ExportSource {
+        ExportSource::new(
+            "Library".to_string(),
+            library.path().to_path_buf(),
+            AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None),
+            Box::new(
+                AbsolutePathBuildingCopyOperationFactoryDecorator::new(
+                    library.path().to_path_buf(),
+                    output_dir.to_path_buf(),
+                    factory
+                )
+            )
+        )
+    }
+
+    #[test]
+    fn asks_for_confirmation_through_the_user_interaction() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::jpeg(2).offloaded());
+
+        let export = |answer| {
+            let interaction = RecordingInteraction::new(answer);
+            let summary = Exporter::new(
+                vec![source("Library", &library)],
+                Box::new(DryRunAssetCopyStrategy::new()),
+                vec![],
+                ExportOptions::default(),
+                PathBuf::new(),
+                None
+            ).with_interaction(interaction.clone()).export().unwrap();
+            (summary.exported, interaction.prompts.take(), interaction.warnings.take())
+        };
+
+        let (exported, prompts, warnings) = export(Answer::No);
+        assert_eq!(exported, 0);
+        assert_eq!(prompts, ["Continue anyway?"]);
+        assert_eq!(warnings, ["1 of 2 assets in your library are not locally available and can not be exported."]);
+
+        let (exported, prompts, _) = export(Answer::Yes);
+        assert_eq!(exported, 1);
+        assert_eq!(prompts, ["Continue anyway?", "Export 1 assets?"]);
+    }
+
+    #[test]
+    fn confirms_automatically_below_the_threshold_only() {
+        let library = SyntheticLibrary::new();
+        let interaction = RecordingInteraction::new(Answer::No);
+        let exporter = Exporter::new(
+            vec![source("Library", &library)],
+            Box::new(DryRunAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default().with_auto_confirm_below(Some(3)),
+            PathBuf::new(),
+            None
+        ).with_interaction(interaction.clone());
+
+        assert_eq!(exporter.confirm(String::from("Export 2 assets?"), 2), Answer::Yes);
+        assert!(interaction.prompts.take().is_empty());
+
+        assert_eq!(exporter.confirm(String::from("Export 3 assets?"), 3), Answer::No);
+        assert_eq!(exporter.confirm(String::from("Export 4 assets?"), 4), Answer::No);
+        assert_eq!(interaction.prompts.take(), ["Export 3 assets?", "Export 4 assets?"]);
+    }
+
+    #[test]
+    fn always_asks_without_a_threshold() {
+        let library = SyntheticLibrary::new();
+        let interaction = RecordingInteraction::new(Answer::Yes);
+        let exporter = Exporter::new(
+            vec![source("Library", &library)],
+            Box::new(DryRunAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default(),
+            PathBuf::new(),
+            None
+        ).with_interaction(interaction.clone());
+
+        assert_eq!(exporter.confirm(String::from("Export 0 assets?"), 0), Answer::Yes);
+        assert_eq!(interaction.prompts.take(), ["Export 0 assets?"]);
+    }
+
+    #[test]
+    fn reports_errors_through_the_user_interaction() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let interaction = RecordingInteraction::new(Answer::Yes);
+
+        let result = Exporter::new(
+            vec![source("Library", &library)],
+            Box::new(DryRunAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default().with_expected_count(ExpectedCount { at_least: Some(2), at_most: None }),
+            PathBuf::new(),
+            None
+        ).with_interaction(interaction.clone()).export();
+
+        assert!(result.is_err());
+        assert_eq!(interaction.errors.take(), ["The export planned 1 files, but at least 2 were expected"]);
+    }
+
+    #[test]
+    fn confirms_removing_more_empty_directories_than_the_threshold() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let output = tempfile::tempdir().unwrap();
+        let previous_files = vec![PathBuf::from("Trips/Summer/IMG_0002.jpeg"), PathBuf::from("Family/IMG_0003.jpeg")];
+
+        let export = |threshold| {
+            std::fs::create_dir_all(output.path().join("Trips").join("Summer")).unwrap();
+            std::fs::create_dir_all(output.path().join("Family")).unwrap();
+            let interaction = RecordingInteraction::new(Answer::No);
+            let summary = Exporter::new(
+                vec![resolving_source(&library, output.path(), Box::new(OriginalsCopyOperationFactory::new()))],
+                Box::new(DefaultAssetCopyStrategy::new()),
+                vec![],
+                ExportOptions::default()
+                    .with_auto_confirm_below(Some(10))
+                    .with_pruning(previous_files.clone())
+                    .with_confirm_deletions_above(Some(threshold)),
+                output.path().to_path_buf(),
+                None
+            ).with_interaction(interaction.clone()).export().unwrap();
+
+            (summary.exported, summary.deleted, interaction.prompts.take())
+        };
+
+        assert_eq!(export(2), (1, 0, vec![String::from("Remove 3 empty directories from the output directory?")]));
+        assert!(output.path().join("Trips").join("Summer").is_dir());
+        assert!(output.path().join("Family").is_dir());
+
+        assert_eq!(export(3), (1, 3, vec![]));
+        assert!(!output.path().join("Trips").exists());
+        assert!(!output.path().join("Family").exists());
+    }
+
+    #[test]
+    fn removes_placeholders_of_exported_files_without_writing_placeholders() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let output = tempfile::tempdir().unwrap();
+        let placeholder = get_placeholder_path(&output.path().join("ASSET-0001.jpeg"));
+        std::fs::write(&placeholder, "{}").unwrap();
+
+        let summary = Exporter::new(
+            vec![resolving_source(&library, output.path(), Box::new(OriginalsCopyOperationFactory::new()))],
+            Box::new(DefaultAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default()
+                .with_auto_confirm_below(Some(10))
+                .with_placeholder_removal(true),
+            output.path().to_path_buf(),
+            None
+        ).with_interaction(RecordingInteraction::new(Answer::No)).export().unwrap();
+
+        assert_eq!(summary.exported, 1);
+        assert!(!placeholder.exists());
+    }
+
+    #[test]
+    fn exports_assets_of_skipped_albums_as_if_they_were_in_no_album() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Small"));
+        library.add_album(&TestAlbum::album(3, "Large"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2]));
+        library.add_asset(&TestAsset::jpeg(2).in_albums(&[2, 3]));
+        library.add_asset(&TestAsset::jpeg(3).in_albums(&[3]));
+        library.add_asset(&TestAsset::jpeg(4).in_albums(&[3]));
+        let output = tempfile::tempdir().unwrap();
+
+        let album_repo = AlbumRepository::new(library.db_path(), None);
+        let albums = album_repo.get_all().unwrap();
+        let small_albums = with_asset_count_outside(&albums, &album_repo.get_asset_counts().unwrap(), Some(3), None);
+        let factory = OutputStructureCopyOperationFactoryDecorator::new(
+            Box::new(OriginalsCopyOperationFactory::new()),
+            Box::new(AlbumOutputStrategy::new(false, albums))
+        );
+        let exporter = Exporter::new(
+            vec![
+                resolving_source(&library, output.path(), Box::new(factory))
+                    .with_detached_albums(small_albums.into_iter().collect())
+            ],
+            Box::new(DefaultAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default(),
+            output.path().to_path_buf(),
+            None
+        ).with_interaction(Rc::new(NonInteractive::new(Answer::Yes)));
+        exporter.export().unwrap();
+
+        assert!(output.path().join(TestAsset::jpeg(1).filename()).exists());
+        assert!(output.path().join("Large").join(TestAsset::jpeg(2).filename()).exists());
+        assert!(!output.path().join(TestAsset::jpeg(2).filename()).exists());
+        assert!(output.path().join("Large").join(TestAsset::jpeg(3).filename()).exists());
+        assert!(!output.path().join("Small").exists());
+    }
+
+    #[test]
+    fn dates_album_folders_to_their_start_date() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum::album(3, "Summer").in_folder(2).starting_at(740_000_000.0));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[3]));
+        let output = tempfile::tempdir().unwrap();
+
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let factory = OutputStructureCopyOperationFactoryDecorator::new(
+            Box::new(OriginalsCopyOperationFactory::new()),
+            Box::new(AlbumOutputStrategy::new(false, albums))
+        );
+        let exporter = Exporter::new(
+            vec![resolving_source(&library, output.path(), Box::new(factory))],
+            Box::new(DefaultAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default().with_album_folder_dates(true),
+            output.path().to_path_buf(),
+            None
+        ).with_interaction(Rc::new(NonInteractive::new(Answer::Yes)));
+        exporter.export().unwrap();
+
+        let assets = exporter.get_exportable_assets().unwrap().remove(0);
+        let start_date = assets[0].album.as_ref().and_then(|album| album.start_date).unwrap();
+        let start_date: SystemTime = Local.from_local_datetime(&start_date).unwrap().into();
+        let modified = |path: &Path| metadata(path).unwrap().modified().unwrap();
+        assert_eq!(modified(&output.path().join("Trips").join("Summer")), start_date);
+        assert_ne!(modified(&output.path().join("Trips")), start_date);
+    }
+
+    #[test]
+    fn disambiguates_assets_present_in_multiple_libraries() {
+        let first = SyntheticLibrary::new();
+        first.add_asset(&TestAsset::jpeg(1));
+        let second = SyntheticLibrary::new();
+        second.add_asset(&TestAsset::jpeg(1));
+        second.add_asset(&TestAsset::jpeg(2));
+
+        let exporter = Exporter::new(
+            vec![source("First", &first), source("Second", &second)],
+            Box::new(DryRunAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default(),
+            PathBuf::new(),
+            None
+        );
+        let assets = exporter.get_exportable_assets().unwrap();
+        let mut filenames: Vec<String> = exporter
+            .get_copy_operations(&assets)
+            .unwrap()
+            .into_iter()
+            .map(|(_, op)| op.output_filename)
+            .collect();
+        filenames.sort();
+
+        assert_eq!(filenames, ["ASSET-0001", "ASSET-0001_Second", "ASSET-0002"]);
+    }
+
+    #[test]
+    fn disambiguates_paths_only_differing_in_case() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(
+            &TestAsset { original_filename: "img_0001.jpg".to_string(), ..TestAsset::jpeg(2) }
+        );
+        library.add_asset(&TestAsset::jpeg(3));
+
+        let exporter = Exporter::new(
+            vec![
+                ExportSource::new(
+                    "Library".to_string(),
+                    library.path().to_path_buf(),
+                    AssetRepository::new(
+                        library.db_path(),
+                        HiddenAssetsFilter::Include,
+                        AlbumFilter::None,
+                        vec![],
+                        None
+                    ),
+                    Box::new(
+                        FilenameRestoringCopyOperationFactoryDecorator::new(
+                            Box::new(OriginalsCopyOperationFactory::new())
+                        )
+                    )
+                )
+            ],
+            Box::new(DryRunAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default(),
+            PathBuf::new(),
+            None
+        );
+        let assets = exporter.get_exportable_assets().unwrap();
+        let mut operations = exporter.get_copy_operations(&assets).unwrap();
+
+        assert_eq!(disambiguate_case_insensitive_collisions(&mut operations), 1);
+
+        let mut paths: Vec<(String, PathBuf)> = operations
+            .iter()
+            .map(|(asset, op)| (asset.uuid.clone(), op.get_output_path()))
+            .collect();
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            [
+                ("ASSET-0001".to_string(), PathBuf::from("IMG_0001.jpeg")),
+                ("ASSET-0002".to_string(), PathBuf::from("img_0001_2.jpeg")),
+                ("ASSET-0003".to_string(), PathBuf::from("IMG_0003.jpeg")),
+            ]
+        );
+    }
+
+    #[test]
+    fn identifies_assets_in_errors() {
+        struct FailingCopyOperationFactory;
+        impl CopyOperationFactory for FailingCopyOperationFactory {
+            fn build(&self, _: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+                Err("Failed to get file stem".to_string())
+            }
+        }
+
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let unknown = SyntheticLibrary::new();
+        unknown.add_asset(&TestAsset { uti: "public.unknown", ..TestAsset::jpeg(2) });
+
+        let exporter = |source| Exporter::new(
+            vec![source],
+            Box::new(DryRunAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default(),
+            PathBuf::new(),
+            None
+        );
+
+        let failing = exporter(
+            ExportSource::new(
+                "Library".to_string(),
+                library.path().to_path_buf(),
+                AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None),
+                Box::new(FailingCopyOperationFactory)
+            )
+        );
+        let assets = failing.get_exportable_assets().unwrap();
+        let error = failing.get_copy_operations(&assets).err().unwrap();
+        assert_eq!(
+            error,
+            AssetError::new("ASSET-0001", "IMG_0001.JPG", AssetErrorStep::Planning, "Failed to get file stem")
+        );
+        assert_eq!(
+            error.to_string(),
+            "Error while planning the files of asset ASSET-0001 (IMG_0001.JPG): Failed to get file stem"
+        );
+
+        let dtos = AssetRepository::new(unknown.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+            .get_exportable()
+            .unwrap();
+        let error = load_assets(&dtos).err().unwrap();
+        assert_eq!((error.uuid.as_str(), error.step), ("ASSET-0002", AssetErrorStep::Loading));
+        let messages = exporter(source("Unknown", &unknown)).get_exportable_assets().err().unwrap().messages;
+        assert!(messages[0].starts_with("Error while loading asset ASSET-0002 (IMG_0002.JPG): "), "{:?}", messages);
+    }
+
+    #[test]
+    fn selects_subsets_of_copy_operations() {
+        let items: Vec<usize> = (0..100).collect();
+
+        assert_eq!(ExportSubset::Limit(3).select(items.clone()), [0, 1, 2]);
+        assert_eq!(ExportSubset::Limit(200).select(items.clone()), items);
+        assert_eq!(ExportSubset::Sample(200, None).select(items.clone()), items);
+
+        let sample = ExportSubset::Sample(10, None).select(items.clone());
+        assert_eq!(sample.len(), 10);
+        assert!(sample.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert_eq!(
+            ExportSubset::Sample(10, Some(42)).select(items.clone()),
+            ExportSubset::Sample(10, Some(42)).select(items)
+        );
+    }
+
+    #[test]
+    fn detects_empty_and_unreadable_sources() {
+        let library = SyntheticLibrary::new();
+        let asset = TestAsset::jpeg(1);
+        library.add_asset(&asset);
+        library.write_file(&TestAsset::jpeg(2).original_path(), "");
+        library.write_file(&TestAsset::jpeg(4).original_path(), "unreadable");
+
+        let uti = Uti::from_name("public.jpeg").unwrap();
+        let op = |path| CopyOperation::new(library.path().join(path), uti, String::new(), None, None);
+
+        assert_eq!(check_source(&op(asset.original_path())), None);
+        assert_eq!(check_source(&op(TestAsset::jpeg(2).original_path())), Some(SourceProblem::Empty));
+        assert_eq!(check_source(&op(TestAsset::jpeg(3).original_path())), None);
+
+        let unreadable = library.path().join(TestAsset::jpeg(4).original_path());
+        std::fs::set_permissions(&unreadable, std::os::unix::fs::PermissionsExt::from_mode(0o000)).unwrap();
+        // Permissions do not apply to root
+        if File::open(&unreadable).is_err() {
+            assert!(matches!(check_source(&op(TestAsset::jpeg(4).original_path())), Some(SourceProblem::Unreadable(_))));
+        }
+    }
+
+    #[test]
+    fn finds_destination_collisions() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_album(&TestAlbum::album(3, "Winter"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2, 3]));
+        library.add_asset(
+            &TestAsset { original_filename: "IMG_0001.JPG".to_string(), ..TestAsset::jpeg(2) }
+        );
+        library.add_asset(&TestAsset::jpeg(3));
+
+        let exporter = Exporter::new(
+            vec![
+                ExportSource::new(
+                    "Library".to_string(),
+                    library.path().to_path_buf(),
+                    AssetRepository::new(
+                        library.db_path(),
+                        HiddenAssetsFilter::Include,
+                        AlbumFilter::None,
+                        vec![],
+                        None
+                    ),
+                    Box::new(
+                        FilenameRestoringCopyOperationFactoryDecorator::new(
+                            Box::new(OriginalsCopyOperationFactory::new())
+                        )
+                    )
+                )
+            ],
+            Box::new(DryRunAssetCopyStrategy::new()),
+            vec![],
+            ExportOptions::default(),
+            PathBuf::new(),
+            None
+        );
+        let assets = exporter.get_exportable_assets().unwrap();
+        let operations = exporter.get_copy_operations(&assets).unwrap();
+
+        assert_eq!(
+            find_destination_collisions(&operations),
+            [(PathBuf::from("IMG_0001.jpeg"), vec!["ASSET-0001".to_string(), "ASSET-0002".to_string()])]
+        );
+    }
+    #[test]
+    fn checks_expected_counts() {
+        let expected = ExpectedCount { at_least: Some(2), at_most: Some(3) };
+
+        assert!(expected.check(1).is_err());
+        assert!(expected.check(2).is_ok());
+        assert!(expected.check(3).is_ok());
+        assert!(expected.check(4).is_err());
+        assert!(ExpectedCount::default().check(0).is_ok());
+    }
+
+    #[test]
+    fn lists_badges_of_present_states_only() {
+        let badges = AssetBadges { favorite: true, hidden: false, edited: true, raw: true };
+        assert_eq!(badges.to_string(), "★ ✎ RAW");
+
+        let badges = AssetBadges { favorite: false, hidden: false, edited: false, raw: false };
+        assert_eq!(badges.to_string(), "");
     }
 }
\ No newline at end of file
@@ -0,0 +1,285 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs::{read_to_string, remove_file, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::cache::get_database_state;
+
+pub const JOURNAL_FILENAME: &str = ".apple-photos-export.journal";
+pub const FINGERPRINT_FILENAME: &str = ".apple-photos-export.fingerprint";
+
+/// Flags that do not change the exported files, and thus are not part of a fingerprint.
+const FINGERPRINT_IGNORED_FLAGS: [&str; 4] = ["--force", "--no-cache", "--non-interactive", "--resume"];
+
+/// Prefix of the lines recording the number of files planned by a run. Output paths are absolute,
+/// so they never start with it.
+const PLANNED_PREFIX: &str = "# planned ";
+
+/// Records the output paths of all files copied during an export in the output directory, so
+/// that an interrupted export can be resumed by skipping the files already copied.
+///
+/// Each copied file is appended to the journal and flushed to disk immediately, so that the
+/// progress is also kept if the process is killed or the machine reboots. The journal is removed
+/// once the export has been completed.
+pub struct ExportJournal {
+    path: PathBuf,
+    /// Whether to skip the files recorded by a previous, interrupted run
+    resume: Cell<bool>,
+    /// Fingerprint of this run, recorded once it has been completed
+    fingerprint: Option<ExportFingerprint>,
+}
+
+/// State of the exported libraries along with the arguments of an export, recorded next to the
+/// journal once an export has been completed. Running the same export again before any library has
+/// changed would not copy anything new, so it can be skipped.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ExportFingerprint {
+    version: String,
+    /// Canonical paths of the libraries' databases along with their state
+    libraries: Vec<(PathBuf, [SystemTime; 2])>,
+    args: Vec<String>,
+}
+
+impl ExportFingerprint {
+
+    /// Creates the fingerprint of an export of the given databases with the given arguments, or
+    /// returns `None` if the state of a database can not be determined.
+    pub fn new(db_paths: &[String], args: &[String]) -> Option<Self> {
+        let libraries = db_paths
+            .iter()
+            .map(|db_path| Some((std::fs::canonicalize(db_path).ok()?, get_database_state(db_path)?)))
+            .collect::<Option<Vec<_>>>()?;
+        let args = args
+            .iter()
+            .filter(|arg| !FINGERPRINT_IGNORED_FLAGS.contains(&arg.as_str()))
+            .cloned()
+            .collect();
+
+        Some(ExportFingerprint { version: env!("CARGO_PKG_VERSION").to_string(), libraries, args })
+    }
+}
+
+/// How far the run that wrote a journal got.
+#[derive(Debug, PartialEq)]
+pub struct JournalProgress {
+    /// Number of distinct files copied
+    pub completed: usize,
+    /// Number of files planned by the last run, unknown for journals of older versions
+    pub planned: Option<usize>,
+}
+
+impl ExportJournal {
+
+    pub fn new(output_dir: &Path, resume: bool) -> Self {
+        ExportJournal { path: output_dir.join(JOURNAL_FILENAME), resume: Cell::new(resume), fingerprint: None }
+    }
+
+    pub fn with_fingerprint(self, fingerprint: ExportFingerprint) -> Self {
+        ExportJournal { fingerprint: Some(fingerprint), ..self }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+
+    pub fn is_resuming(&self) -> bool {
+        self.resume.get()
+    }
+
+    /// Resumes the previous run, e.g. after the user has chosen to, as if resuming had been
+    /// requested when creating the journal.
+    pub fn resume(&self) {
+        self.resume.set(true);
+    }
+
+    /// Returns the output paths copied by previous runs if resuming, or an empty set otherwise.
+    pub fn completed(&self) -> Result<HashSet<PathBuf>, String> {
+        if !self.is_resuming() || !self.exists() {
+            return Ok(HashSet::new());
+        }
+
+        Ok(self.read()?.0)
+    }
+
+    /// Returns how far the previous run got, regardless of whether it is resumed.
+    pub fn progress(&self) -> Result<JournalProgress, String> {
+        let (completed, planned) = self.read()?;
+        Ok(JournalProgress { completed: completed.len(), planned })
+    }
+
+    /// Returns the recorded output paths along with the number of files planned by the last run.
+    fn read(&self) -> Result<(HashSet<PathBuf>, Option<usize>), String> {
+        let content = read_to_string(&self.path)
+            .map_err(|e| format!("Unable to read '{}': {}", self.path.to_string_lossy(), e))?;
+
+        let mut completed = HashSet::new();
+        let mut planned = None;
+        for line in content.lines() {
+            match line.strip_prefix(PLANNED_PREFIX) {
+                Some(count) => planned = count.parse().ok(),
+                None => {
+                    completed.insert(PathBuf::from(line));
+                },
+            }
+        }
+
+        Ok((completed, planned))
+    }
+
+    /// Starts a new journal unless resuming, in which case further entries are appended. The
+    /// number of files planned by this run is recorded to report the progress if it is
+    /// interrupted.
+    pub fn start(&self, planned: usize) -> Result<(), String> {
+        if !self.is_resuming() && self.exists() {
+            self.remove()?;
+        }
+        self.append(&format!("{}{}", PLANNED_PREFIX, planned))
+    }
+
+    pub fn record(&self, output_path: &Path) -> Result<(), String> {
+        self.append(&output_path.to_string_lossy())
+    }
+
+    fn append(&self, line: &str) -> Result<(), String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Unable to open '{}': {}", self.path.to_string_lossy(), e))?;
+
+        writeln!(file, "{}", line)
+            .and_then(|_| file.sync_data())
+            .map_err(|e| format!("Unable to write '{}': {}", self.path.to_string_lossy(), e))
+    }
+
+    /// Returns whether the last export into the output directory has been completed with the same
+    /// fingerprint as this one, i.e. whether this export would not copy anything new.
+    pub fn is_up_to_date(&self) -> bool {
+        let Some(fingerprint) = &self.fingerprint else {
+            return false;
+        };
+        if self.exists() {
+            return false;
+        }
+
+        read_to_string(self.fingerprint_path())
+            .ok()
+            .and_then(|content| serde_json::from_str::<ExportFingerprint>(&content).ok())
+            .is_some_and(|recorded| recorded == *fingerprint)
+    }
+
+    /// Marks the export as completed by removing the journal and recording its fingerprint, if
+    /// any.
+    pub fn complete(&self) -> Result<(), String> {
+        let fingerprint_path = self.fingerprint_path();
+        match &self.fingerprint {
+            Some(fingerprint) => {
+                let file = File::create(&fingerprint_path)
+                    .map_err(|e| format!("Unable to create '{}': {}", fingerprint_path.to_string_lossy(), e))?;
+                serde_json::to_writer_pretty(file, fingerprint)
+                    .map_err(|e| format!("Unable to write '{}': {}", fingerprint_path.to_string_lossy(), e))?;
+            },
+            // A fingerprint of an earlier export is outdated by this one
+            None if fingerprint_path.exists() => {
+                remove_file(&fingerprint_path)
+                    .map_err(|e| format!("Unable to remove '{}': {}", fingerprint_path.to_string_lossy(), e))?;
+            },
+            None => {},
+        }
+
+        if self.exists() {
+            self.remove()?;
+        }
+        Ok(())
+    }
+
+    fn fingerprint_path(&self) -> PathBuf {
+        self.path.with_file_name(FINGERPRINT_FILENAME)
+    }
+
+    pub fn remove(&self) -> Result<(), String> {
+        remove_file(&self.path)
+            .map_err(|e| format!("Unable to remove '{}': {}", self.path.to_string_lossy(), e))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::library::{SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn resumes_recorded_paths() {
+        let output = tempfile::tempdir().unwrap();
+
+        let journal = ExportJournal::new(output.path(), false);
+        journal.start(3).unwrap();
+        journal.record(Path::new("/out/a.jpeg")).unwrap();
+        journal.record(Path::new("/out/b c.jpeg")).unwrap();
+        assert!(journal.completed().unwrap().is_empty());
+
+        let resumed = ExportJournal::new(output.path(), true);
+        resumed.start(3).unwrap();
+        assert_eq!(
+            resumed.completed().unwrap(),
+            HashSet::from([PathBuf::from("/out/a.jpeg"), PathBuf::from("/out/b c.jpeg")])
+        );
+
+        let restarted = ExportJournal::new(output.path(), false);
+        restarted.start(3).unwrap();
+        assert_eq!(restarted.progress(), Ok(JournalProgress { completed: 0, planned: Some(3) }));
+    }
+
+    #[test]
+    fn reports_progress_of_interrupted_runs() {
+        let output = tempfile::tempdir().unwrap();
+
+        let journal = ExportJournal::new(output.path(), false);
+        journal.start(3).unwrap();
+        journal.record(Path::new("/out/a.jpeg")).unwrap();
+        journal.record(Path::new("/out/b.jpeg")).unwrap();
+        assert_eq!(journal.progress(), Ok(JournalProgress { completed: 2, planned: Some(3) }));
+
+        let stale = ExportJournal::new(output.path(), false);
+        assert!(!stale.is_resuming());
+        stale.resume();
+        stale.start(4).unwrap();
+        stale.record(Path::new("/out/a.jpeg")).unwrap();
+        stale.record(Path::new("/out/c.jpeg")).unwrap();
+        assert_eq!(stale.progress(), Ok(JournalProgress { completed: 3, planned: Some(4) }));
+        assert_eq!(stale.completed().unwrap().len(), 3);
+
+        std::fs::write(output.path().join(JOURNAL_FILENAME), "/out/a.jpeg\n").unwrap();
+        assert_eq!(journal.progress(), Ok(JournalProgress { completed: 1, planned: None }));
+    }
+
+    #[test]
+    fn detects_completed_exports_with_the_same_fingerprint() {
+        let library = SyntheticLibrary::new();
+        let db_paths = [library.db_path()];
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let output = tempfile::tempdir().unwrap();
+        let journal = |args: &[String]| {
+            ExportJournal::new(output.path(), false).with_fingerprint(ExportFingerprint::new(&db_paths, args).unwrap())
+        };
+
+        let first = journal(&args(&["export", "Library", "out", "--by-year"]));
+        assert!(!first.is_up_to_date());
+        first.start(1).unwrap();
+        assert!(!first.is_up_to_date());
+        first.complete().unwrap();
+        assert!(!first.exists());
+
+        assert!(journal(&args(&["export", "Library", "out", "--by-year", "--force", "--non-interactive"])).is_up_to_date());
+        assert!(!journal(&args(&["export", "Library", "out", "--by-month"])).is_up_to_date());
+        assert!(!ExportJournal::new(output.path(), false).is_up_to_date());
+
+        library.add_asset(&TestAsset::jpeg(1));
+        assert!(!journal(&args(&["export", "Library", "out", "--by-year"])).is_up_to_date());
+    }
+}
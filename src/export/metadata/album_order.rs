@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, write};
+use std::path::PathBuf;
+
+use derive_new::new;
+
+use crate::export::copying::CopyOperation;
+use crate::export::metadata::MetadataWriter;
+use crate::model::asset::ExportAsset;
+
+pub const ALBUM_ORDER_FILENAME: &str = "order.txt";
+
+/// Writes an index file to each output folder containing album assets, listing the filenames of
+/// the exported assets in the manual sort order of their album, one per line.
+#[derive(new)]
+pub struct AlbumOrderIndexWriter;
+
+impl MetadataWriter for AlbumOrderIndexWriter {
+
+    fn write(&self, exported: &[(&ExportAsset, &CopyOperation)]) -> Result<(), String> {
+        let mut entries_by_folder: BTreeMap<PathBuf, Vec<(i32, usize, String)>> = BTreeMap::new();
+
+        for (asset, op) in exported {
+            let (Some(album), Some(position)) = (&asset.album, asset.album_position) else {
+                continue;
+            };
+
+            let output_path = op.get_output_path();
+            let folder = output_path.parent().map(PathBuf::from).unwrap_or_default();
+            let filename = output_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            entries_by_folder
+                .entry(folder)
+                .or_default()
+                .push((album.id, position, filename));
+        }
+
+        for (folder, mut entries) in entries_by_folder {
+            // Assets of the same position, e.g. edited versions, keep the order of their export
+            entries.sort_by_key(|(album_id, position, _)| (*album_id, *position));
+
+            let content: String = entries
+                .into_iter()
+                .map(|(_, _, filename)| format!("{}\n", filename))
+                .collect();
+
+            create_dir_all(&folder)
+                .map_err(|e| format!("Unable to create '{}': {}", folder.to_string_lossy(), e))?;
+
+            let path = folder.join(ALBUM_ORDER_FILENAME);
+            write(&path, content)
+                .map_err(|e| format!("Unable to write '{}': {}", path.to_string_lossy(), e))?;
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::db::repo::album::AlbumRepository;
+    use crate::export::copying::{OriginalsCopyOperationFactory, OutputStructureCopyOperationFactoryDecorator};
+    use crate::export::structure::AlbumOutputStrategy;
+    use crate::test_util::library::{load_operations, SyntheticLibrary, TestAlbum, TestAsset};
+
+    #[test]
+    fn writes_manual_album_order() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Slideshow"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2]).sorted_at(3072));
+        library.add_asset(&TestAsset::jpeg(2).in_albums(&[2]).sorted_at(1024));
+        library.add_asset(&TestAsset::jpeg(3).in_albums(&[2]).sorted_at(2048));
+        library.add_asset(&TestAsset::jpeg(4));
+        let output = tempfile::tempdir().unwrap();
+
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        let planned = load_operations(
+            &library,
+            output.path(),
+            Box::new(
+                OutputStructureCopyOperationFactoryDecorator::new(
+                    Box::new(OriginalsCopyOperationFactory::new()),
+                    Box::new(AlbumOutputStrategy::new(false, albums))
+                )
+            )
+        );
+
+        AlbumOrderIndexWriter::new().write(&planned.get_all()).unwrap();
+
+        assert_eq!(
+            read_to_string(output.path().join("Slideshow").join(ALBUM_ORDER_FILENAME)).unwrap(),
+            "ASSET-0002.jpeg\nASSET-0003.jpeg\nASSET-0001.jpeg\n"
+        );
+        assert!(!output.path().join(ALBUM_ORDER_FILENAME).exists());
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+use std::fs::{create_dir_all, write};
+use std::path::PathBuf;
+
+use derive_new::new;
+
+use crate::export::copying::CopyOperation;
+use crate::export::metadata::MetadataWriter;
+use crate::model::asset::ExportAsset;
+
+const COLUMNS: [&str; 9] = [
+    "SourceFile",
+    "DateTimeOriginal",
+    "GPSLatitude",
+    "GPSLatitudeRef",
+    "GPSLongitude",
+    "GPSLongitudeRef",
+    "Keywords",
+    "Title",
+    "Description",
+];
+
+/// Separator of list items in the `Keywords` column. It has to be passed to exiftool using
+/// `-sep` for the keywords to be written as separate list items.
+const KEYWORD_SEPARATOR: &str = ", ";
+
+/// Writes a CSV file in the format consumed by `exiftool -csv=`, containing the metadata stored in
+/// the database for each exported file.
+///
+/// Empty cells are ignored by exiftool, so tags without value in the database are left untouched.
+#[derive(new)]
+pub struct ExifToolCsvMetadataWriter {
+    csv_path: PathBuf,
+}
+
+impl ExifToolCsvMetadataWriter {
+
+    fn build_row(&self, asset: &ExportAsset, copy_operation: &CopyOperation) -> Vec<String> {
+        let (latitude, latitude_ref, longitude, longitude_ref) = match asset.location {
+            Some(location) => (
+                location.latitude.abs().to_string(),
+                if location.latitude < 0.0 { "S" } else { "N" }.to_string(),
+                location.longitude.abs().to_string(),
+                if location.longitude < 0.0 { "W" } else { "E" }.to_string(),
+            ),
+            None => Default::default(),
+        };
+
+        vec![
+            copy_operation.get_output_path().to_string_lossy().to_string(),
+            asset.datetime.format("%Y:%m:%d %H:%M:%S").to_string(),
+            latitude,
+            latitude_ref,
+            longitude,
+            longitude_ref,
+            asset.keywords.join(KEYWORD_SEPARATOR),
+            asset.title.clone().unwrap_or_default(),
+            asset.description.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl MetadataWriter for ExifToolCsvMetadataWriter {
+
+    fn write(&self, exported: &[(&ExportAsset, &CopyOperation)]) -> Result<(), String> {
+        let mut content = to_csv_line(COLUMNS.iter().map(|c| c.to_string()).collect());
+        let mut written_paths = HashSet::new();
+        for (asset, op) in exported {
+            // Assets of multiple albums are exported to the same file unless grouped by album
+            if !written_paths.insert(op.get_output_path()) {
+                continue;
+            }
+            content.push_str(&to_csv_line(self.build_row(asset, op)));
+        }
+
+        if let Some(parent) = self.csv_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            create_dir_all(parent)
+                .map_err(|e| format!("Unable to create '{}': {}", parent.to_string_lossy(), e))?;
+        }
+
+        write(&self.csv_path, content)
+            .map_err(|e| format!("Unable to write '{}': {}", self.csv_path.to_string_lossy(), e))
+    }
+}
+
+fn to_csv_line(fields: Vec<String>) -> String {
+    let escaped: Vec<String> = fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect();
+    format!("{}\n", escaped.join(","))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::export::copying::OriginalsCopyOperationFactory;
+    use crate::test_util::library::{load_operations, SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn writes_database_metadata_of_exported_files() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(
+            &TestAsset::jpeg(1)
+                .at(-33.86, 151.21)
+                .titled("Harbour, at night")
+                .described("Taken from the \"ferry\"")
+                .tagged(&["travel", "australia"])
+        );
+        library.add_asset(&TestAsset::jpeg(2));
+        let output = tempfile::tempdir().unwrap();
+
+        let planned = load_operations(&library, output.path(), Box::new(OriginalsCopyOperationFactory::new()));
+        let assets = &planned.assets;
+        let exported = planned.get_all();
+
+        let csv_path = output.path().join("metadata").join("tags.csv");
+        ExifToolCsvMetadataWriter::new(csv_path.clone())
+            .write(&exported)
+            .unwrap();
+
+        let written = read_to_string(csv_path).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        let datetime = assets[0].datetime.format("%Y:%m:%d %H:%M:%S");
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},{},33.86,S,151.21,E,\"australia, travel\",\"Harbour, at night\",\
+                 \"Taken from the \"\"ferry\"\"\"",
+                output.path().join("ASSET-0001.jpeg").to_string_lossy(),
+                datetime
+            )
+        );
+        assert!(lines[2].ends_with(",,,,,,,"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,105 @@
+use std::fs::{create_dir_all, File};
+use std::path::PathBuf;
+
+use derive_new::new;
+use serde_json::{json, Value};
+
+use crate::export::copying::CopyOperation;
+use crate::export::metadata::MetadataWriter;
+use crate::model::asset::ExportAsset;
+
+pub const LOCATIONS_FILENAME: &str = "locations.geojson";
+
+/// Writes a GeoJSON `FeatureCollection` to the export root containing a point feature for each
+/// exported asset with location information.
+#[derive(new)]
+pub struct GeoJsonMetadataWriter {
+    output_dir: PathBuf,
+}
+
+impl GeoJsonMetadataWriter {
+
+    fn build_feature(&self, asset: &ExportAsset, copy_operation: &CopyOperation) -> Option<Value> {
+        let location = asset.location?;
+
+        let output_path = copy_operation.get_output_path();
+        let relative_path = output_path
+            .strip_prefix(&self.output_dir)
+            .unwrap_or(&output_path);
+
+        Some(
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    // GeoJSON positions are ordered longitude first
+                    "coordinates": [location.longitude, location.latitude]
+                },
+                "properties": {
+                    "uuid": asset.uuid,
+                    "path": relative_path.to_string_lossy(),
+                    "datetime": asset.datetime.format("%Y-%m-%dT%H:%M:%S").to_string()
+                }
+            })
+        )
+    }
+}
+
+impl MetadataWriter for GeoJsonMetadataWriter {
+
+    fn write(&self, exported: &[(&ExportAsset, &CopyOperation)]) -> Result<(), String> {
+        let features: Vec<Value> = exported
+            .iter()
+            .filter_map(|(asset, op)| self.build_feature(asset, op))
+            .collect();
+
+        let collection = json!({
+            "type": "FeatureCollection",
+            "features": features
+        });
+
+        create_dir_all(&self.output_dir)
+            .map_err(|e| format!("Unable to create output directory: {}", e))?;
+
+        let path = self.output_dir.join(LOCATIONS_FILENAME);
+        let file = File::create(&path)
+            .map_err(|e| format!("Unable to create '{}': {}", path.to_string_lossy(), e))?;
+
+        serde_json::to_writer_pretty(file, &collection)
+            .map_err(|e| format!("Unable to write '{}': {}", path.to_string_lossy(), e))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::export::copying::OriginalsCopyOperationFactory;
+    use crate::test_util::library::{load_operations, SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn writes_features_of_located_assets() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).at(53.55, 9.99));
+        library.add_asset(&TestAsset::jpeg(2));
+        let output = tempfile::tempdir().unwrap();
+
+        let planned = load_operations(&library, output.path(), Box::new(OriginalsCopyOperationFactory::new()));
+
+        GeoJsonMetadataWriter::new(output.path().to_path_buf())
+            .write(&planned.get_all())
+            .unwrap();
+
+        let written: Value = serde_json::from_str(
+            &read_to_string(output.path().join(LOCATIONS_FILENAME)).unwrap()
+        ).unwrap();
+        let features = written["features"].as_array().unwrap();
+
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0]["properties"]["uuid"], "ASSET-0001");
+        assert_eq!(features[0]["properties"]["path"], "ASSET-0001.jpeg");
+        assert_eq!(features[0]["geometry"]["coordinates"], json!([9.99, 53.55]));
+    }
+}
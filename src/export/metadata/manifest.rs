@@ -0,0 +1,316 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs::{create_dir_all, metadata, read_to_string, File};
+use std::path::{Path, PathBuf};
+
+use derive_new::new;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::export::copying::CopyOperation;
+use crate::export::metadata::MetadataWriter;
+use crate::export::verification::{collect_files, is_export_metadata, relative_to};
+use crate::model::asset::ExportAsset;
+use crate::util::checksum::sha256_file;
+
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// Writes a JSON manifest to the export root listing each exported file along with the asset it
+/// has been exported from.
+///
+/// Each entry contains the size and SHA-256 checksum of the exported file along with the pixel
+/// dimensions of the asset's original and the duration of videos as recorded by Photos, so that
+/// tools do not need to open each file to learn them. The dimensions are those of the original
+/// even for edited versions, which may have been cropped.
+///
+/// All paths are relative to the export root, so that the export can be moved and still be
+/// verified against the manifest using [verify_manifest]. Files skipped because their content had already been
+/// exported for another asset are listed separately along with the file they duplicate.
+#[derive(new)]
+pub struct ManifestMetadataWriter {
+    output_dir: PathBuf,
+}
+
+impl ManifestMetadataWriter {
+
+    fn build_entry(&self, asset: &ExportAsset, copy_operation: &CopyOperation) -> Result<Value, String> {
+        let output_path = copy_operation.get_output_path();
+        let relative_path = relative_to(&output_path, &self.output_dir);
+
+        let size = metadata(&output_path)
+            .map_err(|e| format!("Unable to read '{}': {}", output_path.to_string_lossy(), e))?
+            .len();
+        let checksum = sha256_file(&output_path)
+            .map_err(|e| format!("Unable to compute checksum of '{}': {}", output_path.to_string_lossy(), e))?;
+
+        Ok(json!({
+            "path": relative_path.to_string_lossy(),
+            "size": size,
+            "sha256": checksum,
+            "uuid": asset.uuid,
+            "original_filename": asset.original_filename,
+            "datetime": asset.datetime.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "album": asset.album.as_ref().and_then(|album| album.name.clone()),
+            "contributor": asset.contributor,
+            "original_width": asset.dimensions.map(|d| d.width),
+            "original_height": asset.dimensions.map(|d| d.height),
+            "duration": asset.duration
+        }))
+    }
+}
+
+impl MetadataWriter for ManifestMetadataWriter {
+
+    fn write(&self, exported: &[(&ExportAsset, &CopyOperation)]) -> Result<(), String> {
+        self.write_with_duplicates(exported, &[])
+    }
+
+    fn write_with_duplicates(
+        &self,
+        exported: &[(&ExportAsset, &CopyOperation)],
+        duplicates: &[(PathBuf, PathBuf)]
+    ) -> Result<(), String> {
+        let mut written_paths = HashSet::new();
+        let files: Vec<Value> = exported
+            .iter()
+            // Assets of multiple albums are exported to the same file unless grouped by album
+            .filter(|(_, op)| written_paths.insert(op.get_output_path()))
+            .map(|(asset, op)| self.build_entry(asset, op))
+            .collect::<Result<_, _>>()?;
+        let duplicates: Vec<Value> = duplicates
+            .iter()
+            .map(|(skipped, original)| {
+                json!({
+                    "path": relative_to(skipped, &self.output_dir).to_string_lossy(),
+                    "duplicate_of": relative_to(original, &self.output_dir).to_string_lossy()
+                })
+            })
+            .collect();
+
+        create_dir_all(&self.output_dir)
+            .map_err(|e| format!("Unable to create output directory: {}", e))?;
+
+        let path = self.output_dir.join(MANIFEST_FILENAME);
+        let file = File::create(&path)
+            .map_err(|e| format!("Unable to create '{}': {}", path.to_string_lossy(), e))?;
+
+        serde_json::to_writer_pretty(file, &json!({ "files": files, "duplicates": duplicates }))
+            .map_err(|e| format!("Unable to write '{}': {}", path.to_string_lossy(), e))
+    }
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    files: Vec<ManifestEntry>,
+}
+
+#[derive(Deserialize)]
+struct ManifestEntry {
+    path: PathBuf,
+    size: Option<u64>,
+    sha256: Option<String>,
+}
+
+/// Result of verifying an export against its manifest. All paths are relative to the export root.
+#[derive(Debug, Default, PartialEq)]
+pub struct ManifestReport {
+    pub verified: usize,
+    /// Files listed in the manifest that do not exist
+    pub missing: Vec<PathBuf>,
+    /// Files whose size or checksum differs from the manifest, along with the difference
+    pub mismatched: Vec<(PathBuf, String)>,
+    /// Files in the export that are not listed in the manifest
+    pub unlisted: Vec<PathBuf>,
+}
+
+impl ManifestReport {
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty() && self.unlisted.is_empty()
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<Manifest, String> {
+    let content = read_to_string(path)
+        .map_err(|e| format!("Unable to read '{}': {}", path.to_string_lossy(), e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Unable to parse '{}': {}", path.to_string_lossy(), e))
+}
+
+/// Returns the paths of the files listed in the manifest in the root of the given export, relative
+/// to it, or no paths if the export has no manifest yet.
+pub fn read_manifest_paths(export_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let path = export_dir.join(MANIFEST_FILENAME);
+    if !path.is_file() {
+        return Ok(vec![]);
+    }
+
+    Ok(read_manifest(&path)?.files.into_iter().map(|entry| entry.path).collect())
+}
+
+/// Verifies the files of an export against the manifest in its root, comparing their sizes and
+/// checksums. Only the export itself is needed, so it may have been moved since.
+pub fn verify_manifest(export_dir: &Path) -> Result<ManifestReport, String> {
+    let path = export_dir.join(MANIFEST_FILENAME);
+    let manifest = read_manifest(&path)?;
+
+    let mut report = ManifestReport::default();
+    let mut listed = HashSet::new();
+
+    for entry in manifest.files {
+        let (Some(size), Some(checksum)) = (entry.size, &entry.sha256) else {
+            return Err(
+                format!(
+                    "'{}' has been written by an older version without checksums. Export again with --write-manifest.",
+                    path.to_string_lossy()
+                )
+            );
+        };
+
+        let file = export_dir.join(&entry.path);
+        listed.insert(entry.path.clone());
+
+        let Ok(actual_size) = metadata(&file).map(|m| m.len()) else {
+            report.missing.push(entry.path);
+            continue;
+        };
+        if actual_size != size {
+            report.mismatched.push((entry.path, format!("size {} instead of {}", actual_size, size)));
+            continue;
+        }
+
+        let actual_checksum = sha256_file(&file)
+            .map_err(|e| format!("Unable to compute checksum of '{}': {}", file.to_string_lossy(), e))?;
+        if &actual_checksum != checksum {
+            report.mismatched.push((entry.path, "checksum differs".to_string()));
+            continue;
+        }
+
+        report.verified += 1;
+    }
+
+    let mut files = BTreeSet::new();
+    collect_files(export_dir, &mut files)?;
+    report.unlisted = files
+        .iter()
+        .filter(|file| !is_export_metadata(file))
+        .map(|file| relative_to(file, export_dir))
+        .filter(|file| !listed.contains(file))
+        .collect();
+
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::export::copying::{AssetCopyStrategy, DefaultAssetCopyStrategy, OriginalsCopyOperationFactory};
+    use crate::test_util::library::{load_operations, SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn lists_exported_files_with_contributors_and_dimensions() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        library.add_asset(&TestAsset::mov(2).shared_by("Jane Appleseed"));
+        library.add_asset(&TestAsset::jpeg(3).measuring(None));
+        let output = tempfile::tempdir().unwrap();
+
+        let planned = load_operations(&library, output.path(), Box::new(OriginalsCopyOperationFactory::new()));
+        let exported = planned.get_all();
+        for (_, op) in &exported {
+            DefaultAssetCopyStrategy::new().copy_asset(op).unwrap();
+        }
+
+        ManifestMetadataWriter::new(output.path().to_path_buf())
+            .write(&exported)
+            .unwrap();
+
+        let written: Value = serde_json::from_str(
+            &read_to_string(output.path().join(MANIFEST_FILENAME)).unwrap()
+        ).unwrap();
+        let files = written["files"].as_array().unwrap();
+
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0]["path"], "ASSET-0001.jpeg");
+        assert_eq!(files[0]["size"], "original of ASSET-0001".len());
+        assert_eq!(files[0]["sha256"], sha256_file(&output.path().join("ASSET-0001.jpeg")).unwrap());
+        assert_eq!(files[0]["original_filename"], "IMG_0001.JPG");
+        assert_eq!(files[0]["contributor"], Value::Null);
+        assert_eq!(files[1]["contributor"], "Jane Appleseed");
+        assert_eq!(
+            (&files[0]["original_width"], &files[0]["original_height"], &files[0]["duration"]),
+            (&json!(4032), &json!(3024), &Value::Null)
+        );
+        assert_eq!(
+            (&files[1]["original_width"], &files[1]["original_height"], &files[1]["duration"]),
+            (&json!(1920), &json!(1080), &json!(12.5))
+        );
+        assert_eq!((&files[2]["original_width"], &files[2]["original_height"]), (&Value::Null, &Value::Null));
+    }
+
+    #[test]
+    fn lists_skipped_duplicates_along_with_the_file_they_duplicate() {
+        let output = tempfile::tempdir().unwrap();
+
+        ManifestMetadataWriter::new(output.path().to_path_buf())
+            .write_with_duplicates(
+                &[],
+                &[(output.path().join("Trips/IMG_0002.jpeg"), output.path().join("IMG_0001.jpeg"))]
+            )
+            .unwrap();
+
+        let written: Value = serde_json::from_str(
+            &read_to_string(output.path().join(MANIFEST_FILENAME)).unwrap()
+        ).unwrap();
+
+        assert_eq!(
+            written["duplicates"],
+            json!([{ "path": "Trips/IMG_0002.jpeg", "duplicate_of": "IMG_0001.jpeg" }])
+        );
+        assert!(verify_manifest(output.path()).unwrap().is_consistent());
+    }
+
+    #[test]
+    fn verifies_moved_exports_against_the_manifest() {
+        let export = tempfile::tempdir().unwrap();
+        let moved = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(export.path().join("Trips")).unwrap();
+        let names = ["IMG_0001.jpeg", "IMG_0002.jpeg", "Trips/IMG_0003.jpeg", "Trips/IMG_0004.jpeg"];
+        let entries: Vec<Value> = names
+            .iter()
+            .map(|name| {
+                std::fs::write(export.path().join(name), name).unwrap();
+                json!({
+                    "path": name,
+                    "size": name.len(),
+                    "sha256": sha256_file(&export.path().join(name)).unwrap()
+                })
+            })
+            .collect();
+        let manifest = json!({ "files": entries });
+        std::fs::write(export.path().join(MANIFEST_FILENAME), manifest.to_string()).unwrap();
+
+        let target = moved.path().join("export");
+        std::fs::rename(export.path(), &target).unwrap();
+        assert!(verify_manifest(&target).unwrap().is_consistent());
+
+        std::fs::remove_file(target.join("IMG_0002.jpeg")).unwrap();
+        std::fs::write(target.join("Trips/IMG_0003.jpeg"), "Trips/IMG_0003.jpeX").unwrap();
+        std::fs::write(target.join("Trips/IMG_0004.jpeg"), "truncated").unwrap();
+        std::fs::write(target.join("IMG_0005.jpeg"), "new").unwrap();
+
+        assert_eq!(
+            verify_manifest(&target).unwrap(),
+            ManifestReport {
+                verified: 1,
+                missing: vec![PathBuf::from("IMG_0002.jpeg")],
+                mismatched: vec![
+                    (PathBuf::from("Trips/IMG_0003.jpeg"), "checksum differs".to_string()),
+                    (PathBuf::from("Trips/IMG_0004.jpeg"), "size 9 instead of 19".to_string()),
+                ],
+                unlisted: vec![PathBuf::from("IMG_0005.jpeg")],
+            }
+        );
+    }
+}
\ No newline at end of file
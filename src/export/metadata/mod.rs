@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use crate::export::copying::CopyOperation;
+use crate::model::asset::ExportAsset;
+
+pub mod album_order;
+pub mod exiftool;
+pub mod geojson;
+pub mod manifest;
+pub mod provenance;
+
+/// Writes additional files describing the exported assets after all copy operations have been
+/// performed.
+pub trait MetadataWriter {
+
+    /// Writes the metadata for the given successfully exported assets and their copy operations.
+    fn write(&self, exported: &[(&ExportAsset, &CopyOperation)]) -> Result<(), String>;
+
+    /// Writes the metadata like [MetadataWriter::write], additionally given the output paths of
+    /// the files skipped because their content had already been exported, each along with the path
+    /// it had been exported to. Writers that do not report duplicates ignore them.
+    fn write_with_duplicates(
+        &self,
+        exported: &[(&ExportAsset, &CopyOperation)],
+        _duplicates: &[(PathBuf, PathBuf)]
+    ) -> Result<(), String> {
+        self.write(exported)
+    }
+}
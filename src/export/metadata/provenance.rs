@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use derive_new::new;
+
+use crate::export::copying::CopyOperation;
+use crate::export::metadata::MetadataWriter;
+use crate::model::asset::ExportAsset;
+use crate::util::xattr::set_xattr;
+
+pub const UUID_XATTR: &str = "user.apple-photos-export.uuid";
+pub const EXPORTED_AT_XATTR: &str = "user.apple-photos-export.exported-at";
+
+/// Records the provenance of each exported file in its extended attributes, i.e. the UUID of the
+/// asset it has been exported from and when it has been exported, so that files can be traced
+/// back to their assets even after being renamed or moved by other programs.
+#[derive(new)]
+pub struct ProvenanceXattrWriter {
+    exported_at: DateTime<Utc>,
+}
+
+impl MetadataWriter for ProvenanceXattrWriter {
+
+    fn write(&self, exported: &[(&ExportAsset, &CopyOperation)]) -> Result<(), String> {
+        let exported_at = self.exported_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let mut written_paths = HashSet::new();
+
+        exported
+            .iter()
+            // Assets of multiple albums are exported to the same file unless grouped by album
+            .filter(|(_, op)| written_paths.insert(op.get_output_path()))
+            .try_for_each(|(asset, op)| {
+                let path = op.get_output_path();
+                set_xattr(&path, UUID_XATTR, asset.uuid.as_bytes())
+                    .and_then(|_| set_xattr(&path, EXPORTED_AT_XATTR, exported_at.as_bytes()))
+                    .map_err(|e| format!("Unable to set extended attributes of '{}': {}", path.to_string_lossy(), e))
+            })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::copying::OriginalsCopyOperationFactory;
+    use crate::test_util::library::{load_operations, SyntheticLibrary, TestAsset};
+    use crate::util::xattr::get_xattr;
+
+    #[test]
+    fn records_asset_uuids_in_extended_attributes() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let output = tempfile::tempdir().unwrap();
+
+        let planned = load_operations(&library, output.path(), Box::new(OriginalsCopyOperationFactory::new()));
+        let exported = planned.get_all();
+        let op = exported[0].1;
+        std::fs::copy(&op.source_path, op.get_output_path()).unwrap();
+
+        let exported_at = DateTime::parse_from_rfc3339("2024-03-01T12:00:00+00:00").unwrap().to_utc();
+        ProvenanceXattrWriter::new(exported_at)
+            .write(&exported)
+            .unwrap();
+
+        let path = op.get_output_path();
+        assert_eq!(get_xattr(&path, UUID_XATTR).unwrap(), Some(b"ASSET-0001".to_vec()));
+        assert_eq!(get_xattr(&path, EXPORTED_AT_XATTR).unwrap(), Some(b"2024-03-01T12:00:00Z".to_vec()));
+        assert_eq!(get_xattr(&path, "user.other").unwrap(), None);
+    }
+}
\ No newline at end of file
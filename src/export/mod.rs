@@ -1,35 +1,96 @@
 use std::fs::File;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 use colored::Colorize;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 
-use crate::db::repo::asset::AssetRepository;
-use crate::export::copying::{AssetCopyStrategy, CopyOperationFactory};
-use crate::export::exporter::Exporter;
+use crate::export::copying::{AssetCopyStrategy, DryRunAssetCopyStrategy};
+use crate::export::exporter::{ExportOptions, ExportSource, Exporter};
+use crate::export::journal::ExportJournal;
+use crate::export::metadata::manifest::verify_manifest;
+use crate::export::metadata::MetadataWriter;
+use crate::export::run_log::RunLog;
 use crate::result::{PhotosExportError, PhotosExportResult};
+use crate::util::interaction::UserInteraction;
+use crate::util::paths::create_dirs;
 
 pub mod structure;
 pub mod exporter;
+pub mod journal;
+pub mod pipeline;
+pub mod placeholders;
+pub mod plan;
+pub mod run_log;
 pub mod copying;
+pub mod benchmark;
+pub mod diff;
+pub mod duplicates;
+pub mod metadata;
+pub mod statistics;
+pub mod verification;
 
+#[allow(clippy::too_many_arguments)]
 pub fn export_assets(
-    asset_repo: AssetRepository,
-    copy_operation_factory: Box<dyn CopyOperationFactory>,
+    sources: Vec<ExportSource>,
     copy_strategy: Box<dyn AssetCopyStrategy>,
+    metadata_writers: Vec<Box<dyn MetadataWriter>>,
+    options: ExportOptions,
+    output_dir: PathBuf,
+    journal: Option<ExportJournal>,
+    run_log: Option<RunLog>,
+    interaction: Rc<dyn UserInteraction>,
 ) -> PhotosExportResult<()> {
+    let metadata_only = options.is_metadata_only();
 
-    let exporter = Exporter::new(
-        asset_repo,
-        copy_operation_factory,
+    let mut exporter = Exporter::new(
+        sources,
         copy_strategy,
-    );
+        metadata_writers,
+        options,
+        output_dir,
+        journal,
+    ).with_interaction(interaction.clone());
+    if let Some(run_log) = run_log {
+        exporter = exporter.with_run_log(run_log);
+    }
 
     exporter.export()
-        .map(|count| {
-            println!("{}", format!("\nAll {} assets have successfully been exported.", count).green());
-            ()
+        .map(|summary| {
+            if !summary.interrupted {
+                let message = if metadata_only {
+                    format!("\nThe metadata of all {} assets has successfully been written.", summary.exported)
+                } else {
+                    format!("\nAll {} assets have successfully been exported.", summary.exported)
+                };
+                interaction.info(&message.green().to_string());
+                if summary.original_fallbacks > 0 {
+                    interaction.info(&format!(
+                        "{} of them have been exported as originals as their edited versions are missing.",
+                        summary.original_fallbacks
+                    ));
+                }
+                if summary.unknown_album_fallbacks > 0 {
+                    interaction.info(&format!(
+                        "{} of them have been exported as if they were in no album as their album no longer exists.",
+                        summary.unknown_album_fallbacks
+                    ));
+                }
+                if summary.placeholders > 0 {
+                    interaction.info(&format!(
+                        "{} placeholders have been written for assets that are not locally available.",
+                        summary.placeholders
+                    ));
+                }
+                if summary.deleted > 0 {
+                    interaction.info(&format!(
+                        "{} empty directories have been removed from the output directory.",
+                        summary.deleted
+                    ));
+                }
+            }
         })
         .map_err(|export| {
             eprintln!(
@@ -42,8 +103,206 @@ pub fn export_assets(
             }
         })
 }
+/// Verifies an existing export against the libraries, printing all files that are missing, not
+/// traceable to an asset or differ from their source.
+pub fn verify_existing_export(
+    sources: Vec<ExportSource>,
+    options: ExportOptions,
+    output_dir: PathBuf,
+    compare_checksums: bool
+) -> PhotosExportResult<()> {
+    let exporter = Exporter::new(
+        sources,
+        Box::new(DryRunAssetCopyStrategy::new()),
+        vec![],
+        options,
+        output_dir,
+        None,
+    );
+
+    let report = exporter.verify(compare_checksums)?;
+
+    print_paths("missing from the export", &report.missing);
+    print_paths("not traceable to the library", &report.extra);
+    if !report.mismatched.is_empty() {
+        println!("\n{} files differ from their source:", report.mismatched.len());
+        for (path, difference) in &report.mismatched {
+            println!("  - {} ({})", path.to_string_lossy().dimmed(), difference);
+        }
+    }
+
+    if report.is_consistent() {
+        println!("{}", "The export is consistent with the library.".green());
+        Ok(())
+    } else {
+        Err(PhotosExportError::from("The export differs from the library"))
+    }
+}
+
+/// Spot-checks a sample of the files of an existing export against the libraries, printing
+/// whether each of them has passed.
+pub fn audit_existing_export(
+    sources: Vec<ExportSource>,
+    options: ExportOptions,
+    output_dir: PathBuf
+) -> PhotosExportResult<()> {
+    let exporter = Exporter::new(
+        sources,
+        Box::new(DryRunAssetCopyStrategy::new()),
+        vec![],
+        options,
+        output_dir,
+        None,
+    );
+
+    let report = exporter.audit()?;
+
+    for path in &report.passed {
+        println!("  {} {}", "pass".green(), path.to_string_lossy().dimmed());
+    }
+    for (path, failure) in &report.failed {
+        println!("  {} {} ({})", "fail".red(), path.to_string_lossy(), failure);
+    }
+
+    let checked = report.passed.len() + report.failed.len();
+    if report.is_passed() {
+        println!("{}", format!("\nAll {} sampled files match their source.", checked).green());
+        Ok(())
+    } else {
+        Err(PhotosExportError::from(format!("{} of {} sampled files failed the audit", report.failed.len(), checked)))
+    }
+}
+
+/// Verifies the files of an export against its manifest, printing all files that are missing,
+/// not listed in the manifest or differ from it.
+pub fn verify_export_manifest(export_dir: &Path) -> PhotosExportResult<()> {
+    let report = verify_manifest(export_dir)?;
+
+    print_paths("missing from the export", &report.missing);
+    print_paths("not listed in the manifest", &report.unlisted);
+    if !report.mismatched.is_empty() {
+        println!("\n{} files differ from the manifest:", report.mismatched.len());
+        for (path, difference) in &report.mismatched {
+            println!("  - {} ({})", path.to_string_lossy().dimmed(), difference);
+        }
+    }
+
+    if report.is_consistent() {
+        println!("{}", format!("All {} files match the manifest.", report.verified).green());
+        Ok(())
+    } else {
+        Err(PhotosExportError::from("The export differs from its manifest"))
+    }
+}
+
+/// Prints how an export with the given options would differ from an existing export without
+/// copying anything.
+pub fn diff_existing_export(
+    sources: Vec<ExportSource>,
+    options: ExportOptions,
+    output_dir: PathBuf,
+    existing_dir: &Path
+) -> PhotosExportResult<()> {
+    let exporter = Exporter::new(
+        sources,
+        Box::new(DryRunAssetCopyStrategy::new()),
+        vec![],
+        options,
+        output_dir,
+        None,
+    );
+
+    let diff = exporter.diff(existing_dir)?;
+
+    print_paths("new", &diff.added);
+    if !diff.moved.is_empty() {
+        println!("\n{} files would move:", diff.moved.len());
+        for (previous, path) in &diff.moved {
+            println!("  - {} -> {}", previous.to_string_lossy().dimmed(), path.to_string_lossy());
+        }
+    }
+    print_paths("no longer exported", &diff.removed);
+
+    if diff.is_empty() {
+        println!("{}", format!("All {} files would be exported to the same paths.", diff.unchanged).green());
+    } else {
+        println!("\n{} files would be exported to the same paths.", diff.unchanged);
+    }
+
+    Ok(())
+}
+
+/// Creates the directories the export would copy files to without copying anything, e.g. to
+/// provision a network share with the given directory mode before the actual export.
+pub fn create_export_structure(
+    sources: Vec<ExportSource>,
+    options: ExportOptions,
+    output_dir: PathBuf,
+    dir_mode: Option<u32>
+) -> PhotosExportResult<()> {
+    let exporter = Exporter::new(
+        sources,
+        Box::new(DryRunAssetCopyStrategy::new()),
+        vec![],
+        options,
+        output_dir,
+        None,
+    );
+
+    let directories = exporter.plan_directories()?;
+    let mut created = 0;
+    for dir in &directories {
+        created += create_dirs(dir, dir_mode)?;
+    }
+
+    println!(
+        "{}",
+        format!("The directory structure of the export is in place, {} directories have been created.", created).green()
+    );
+
+    Ok(())
+}
+
+/// Plans the export without copying anything and writes the planned copy operations to the given
+/// file, so that they can be reviewed before being executed using [ExportOptions::with_plan].
+pub fn write_export_plan(
+    sources: Vec<ExportSource>,
+    options: ExportOptions,
+    output_dir: PathBuf,
+    plan_path: &Path
+) -> PhotosExportResult<()> {
+    let exporter = Exporter::new(
+        sources,
+        Box::new(DryRunAssetCopyStrategy::new()),
+        vec![],
+        options,
+        output_dir,
+        None,
+    );
+
+    let plan = exporter.plan()?;
+    plan.write(plan_path)?;
+
+    println!(
+        "{}",
+        format!("The plan of {} copy operations has been written to '{}'.", plan.len(), plan_path.to_string_lossy()).green()
+    );
+
+    Ok(())
+}
+
+fn print_paths(description: &str, paths: &[PathBuf]) {
+    if paths.is_empty() {
+        return;
+    }
+
+    println!("\n{} files are {}:", paths.len(), description);
+    for path in paths {
+        println!("  - {}", path.to_string_lossy().dimmed());
+    }
+}
 
-fn write_error_log(messages: &Vec<String>) -> Result<(), String> {
+fn write_error_log(messages: &[String]) -> Result<(), String> {
     let random_suffix: String = rand::thread_rng()
         .sample_iter(&Alphanumeric)
         .take(8)
@@ -0,0 +1,909 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use clap::ValueEnum;
+use colored::Colorize;
+use derive_new::new;
+
+use crate::export::benchmark::{PlanningBenchmark, TimingCopyOperationFactoryDecorator};
+use crate::export::copying::{AbsolutePathBuildingCopyOperationFactoryDecorator, AlbumOrderPrefixingCopyOperationFactoryDecorator, AssetPredicateFunc, CameraFilter, CombiningCopyOperationFactory, CompanionResourcesCopyOperationFactory, CopyOperation, CopyOperationFactory, DateTreeCopyOperationFactoryDecorator, DerivatesCopyOperationFactory, FavoritesFolderCopyOperationFactoryDecorator, FilenameRestoringCopyOperationFactoryDecorator, FilteringCopyOperationFactoryDecorator, FlatFrameCopyOperationFactoryDecorator, FlatOutputRegistry, KeywordGroupingCopyOperationFactoryDecorator, LibraryStructureCopyOperationFactoryDecorator, MissingDerivateRegistry, OriginalFallbackCopyOperationFactoryDecorator, OriginalResolvingCopyOperationFactoryDecorator, OriginalsCopyOperationFactory, OutputStructureCopyOperationFactoryDecorator, PathLengthLimitingCopyOperationFactoryDecorator, RawConvertingCopyOperationFactoryDecorator, RawSubfolderCopyOperationFactoryDecorator, RelocatedOriginalRegistry, StableNamingCopyOperationFactoryDecorator, SuffixSettingCopyOperationFactoryDecorator, TitleNamingCopyOperationFactoryDecorator, UtiFilter, UtiFilteringCopyOperationFactoryDecorator, VideoTranscodingCopyOperationFactoryDecorator};
+use crate::export::structure::{OutputStrategy, PlainOutputStrategy};
+use crate::model::asset::ExportAsset;
+use crate::model::uti::MediaType;
+use crate::util::date_range::DateRange;
+use crate::util::interaction::UserInteraction;
+
+/// Media types the edited-version flags apply to
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum EditedMediaTypes {
+    All,
+    Photos,
+    Videos,
+}
+
+impl EditedMediaTypes {
+    fn includes(&self, media_type: MediaType) -> bool {
+        match self {
+            EditedMediaTypes::All => true,
+            EditedMediaTypes::Photos => media_type == MediaType::Photo,
+            EditedMediaTypes::Videos => media_type == MediaType::Video,
+        }
+    }
+
+    /// Returns a predicate matching all edited assets of the selected media types.
+    pub fn edited_asset_predicate(self) -> AssetPredicateFunc {
+        Box::new(move |asset| asset.has_adjustments && self.includes(asset.derivate_uti.media_type))
+    }
+}
+
+/// Versions of slow-motion and time-lapse videos to export
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum RenderedVideoVersions {
+    /// The rendered version as played back by Photos (falls back to the original if not rendered)
+    Rendered,
+    /// The original recording
+    Original,
+    /// Both the original and the rendered version
+    Both,
+}
+
+/// Step of building the copy operations of an asset, each implemented by a copy operation factory
+/// decorating the factories of the previous steps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PipelineStage {
+    SourceFiles,
+    OriginalResolution,
+    CompanionResources,
+    ReferencedFileFilter,
+    EditedFilter,
+    DateRangeFilter,
+    FileTypeFilter,
+    CameraFilter,
+    RawConversion,
+    VideoTranscoding,
+    LibraryStructure,
+    OutputStructure,
+    DateTree,
+    KeywordGrouping,
+    FavoritesFolder,
+    RawSubfolder,
+    OriginalFilenames,
+    TitleNames,
+    StableNames,
+    AlbumOrderPrefixes,
+    FlatFrameMode,
+    PathLengthLimit,
+    AbsolutePaths,
+}
+
+impl PipelineStage {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PipelineStage::SourceFiles => "Source files",
+            PipelineStage::OriginalResolution => "Original resolution",
+            PipelineStage::CompanionResources => "Companion resources",
+            PipelineStage::ReferencedFileFilter => "Referenced file filter",
+            PipelineStage::EditedFilter => "Edited filter",
+            PipelineStage::DateRangeFilter => "Date range filter",
+            PipelineStage::FileTypeFilter => "File type filter",
+            PipelineStage::CameraFilter => "Camera filter",
+            PipelineStage::RawConversion => "RAW conversion",
+            PipelineStage::VideoTranscoding => "Video transcoding",
+            PipelineStage::LibraryStructure => "Library structure",
+            PipelineStage::OutputStructure => "Output structure",
+            PipelineStage::DateTree => "Date tree",
+            PipelineStage::KeywordGrouping => "Keyword grouping",
+            PipelineStage::FavoritesFolder => "Favorites folder",
+            PipelineStage::RawSubfolder => "RAW subfolder",
+            PipelineStage::OriginalFilenames => "Original filenames",
+            PipelineStage::TitleNames => "Title names",
+            PipelineStage::StableNames => "Stable names",
+            PipelineStage::AlbumOrderPrefixes => "Album order prefixes",
+            PipelineStage::FlatFrameMode => "Flat frame mode",
+            PipelineStage::PathLengthLimit => "Path length limit",
+            PipelineStage::AbsolutePaths => "Absolute paths",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            PipelineStage::SourceFiles => "Selects the original, edited or rendered files of the asset",
+            PipelineStage::OriginalResolution => "Looks up originals missing at their default location in alternative ones",
+            PipelineStage::CompanionResources => "Adds companion resources of the original, e.g. HDR gain maps",
+            PipelineStage::ReferencedFileFilter => "Skips referenced files stored outside the library",
+            PipelineStage::EditedFilter => "Skips assets without adjustments",
+            PipelineStage::DateRangeFilter => "Skips assets created within an excluded date range",
+            PipelineStage::FileTypeFilter => "Includes or excludes files by their file type",
+            PipelineStage::CameraFilter => "Includes or excludes assets by the camera they have been taken with",
+            PipelineStage::RawConversion => "Marks RAW files for conversion to DNG",
+            PipelineStage::VideoTranscoding => "Marks videos for transcoding",
+            PipelineStage::LibraryStructure => "Places files at their path within the library",
+            PipelineStage::OutputStructure => "Places files in the folders of the selected grouping, e.g. by album or date",
+            PipelineStage::DateTree => "Additionally exports each asset to a year/month tree in a _by_date folder",
+            PipelineStage::KeywordGrouping => "Exports assets once per keyword to a folder per keyword",
+            PipelineStage::FavoritesFolder => "Additionally exports favorites to a _favorites folder",
+            PipelineStage::RawSubfolder => "Moves RAW files to a subfolder of their folder",
+            PipelineStage::OriginalFilenames => "Renames files to the filename they have been imported with",
+            PipelineStage::TitleNames => "Names files after the title of their asset, or the imported filename if untitled",
+            PipelineStage::StableNames => "Names files after the UUID of their asset",
+            PipelineStage::AlbumOrderPrefixes => "Prefixes filenames with the position of their asset in its album",
+            PipelineStage::FlatFrameMode => "Places all files in the output directory with date-prefixed, unique names",
+            PipelineStage::PathLengthLimit => "Shortens folders and filenames exceeding the maximum path length",
+            PipelineStage::AbsolutePaths => "Resolves the paths against the library and the output directory",
+        }
+    }
+
+    /// Describes the assets the stage has built no copy operations for, e.g. in the exclusion note.
+    pub fn exclusion_reason(&self) -> String {
+        match self {
+            PipelineStage::SourceFiles => "without source files".to_string(),
+            _ => format!("excluded by {}", self.name().to_lowercase()),
+        }
+    }
+}
+
+/// Assets the filtering stages of a pipeline have built no copy operations for. Assets excluded by
+/// multiple stages are attributed to the last stage excluding them, as filtering stages do not
+/// build the earlier stages for the assets they exclude.
+#[derive(Default)]
+pub struct PipelineExclusionRegistry {
+    stages_by_asset: RefCell<HashMap<i32, PipelineStage>>,
+}
+
+impl PipelineExclusionRegistry {
+
+    /// Returns the number of excluded assets per stage, in the order the stages are applied.
+    pub fn get_all(&self) -> Vec<(PipelineStage, usize)> {
+        let mut counts: BTreeMap<PipelineStage, usize> = BTreeMap::new();
+        for stage in self.stages_by_asset.borrow().values() {
+            *counts.entry(*stage).or_default() += 1;
+        }
+        counts.into_iter().collect()
+    }
+}
+
+/// Records the assets the inner factory, a filtering stage, builds no copy operations for in the
+/// given registry. Assets the earlier stages have already excluded keep being attributed to them.
+#[derive(new)]
+struct ExclusionCountingCopyOperationFactoryDecorator {
+    inner: Box<dyn CopyOperationFactory>,
+    stage: PipelineStage,
+    registry: Rc<PipelineExclusionRegistry>,
+}
+
+impl CopyOperationFactory for ExclusionCountingCopyOperationFactoryDecorator {
+    fn build(&self, asset: &ExportAsset) -> Result<Vec<CopyOperation>, String> {
+        let operations = self.inner.build(asset)?;
+        if operations.is_empty() {
+            self.registry.stages_by_asset.borrow_mut().entry(asset.id).or_insert(self.stage);
+        }
+        Ok(operations)
+    }
+}
+
+/// Options selecting the source files of each asset and the stages of the pipeline they are
+/// passed through.
+#[derive(Default)]
+pub struct PipelineOptions {
+    pub library_path: PathBuf,
+    pub output_dir: PathBuf,
+    pub missing_derivates: Rc<MissingDerivateRegistry>,
+    pub relocated_originals: Rc<RelocatedOriginalRegistry>,
+    pub excluded_assets: Rc<PipelineExclusionRegistry>,
+    /// Export the edited version of the given media types along with the original
+    pub include_edited: Option<EditedMediaTypes>,
+    /// Export the edited version of the given media types instead of the original
+    pub prefer_edited: Option<EditedMediaTypes>,
+    /// Versions of slow-motion and time-lapse videos, exported like other assets if `None`
+    pub slomo: Option<RenderedVideoVersions>,
+    pub include_companion_resources: bool,
+    pub skip_referenced: bool,
+    /// Only export assets with adjustments
+    pub only_edited: bool,
+    pub exclude_date_ranges: Vec<DateRange>,
+    pub file_type_filter: Option<UtiFilter>,
+    pub camera_filter: Option<CameraFilter>,
+    pub convert_raw_to_dng: bool,
+    pub transcode_video: bool,
+    /// Place files at their path within the library instead of using the output strategy
+    pub keep_library_structure: bool,
+    /// Strategy determining the output folders, plain if `None`
+    pub output_strategy: Option<Box<dyn OutputStrategy>>,
+    /// Strategy of the additional date tree, if any
+    pub date_tree_strategy: Option<Box<dyn OutputStrategy>>,
+    pub group_by_keyword: bool,
+    pub favorites_subdir: bool,
+    pub raw_subdir: Option<String>,
+    pub restore_original_filenames: bool,
+    pub use_titles: bool,
+    /// Name files after the UUID of their asset, prefixed with its date if `true`
+    pub stable_names: Option<bool>,
+    pub album_order_prefixes: bool,
+    /// Registry shared by the flat exports of all libraries, if the export is flat
+    pub flat_output_registry: Option<Rc<FlatOutputRegistry>>,
+    pub max_path_length: Option<usize>,
+    /// Interaction to warn about each shortened output path on, e.g. in a dry run
+    pub path_length_warnings: Option<Rc<dyn UserInteraction>>,
+}
+
+/// Copy operation factory assembled from the stages selected by the options, along with the
+/// stages in the order they are applied.
+pub struct CopyOperationPipeline {
+    factory: Box<dyn CopyOperationFactory>,
+    stages: Vec<PipelineStage>,
+}
+
+impl CopyOperationPipeline {
+
+    /// Assembles the pipeline selected by the options, timing each stage as part of the planning
+    /// benchmark, if any.
+    pub fn build(options: PipelineOptions, planning_benchmark: &Option<Rc<PlanningBenchmark>>) -> Self {
+        let mut pipeline = PipelineAssembly {
+            stages: vec![],
+            planning_benchmark,
+            excluded_assets: options.excluded_assets.clone(),
+        };
+
+        let source_factory = pipeline.add_filter(PipelineStage::SourceFiles, source_copy_operation_factory(&options));
+        let mut source_factory = pipeline.add(
+            PipelineStage::OriginalResolution,
+            Box::new(
+                OriginalResolvingCopyOperationFactoryDecorator::new(
+                    source_factory,
+                    options.library_path.clone(),
+                    options.relocated_originals.clone()
+                )
+            )
+        );
+
+        if options.include_companion_resources {
+            source_factory = Box::new(
+                CombiningCopyOperationFactory::new(
+                    vec![
+                        source_factory,
+                        pipeline.add(
+                            PipelineStage::CompanionResources,
+                            Box::new(CompanionResourcesCopyOperationFactory::new(options.library_path.clone()))
+                        )
+                    ]
+                )
+            );
+        }
+
+        if options.skip_referenced {
+            source_factory = pipeline.add_filter(
+                PipelineStage::ReferencedFileFilter,
+                Box::new(
+                    FilteringCopyOperationFactoryDecorator::new(source_factory, Box::new(|asset| asset.referenced_path.is_none()))
+                )
+            );
+        }
+
+        if options.only_edited {
+            source_factory = pipeline.add_filter(
+                PipelineStage::EditedFilter,
+                Box::new(
+                    FilteringCopyOperationFactoryDecorator::new(source_factory, Box::new(|asset| asset.has_adjustments))
+                )
+            );
+        }
+
+        for &range in &options.exclude_date_ranges {
+            source_factory = pipeline.add_filter(
+                PipelineStage::DateRangeFilter,
+                Box::new(
+                    FilteringCopyOperationFactoryDecorator::new(source_factory, Box::new(move |asset| !range.contains(&asset.datetime)))
+                )
+            );
+        }
+
+        if let Some(filter) = options.file_type_filter {
+            source_factory = pipeline.add_filter(
+                PipelineStage::FileTypeFilter,
+                Box::new(UtiFilteringCopyOperationFactoryDecorator::new(source_factory, filter))
+            );
+        }
+
+        if let Some(filter) = options.camera_filter {
+            source_factory = pipeline.add_filter(
+                PipelineStage::CameraFilter,
+                Box::new(
+                    FilteringCopyOperationFactoryDecorator::new(source_factory, Box::new(move |asset| filter.matches(asset)))
+                )
+            );
+        }
+
+        if options.convert_raw_to_dng {
+            source_factory = pipeline.add(
+                PipelineStage::RawConversion,
+                Box::new(RawConvertingCopyOperationFactoryDecorator::new(source_factory))
+            );
+        }
+
+        if options.transcode_video {
+            source_factory = pipeline.add(
+                PipelineStage::VideoTranscoding,
+                Box::new(VideoTranscodingCopyOperationFactoryDecorator::new(source_factory))
+            );
+        }
+
+        if options.keep_library_structure {
+            let factory = pipeline.add(
+                PipelineStage::LibraryStructure,
+                Box::new(LibraryStructureCopyOperationFactoryDecorator::new(source_factory))
+            );
+            return pipeline.finish(factory, &options.library_path, &options.output_dir);
+        }
+
+        let mut factory = pipeline.add(
+            PipelineStage::OutputStructure,
+            Box::new(
+                OutputStructureCopyOperationFactoryDecorator::new(
+                    source_factory,
+                    options.output_strategy.unwrap_or_else(|| Box::new(PlainOutputStrategy::new()))
+                )
+            )
+        );
+
+        if let Some(strategy) = options.date_tree_strategy {
+            factory = pipeline.add(
+                PipelineStage::DateTree,
+                Box::new(DateTreeCopyOperationFactoryDecorator::new(factory, strategy))
+            );
+        }
+
+        if options.group_by_keyword {
+            factory = pipeline.add(
+                PipelineStage::KeywordGrouping,
+                Box::new(KeywordGroupingCopyOperationFactoryDecorator::new(factory))
+            );
+        }
+
+        if options.favorites_subdir {
+            factory = pipeline.add(
+                PipelineStage::FavoritesFolder,
+                Box::new(FavoritesFolderCopyOperationFactoryDecorator::new(factory))
+            );
+        }
+
+        if let Some(subfolder) = &options.raw_subdir {
+            factory = pipeline.add(
+                PipelineStage::RawSubfolder,
+                Box::new(RawSubfolderCopyOperationFactoryDecorator::new(factory, subfolder.clone()))
+            );
+        }
+
+        if options.restore_original_filenames {
+            factory = pipeline.add(
+                PipelineStage::OriginalFilenames,
+                Box::new(FilenameRestoringCopyOperationFactoryDecorator::new(factory))
+            );
+        }
+
+        if options.use_titles {
+            factory = pipeline.add(
+                PipelineStage::TitleNames,
+                Box::new(TitleNamingCopyOperationFactoryDecorator::new(factory))
+            );
+        }
+
+        if let Some(date_prefixed) = options.stable_names {
+            factory = pipeline.add(
+                PipelineStage::StableNames,
+                Box::new(StableNamingCopyOperationFactoryDecorator::new(factory, date_prefixed))
+            );
+        }
+
+        if options.album_order_prefixes {
+            factory = pipeline.add(
+                PipelineStage::AlbumOrderPrefixes,
+                Box::new(AlbumOrderPrefixingCopyOperationFactoryDecorator::new(factory))
+            );
+        }
+
+        if let Some(registry) = &options.flat_output_registry {
+            factory = pipeline.add(
+                PipelineStage::FlatFrameMode,
+                Box::new(
+                    FlatFrameCopyOperationFactoryDecorator::new(factory, options.library_path.clone(), registry.clone())
+                )
+            );
+        }
+
+        if let Some(max_length) = options.max_path_length {
+            factory = pipeline.add(
+                PipelineStage::PathLengthLimit,
+                Box::new(PathLengthLimitingCopyOperationFactoryDecorator::new(factory, max_length, options.path_length_warnings.clone()))
+            );
+        }
+
+        pipeline.finish(factory, &options.library_path, &options.output_dir)
+    }
+
+    /// Returns the stages in the order they are applied to the copy operations.
+    pub fn get_stages(&self) -> &[PipelineStage] {
+        &self.stages
+    }
+
+    pub fn into_factory(self) -> Box<dyn CopyOperationFactory> {
+        self.factory
+    }
+
+    pub fn print(&self) {
+        let stages = self.get_stages();
+        let width = stages.iter().map(|stage| stage.name().chars().count()).max().unwrap_or(0);
+
+        println!("{}", "Copy operation pipeline:".blue());
+        for (index, stage) in stages.iter().enumerate() {
+            println!("  {:>2}. {:width$}  {}", index + 1, stage.name(), stage.description().dimmed(), width = width);
+        }
+    }
+}
+
+fn source_copy_operation_factory(options: &PipelineOptions) -> Box<dyn CopyOperationFactory> {
+    let factory = edited_copy_operation_factory(options);
+
+    match options.slomo {
+        None => factory,
+        Some(versions) => Box::new(
+            CombiningCopyOperationFactory::new(
+                vec![
+                    Box::new(
+                        FilteringCopyOperationFactoryDecorator::new(
+                            factory,
+                            Box::new(|asset| !asset.subtype.is_rendered_video_variant())
+                        )
+                    ),
+                    Box::new(
+                        FilteringCopyOperationFactoryDecorator::new(
+                            rendered_video_copy_operation_factory(options, versions),
+                            Box::new(|asset| asset.subtype.is_rendered_video_variant())
+                        )
+                    )
+                ]
+            )
+        )
+    }
+}
+
+fn rendered_video_copy_operation_factory(
+    options: &PipelineOptions,
+    versions: RenderedVideoVersions
+) -> Box<dyn CopyOperationFactory> {
+    match versions {
+        RenderedVideoVersions::Rendered => Box::new(
+            CombiningCopyOperationFactory::new(
+                vec![
+                    Box::new(
+                        FilteringCopyOperationFactoryDecorator::new(
+                            Box::new(OriginalsCopyOperationFactory::new()),
+                            Box::new(|asset| !asset.has_adjustments)
+                        )
+                    ),
+                    Box::new(
+                        OriginalFallbackCopyOperationFactoryDecorator::new(
+                            Box::new(DerivatesCopyOperationFactory::new()),
+                            options.library_path.clone(),
+                            options.missing_derivates.clone()
+                        )
+                    )
+                ]
+            )
+        ),
+        RenderedVideoVersions::Original => Box::new(OriginalsCopyOperationFactory::new()),
+        RenderedVideoVersions::Both => Box::new(
+            CombiningCopyOperationFactory::new(
+                vec![
+                    Box::new(
+                        SuffixSettingCopyOperationFactoryDecorator::new(
+                            Box::new(OriginalsCopyOperationFactory::new()),
+                            "_original".to_string()
+                        )
+                    ),
+                    Box::new(DerivatesCopyOperationFactory::new())
+                ]
+            )
+        ),
+    }
+}
+
+fn edited_copy_operation_factory(options: &PipelineOptions) -> Box<dyn CopyOperationFactory> {
+    if let Some(media_types) = options.include_edited {
+        Box::new(
+            CombiningCopyOperationFactory::new(
+                vec![
+                    Box::new(
+                        SuffixSettingCopyOperationFactoryDecorator::new(
+                            Box::new(OriginalsCopyOperationFactory::new()),
+                            "_original".to_string()
+                        )
+                    ),
+                    Box::new(
+                        FilteringCopyOperationFactoryDecorator::new(
+                            Box::new(DerivatesCopyOperationFactory::new()),
+                            media_types.edited_asset_predicate()
+                        )
+                    )
+                ]
+            )
+        )
+    } else if let Some(media_types) = options.prefer_edited {
+        let prefers_derivate = media_types.edited_asset_predicate();
+        Box::new(
+            CombiningCopyOperationFactory::new(
+                vec![
+                    Box::new(
+                        FilteringCopyOperationFactoryDecorator::new(
+                            Box::new(OriginalsCopyOperationFactory::new()),
+                            Box::new(move |asset| !prefers_derivate(asset))
+                        )
+                    ),
+                    Box::new(
+                        FilteringCopyOperationFactoryDecorator::new(
+                            Box::new(
+                                OriginalFallbackCopyOperationFactoryDecorator::new(
+                                    Box::new(DerivatesCopyOperationFactory::new()),
+                                    options.library_path.clone(),
+                                    options.missing_derivates.clone()
+                                )
+                            ),
+                            media_types.edited_asset_predicate()
+                        )
+                    )
+                ]
+            )
+        )
+    } else {
+        Box::new(OriginalsCopyOperationFactory::new())
+    }
+}
+
+/// Stages added to a pipeline so far.
+struct PipelineAssembly<'a> {
+    stages: Vec<PipelineStage>,
+    planning_benchmark: &'a Option<Rc<PlanningBenchmark>>,
+    excluded_assets: Rc<PipelineExclusionRegistry>,
+}
+
+impl PipelineAssembly<'_> {
+
+    /// Records the factory as the given stage, timing it as part of the planning benchmark.
+    fn add(&mut self, stage: PipelineStage, factory: Box<dyn CopyOperationFactory>) -> Box<dyn CopyOperationFactory> {
+        self.stages.push(stage);
+        match self.planning_benchmark {
+            Some(benchmark) => Box::new(
+                TimingCopyOperationFactoryDecorator::new(factory, stage.name().to_string(), benchmark.clone())
+            ),
+            None => factory,
+        }
+    }
+
+    /// Records the factory as the given stage like [Self::add], additionally recording the assets
+    /// it excludes.
+    fn add_filter(&mut self, stage: PipelineStage, factory: Box<dyn CopyOperationFactory>) -> Box<dyn CopyOperationFactory> {
+        self.add(
+            stage,
+            Box::new(ExclusionCountingCopyOperationFactoryDecorator::new(factory, stage, self.excluded_assets.clone()))
+        )
+    }
+
+    /// Adds the final stage resolving the paths against the library and the output directory.
+    fn finish(
+        mut self,
+        factory: Box<dyn CopyOperationFactory>,
+        library_path: &Path,
+        output_dir: &Path
+    ) -> CopyOperationPipeline {
+        let factory = self.add(
+            PipelineStage::AbsolutePaths,
+            Box::new(
+                AbsolutePathBuildingCopyOperationFactoryDecorator::new(library_path.to_path_buf(), output_dir.to_path_buf(), factory)
+            )
+        );
+
+        CopyOperationPipeline { factory, stages: self.stages }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{read_to_string, write};
+    use crate::test_util::library::{load_exportable, SyntheticLibrary, TestAlbum, TestAsset};
+    use crate::model::asset::ExportAsset;
+    use crate::db::repo::album::AlbumRepository;
+    use crate::db::repo::asset::{AlbumFilter, AssetRepository, HiddenAssetsFilter};
+    use crate::export::structure::{parse_album_folder_template, AlbumOutputStrategy, DateGranularity, DateOutputStrategy, HiddenAssetHandlingOutputStrategyDecorator};
+
+    /// Destinations of the assets of [golden_library] for the options of each [golden_scenarios],
+    /// relative to the crate root. Run the tests with `UPDATE_GOLDEN=1` to accept changes.
+    const GOLDEN_PATH: &str = "src/export/testdata/pipeline_destinations.golden";
+
+    const EXPORT_DIR: &str = "/export";
+
+    /// Returns the output paths of the files the pipeline exports for the asset, relative to
+    /// [EXPORT_DIR], without touching the file system.
+    fn plan_destinations(pipeline: &CopyOperationPipeline, asset: &ExportAsset) -> Vec<PathBuf> {
+        pipeline.factory
+            .build(asset)
+            .unwrap()
+            .iter()
+            .map(|op| op.get_output_path().strip_prefix(EXPORT_DIR).unwrap().to_path_buf())
+            .collect()
+    }
+
+    fn stages(options: PipelineOptions) -> Vec<PipelineStage> {
+        CopyOperationPipeline::build(options, &None)
+            .get_stages()
+            .to_vec()
+    }
+
+    #[test]
+    fn orders_stages_independently_of_the_options() {
+        use PipelineStage::*;
+
+        assert_eq!(
+            stages(PipelineOptions::default()),
+            [SourceFiles, OriginalResolution, OutputStructure, AbsolutePaths]
+        );
+        assert_eq!(
+            stages(
+                PipelineOptions {
+                    restore_original_filenames: true,
+                    favorites_subdir: true,
+                    group_by_keyword: true,
+                    stable_names: Some(false),
+                    max_path_length: Some(255),
+                    skip_referenced: true,
+                    only_edited: true,
+                    ..PipelineOptions::default()
+                }
+            ),
+            [
+                SourceFiles, OriginalResolution, ReferencedFileFilter, EditedFilter, OutputStructure, KeywordGrouping,
+                FavoritesFolder, OriginalFilenames, StableNames, PathLengthLimit, AbsolutePaths
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_output_stages_when_keeping_the_library_structure() {
+        use PipelineStage::*;
+
+        assert_eq!(
+            stages(PipelineOptions { keep_library_structure: true, favorites_subdir: true, ..PipelineOptions::default() }),
+            [SourceFiles, OriginalResolution, LibraryStructure, AbsolutePaths]
+        );
+    }
+
+    #[test]
+    fn builds_absolute_copy_operations() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let assets = load_exportable(
+            &AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+        );
+
+        let pipeline = CopyOperationPipeline::build(
+            PipelineOptions {
+                library_path: library.path().to_path_buf(),
+                output_dir: PathBuf::from("/export"),
+                ..PipelineOptions::default()
+            },
+            &None
+        );
+        let ops = pipeline.into_factory().build(&assets[0]).unwrap();
+
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].source_path, library.path().join(TestAsset::jpeg(1).original_path()));
+        assert!(ops[0].get_output_path().starts_with("/export"));
+    }
+
+    #[test]
+    fn counts_assets_excluded_by_the_last_filtering_stage_excluding_them() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        library.add_album(&TestAlbum::album(3, "Winter"));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[2, 3]));
+        library.add_asset(&TestAsset::jpeg(2).edited());
+        library.add_asset(&TestAsset::jpeg(3).edited().referenced_at("Photos", "2010/IMG_0003.JPG"));
+        library.add_asset(&TestAsset::jpeg(4).referenced_at("Photos", "2010/IMG_0004.JPG"));
+        let assets = load_exportable(
+            &AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+        );
+
+        let excluded_assets = Rc::new(PipelineExclusionRegistry::default());
+        let factory = CopyOperationPipeline::build(
+            PipelineOptions {
+                library_path: library.path().to_path_buf(),
+                output_dir: PathBuf::from(EXPORT_DIR),
+                excluded_assets: excluded_assets.clone(),
+                skip_referenced: true,
+                only_edited: true,
+                ..PipelineOptions::default()
+            },
+            &None
+        ).into_factory();
+        for asset in &assets {
+            factory.build(asset).unwrap();
+        }
+
+        assert_eq!(
+            excluded_assets.get_all(),
+            [(PipelineStage::ReferencedFileFilter, 1), (PipelineStage::EditedFilter, 2)]
+        );
+    }
+
+    #[test]
+    fn filters_assets_by_camera_name_or_model() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).shot_with("FUJIFILM", "X-T5", None));
+        library.add_asset(&TestAsset::jpeg(2).shot_with("Apple", "iPhone 15 Pro", None));
+        library.add_asset(&TestAsset::jpeg(3));
+        let mut assets = load_exportable(
+            &AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+        );
+        assets.sort_by_key(|a| a.id);
+
+        let exported = |filter| {
+            let factory = CopyOperationPipeline::build(
+                PipelineOptions { camera_filter: Some(filter), ..PipelineOptions::default() },
+                &None
+            ).into_factory();
+            assets
+                .iter()
+                .filter(|asset| !factory.build(asset).unwrap().is_empty())
+                .map(|asset| asset.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(exported(CameraFilter::Include(vec!["x-t5".to_string()])), [1]);
+        assert_eq!(exported(CameraFilter::Exclude(vec!["Apple iPhone 15 Pro".to_string()])), [1, 3]);
+    }
+
+    #[test]
+    fn plans_same_destinations_for_unix_and_windows_separators() {
+        let destinations = |separator: &str| {
+            let library = SyntheticLibrary::new();
+            library.add_album(&TestAlbum::folder(2, &format!("Trips{}Europe", separator)));
+            library.add_album(&TestAlbum::album(3, &format!("Rome{}Florence", separator)).in_folder(2).starting_at(JUNE_13_2024));
+            library.add_asset(&TestAsset::jpeg(1).in_albums(&[3]).tagged(&[&format!("Food{}Drink", separator)]));
+            let assets = load_exportable(
+                &AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+            );
+
+            let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+            let template = parse_album_folder_template(&format!("{{year}}{}{{name}}", separator)).unwrap();
+            let pipeline = CopyOperationPipeline::build(
+                PipelineOptions {
+                    library_path: library.path().to_path_buf(),
+                    output_dir: PathBuf::from(EXPORT_DIR),
+                    output_strategy: Some(Box::new(AlbumOutputStrategy::new(false, albums).with_folder_template(template))),
+                    group_by_keyword: true,
+                    ..PipelineOptions::default()
+                },
+                &None
+            );
+            plan_destinations(&pipeline, &assets[0])
+        };
+
+        let unix = destinations("/");
+        assert_eq!(unix, [PathBuf::from("Trips-Europe/2024/Rome-Florence/Food-Drink/ASSET-0001.jpeg")]);
+        assert_eq!(destinations("\\"), unix);
+    }
+
+    /// Noon UTC, so that the local UTC offset does not change the day. Scenarios must not depend
+    /// on the time of day, e.g. the flat frame mode.
+    const JUNE_13_2024: f32 = 739_972_800.0;
+
+    /// A library covering the cases options treat differently: an album in a folder, titled
+    /// photos, an edited photo, a RAW file, a hidden photo, a photo in multiple albums and a
+    /// favorite.
+    fn golden_library() -> SyntheticLibrary {
+        let library = SyntheticLibrary::new();
+        let photo = |id| TestAsset::jpeg(id).taken_at(JUNE_13_2024);
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum::album(3, "Rome").in_folder(2));
+        library.add_album(&TestAlbum::album(4, "Family"));
+        library.add_asset(&photo(1).titled("Colosseum at dusk").in_albums(&[3]));
+        library.add_asset(&photo(2).edited().titled("Dinner: 2/3").in_albums(&[3]));
+        library.add_asset(&TestAsset {
+            extension: "raf".to_string(),
+            uti: "com.fuji.raw-image",
+            compact_uti: Some("21"),
+            original_filename: "DSCF0003.RAF".to_string(),
+            ..photo(3).in_albums(&[3])
+        });
+        library.add_asset(&photo(4).hidden());
+        library.add_asset(&photo(5).in_albums(&[3, 4]));
+        library.add_asset(&photo(6).favorite());
+        library
+    }
+
+    fn album_strategy(library: &SyntheticLibrary, flatten: bool) -> Option<Box<dyn OutputStrategy>> {
+        let albums = AlbumRepository::new(library.db_path(), None).get_all().unwrap();
+        Some(Box::new(HiddenAssetHandlingOutputStrategyDecorator::new(Box::new(AlbumOutputStrategy::new(flatten, albums)))))
+    }
+
+    type Scenario = (&'static str, fn(&SyntheticLibrary) -> PipelineOptions);
+
+    fn golden_scenarios() -> Vec<Scenario> {
+        vec![
+            ("plain", |_| PipelineOptions::default()),
+            ("by album", |library| PipelineOptions { output_strategy: album_strategy(library, false), ..PipelineOptions::default() }),
+            ("by album, flattened", |library| PipelineOptions { output_strategy: album_strategy(library, true), ..PipelineOptions::default() }),
+            ("by year/month", |_| PipelineOptions {
+                output_strategy: Some(Box::new(DateOutputStrategy::asset_date_based(DateGranularity::Month))),
+                ..PipelineOptions::default()
+            }),
+            ("include edited", |_| PipelineOptions { include_edited: Some(EditedMediaTypes::All), ..PipelineOptions::default() }),
+            ("prefer edited", |_| PipelineOptions { prefer_edited: Some(EditedMediaTypes::All), ..PipelineOptions::default() }),
+            ("raw subfolder by album", |library| PipelineOptions {
+                output_strategy: album_strategy(library, false),
+                raw_subdir: Some("RAW".to_string()),
+                ..PipelineOptions::default()
+            }),
+            ("only edited, prefer edited", |_| PipelineOptions {
+                only_edited: true,
+                prefer_edited: Some(EditedMediaTypes::All),
+                ..PipelineOptions::default()
+            }),
+            ("favorites subfolder", |_| PipelineOptions { favorites_subdir: true, ..PipelineOptions::default() }),
+            ("original filenames, include edited", |_| PipelineOptions {
+                restore_original_filenames: true,
+                include_edited: Some(EditedMediaTypes::All),
+                ..PipelineOptions::default()
+            }),
+            ("titles, include edited", |_| PipelineOptions {
+                use_titles: true,
+                include_edited: Some(EditedMediaTypes::All),
+                ..PipelineOptions::default()
+            }),
+            ("stable names with date", |_| PipelineOptions { stable_names: Some(true), ..PipelineOptions::default() }),
+            ("library structure", |_| PipelineOptions { keep_library_structure: true, ..PipelineOptions::default() }),
+        ]
+    }
+
+    #[test]
+    fn plans_golden_destinations() {
+        let library = golden_library();
+        let assets = load_exportable(
+            &AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+        );
+
+        let mut actual = String::new();
+        for (name, options) in golden_scenarios() {
+            let pipeline = CopyOperationPipeline::build(
+                PipelineOptions {
+                    library_path: library.path().to_path_buf(),
+                    output_dir: PathBuf::from(EXPORT_DIR),
+                    ..options(&library)
+                },
+                &None
+            );
+
+            actual.push_str(&format!("## {}\n", name));
+            for asset in &assets {
+                for destination in plan_destinations(&pipeline, asset) {
+                    actual.push_str(&format!("{} -> {}\n", asset.uuid, destination.to_string_lossy()));
+                }
+            }
+            actual.push('\n');
+        }
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_PATH);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            write(&path, &actual).unwrap();
+        }
+        assert_eq!(
+            actual,
+            read_to_string(&path).unwrap_or_default(),
+            "Planned destinations differ from {}, run the tests with UPDATE_GOLDEN=1 to accept the changes",
+            GOLDEN_PATH
+        );
+    }
+}
\ No newline at end of file
@@ -0,0 +1,113 @@
+use std::fs::{create_dir_all, remove_file, File};
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use crate::export::copying::CopyOperation;
+use crate::model::asset::ExportAsset;
+
+/// Suffix appended to the planned output path of an asset to get the path of its placeholder,
+/// e.g. `IMG_0001.jpeg.missing.json`.
+pub const PLACEHOLDER_SUFFIX: &str = ".missing.json";
+
+/// Returns the path of the placeholder standing in for a file that would be exported to the given
+/// path.
+pub fn get_placeholder_path(output_path: &Path) -> PathBuf {
+    let mut path = output_path.as_os_str().to_owned();
+    path.push(PLACEHOLDER_SUFFIX);
+    PathBuf::from(path)
+}
+
+pub fn is_placeholder(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().ends_with(PLACEHOLDER_SUFFIX))
+        .unwrap_or(false)
+}
+
+/// Writes a placeholder describing the given asset next to where the given copy operation would
+/// export it, for assets that can not be exported as their original is not locally available,
+/// e.g. as it has been offloaded to iCloud. Returns the path of the placeholder.
+pub fn write_placeholder(asset: &ExportAsset, copy_operation: &CopyOperation) -> Result<PathBuf, String> {
+    let output_path = copy_operation.get_output_path();
+    let path = get_placeholder_path(&output_path);
+
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)
+            .map_err(|e| format!("Unable to create '{}': {}", parent.to_string_lossy(), e))?;
+    }
+
+    let file = File::create(&path)
+        .map_err(|e| format!("Unable to create '{}': {}", path.to_string_lossy(), e))?;
+
+    serde_json::to_writer_pretty(
+        file,
+        &json!({
+            "filename": output_path.file_name().map(|name| name.to_string_lossy()),
+            "uuid": asset.uuid,
+            "original_filename": asset.original_filename,
+            "datetime": asset.datetime.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "title": asset.title,
+            "description": asset.description,
+            "keywords": asset.keywords,
+            "album": asset.album.as_ref().and_then(|album| album.name.clone()),
+            "favorite": asset.favorite,
+            "hidden": asset.hidden,
+            "contributor": asset.contributor,
+            "width": asset.dimensions.map(|d| d.width),
+            "height": asset.dimensions.map(|d| d.height),
+            "duration": asset.duration
+        })
+    )
+        .map_err(|e| format!("Unable to write '{}': {}", path.to_string_lossy(), e))?;
+
+    Ok(path)
+}
+
+/// Removes the placeholder of a file that has been exported to the given path, if any, returning
+/// whether there has been one.
+pub fn remove_placeholder(output_path: &Path) -> Result<bool, String> {
+    let path = get_placeholder_path(output_path);
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    remove_file(&path)
+        .map(|_| true)
+        .map_err(|e| format!("Unable to remove '{}': {}", path.to_string_lossy(), e))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::copying::OriginalsCopyOperationFactory;
+    use crate::test_util::library::{load_offloaded_operations, SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn writes_and_removes_placeholders_of_offloaded_assets() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).offloaded().titled("Beach").tagged(&["Summer"]));
+        let output = tempfile::tempdir().unwrap();
+
+        let planned = load_offloaded_operations(
+            &library,
+            &output.path().join("2024"),
+            Box::new(OriginalsCopyOperationFactory::new())
+        );
+        let (asset, op) = planned.get_all()[0];
+
+        let path = write_placeholder(asset, op).unwrap();
+        let content: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+        assert_eq!(path, output.path().join("2024").join("ASSET-0001.jpeg.missing.json"));
+        assert!(is_placeholder(&path));
+        assert_eq!(content["uuid"], "ASSET-0001");
+        assert_eq!(content["title"], "Beach");
+        assert_eq!(content["keywords"], json!(["Summer"]));
+        assert_eq!((&content["width"], &content["height"]), (&json!(4032), &json!(3024)));
+
+        assert_eq!(remove_placeholder(&op.get_output_path()), Ok(true));
+        assert!(!path.exists());
+        assert_eq!(remove_placeholder(&op.get_output_path()), Ok(false));
+    }
+}
\ No newline at end of file
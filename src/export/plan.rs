@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::fs::{read_to_string, File};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::copying::CopyOperation;
+use crate::model::asset::{ExportAsset, Orientation};
+use crate::model::uti::Uti;
+use crate::util::paths::validate_output_outside_libraries;
+
+const PLAN_VERSION: u32 = 1;
+
+/// Fully mapped copy operations of an export, written to a file to be reviewed and possibly edited
+/// before being executed by a later export without planning it again.
+///
+/// Output paths are stored relative to the output directory of the export, so that an edited plan
+/// cannot write outside of it.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ExportPlan {
+    version: u32,
+    operations: Vec<PlannedCopyOperation>,
+}
+
+/// A copy operation along with the asset it has been planned for.
+///
+/// The asset is identified by the library it belongs to, its UUID and the album it has been
+/// exported for, as assets of multiple albums may be exported once per album.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct PlannedCopyOperation {
+    library: String,
+    uuid: String,
+    album_id: Option<i32>,
+    source_path: PathBuf,
+    uti: String,
+    output_folder: Option<PathBuf>,
+    output_filename: String,
+    output_filename_suffix: Option<String>,
+    converted_from: Option<String>,
+    orientation: i32,
+}
+
+impl ExportPlan {
+
+    /// Creates a plan of the given copy operations, each along with the name of the library its
+    /// asset belongs to.
+    ///
+    /// Fails if the output folder of an operation does not lie inside the given output directory.
+    pub fn new(operations: &[(&str, &ExportAsset, &CopyOperation)], output_dir: &Path) -> Result<Self, String> {
+        Ok(
+            ExportPlan {
+                version: PLAN_VERSION,
+                operations: operations
+                    .iter()
+                    .map(|(library, asset, op)| {
+                        let output_folder = op.output_folder
+                            .as_ref()
+                            .map(|folder| {
+                                folder
+                                    .strip_prefix(output_dir)
+                                    .map(PathBuf::from)
+                                    .map_err(|_| {
+                                        format!(
+                                            "Output folder '{}' of asset {} lies outside the output directory",
+                                            folder.to_string_lossy(),
+                                            asset.uuid
+                                        )
+                                    })
+                            })
+                            .transpose()?;
+
+                        Ok(
+                            PlannedCopyOperation {
+                                library: library.to_string(),
+                                uuid: asset.uuid.clone(),
+                                album_id: asset.album.as_ref().map(|album| album.id),
+                                source_path: op.source_path.clone(),
+                                uti: op.uti.uti.to_string(),
+                                output_folder,
+                                output_filename: op.output_filename.clone(),
+                                output_filename_suffix: op.output_filename_suffix.clone(),
+                                converted_from: op.converted_from.map(|uti| uti.uti.to_string()),
+                                orientation: op.orientation.into(),
+                            }
+                        )
+                    })
+                    .collect::<Result<_, String>>()?,
+            }
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content = read_to_string(path)
+            .map_err(|e| format!("Unable to read plan '{}': {}", path.to_string_lossy(), e))?;
+        let plan: ExportPlan = serde_json::from_str(&content)
+            .map_err(|e| format!("Unable to parse plan '{}': {}", path.to_string_lossy(), e))?;
+
+        if plan.version != PLAN_VERSION {
+            return Err(format!(
+                "Unsupported version {} of plan '{}', expected {}",
+                plan.version,
+                path.to_string_lossy(),
+                PLAN_VERSION
+            ));
+        }
+
+        Ok(plan)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Unable to create '{}': {}", path.to_string_lossy(), e))?;
+
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|e| format!("Unable to write '{}': {}", path.to_string_lossy(), e))
+    }
+
+    /// Returns the planned copy operations along with the assets they have been planned for,
+    /// looked up in the assets of the libraries with the given names and paths. Output paths are
+    /// resolved against the given output directory.
+    ///
+    /// Fails if an asset of the plan is not exportable anymore, e.g. as it has been deleted or the
+    /// export's filters exclude it, or if an operation of an edited plan would read from outside
+    /// its asset's library or write outside the output directory or into one of the libraries.
+    pub fn resolve<'a>(
+        &self,
+        libraries: &[(&str, &Path, &'a [ExportAsset])],
+        output_dir: &Path
+    ) -> Result<Vec<(&'a ExportAsset, CopyOperation)>, String> {
+        let assets: HashMap<(&str, &str, Option<i32>), (&Path, &ExportAsset)> = libraries
+            .iter()
+            .flat_map(|(library, library_path, assets)| {
+                assets.iter().map(|asset| {
+                    ((*library, asset.uuid.as_str(), asset.album.as_ref().map(|a| a.id)), (*library_path, asset))
+                })
+            })
+            .collect();
+        let library_paths: Vec<String> = libraries
+            .iter()
+            .map(|(_, library_path, _)| library_path.to_string_lossy().to_string())
+            .collect();
+
+        self.operations
+            .iter()
+            .map(|planned| {
+                let (library_path, asset) = assets
+                    .get(&(planned.library.as_str(), planned.uuid.as_str(), planned.album_id))
+                    .ok_or_else(|| {
+                        format!(
+                            "Asset {} of library '{}' in the plan is not exportable with the given options anymore",
+                            planned.uuid,
+                            planned.library
+                        )
+                    })?;
+
+                let op = planned.to_copy_operation(output_dir)?;
+                planned.validate_source(library_path, asset)?;
+                planned.validate_output(&op.get_output_path(), output_dir)?;
+                validate_output_outside_libraries(&op.get_output_path(), &library_paths)?;
+
+                Ok((*asset, op))
+            })
+            .collect()
+    }
+}
+
+impl PlannedCopyOperation {
+
+    fn to_copy_operation(&self, output_dir: &Path) -> Result<CopyOperation, String> {
+        Ok(
+            CopyOperation {
+                converted_from: self.converted_from.as_deref().map(Uti::from_name).transpose()?,
+                orientation: Orientation::from(self.orientation),
+                ..CopyOperation::new(
+                    self.source_path.clone(),
+                    Uti::from_name(&self.uti)?,
+                    self.output_filename.clone(),
+                    self.output_filename_suffix.clone(),
+                    Some(output_dir.join(self.output_folder.clone().unwrap_or_default())),
+                )
+            }
+        )
+    }
+
+    /// Fails unless the source path lies inside the given library or is the referenced original
+    /// of the asset, which is stored outside the library.
+    fn validate_source(&self, library_path: &Path, asset: &ExportAsset) -> Result<(), String> {
+        if asset.referenced_path.as_ref() == Some(&self.source_path) {
+            return Ok(());
+        }
+
+        match self.source_path.strip_prefix(library_path) {
+            Ok(relative) if is_plain_relative(relative) => Ok(()),
+            _ => Err(
+                format!(
+                    "Source '{}' of asset {} in the plan lies outside the library '{}'",
+                    self.source_path.to_string_lossy(),
+                    self.uuid,
+                    self.library
+                )
+            ),
+        }
+    }
+
+    /// Fails unless the given output path lies inside the given output directory.
+    fn validate_output(&self, output_path: &Path, output_dir: &Path) -> Result<(), String> {
+        match output_path.strip_prefix(output_dir) {
+            Ok(relative) if is_plain_relative(relative) => Ok(()),
+            _ => Err(
+                format!(
+                    "Output '{}' of asset {} in the plan lies outside the output directory",
+                    output_path.to_string_lossy(),
+                    self.uuid
+                )
+            ),
+        }
+    }
+}
+
+/// Whether the given path is relative and does not leave the directory it is relative to, i.e.
+/// does not contain `..` or root components.
+fn is_plain_relative(path: &Path) -> bool {
+    path.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::FromDbModel;
+    use crate::test_util::library::{SyntheticLibrary, TestAsset};
+    use crate::db::repo::asset::{AlbumFilter, AssetRepository, HiddenAssetsFilter};
+
+    fn load_assets(library: &SyntheticLibrary) -> Vec<ExportAsset> {
+        AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+            .get_exportable()
+            .unwrap()
+            .iter()
+            .map(|a| ExportAsset::from_db_model(a).unwrap())
+            .collect()
+    }
+
+    fn jpeg_operation(source_path: PathBuf, output_folder: PathBuf) -> CopyOperation {
+        CopyOperation::new(source_path, Uti::from_name("public.jpeg").unwrap(), "IMG_0001".to_string(), None, Some(output_folder))
+    }
+
+    /// Writes a plan of the given operation, then edits it with the given function before resolving it.
+    fn resolve_edited(
+        library: &SyntheticLibrary,
+        assets: &[ExportAsset],
+        op: &CopyOperation,
+        edit: impl Fn(&mut PlannedCopyOperation)
+    ) -> Result<Vec<CopyOperation>, String> {
+        let mut plan = ExportPlan::new(&[("Library", &assets[0], op)], Path::new("/export")).unwrap();
+        plan.operations.iter_mut().for_each(edit);
+
+        plan.resolve(&[("Library", library.path(), assets)], Path::new("/export"))
+            .map(|resolved| resolved.into_iter().map(|(_, op)| op).collect())
+    }
+
+    #[test]
+    fn restores_planned_copy_operations() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).oriented(6));
+        let assets = load_assets(&library);
+
+        let op = CopyOperation {
+            converted_from: Some(Uti::from_name("public.heic").unwrap()),
+            ..CopyOperation::new(
+                library.path().join("originals/1.heic"),
+                Uti::from_name("public.jpeg").unwrap(),
+                "IMG_0001".to_string(),
+                Some("_edited".to_string()),
+                Some(PathBuf::from("/export/2024")),
+            )
+        }.with_orientation(Orientation::Rotated90Clockwise);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.json");
+        ExportPlan::new(&[("Library", &assets[0], &op)], Path::new("/export")).unwrap().write(&path).unwrap();
+        let resolved = ExportPlan::read(&path)
+            .unwrap()
+            .resolve(&[("Library", library.path(), &assets)], Path::new("/export"))
+            .unwrap();
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].0.uuid, assets[0].uuid);
+        assert_eq!(resolved[0].1.get_output_path(), op.get_output_path());
+        assert_eq!(resolved[0].1.source_path, op.source_path);
+        assert_eq!(resolved[0].1.converted_from, op.converted_from);
+        assert_eq!(resolved[0].1.orientation, op.orientation);
+    }
+
+    #[test]
+    fn stores_output_folders_relative_to_the_output_directory() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let assets = load_assets(&library);
+        let op = jpeg_operation(library.path().join("originals/1.jpeg"), PathBuf::from("/export/2024"));
+
+        let plan = ExportPlan::new(&[("Library", &assets[0], &op)], Path::new("/export")).unwrap();
+
+        assert_eq!(plan.operations[0].output_folder, Some(PathBuf::from("2024")));
+        assert!(ExportPlan::new(&[("Library", &assets[0], &op)], Path::new("/other")).is_err());
+    }
+
+    #[test]
+    fn rejects_edited_outputs_outside_the_output_directory() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let assets = load_assets(&library);
+        let op = jpeg_operation(library.path().join("originals/1.jpeg"), PathBuf::from("/export/2024"));
+
+        assert!(resolve_edited(&library, &assets, &op, |_| {}).is_ok());
+        assert!(resolve_edited(&library, &assets, &op, |planned| planned.output_folder = Some(PathBuf::from("/etc"))).is_err());
+        assert!(resolve_edited(&library, &assets, &op, |planned| planned.output_folder = Some(PathBuf::from("../etc"))).is_err());
+        assert!(resolve_edited(&library, &assets, &op, |planned| planned.output_filename = "../../etc/passwd".to_string()).is_err());
+    }
+
+    #[test]
+    fn rejects_edited_outputs_inside_a_library() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let assets = load_assets(&library);
+        let op = jpeg_operation(library.path().join("originals/1.jpeg"), library.path().join("export"));
+
+        let plan = ExportPlan::new(&[("Library", &assets[0], &op)], library.path()).unwrap();
+
+        assert!(plan.resolve(&[("Library", library.path(), &assets)], library.path()).is_err());
+    }
+
+    #[test]
+    fn rejects_edited_sources_outside_the_library() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let assets = load_assets(&library);
+        let op = jpeg_operation(library.path().join("originals/1.jpeg"), PathBuf::from("/export"));
+
+        assert!(resolve_edited(&library, &assets, &op, |planned| planned.source_path = PathBuf::from("/etc/passwd")).is_err());
+        assert!(
+            resolve_edited(&library, &assets, &op, |planned| {
+                planned.source_path = library.path().join("../../etc/passwd")
+            }).is_err()
+        );
+    }
+
+    #[test]
+    fn accepts_referenced_originals_outside_the_library() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).referenced_at("Photos", "2024/IMG_0001.jpeg"));
+        let assets = load_assets(&library);
+        let referenced_path = assets[0].referenced_path.clone().unwrap();
+        let op = jpeg_operation(referenced_path, PathBuf::from("/export"));
+
+        assert!(resolve_edited(&library, &assets, &op, |_| {}).is_ok());
+    }
+
+    #[test]
+    fn rejects_assets_not_exportable_anymore() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let assets = load_assets(&library);
+        let op = jpeg_operation(library.path().join("originals/1.jpeg"), PathBuf::from("/export"));
+
+        let plan = ExportPlan::new(&[("Library", &assets[0], &op)], Path::new("/export")).unwrap();
+
+        assert!(plan.resolve(&[("Other", library.path(), &assets)], Path::new("/export")).is_err());
+        assert!(plan.resolve(&[("Library", library.path(), &[])], Path::new("/export")).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,125 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::Local;
+use serde::Serialize;
+
+use crate::export::copying::CopyOperation;
+use crate::model::asset::ExportAsset;
+
+/// What happened to a single planned file during an export.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunLogOutcome {
+    Exported,
+    Failed,
+    SkippedEmptySource,
+    SkippedDuplicate,
+    /// Copied by a previous, interrupted export that has been resumed
+    AlreadyExported,
+}
+
+#[derive(Serialize)]
+struct RunLogEntry<'a> {
+    timestamp: String,
+    uuid: &'a str,
+    source: String,
+    destination: String,
+    outcome: RunLogOutcome,
+    duration_ms: u128,
+    bytes: u64,
+    error: Option<&'a str>,
+}
+
+/// Appends one JSON line per processed file to a log file, so that failed or slow assets can be
+/// analyzed after the export. Entries of multiple runs are appended to the same file.
+pub struct RunLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl RunLog {
+
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Unable to open '{}': {}", path.to_string_lossy(), e))?;
+
+        Ok(RunLog { path, file })
+    }
+
+    pub fn record(
+        &self,
+        asset: &ExportAsset,
+        copy_operation: &CopyOperation,
+        outcome: RunLogOutcome,
+        duration: Duration,
+        bytes: u64,
+        error: Option<&str>
+    ) -> Result<(), String> {
+        let entry = RunLogEntry {
+            timestamp: Local::now().to_rfc3339(),
+            uuid: &asset.uuid,
+            source: copy_operation.source_path.to_string_lossy().to_string(),
+            destination: copy_operation.get_output_path().to_string_lossy().to_string(),
+            outcome,
+            duration_ms: duration.as_millis(),
+            bytes,
+            error,
+        };
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| format!("Unable to serialize run log entry: {}", e))?;
+
+        writeln!(&self.file, "{}", line)
+            .map_err(|e| format!("Unable to write '{}': {}", self.path.to_string_lossy(), e))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::db::repo::asset::{AlbumFilter, AssetRepository, HiddenAssetsFilter};
+    use crate::export::copying::{CopyOperationFactory, OriginalsCopyOperationFactory};
+    use crate::test_util::library::{load_exportable, SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn appends_one_line_per_file() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1));
+        let assets = load_exportable(
+            &AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+        );
+        let op = OriginalsCopyOperationFactory::new().build(&assets[0]).unwrap().remove(0);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+
+        let log = RunLog::open(path.clone()).unwrap();
+        log.record(&assets[0], &op, RunLogOutcome::Exported, Duration::from_millis(12), 42, None).unwrap();
+        let log = RunLog::open(path.clone()).unwrap();
+        log.record(&assets[0], &op, RunLogOutcome::Failed, Duration::ZERO, 0, Some("disk full")).unwrap();
+
+        let entries: Vec<Value> = read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["uuid"], "ASSET-0001");
+        assert_eq!(entries[0]["destination"], "ASSET-0001.jpeg");
+        assert_eq!(entries[0]["outcome"], "exported");
+        assert_eq!(entries[0]["duration_ms"], 12);
+        assert_eq!(entries[0]["bytes"], 42);
+        assert_eq!(entries[1]["outcome"], "failed");
+        assert_eq!(entries[1]["error"], "disk full");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::metadata;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+
+use crate::util::interaction::UserInteraction;
+
+#[derive(Default)]
+struct FolderStatistics {
+    files: u64,
+    bytes: u64,
+}
+
+#[derive(Default)]
+struct DeviceStatistics {
+    bytes: u64,
+    /// Time spent copying files to the device
+    io_time: Duration,
+}
+
+/// Number of exported files and bytes per top-level folder of the output directory, and the copy
+/// throughput per destination device.
+pub struct ExportStatistics {
+    output_dir: PathBuf,
+    folders: BTreeMap<String, FolderStatistics>,
+    /// Statistics per device, keyed by the directory the device is mounted at
+    devices: BTreeMap<PathBuf, DeviceStatistics>,
+    mount_points: HashMap<u64, PathBuf>,
+    started: Instant,
+}
+
+impl ExportStatistics {
+
+    pub fn new(output_dir: PathBuf) -> Self {
+        ExportStatistics {
+            output_dir,
+            folders: BTreeMap::new(),
+            devices: BTreeMap::new(),
+            mount_points: HashMap::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Records the time it took to copy a file, attributed to the device the file has been
+    /// written to. Files that do not exist, e.g. in a dry run, are not recorded.
+    pub fn record_copy(&mut self, output_path: &Path, bytes: u64, io_time: Duration) {
+        let Ok(device) = metadata(output_path).map(|m| m.dev()) else {
+            return;
+        };
+
+        let mount_point = self.mount_points
+            .entry(device)
+            .or_insert_with(|| find_mount_point(output_path, device))
+            .clone();
+
+        let statistics = self.devices.entry(mount_point).or_default();
+        statistics.bytes += bytes;
+        statistics.io_time += io_time;
+    }
+
+    /// Records an exported file. Files placed directly in the output directory are recorded
+    /// under `.`.
+    pub fn record(&mut self, output_path: &Path, bytes: u64) {
+        let relative_path = output_path
+            .strip_prefix(&self.output_dir)
+            .unwrap_or(output_path);
+
+        let mut components = relative_path.components();
+        let folder = match (components.next(), components.next()) {
+            (Some(Component::Normal(folder)), Some(_)) => folder.to_string_lossy().to_string(),
+            _ => String::from("."),
+        };
+
+        let statistics = self.folders.entry(folder).or_default();
+        statistics.files += 1;
+        statistics.bytes += bytes;
+    }
+
+    /// Prints the statistics per folder, unless all files have been exported to the output
+    /// directory itself, followed by the copy throughput.
+    pub fn print(&self, interaction: &dyn UserInteraction) {
+        self.print_folders(interaction);
+        self.print_throughput(interaction);
+    }
+
+    fn print_folders(&self, interaction: &dyn UserInteraction) {
+        if self.folders.keys().all(|folder| folder == ".") {
+            return;
+        }
+
+        let width = self.folders.keys().map(|f| f.chars().count()).max().unwrap_or(0);
+
+        interaction.info(&format!("\n{}", "Exported files per folder:".blue()));
+        for (folder, statistics) in &self.folders {
+            interaction.info(&format!(
+                "  {:width$}  {:>7} files  {:>10}",
+                folder,
+                statistics.files,
+                format_bytes(statistics.bytes),
+                width = width
+            ));
+        }
+    }
+
+    /// Prints the average throughput per device and how much of the export has been spent
+    /// waiting for files to be copied, e.g. to judge whether a slow disk limits the export.
+    fn print_throughput(&self, interaction: &dyn UserInteraction) {
+        if self.devices.is_empty() {
+            return;
+        }
+
+        let labels: Vec<String> = self.devices.keys().map(|p| p.to_string_lossy().to_string()).collect();
+        let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+        interaction.info(&format!("\n{}", "Copy throughput per device:".blue()));
+        for (label, statistics) in labels.iter().zip(self.devices.values()) {
+            interaction.info(&format!(
+                "  {:width$}  {:>10} in {:>7.1}s  {:>10}/s",
+                label,
+                format_bytes(statistics.bytes),
+                statistics.io_time.as_secs_f64(),
+                format_bytes(throughput(statistics.bytes, statistics.io_time)),
+                width = width
+            ));
+        }
+
+        let io_time: Duration = self.devices.values().map(|d| d.io_time).sum();
+        let total_time = self.started.elapsed();
+        interaction.info(&format!(
+            "  Blocked on IO for {:.1}s of {:.1}s ({:.0}%)",
+            io_time.as_secs_f64(),
+            total_time.as_secs_f64(),
+            100.0 * io_time.as_secs_f64() / total_time.as_secs_f64().max(f64::EPSILON)
+        ));
+    }
+}
+
+/// Returns the outermost ancestor of the given path on the same device, i.e. the directory the
+/// device is mounted at.
+fn find_mount_point(path: &Path, device: u64) -> PathBuf {
+    path.ancestors()
+        .skip(1)
+        .take_while(|ancestor| metadata(ancestor).is_ok_and(|m| m.dev() == device))
+        .last()
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+/// Returns the average number of bytes copied per second.
+fn throughput(bytes: u64, io_time: Duration) -> u64 {
+    if io_time.is_zero() {
+        return bytes;
+    }
+    (bytes as f64 / io_time.as_secs_f64()) as u64
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregates_by_top_level_folder() {
+        let mut statistics = ExportStatistics::new(PathBuf::from("/out"));
+        statistics.record(Path::new("/out/2023/05/a.jpeg"), 100);
+        statistics.record(Path::new("/out/2023/06/b.jpeg"), 200);
+        statistics.record(Path::new("/out/2024/01/c.jpeg"), 300);
+        statistics.record(Path::new("/out/d.jpeg"), 400);
+
+        let folders: Vec<(&str, u64, u64)> = statistics.folders
+            .iter()
+            .map(|(folder, s)| (folder.as_str(), s.files, s.bytes))
+            .collect();
+
+        assert_eq!(folders, [(".", 1, 400), ("2023", 2, 300), ("2024", 1, 300)]);
+    }
+
+    #[test]
+    fn aggregates_copies_by_device() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("2023")).unwrap();
+        std::fs::write(dir.path().join("2023").join("a.jpeg"), "a").unwrap();
+        std::fs::write(dir.path().join("b.jpeg"), "b").unwrap();
+
+        let mut statistics = ExportStatistics::new(dir.path().to_path_buf());
+        statistics.record_copy(&dir.path().join("2023").join("a.jpeg"), 100, Duration::from_secs(1));
+        statistics.record_copy(&dir.path().join("b.jpeg"), 300, Duration::from_secs(3));
+        statistics.record_copy(&dir.path().join("missing.jpeg"), 500, Duration::from_secs(5));
+
+        let devices: Vec<(u64, Duration)> = statistics.devices
+            .values()
+            .map(|d| (d.bytes, d.io_time))
+            .collect();
+
+        assert_eq!(devices, [(400, Duration::from_secs(4))]);
+        assert!(dir.path().starts_with(statistics.devices.keys().next().unwrap()));
+    }
+
+    #[test]
+    fn computes_throughput() {
+        assert_eq!(throughput(3000, Duration::from_millis(1500)), 2000);
+        assert_eq!(throughput(3000, Duration::ZERO), 3000);
+    }
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+}
@@ -1,15 +1,26 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::PathBuf;
+use std::rc::Rc;
 
-use chrono::NaiveDateTime;
+use chrono::{Locale, NaiveDateTime};
+use clap::ValueEnum;
 use derive_new::new;
 
 use crate::db::model::album::AlbumDto;
+use crate::foundation::cocoa::parse_cocoa_timestamp;
+use crate::model::album::AlbumReference;
 use crate::model::asset::ExportAsset;
+use crate::util::paths::{to_folder_name, PATH_SEPARATORS};
 
 pub trait OutputStrategy {
 
     fn get_relative_output_dir(&self, asset: &ExportAsset) -> Result<PathBuf, String>;
+
+    /// Returns whether the output directory of the given asset is the folder of its album.
+    fn is_album_folder(&self, _asset: &ExportAsset) -> bool {
+        false
+    }
 }
 
 
@@ -24,9 +35,114 @@ impl OutputStrategy for PlainOutputStrategy {
 }
 
 
+pub const UNKNOWN_CAMERA_FOLDER_NAME: &str = "Unknown camera";
+
+/// Groups assets by the camera they have been taken with, e.g. `Apple iPhone 15 Pro/`. Assets
+/// without camera information, e.g. screenshots, are placed in an `Unknown camera` folder.
+#[derive(new)]
+pub struct CameraOutputStrategy;
+
+impl OutputStrategy for CameraOutputStrategy {
+    fn get_relative_output_dir(&self, asset: &ExportAsset) -> Result<PathBuf, String> {
+        let folder = asset
+            .get_camera_name()
+            .map(|name| to_folder_name(&name))
+            .unwrap_or(UNKNOWN_CAMERA_FOLDER_NAME.to_string());
+        Ok(PathBuf::from(folder))
+    }
+}
+
+
+/// Template for the names of exported album folders, e.g. `{year} – {name}`.
+///
+/// Supported placeholders are `{name}`, `{year}`, `{month}` and `{day}`, the latter referring to
+/// the start date of the album. Albums without start date, e.g. empty ones, are exported to a
+/// folder with their plain name instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlbumFolderTemplate {
+    template: String,
+}
+
+const ALBUM_FOLDER_PLACEHOLDERS: [&str; 4] = ["name", "year", "month", "day"];
+
+impl AlbumFolderTemplate {
+
+    fn render(&self, name: &str, start_date: Option<NaiveDateTime>) -> String {
+        match start_date {
+            Some(date) => self.template
+                .replace("{year}", &date.format("%Y").to_string())
+                .replace("{month}", &date.format("%m").to_string())
+                .replace("{day}", &date.format("%d").to_string())
+                .replace("{name}", name),
+            None => name.to_string(),
+        }
+    }
+}
+
+/// Argument parser for album folder templates, rejecting unknown placeholders.
+///
+/// Both `/` and `\\` separate nested folders, e.g. `{year}\\{name}` is the same as `{year}/{name}`.
+pub fn parse_album_folder_template(template: &str) -> Result<AlbumFolderTemplate, String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or(format!("Unclosed placeholder in '{}'", template))?;
+        let placeholder = &rest[start + 1..start + end];
+        if !ALBUM_FOLDER_PLACEHOLDERS.contains(&placeholder) {
+            return Err(
+                format!(
+                    "Unknown placeholder '{{{}}}', supported are {}",
+                    placeholder,
+                    ALBUM_FOLDER_PLACEHOLDERS.map(|p| format!("{{{}}}", p)).join(", ")
+                )
+            );
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    Ok(AlbumFolderTemplate { template: template.replace(PATH_SEPARATORS, "/") })
+}
+
+
+/// Assets whose album, or one of its parent folders, is unknown to an [AlbumOutputStrategy], e.g.
+/// as it has been deleted or trashed.
+#[derive(Default)]
+pub struct UnknownAlbumRegistry {
+    occurrences: RefCell<BTreeSet<(String, i32)>>,
+}
+
+impl UnknownAlbumRegistry {
+
+    /// Returns the UUIDs of the assets along with the ids of the unknown albums.
+    pub fn get_all(&self) -> Vec<(String, i32)> {
+        self.occurrences.borrow().iter().cloned().collect()
+    }
+}
+
+/// Albums renamed by [disambiguate_album_names] as they share their name with another album.
+#[derive(Default)]
+pub struct RenamedAlbumRegistry {
+    descriptions: RefCell<Vec<String>>,
+}
+
+impl RenamedAlbumRegistry {
+
+    pub fn record(&self, descriptions: Vec<String>) {
+        self.descriptions.borrow_mut().extend(descriptions);
+    }
+
+    /// Returns a description of each renamed album.
+    pub fn get_all(&self) -> Vec<String> {
+        self.descriptions.borrow().clone()
+    }
+}
+
 pub struct AlbumOutputStrategy {
     flatten: bool,
     albums_by_id: HashMap<i32, AlbumDto>,
+    folder_template: Option<AlbumFolderTemplate>,
+    unknown_albums: Option<Rc<UnknownAlbumRegistry>>,
 }
 
 impl AlbumOutputStrategy {
@@ -39,26 +155,50 @@ impl AlbumOutputStrategy {
 
         Self {
             flatten,
-            albums_by_id
+            albums_by_id,
+            folder_template: None,
+            unknown_albums: None
         }
     }
 
-    fn get_path_recursively(&self, album_id: i32) -> Result<PathBuf, String> {
+    /// Exports assets whose album is unknown as if they were in no album, recording them in the
+    /// given registry, instead of failing.
+    pub fn with_unknown_album_fallback(self, registry: Rc<UnknownAlbumRegistry>) -> Self {
+        AlbumOutputStrategy { unknown_albums: Some(registry), ..self }
+    }
+
+    /// Names album folders after the given template instead of just the album name.
+    pub fn with_folder_template(self, template: AlbumFolderTemplate) -> Self {
+        AlbumOutputStrategy { folder_template: Some(template), ..self }
+    }
+
+    fn get_folder_name(&self, name: &str, start_date: Option<NaiveDateTime>) -> String {
+        let name = to_folder_name(name);
+        match &self.folder_template {
+            Some(template) => template.render(&name, start_date),
+            None => name,
+        }
+    }
+
+    /// Returns the path of the album, or the id of the first album or folder on the way to the root
+    /// that is unknown.
+    fn get_path_recursively(&self, album_id: i32) -> Result<PathBuf, i32> {
         let album = self.albums_by_id
             .get(&album_id)
-            .ok_or(format!("Album with ID {} not found", album_id))?;
+            .ok_or(album_id)?;
 
         match album.parent_id {
             None => {
                 let mut buffer = PathBuf::new();
                 if let Some(name) = &album.name {
-                    buffer.push(name);
+                    buffer.push(to_folder_name(name));
                 }
                 Ok(buffer)
             },
             Some(parent_id) => {
                 let path = self.get_path_recursively(parent_id)?;
-                Ok(path.join(album.name.clone().unwrap_or(String::from("unnamed"))))
+                let start_date = album.start_date.and_then(|d| parse_cocoa_timestamp(d).ok());
+                Ok(path.join(self.get_folder_name(album.name.as_deref().unwrap_or("unnamed"), start_date)))
             }
         }
     }
@@ -68,51 +208,288 @@ impl OutputStrategy for AlbumOutputStrategy {
 
     fn get_relative_output_dir(&self, asset: &ExportAsset) -> Result<PathBuf, String> {
         let path = match &asset.album {
-            None => PathBuf::new(),
+            None => Ok(PathBuf::new()),
             Some(a) => {
                 if self.flatten {
-                    PathBuf::from(a.name.clone().unwrap_or(String::from("unnamed")))
+                    // The albums given to the strategy may have been renamed
+                    match self.albums_by_id.get(&a.id) {
+                        None if self.unknown_albums.is_some() => Err(a.id),
+                        album => {
+                            let name = album.and_then(|album| album.name.clone()).or(a.name.clone());
+                            Ok(PathBuf::from(self.get_folder_name(name.as_deref().unwrap_or("unnamed"), a.start_date)))
+                        }
+                    }
                 } else {
-                    self.get_path_recursively(a.id)?
+                    self.get_path_recursively(a.id)
                 }
             }
         };
-        Ok(path)
+
+        match (path, &self.unknown_albums) {
+            (Ok(path), _) => Ok(path),
+            (Err(album_id), Some(registry)) => {
+                registry.occurrences.borrow_mut().insert((asset.uuid.clone(), album_id));
+                Ok(PathBuf::new())
+            },
+            (Err(album_id), None) => Err(format!("Album with ID {} not found", album_id)),
+        }
+    }
+
+    fn is_album_folder(&self, asset: &ExportAsset) -> bool {
+        // Assets whose album is unknown are exported to the export root instead
+        asset.album.is_some() && self.get_relative_output_dir(asset).is_ok_and(|path| path.components().next().is_some())
+    }
+}
+
+
+/// Folder names to export albums to instead of their names, keyed by the id or UUID of the album,
+/// e.g. for albums with emoji-only or very long names.
+#[derive(Clone, Debug, Default)]
+pub struct AlbumNameOverrides {
+    names: Vec<(AlbumReference, String)>,
+}
+
+impl AlbumNameOverrides {
+
+    /// Renames the albums that have an override. Returns the albums along with the references of
+    /// overrides not matching any album.
+    pub fn apply(&self, albums: Vec<AlbumDto>) -> (Vec<AlbumDto>, Vec<String>) {
+        let matches = |reference: &AlbumReference, album: &AlbumDto| match reference {
+            AlbumReference::Id(id) => album.id == *id,
+            AlbumReference::Uuid(uuid) => album.uuid.as_ref() == Some(uuid),
+        };
+
+        let unmatched = self.names
+            .iter()
+            .filter(|(reference, _)| !albums.iter().any(|album| matches(reference, album)))
+            .map(|(reference, _)| match reference {
+                AlbumReference::Id(id) => id.to_string(),
+                AlbumReference::Uuid(uuid) => uuid.clone(),
+            })
+            .collect();
+
+        let albums = albums
+            .into_iter()
+            .map(|album| {
+                match self.names.iter().find(|(reference, _)| matches(reference, &album)) {
+                    Some((_, name)) => AlbumDto { name: Some(name.clone()), ..album },
+                    None => album,
+                }
+            })
+            .collect();
+
+        (albums, unmatched)
+    }
+}
+
+/// Argument parser reading album name overrides from a TOML file mapping album ids or UUIDs to
+/// folder names, e.g. `42 = "Trips"`.
+pub fn parse_album_name_overrides(path: &str) -> Result<AlbumNameOverrides, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read '{}': {}", path, e))?;
+    let table: toml::Table = content
+        .parse()
+        .map_err(|e| format!("Unable to parse '{}': {}", path, e))?;
+
+    let names = table
+        .into_iter()
+        .map(|(key, value)| {
+            let name = value
+                .as_str()
+                .filter(|name| !name.trim().is_empty() && !name.contains(PATH_SEPARATORS))
+                .ok_or(format!("Invalid folder name for album '{}', expected a non-empty string without '/' or '\\'", key))?;
+            Ok((key.parse::<AlbumReference>()?, name.to_string()))
+        })
+        .collect::<Result<Vec<(AlbumReference, String)>, String>>()?;
+
+    Ok(AlbumNameOverrides { names })
+}
+
+
+/// Ways of telling apart albums with the same name that would be exported to the same folder
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum AlbumNameDisambiguation {
+    /// Append the album id, e.g. `Misc (42)`
+    Id,
+    /// Append the start date of the album, e.g. `Misc (2024-06-14)`, or the id if it has none
+    Date,
+}
+
+type AlbumNameKey = (Option<i32>, String);
+
+/// Returns the key of albums exported to the same folder, i.e. their parent folder, or none if the
+/// album structure is flattened, along with their name ignoring case.
+fn album_name_key(album: &AlbumDto, flatten: bool) -> AlbumNameKey {
+    let parent_id = if flatten { None } else { album.parent_id };
+    (parent_id, album.name.clone().unwrap_or_default().to_lowercase())
+}
+
+fn count_album_names(albums: &[AlbumDto], flatten: bool) -> HashMap<AlbumNameKey, usize> {
+    let mut counts = HashMap::new();
+    for album in albums.iter().filter(|a| a.parent_id.is_some()) {
+        *counts.entry(album_name_key(album, flatten)).or_default() += 1;
+    }
+    counts
+}
+
+/// Returns the names shared by multiple albums in the same folder, or by any albums if the album
+/// structure is flattened, whose assets are therefore exported to the same folder.
+pub fn find_ambiguous_album_names(albums: &[AlbumDto], flatten: bool) -> Vec<String> {
+    let counts = count_album_names(albums, flatten);
+    let names: BTreeSet<String> = albums
+        .iter()
+        .filter(|album| counts.get(&album_name_key(album, flatten)).copied().unwrap_or(0) > 1)
+        .map(|album| album.name.clone().unwrap_or(String::from("unnamed")))
+        .collect();
+
+    names.into_iter().collect()
+}
+
+/// Renames albums sharing their name with another album in the same folder, or with any other
+/// album if the album structure is flattened, so that their assets are exported to different
+/// folders. Returns the albums along with a description of each renamed album.
+pub fn disambiguate_album_names(
+    albums: Vec<AlbumDto>,
+    flatten: bool,
+    mode: AlbumNameDisambiguation
+) -> (Vec<AlbumDto>, Vec<String>) {
+    let counts = count_album_names(&albums, flatten);
+
+    let mut renamed = Vec::new();
+    let albums = albums
+        .into_iter()
+        .map(|album| {
+            if album.parent_id.is_none() || counts.get(&album_name_key(&album, flatten)).copied().unwrap_or(0) < 2 {
+                return album;
+            }
+
+            let name = album.name.clone().unwrap_or(String::from("unnamed"));
+            let date = album.start_date
+                .and_then(|d| parse_cocoa_timestamp(d).ok())
+                .map(|d| d.format("%Y-%m-%d").to_string());
+            let suffix = match (mode, date) {
+                (AlbumNameDisambiguation::Date, Some(date)) => date,
+                _ => album.id.to_string(),
+            };
+            let new_name = format!("{} ({})", name, suffix);
+
+            renamed.push(format!("'{}' ({}) exported as '{}'", name, album.id, new_name));
+            AlbumDto { name: Some(new_name), ..album }
+        })
+        .collect();
+
+    (albums, renamed)
+}
+
+/// Exports the assets of the given albums as if they were in no album, e.g. as the albums are too
+/// small or too large to get a folder of their own. Assets also part of another album are only
+/// exported to that album, and assets of several of the given albums are only exported once.
+pub fn detach_from_albums(assets: Vec<ExportAsset>, album_ids: &HashSet<i32>) -> Vec<ExportAsset> {
+    if album_ids.is_empty() {
+        return assets;
     }
+
+    let is_detached = |asset: &ExportAsset| asset.album.as_ref().is_some_and(|album| album_ids.contains(&album.id));
+    let kept: HashSet<i32> = assets
+        .iter()
+        .filter(|asset| !is_detached(asset))
+        .map(|asset| asset.id)
+        .collect();
+
+    let mut detached = HashSet::new();
+    assets
+        .into_iter()
+        .filter_map(|asset| {
+            if !is_detached(&asset) {
+                return Some(asset);
+            }
+            if kept.contains(&asset.id) || !detached.insert(asset.id) {
+                return None;
+            }
+            Some(ExportAsset { album: None, album_position: None, ..asset })
+        })
+        .collect()
 }
 
 
 type DateSelectorFunc = Box<dyn Fn(&ExportAsset) -> NaiveDateTime>;
 
-pub struct YearMonthOutputStrategy {
-    datetime_selector: DateSelectorFunc
+/// Depth of the date folders created by [DateOutputStrategy]
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum DateGranularity {
+    /// `<year>/`
+    Year,
+    /// `<year>/<month>/`
+    Month,
+    /// `<year>/<month>/<day>/`
+    Day,
 }
 
-impl YearMonthOutputStrategy {
+impl DateGranularity {
+    fn format(&self, month_names: bool) -> &'static str {
+        match (self, month_names) {
+            (DateGranularity::Year, _) => "%Y/",
+            (DateGranularity::Month, false) => "%Y/%m/",
+            (DateGranularity::Month, true) => "%Y/%m %B/",
+            (DateGranularity::Day, false) => "%Y/%m/%d/",
+            (DateGranularity::Day, true) => "%Y/%m %B/%d/",
+        }
+    }
+}
+
+/// Argument parser for locales used to name month folders, e.g. `de-DE` or `fr_FR`.
+pub fn parse_locale(locale: &str) -> Result<Locale, String> {
+    Locale::try_from(locale.replace('-', "_").as_str())
+        .map_err(|_| format!("'{}' is not a supported locale", locale))
+}
+
+pub struct DateOutputStrategy {
+    granularity: DateGranularity,
+    datetime_selector: DateSelectorFunc,
+    /// Locale to append the month name in, e.g. `2024/05 Mai`
+    locale: Option<Locale>,
+}
+
+impl DateOutputStrategy {
 
-    pub fn asset_date_based() -> YearMonthOutputStrategy {
-        YearMonthOutputStrategy {
-            datetime_selector: Box::new(|asset| asset.datetime)
+    pub fn asset_date_based(granularity: DateGranularity) -> DateOutputStrategy {
+        DateOutputStrategy {
+            granularity,
+            datetime_selector: Box::new(|asset| asset.datetime),
+            locale: None
         }
     }
 
-    pub fn album_date_based() -> YearMonthOutputStrategy {
-        YearMonthOutputStrategy {
+    pub fn album_date_based(granularity: DateGranularity) -> DateOutputStrategy {
+        DateOutputStrategy {
+            granularity,
             datetime_selector: Box::new(|asset| {
                 match asset.album.clone() {
                     None => asset.datetime,
                     Some(album) => album.start_date.unwrap_or(asset.datetime)
                 }
-            })
+            }),
+            locale: None
         }
     }
+
+    /// Appends the name of the month in the given locale to month folders.
+    pub fn with_month_names(self, locale: Locale) -> Self {
+        DateOutputStrategy { locale: Some(locale), ..self }
+    }
 }
 
-impl OutputStrategy for YearMonthOutputStrategy {
+impl OutputStrategy for DateOutputStrategy {
 
     fn get_relative_output_dir(&self, asset: &ExportAsset) -> Result<PathBuf, String> {
         let datetime = (self.datetime_selector)(asset);
-        let formatted = format!("{}", datetime.format("%Y/%m/"));
+        let formatted = match self.locale {
+            Some(locale) => datetime
+                .date()
+                .format_localized(self.granularity.format(true), locale)
+                .to_string(),
+            None => datetime.format(self.granularity.format(false)).to_string(),
+        };
         Ok(PathBuf::from(formatted))
     }
 }
@@ -132,6 +509,10 @@ impl OutputStrategy for NestingOutputStrategyDecorator {
                 Ok(path.join(dir))
             })
     }
+
+    fn is_album_folder(&self, asset: &ExportAsset) -> bool {
+        self.strategies.last().is_some_and(|strategy| strategy.is_album_folder(asset))
+    }
 }
 
 
@@ -151,4 +532,267 @@ impl OutputStrategy for HiddenAssetHandlingOutputStrategyDecorator {
 
         Ok(path)
     }
+
+    fn is_album_folder(&self, asset: &ExportAsset) -> bool {
+        self.strategy.is_album_folder(asset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repo::album::AlbumRepository;
+    use crate::db::repo::asset::{AlbumFilter, AssetRepository, HiddenAssetsFilter};
+    use crate::test_util::library::{load_exportable, SyntheticLibrary, TestAlbum, TestAsset};
+
+    // Dates are picked mid-month so that the local UTC offset does not change the month
+    /// Noon UTC, so that the local UTC offset does not change the day
+    const MARCH_2023: f32 = 699_969_600.0;
+    const JUNE_2024: f32 = 739_972_800.0;
+
+    fn library() -> SyntheticLibrary {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips"));
+        library.add_album(&TestAlbum::album(3, "Summer").in_folder(2).starting_at(JUNE_2024));
+        library.add_asset(&TestAsset::jpeg(1).taken_at(MARCH_2023).in_albums(&[3]));
+        library.add_asset(&TestAsset::jpeg(2).taken_at(MARCH_2023));
+        library
+    }
+
+    fn output_dirs(library: &SyntheticLibrary, strategy: &dyn OutputStrategy) -> Vec<String> {
+        let repo = AssetRepository::new(
+            library.db_path(),
+            HiddenAssetsFilter::Include,
+            AlbumFilter::None,
+            vec![],
+            None
+        );
+        let mut assets = load_exportable(&repo);
+        assets.sort_by_key(|a| a.id);
+
+        assets
+            .iter()
+            .map(|a| strategy.get_relative_output_dir(a).unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    fn albums(library: &SyntheticLibrary) -> Vec<AlbumDto> {
+        AlbumRepository::new(library.db_path(), None).get_all().unwrap()
+    }
+
+    #[test]
+    fn detaches_assets_only_from_the_given_albums() {
+        let library = library();
+        library.add_album(&TestAlbum::album(4, "Small"));
+        library.add_album(&TestAlbum::album(5, "Tiny"));
+        library.add_asset(&TestAsset::jpeg(3).in_albums(&[3, 4]));
+        library.add_asset(&TestAsset::jpeg(4).in_albums(&[4, 5]));
+        let repo = AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None);
+
+        let mut assets: Vec<(i32, Option<i32>)> = detach_from_albums(load_exportable(&repo), &HashSet::from([4, 5]))
+            .iter()
+            .map(|asset| (asset.id, asset.album.as_ref().map(|album| album.id)))
+            .collect();
+        assets.sort();
+
+        assert_eq!(assets, [(1, Some(3)), (2, None), (3, Some(3)), (4, None)]);
+    }
+
+    #[test]
+    fn nests_albums_in_folders() {
+        let library = library();
+        let strategy = AlbumOutputStrategy::new(false, albums(&library));
+
+        assert_eq!(output_dirs(&library, &strategy), ["Trips/Summer", ""]);
+    }
+
+    #[test]
+    fn flattens_albums() {
+        let library = library();
+        let strategy = AlbumOutputStrategy::new(true, albums(&library));
+
+        assert_eq!(output_dirs(&library, &strategy), ["Summer", ""]);
+    }
+
+    #[test]
+    fn falls_back_to_date_for_unknown_albums() {
+        let library = library();
+        let without_folder = || albums(&library).into_iter().filter(|a| a.id != 2).collect::<Vec<_>>();
+        let registry = Rc::new(UnknownAlbumRegistry::default());
+        let strategy = NestingOutputStrategyDecorator::new(
+            vec![
+                Box::new(DateOutputStrategy::album_date_based(DateGranularity::Month)),
+                Box::new(AlbumOutputStrategy::new(false, without_folder()).with_unknown_album_fallback(registry.clone())),
+            ]
+        );
+        let assets = load_exportable(
+            &AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+        );
+        let albumed = assets.iter().find(|a| a.album.is_some()).unwrap();
+
+        assert_eq!(output_dirs(&library, &strategy), ["2024/06/", "2023/03/"]);
+        assert_eq!(registry.get_all(), [("ASSET-0001".to_string(), 2)]);
+        assert!(AlbumOutputStrategy::new(false, without_folder()).get_relative_output_dir(albumed).is_err());
+    }
+
+    #[test]
+    fn names_album_folders_after_template() {
+        let library = library();
+        let template = parse_album_folder_template("{year} – {name}").unwrap();
+        let nested = AlbumOutputStrategy::new(false, albums(&library)).with_folder_template(template.clone());
+        let flat = AlbumOutputStrategy::new(true, albums(&library)).with_folder_template(template);
+
+        assert_eq!(output_dirs(&library, &nested), ["Trips/2024 – Summer", ""]);
+        assert_eq!(output_dirs(&library, &flat), ["2024 – Summer", ""]);
+    }
+
+    #[test]
+    fn normalizes_separators_in_album_names_and_templates() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::folder(2, "Trips\\Europe"));
+        library.add_album(&TestAlbum::album(3, "Rome/Florence").in_folder(2).starting_at(JUNE_2024));
+        library.add_asset(&TestAsset::jpeg(1).in_albums(&[3]));
+
+        for template in ["{year}/{name}", "{year}\\{name}"] {
+            let template = parse_album_folder_template(template).unwrap();
+            let nested = AlbumOutputStrategy::new(false, albums(&library)).with_folder_template(template.clone());
+            let flat = AlbumOutputStrategy::new(true, albums(&library)).with_folder_template(template);
+
+            assert_eq!(output_dirs(&library, &nested), ["Trips-Europe/2024/Rome-Florence"]);
+            assert_eq!(output_dirs(&library, &flat), ["2024/Rome-Florence"]);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_album_folder_placeholders() {
+        assert!(parse_album_folder_template("{year}-{month}-{day} {name}").is_ok());
+        assert!(parse_album_folder_template("{year} {title}").is_err());
+        assert!(parse_album_folder_template("{year").is_err());
+    }
+
+    #[test]
+    fn groups_by_asset_date() {
+        let library = library();
+        let strategy = DateOutputStrategy::asset_date_based(DateGranularity::Month);
+
+        assert_eq!(output_dirs(&library, &strategy), ["2023/03/", "2023/03/"]);
+    }
+
+    #[test]
+    fn groups_by_date_granularity() {
+        let library = library();
+        let year = DateOutputStrategy::asset_date_based(DateGranularity::Year);
+        let day = DateOutputStrategy::asset_date_based(DateGranularity::Day);
+
+        assert_eq!(output_dirs(&library, &year), ["2023/", "2023/"]);
+        assert!(output_dirs(&library, &day).iter().all(|dir| dir.starts_with("2023/03/") && dir.len() == 11));
+    }
+
+    #[test]
+    fn names_month_folders_in_locale() {
+        let library = library();
+        let month = DateOutputStrategy::asset_date_based(DateGranularity::Month)
+            .with_month_names(parse_locale("de-DE").unwrap());
+        let day = DateOutputStrategy::album_date_based(DateGranularity::Day)
+            .with_month_names(parse_locale("fr_FR").unwrap());
+
+        assert_eq!(output_dirs(&library, &month), ["2023/03 März/", "2023/03 März/"]);
+        assert!(output_dirs(&library, &day)[0].starts_with("2024/06 juin/"));
+        assert!(parse_locale("xx-YY").is_err());
+    }
+
+    #[test]
+    fn groups_by_camera() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).shot_with("Apple", "iPhone 15 Pro", None));
+        library.add_asset(&TestAsset::jpeg(2).shot_with("Canon", "Canon EOS R5", Some("RF24-105mm")));
+        library.add_asset(&TestAsset::jpeg(3).shot_with("FUJIFILM", "X-T5", None));
+        library.add_asset(&TestAsset::jpeg(4));
+
+        assert_eq!(
+            output_dirs(&library, &CameraOutputStrategy::new()),
+            ["Apple iPhone 15 Pro", "Canon EOS R5", "FUJIFILM X-T5", UNKNOWN_CAMERA_FOLDER_NAME]
+        );
+    }
+
+    #[test]
+    fn groups_by_album_date() {
+        let library = library();
+        let strategy = DateOutputStrategy::album_date_based(DateGranularity::Month);
+
+        assert_eq!(output_dirs(&library, &strategy), ["2024/06/", "2023/03/"]);
+    }
+
+    #[test]
+    fn overrides_album_names() {
+        let library = library();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("albums.toml");
+        std::fs::write(&path, "\"ALBUM-3\" = \"Beach\"\n2 = \"Travel\"\n99 = \"Unknown\"").unwrap();
+
+        let overrides = parse_album_name_overrides(&path.to_string_lossy()).unwrap();
+        let (renamed, unmatched) = overrides.apply(albums(&library));
+
+        assert_eq!(unmatched, ["99"]);
+        assert_eq!(
+            output_dirs(&library, &AlbumOutputStrategy::new(false, renamed.clone())),
+            ["Travel/Beach", ""]
+        );
+        assert_eq!(output_dirs(&library, &AlbumOutputStrategy::new(true, renamed)), ["Beach", ""]);
+
+        std::fs::write(&path, "2 = \"a/b\"").unwrap();
+        assert!(parse_album_name_overrides(&path.to_string_lossy()).is_err());
+        std::fs::write(&path, "2 = 'a\\b'").unwrap();
+        assert!(parse_album_name_overrides(&path.to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn disambiguates_sibling_albums_with_same_name() {
+        let library = library();
+        library.add_album(&TestAlbum::album(4, "Misc").in_folder(2));
+        library.add_album(&TestAlbum::album(5, "misc").in_folder(2));
+        library.add_album(&TestAlbum::album(6, "Misc"));
+
+        let (albums, renamed) = disambiguate_album_names(
+            albums(&library),
+            false,
+            AlbumNameDisambiguation::Id
+        );
+        let mut names: Vec<String> = albums.into_iter().filter_map(|a| a.name).collect();
+        names.sort();
+
+        assert_eq!(names, ["", "Misc", "Misc (4)", "Summer", "Trips", "misc (5)"]);
+        assert_eq!(renamed.len(), 2);
+    }
+
+    #[test]
+    fn disambiguates_flattened_albums_by_date() {
+        let library = library();
+        library.add_album(&TestAlbum::album(4, "Summer").starting_at(MARCH_2023));
+
+        let (albums, _) = disambiguate_album_names(
+            albums(&library),
+            true,
+            AlbumNameDisambiguation::Date
+        );
+        let mut names: Vec<String> = albums
+            .into_iter()
+            .filter_map(|a| a.name)
+            .filter(|name| name.starts_with("Summer"))
+            .collect();
+        names.sort();
+
+        assert_eq!(names, ["Summer (2023-03-08)", "Summer (2024-06-13)"]);
+    }
+
+    #[test]
+    fn finds_albums_sharing_their_name_with_a_sibling() {
+        let library = library();
+        library.add_album(&TestAlbum::album(4, "summer"));
+        library.add_album(&TestAlbum::album(5, "Winter").in_folder(2));
+        library.add_album(&TestAlbum::album(6, "Winter").in_folder(2));
+
+        assert_eq!(find_ambiguous_album_names(&albums(&library), false), ["Winter"]);
+        assert_eq!(find_ambiguous_album_names(&albums(&library), true), ["Summer", "Winter", "summer"]);
+    }
 }
\ No newline at end of file
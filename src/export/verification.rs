@@ -0,0 +1,266 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs::{metadata, read_dir};
+use std::path::{Path, PathBuf};
+
+use crate::export::copying::CopyOperation;
+use crate::export::journal::{FINGERPRINT_FILENAME, JOURNAL_FILENAME};
+use crate::export::metadata::album_order::ALBUM_ORDER_FILENAME;
+use crate::export::metadata::geojson::LOCATIONS_FILENAME;
+use crate::export::metadata::manifest::MANIFEST_FILENAME;
+use crate::export::placeholders::is_placeholder;
+use crate::model::asset::ExportAsset;
+use crate::util::checksum::sha256_file;
+
+/// Files written by the export itself which are not traceable to an asset of the library.
+const EXPORT_METADATA_FILENAMES: [&str; 5] = [
+    JOURNAL_FILENAME,
+    FINGERPRINT_FILENAME,
+    LOCATIONS_FILENAME,
+    MANIFEST_FILENAME,
+    ALBUM_ORDER_FILENAME,
+];
+
+/// Differences between the files an export is expected to contain and the files actually present
+/// in the output directory. All paths are relative to the output directory.
+#[derive(Default, Debug, PartialEq)]
+pub struct VerificationReport {
+    /// Files that are expected but not present
+    pub missing: Vec<PathBuf>,
+    /// Files that are present but not expected
+    pub extra: Vec<PathBuf>,
+    /// Files whose content differs from their source, along with a description of the difference
+    pub mismatched: Vec<(PathBuf, String)>,
+}
+
+impl VerificationReport {
+
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// Outcome of spot-checking a sample of the files of an export. All paths are relative to the
+/// output directory.
+#[derive(Default, Debug, PartialEq)]
+pub struct AuditReport {
+    pub passed: Vec<PathBuf>,
+    /// Files that are missing or differ from their source, along with a description of the failure
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl AuditReport {
+
+    pub fn is_passed(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Outcome of comparing a single exported file to its source.
+enum FileCheck {
+    Passed,
+    Missing,
+    Mismatched(String),
+}
+
+/// Compares the planned copy operations to the files in the output directory.
+///
+/// Files are compared by size, and optionally by their SHA-256 checksum. Files converted while
+/// exporting them and files whose source is not available, e.g. because it has been offloaded to
+/// iCloud, are only checked for existence.
+pub fn verify_export(
+    operations: &[(&ExportAsset, CopyOperation)],
+    output_dir: &Path,
+    compare_checksums: bool
+) -> Result<VerificationReport, String> {
+    let mut report = VerificationReport::default();
+    let mut expected = HashSet::new();
+
+    for (_, op) in operations {
+        let output_path = op.get_output_path();
+        // Assets of multiple albums are exported to the same file unless grouped by album
+        if !expected.insert(output_path.clone()) {
+            continue;
+        }
+
+        let relative_path = relative_to(&output_path, output_dir);
+        match check_file(op, compare_checksums)? {
+            FileCheck::Passed => {},
+            FileCheck::Missing => report.missing.push(relative_path),
+            FileCheck::Mismatched(difference) => report.mismatched.push((relative_path, difference)),
+        }
+    }
+
+    let mut present = BTreeSet::new();
+    if output_dir.exists() {
+        collect_files(output_dir, &mut present)?;
+    }
+    report.extra = present
+        .into_iter()
+        .filter(|path| !expected.contains(path) && !is_export_metadata(path))
+        .map(|path| relative_to(&path, output_dir))
+        .collect();
+
+    report.missing.sort();
+    report.mismatched.sort();
+
+    Ok(report)
+}
+
+/// Compares the exported files of the given copy operations to their source by existence, size
+/// and SHA-256 checksum, e.g. to spot-check a sample of a large export.
+///
+/// As with [verify_export], converted files and files whose source is not available are only
+/// checked for existence.
+pub fn audit_export(
+    operations: &[(&ExportAsset, CopyOperation)],
+    output_dir: &Path
+) -> Result<AuditReport, String> {
+    let mut report = AuditReport::default();
+
+    for (_, op) in operations {
+        let relative_path = relative_to(&op.get_output_path(), output_dir);
+        match check_file(op, true)? {
+            FileCheck::Passed => report.passed.push(relative_path),
+            FileCheck::Missing => report.failed.push((relative_path, "missing".to_string())),
+            FileCheck::Mismatched(difference) => report.failed.push((relative_path, difference)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Compares the exported file of the given copy operation to its source by size, and optionally by
+/// checksum.
+fn check_file(op: &CopyOperation, compare_checksums: bool) -> Result<FileCheck, String> {
+    let output_path = op.get_output_path();
+    let exported = match metadata(&output_path) {
+        Ok(exported) if exported.is_file() => exported,
+        _ => return Ok(FileCheck::Missing),
+    };
+    if op.converted_from.is_some() {
+        return Ok(FileCheck::Passed);
+    }
+    let Ok(source) = metadata(&op.source_path) else {
+        return Ok(FileCheck::Passed);
+    };
+
+    if source.len() != exported.len() {
+        Ok(FileCheck::Mismatched(format!("expected {} bytes, found {} bytes", source.len(), exported.len())))
+    } else if compare_checksums && checksum(&op.source_path)? != checksum(&output_path)? {
+        Ok(FileCheck::Mismatched("checksums differ".to_string()))
+    } else {
+        Ok(FileCheck::Passed)
+    }
+}
+
+pub(crate) fn collect_files(dir: &Path, files: &mut BTreeSet<PathBuf>) -> Result<(), String> {
+    let entries = read_dir(dir)
+        .map_err(|e| format!("Unable to read '{}': {}", dir.to_string_lossy(), e))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Unable to read '{}': {}", dir.to_string_lossy(), e))?
+            .path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether the given file has been written by the export itself, including placeholders of
+/// assets that are not locally available.
+pub(crate) fn is_export_metadata(path: &Path) -> bool {
+    is_placeholder(path) || path.file_name()
+        .map(|name| EXPORT_METADATA_FILENAMES.iter().any(|metadata_name| name == *metadata_name))
+        .unwrap_or(false)
+}
+
+fn checksum(path: &Path) -> Result<String, String> {
+    sha256_file(path)
+        .map_err(|e| format!("Unable to compute checksum of '{}': {}", path.to_string_lossy(), e))
+}
+
+pub(crate) fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    path.strip_prefix(base)
+        .map(PathBuf::from)
+        .unwrap_or(path.to_path_buf())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use super::*;
+    use crate::export::copying::OriginalsCopyOperationFactory;
+    use crate::test_util::library::{load_operations, SyntheticLibrary, TestAsset};
+
+    #[test]
+    fn reports_missing_extra_and_mismatched_files() {
+        let library = SyntheticLibrary::new();
+        for id in 1..=4 {
+            library.add_asset(&TestAsset::jpeg(id));
+        }
+        let output = tempfile::tempdir().unwrap();
+
+        let planned = load_operations(&library, output.path(), Box::new(OriginalsCopyOperationFactory::new()));
+        let operations = planned.to_planned();
+
+        for (_, op) in &operations {
+            std::fs::copy(&op.source_path, op.get_output_path()).unwrap();
+        }
+        std::fs::remove_file(output.path().join("ASSET-0001.jpeg")).unwrap();
+        write(output.path().join("ASSET-0002.jpeg"), "truncated").unwrap();
+        // Same size as the source, different content
+        write(output.path().join("ASSET-0003.jpeg"), "original of ASSET-9999").unwrap();
+        create_dir_all(output.path().join("other")).unwrap();
+        write(output.path().join("other").join("IMG_9999.jpeg"), "").unwrap();
+        write(output.path().join(MANIFEST_FILENAME), "{}").unwrap();
+
+        let sizes_only = verify_export(&operations, output.path(), false).unwrap();
+
+        assert_eq!(sizes_only.missing, [PathBuf::from("ASSET-0001.jpeg")]);
+        assert_eq!(sizes_only.extra, [PathBuf::from("other/IMG_9999.jpeg")]);
+        assert_eq!(sizes_only.mismatched.len(), 1);
+        assert_eq!(sizes_only.mismatched[0].0, PathBuf::from("ASSET-0002.jpeg"));
+
+        let with_checksums = verify_export(&operations, output.path(), true).unwrap();
+
+        assert_eq!(
+            with_checksums.mismatched.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(),
+            [PathBuf::from("ASSET-0002.jpeg"), PathBuf::from("ASSET-0003.jpeg")]
+        );
+        assert!(!with_checksums.is_consistent());
+    }
+    #[test]
+    fn audits_files_by_checksum() {
+        let library = SyntheticLibrary::new();
+        for id in 1..=3 {
+            library.add_asset(&TestAsset::jpeg(id));
+        }
+        let output = tempfile::tempdir().unwrap();
+
+        let planned = load_operations(&library, output.path(), Box::new(OriginalsCopyOperationFactory::new()));
+        let operations = planned.to_planned();
+
+        std::fs::copy(&operations[0].1.source_path, operations[0].1.get_output_path()).unwrap();
+        // Same size as the source, different content
+        write(output.path().join("ASSET-0002.jpeg"), "original of ASSET-9999").unwrap();
+
+        let report = audit_export(&operations, output.path()).unwrap();
+
+        assert_eq!(report.passed, [PathBuf::from("ASSET-0001.jpeg")]);
+        assert_eq!(
+            report.failed,
+            [
+                (PathBuf::from("ASSET-0002.jpeg"), "checksums differ".to_string()),
+                (PathBuf::from("ASSET-0003.jpeg"), "missing".to_string())
+            ]
+        );
+        assert!(!report.is_passed());
+    }
+}
\ No newline at end of file
@@ -1,11 +1,11 @@
 use std::ops::Add;
 
-use chrono::{DateTime, Local, NaiveDateTime, Offset, TimeDelta};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Offset, TimeDelta, Utc};
 use num_traits::cast::FromPrimitive;
 
 /// Delta between the minimal unix and cocoa dates.
 /// Roughly equals 31 years.
-const UNIX_COCOA_DELTA_MILLIS: i64 = 978307200000;
+pub const UNIX_COCOA_DELTA_MILLIS: i64 = 978307200000;
 
 // TODO: Respect the asset's timezone
 pub fn parse_cocoa_timestamp(cocoa_seconds: f32) -> Result<NaiveDateTime, String> {
@@ -18,7 +18,18 @@ pub fn parse_cocoa_timestamp(cocoa_seconds: f32) -> Result<NaiveDateTime, String
         .ok_or("Could not convert timestamp to NaiveDateTime")?;
 
     let cocoa_unix_delta = TimeDelta::milliseconds(UNIX_COCOA_DELTA_MILLIS);
-    let utc_offset = Local::now().offset().fix();
 
-    Ok(datetime.add(cocoa_unix_delta).add(utc_offset).naive_local())
-}
\ No newline at end of file
+    Ok(datetime.add(cocoa_unix_delta).add(local_utc_offset()).naive_local())
+}
+
+/// Returns the current offset of the local time zone, which timestamps are converted to local time
+/// with regardless of the daylight saving time in effect at their date.
+pub fn local_utc_offset() -> FixedOffset {
+    Local::now().offset().fix()
+}
+
+/// Converts a point in time to the number of seconds since the cocoa epoch as stored in the
+/// database.
+pub fn to_cocoa_timestamp(datetime: DateTime<Utc>) -> f32 {
+    (datetime.timestamp_millis() - UNIX_COCOA_DELTA_MILLIS) as f32 / 1000.0
+}
@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+
+use colored::Colorize;
+
+use crate::db::cache::QueryCache;
+use crate::db::repo::asset::{AlbumFilter, AssetRepository, HiddenAssetsFilter, TimelinePeriod, TimelineResolution};
+use crate::export::statistics::format_bytes;
+use crate::model::asset::ExportAsset;
+use crate::model::FromDbModel;
+use crate::result::PhotosExportResult;
+
+const UNKNOWN: &str = "<unknown>";
+
+/// Number of characters of the bar of the period with the most assets in a timeline.
+const TIMELINE_BAR_WIDTH: usize = 40;
+
+/// Prints the number of exportable assets in the library, optionally broken down by the camera
+/// and lens they have been taken with and by the year or month they have been created in.
+pub fn print_library_statistics(
+    db_path: String,
+    cache: Option<QueryCache>,
+    by_camera: bool,
+    timeline: Option<TimelineResolution>
+) -> PhotosExportResult<()> {
+    let repo = AssetRepository::new(db_path, HiddenAssetsFilter::Include, AlbumFilter::None, vec![], cache);
+
+    let mut seen_ids = HashSet::new();
+    let assets: Vec<ExportAsset> = repo
+        .get_exportable()?
+        .iter()
+        // Assets part of multiple albums are returned once per album
+        .filter(|dto| seen_ids.insert(dto.id))
+        .map(ExportAsset::from_db_model)
+        .collect::<Result<Vec<ExportAsset>, String>>()?;
+
+    let counts = repo.get_library_counts()?;
+
+    println!("{} exportable assets", assets.len());
+    println!("  {:>7} hidden", counts.hidden);
+    println!("  {:>7} offloaded to iCloud", counts.offloaded);
+    println!("  {:>7} in Recently Deleted (not exportable)", counts.trashed);
+
+    if by_camera {
+        print_breakdown("Assets per camera:", &count_by(&assets, |a| a.get_camera_name()));
+        print_breakdown("Assets per lens:", &count_by(&assets, |a| a.lens_model.clone()));
+    }
+
+    if let Some(resolution) = timeline {
+        println!("\n{}", "Assets per period:".blue());
+        for line in render_timeline(&repo.get_timeline(resolution)?) {
+            println!("  {}", line);
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts the assets per key, sorted by descending count. Assets without key are counted as
+/// unknown.
+fn count_by<F>(assets: &[ExportAsset], key: F) -> Vec<(String, usize)>
+where
+    F: Fn(&ExportAsset) -> Option<String>
+{
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for asset in assets {
+        *counts.entry(key(asset).unwrap_or(UNKNOWN.to_string())).or_default() += 1;
+    }
+
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a_key, a_count), (b_key, b_count)| b_count.cmp(a_count).then(a_key.cmp(b_key)));
+    counts
+}
+
+fn print_breakdown(title: &str, counts: &[(String, usize)]) {
+    let width = counts.iter().map(|(key, _)| key.chars().count()).max().unwrap_or(0);
+
+    println!("\n{}", title.blue());
+    for (key, count) in counts {
+        println!("  {:width$}  {:>7}", key, count, width = width);
+    }
+}
+
+/// Renders the periods as aligned lines of their asset count, size and a bar proportional to the
+/// count.
+fn render_timeline(periods: &[TimelinePeriod]) -> Vec<String> {
+    let max_count = periods.iter().map(|p| p.count).max().unwrap_or(0).max(1);
+    let sizes: Vec<String> = periods.iter().map(|p| format_bytes(p.bytes.max(0) as u64)).collect();
+    let size_width = sizes.iter().map(|size| size.chars().count()).max().unwrap_or(0);
+
+    periods
+        .iter()
+        .zip(sizes)
+        .map(|(period, size)| {
+            // Round up so that periods with only a few assets remain visible
+            let bar_length = (period.count as usize * TIMELINE_BAR_WIDTH).div_ceil(max_count as usize);
+            format!(
+                "{:7}  {:>7}  {:>size_width$}  {}",
+                period.period,
+                period.count,
+                size,
+                "█".repeat(bar_length),
+                size_width = size_width
+            )
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::library::{load_exportable, SyntheticLibrary, TestAsset};
+
+    /// Cocoa timestamp of mid-June 2023
+    const JUNE_2023: f32 = 708_000_000.0;
+
+    #[test]
+    fn counts_assets_per_camera() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).shot_with("Apple", "iPhone 15 Pro", None));
+        library.add_asset(&TestAsset::jpeg(2).shot_with("FUJIFILM", "X-T5", Some("XF16-55mm")));
+        library.add_asset(&TestAsset::jpeg(3).shot_with("FUJIFILM", "X-T5", Some("XF16-55mm")));
+        library.add_asset(&TestAsset::jpeg(4));
+
+        let assets = load_exportable(
+            &AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+        );
+
+        assert_eq!(
+            count_by(&assets, |a| a.get_camera_name()),
+            [
+                ("FUJIFILM X-T5".to_string(), 2),
+                (UNKNOWN.to_string(), 1),
+                ("Apple iPhone 15 Pro".to_string(), 1),
+            ]
+        );
+        assert_eq!(
+            count_by(&assets, |a| a.lens_model.clone()),
+            [(UNKNOWN.to_string(), 2), ("XF16-55mm".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn groups_assets_by_year_and_month() {
+        let library = SyntheticLibrary::new();
+        library.add_asset(&TestAsset::jpeg(1).taken_at(JUNE_2023).sized(1000));
+        library.add_asset(&TestAsset::jpeg(2).sized(2000));
+        library.add_asset(&TestAsset::jpeg(3).sized(3000).hidden());
+        library.add_asset(&TestAsset::jpeg(4).sized(4000).trashed());
+        library.add_asset(&TestAsset::jpeg(5).taken_at(JUNE_2023 + 31.0 * 86400.0));
+        let repo = AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None);
+
+        assert_eq!(
+            repo.get_timeline(TimelineResolution::Year).unwrap(),
+            [
+                TimelinePeriod { period: "2023".to_string(), count: 2, bytes: 1000 },
+                TimelinePeriod { period: "2024".to_string(), count: 2, bytes: 5000 },
+            ]
+        );
+        assert_eq!(
+            repo.get_timeline(TimelineResolution::Month)
+                .unwrap()
+                .iter()
+                .map(|p| p.period.as_str())
+                .collect::<Vec<_>>(),
+            ["2023-06", "2023-07", "2024-06"]
+        );
+    }
+
+    #[test]
+    fn renders_timeline_bars_relative_to_the_largest_period() {
+        let lines = render_timeline(&[
+            TimelinePeriod { period: "2023".to_string(), count: 1, bytes: 500 },
+            TimelinePeriod { period: "2024".to_string(), count: 4, bytes: 2048 },
+        ]);
+
+        assert_eq!(lines[0], format!("2023           1   500 B  {}", "█".repeat(10)));
+        assert_eq!(lines[1], format!("2024           4  2.0 KB  {}", "█".repeat(40)));
+    }
+}
\ No newline at end of file
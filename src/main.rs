@@ -1,19 +1,51 @@
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use clap::{Args, Parser, Subcommand};
+use chrono::{Locale, NaiveDate, TimeDelta, Utc};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
 use db::version::check_library_version;
 
-use crate::album_list::print_album_tree;
+use crate::album_list::{print_album_tree, AlbumKindFilter, AlbumSort};
 use crate::changelog::print_changelog;
-use crate::db::repo::album::AlbumRepository;
-use crate::db::repo::asset::{AlbumFilter, AssetRepository, HiddenAssetsFilter};
-use crate::export::copying::{AbsolutePathBuildingCopyOperationFactoryDecorator, AssetCopyStrategy, CombiningCopyOperationFactory, CopyOperationFactory, DefaultAssetCopyStrategy, DerivatesCopyOperationFactory, DryRunAssetCopyStrategy, FilenameRestoringCopyOperationFactoryDecorator, OriginalsCopyOperationFactory, OutputStructureCopyOperationFactoryDecorator, SuffixSettingCopyOperationFactoryDecorator};
-use crate::export::export_assets;
-use crate::export::structure::{AlbumOutputStrategy, HiddenAssetHandlingOutputStrategyDecorator, NestingOutputStrategyDecorator, OutputStrategy, PlainOutputStrategy, YearMonthOutputStrategy};
+use crate::db::cache::QueryCache;
+use crate::db::connection::{enable_read_only_connections, redirect_to_snapshot};
+use crate::db::model::album::AlbumDto;
+use crate::db::repo::album::{find_album, with_asset_count_outside, with_descendants, with_name_prefix, with_start_date_between, AlbumRepository};
+use crate::db::repo::asset::{AlbumFilter, AssetRepository, HiddenAssetsFilter, Shard, TimelineResolution};
+use crate::db::snapshot::DatabaseSnapshot;
+use crate::export::copying::{AssetCopyStrategy, CameraFilter, ConversionFallbackRegistry, ConversionKind, ConvertingAssetCopyStrategyDecorator, DefaultAssetCopyStrategy, DryRunAssetCopyStrategy, ExternalDngConverter, FfmpegVideoTranscoder, FlatOutputRegistry, LibraryProtectingAssetCopyStrategyDecorator, MissingDerivateRegistry, PermissionSettingAssetCopyStrategyDecorator, RelocatedOriginalRegistry, ResumableAssetCopyStrategyDecorator, RESUMABLE_COPY_MIN_SIZE, SyncingAssetCopyStrategyDecorator, UtiFilter, VideoCodec};
+use crate::export::benchmark::PlanningBenchmark;
+use crate::export::{audit_existing_export, create_export_structure, diff_existing_export, export_assets, verify_existing_export, verify_export_manifest, write_export_plan};
+use crate::export::exporter::{ExpectedCount, ExportOptions, ExportSource, ExportSubset};
+use crate::export::journal::{ExportFingerprint, ExportJournal};
+use crate::export::pipeline::{CopyOperationPipeline, EditedMediaTypes, PipelineExclusionRegistry, PipelineOptions, RenderedVideoVersions};
+use crate::export::plan::ExportPlan;
+use crate::export::run_log::RunLog;
+use crate::export::metadata::album_order::AlbumOrderIndexWriter;
+use crate::export::metadata::exiftool::ExifToolCsvMetadataWriter;
+use crate::export::metadata::geojson::GeoJsonMetadataWriter;
+use crate::export::metadata::manifest::{read_manifest_paths, ManifestMetadataWriter};
+use crate::export::metadata::provenance::ProvenanceXattrWriter;
+use crate::export::metadata::MetadataWriter;
+use crate::export::structure::{disambiguate_album_names, find_ambiguous_album_names, parse_album_folder_template, parse_album_name_overrides, parse_locale, AlbumFolderTemplate, AlbumNameDisambiguation, AlbumNameOverrides, AlbumOutputStrategy, CameraOutputStrategy, DateGranularity, DateOutputStrategy, HiddenAssetHandlingOutputStrategyDecorator, NestingOutputStrategyDecorator, OutputStrategy, PlainOutputStrategy, RenamedAlbumRegistry, UnknownAlbumRegistry};
+use crate::foundation::cocoa::to_cocoa_timestamp;
+use crate::library_stats::print_library_statistics;
+use crate::model::album::AlbumReference;
+use crate::model::asset::Subtype;
+use crate::model::uti::Uti;
 use crate::result::PhotosExportResult;
+use crate::util::date_range::DateRange;
+use crate::util::duration::parse_duration;
+use crate::util::interaction::{Answer, NonInteractive, TerminalInteraction, UserInteraction};
+use crate::util::interrupt::install_interrupt_handler;
+use crate::util::last_export::{LastExport, LastExportStore, REPEAT_LAST_FLAG};
+use crate::util::paths::{parse_library_path, parse_mode, parse_output_path, parse_subfolder_name, prepare_output_dir, validate_output_outside_libraries};
 
 mod album_list;
+mod library_stats;
 mod export;
 mod util;
 mod changelog;
@@ -21,6 +53,8 @@ mod db;
 mod foundation;
 mod model;
 mod result;
+#[cfg(test)]
+mod test_util;
 
 
 /// Export photos from the macOS Photos library, organized by album and/or date.
@@ -30,6 +64,11 @@ struct Arguments {
 
     #[command(subcommand)]
     command: Commands,
+
+    /// Do not color the output. Colors are also disabled if the NO_COLOR environment variable is set or the output is
+    /// not a terminal.
+    #[arg(long = "no-color", global = true)]
+    no_color: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -41,110 +80,910 @@ enum Commands {
     /// List all albums in the library
     ListAlbums(ListAlbumsArgs),
 
+    /// Print statistics about the assets in the library
+    Stats(StatsArgs),
+
     /// Export assets from the library to a given location
-    Export(ExportArgs)
+    Export(Box<ExportArgs>),
+
+    /// Compare an existing export to the library, given the same options it has been exported with
+    VerifyExport(Box<VerifyExportArgs>),
+
+    /// Verify the files of an export against the manifest written with --write-manifest, without
+    /// requiring the library
+    VerifyManifest(VerifyManifestArgs),
+
+    /// Compare a random sample of the files of an existing export to their source by size and checksum, given
+    /// the sample size (--sample) and the same options it has been exported with
+    Audit(Box<AuditArgs>),
+
+    /// Export a single album or folder, given by its id, UUID or name, into its own directory
+    ExportAlbum(ExportAlbumArgs),
 }
 
 #[derive(Args, Debug)]
-pub struct ListAlbumsArgs {
+pub struct StatsArgs {
 
     /// Path to the Photos library
+    #[arg(value_parser = parse_library_path)]
     library_path: String,
+
+    /// Break the assets down by the camera and lens they have been taken with
+    #[arg(long = "by-camera")]
+    by_camera: bool,
+
+    /// Print a histogram of the number and size of the assets created per year, or per month
+    #[arg(long = "timeline", value_name = "RESOLUTION", num_args = 0..=1, default_missing_value = "year")]
+    timeline: Option<TimelineResolution>,
+
+    /// Do not use cached query results
+    #[arg(long = "no-cache")]
+    no_cache: bool,
+
+    #[command(flatten)]
+    version_check: VersionCheckArgs,
 }
 
 #[derive(Args, Debug)]
-pub struct ExportArgs {
+pub struct VerifyExportArgs {
+
+    #[command(flatten)]
+    planning: PlanningArgs,
+
+    /// Output directory of the export to verify
+    #[arg(value_parser = parse_output_path)]
+    output_dir: String,
+
+    /// Also compare the checksums of the exported files to their source instead of only their sizes
+    #[arg(long = "checksums")]
+    checksums: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct AuditArgs {
+
+    #[command(flatten)]
+    planning: PlanningArgs,
+
+    /// Output directory of the export to audit
+    #[arg(value_parser = parse_output_path)]
+    output_dir: String,
+
+    /// Number of randomly chosen files to check
+    #[arg(long = "sample", value_name = "N")]
+    sample: usize,
+
+    /// Seed of the random choice of --sample, so that the same files are checked each time
+    #[arg(long = "seed", value_name = "SEED")]
+    seed: Option<u64>,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyManifestArgs {
+
+    /// Root directory of the export containing the manifest
+    #[arg(value_parser = parse_output_path)]
+    export_dir: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportAlbumArgs {
 
     /// Path to the Photos library
+    #[arg(value_parser = parse_library_path)]
     library_path: String,
 
-    /// Output directory
+    /// Id, UUID or name of the album or folder to export
+    album: String,
+
+    /// Output directory, created if missing
+    #[arg(value_parser = parse_output_path)]
     output_dir: String,
 
+    /// Additional options of the export subcommand, given after `--`, e.g. `-- --dry-run`
+    #[arg(last = true)]
+    export_options: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ListAlbumsArgs {
+
+    /// Path to the Photos library
+    #[arg(value_parser = parse_library_path)]
+    library_path: String,
+
+    /// Order of the albums within each folder
+    #[arg(long = "sort", value_name = "ORDER", default_value = "date")]
+    sort: AlbumSort,
+
+    /// Kinds of albums to list
+    #[arg(long = "kind", value_name = "KIND", default_value = "user")]
+    kind: AlbumKindFilter,
+
+    /// Do not use cached query results
+    #[arg(long = "no-cache")]
+    no_cache: bool,
+
+    #[command(flatten)]
+    version_check: VersionCheckArgs,
+}
+
+/// Options of the commands reading a library, concerning the check of its version
+#[derive(Args, Debug)]
+pub struct VersionCheckArgs {
+
+    /// Proceed with a warning instead of failing if the library has been created by an unsupported
+    /// version of Photos, at your own risk
+    #[arg(long = "allow-unsupported-version")]
+    allow_unsupported_version: bool,
+}
+
+/// Options of the commands planning an export, selecting the libraries, the assets to export and
+/// the paths they are exported to
+#[derive(Args, Debug)]
+pub struct PlanningArgs {
+
+    /// Path to the Photos library
+    #[arg(value_parser = parse_library_path)]
+    library_path: String,
+
+    /// Path to an additional Photos library whose assets are merged into the export
+    #[arg(short = 'L', long = "merge-library", value_name = "LIBRARY_PATH", value_parser = parse_library_path)]
+    merge_libraries: Vec<String>,
+
     /// Group assets by album
-    #[arg(short = 'a', long = "by-album", group = "strategy")]
+    #[arg(short = 'a', long = "by-album", group = "strategy", group = "album_strategy")]
     album: bool,
 
     /// Group assets by year/month
-    #[arg(short = 'm', long = "by-year-month", group = "strategy")]
+    #[arg(short = 'm', long = "by-year-month", group = "strategy", group = "date_folders")]
     year_month: bool,
 
+    /// Group assets by date with the given granularity
+    #[arg(long = "group-by-date", group = "strategy", group = "date_folders", value_name = "GRANULARITY")]
+    group_by_date: Option<DateGranularity>,
+
     /// Group assets by year/month/album
-    #[arg(short = 'M', long = "by-year-month-album", group = "strategy")]
+    #[arg(short = 'M', long = "by-year-month-album", group = "strategy", group = "album_strategy", group = "date_folders")]
     year_month_album: bool,
 
-    /// Include assets in the albums matching the given ids
-    #[arg(short = 'i', long = "include-albums", group = "ids", num_args = 0.., value_delimiter = ' ')]
-    include: Option<Vec<i32>>,
+    /// Group assets by the camera they have been taken with
+    #[arg(long = "group-by-camera", group = "strategy")]
+    group_by_camera: bool,
+
+    /// Group assets by their keywords, exporting assets with multiple keywords once per keyword
+    #[arg(long = "group-by-keyword", group = "strategy")]
+    group_by_keyword: bool,
+
+    /// Additionally export each asset once to a year/month tree in a _by_date folder, so that an export
+    /// by album also contains all assets independent of their albums. Assets not part of any album are
+    /// only exported to the date tree
+    #[arg(long = "also-by-date", requires = "album_strategy", group = "date_folders")]
+    also_by_date: bool,
 
-    /// Exclude assets in the albums matching the given ids
-    #[arg(short = 'x', long = "exclude-albums", group = "ids", num_args = 1.., value_delimiter = ' ')]
-    exclude: Option<Vec<i32>>,
+    /// Append the month name in the given locale to month folders, e.g. `2024/05 Mai` for de-DE
+    #[arg(long = "locale", value_name = "LOCALE", value_parser = parse_locale, requires = "date_folders")]
+    locale: Option<Locale>,
 
-    /// Include hidden assets
-    #[arg(short = 'H', long = "include-hidden", group = "hidden")]
+    /// Additionally export favorite assets to a _favorites folder in the output directory
+    #[arg(long = "favorites-subdir", conflicts_with = "flat_frame_mode")]
+    favorites_subdir: bool,
+
+    /// Move RAW files, e.g. the RAW part of RAW+JPEG pairs, to a subfolder with the given name in
+    /// their album or date folder
+    #[arg(long = "raw-subdir", value_name = "NAME", value_parser = parse_subfolder_name, conflicts_with = "flat_frame_mode")]
+    raw_subdir: Option<String>,
+
+    /// Export all assets into the output directory without subfolders, with date-prefixed, sanitized and unique
+    /// filenames, e.g. for digital picture frames
+    #[arg(long = "flat-frame-mode", conflicts_with_all = ["strategy", "flatten_albums"])]
+    flat_frame_mode: bool,
+
+    /// Export files under their path inside the library, e.g. `originals/A/<uuid>.jpeg`, so that the export can be
+    /// compared to the library directly
+    #[arg(
+        long = "keep-library-structure",
+        conflicts_with_all = [
+            "strategy", "flat_frame_mode", "favorites_subdir", "restore_original_filenames", "preserve_album_order",
+            "max_path_length", "stable_names", "raw_subdir", "use_titles"
+        ]
+    )]
+    keep_library_structure: bool,
+
+    /// Include assets in the albums matching the given ids or UUIDs
+    #[arg(short = 'i', long = "include-albums", group = "ids", group = "album_selection", num_args = 0.., value_delimiter = ' ')]
+    include: Option<Vec<AlbumReference>>,
+
+    /// Exclude assets in the albums matching the given ids or UUIDs
+    #[arg(short = 'x', long = "exclude-albums", group = "ids", group = "album_selection", num_args = 1.., value_delimiter = ' ')]
+    exclude: Option<Vec<AlbumReference>>,
+
+    /// Only export assets that are not part of any album, e.g. to find the assets missing from an export by album
+    #[arg(long = "only-unalbumed", group = "ids")]
+    only_unalbumed: bool,
+
+    /// Only include assets in the albums and folders (along with all albums nested in them) whose
+    /// start date is on or after the given date, e.g. 2022-01-01
+    #[arg(long = "albums-from", value_name = "DATE", conflicts_with = "ids")]
+    albums_from: Option<NaiveDate>,
+
+    /// Only include assets in the albums and folders (along with all albums nested in them) whose
+    /// start date is on or before the given date, e.g. 2022-12-31
+    #[arg(long = "albums-to", value_name = "DATE", conflicts_with = "ids")]
+    albums_to: Option<NaiveDate>,
+
+    /// Also include or exclude the assets of all albums nested in the given folders
+    #[arg(long = "recursive-album-filter", requires = "album_selection")]
+    recursive_album_filter: bool,
+
+    /// Exclude assets in the albums and folders whose name starts with the given prefix, e.g. zz_ for archived albums
+    #[arg(long = "exclude-album-prefix", value_name = "PREFIX")]
+    exclude_album_prefixes: Vec<String>,
+
+    /// Skip albums with fewer than the given number of assets, e.g. to keep trivial albums out of the output tree. Their assets are exported as if they were in no album.
+    #[arg(long = "min-album-size", value_name = "COUNT", requires = "album_strategy")]
+    min_album_size: Option<i64>,
+
+    /// Skip albums with more than the given number of assets, exporting their assets as if they were in no album
+    #[arg(long = "max-album-size", value_name = "COUNT", requires = "album_strategy")]
+    max_album_size: Option<i64>,
+
+    /// Whether to export hidden assets along with the visible ones, only hidden assets or no hidden assets
+    #[arg(long = "hidden", value_name = "MODE", value_enum, default_value = "exclude", group = "hidden_assets")]
+    hidden: HiddenAssetsFilter,
+
+    /// Deprecated alias of `--hidden include`
+    #[arg(short = 'H', long = "include-hidden", group = "hidden_assets", hide = true)]
     include_hidden: bool,
 
-    /// Assets must be hidden
-    #[arg(long = "must-be-hidden", group = "hidden")]
+    /// Deprecated alias of `--hidden only`
+    #[arg(long = "must-be-hidden", group = "hidden_assets", hide = true)]
     must_be_hidden: bool,
 
+    /// Exclude screenshots
+    #[arg(long = "exclude-screenshots")]
+    exclude_screenshots: bool,
+
+    /// Exclude screen recordings
+    #[arg(long = "exclude-screen-recordings")]
+    exclude_screen_recordings: bool,
+
+    /// Skip referenced assets, whose originals are stored outside the library
+    #[arg(long = "skip-referenced")]
+    skip_referenced: bool,
+
+    /// Only export assets that have been edited, e.g. to back up edits separately. Combine with --include-edited or
+    /// --prefer-edited to choose which versions of them to export.
+    #[arg(long = "edited-assets-only")]
+    edited_assets_only: bool,
+
+    /// Only export files of the given types, e.g. `raf,dng`. Originals and edited versions are
+    /// filtered by their own type.
+    #[arg(long = "include-ext", value_name = "EXTENSIONS", value_delimiter = ',', value_parser = Uti::from_extension, conflicts_with = "exclude_ext")]
+    include_ext: Vec<&'static Uti>,
+
+    /// Do not export files of the given types, e.g. `mov,mp4`
+    #[arg(long = "exclude-ext", value_name = "EXTENSIONS", value_delimiter = ',', value_parser = Uti::from_extension)]
+    exclude_ext: Vec<&'static Uti>,
+
+    /// Only export assets taken with the given cameras, named as by `stats --by-camera` or by their model, e.g.
+    /// `FUJIFILM X-T5` or `X-T5`. May be given multiple times.
+    #[arg(long = "include-camera", value_name = "CAMERA", conflicts_with = "exclude_cameras")]
+    include_cameras: Vec<String>,
+
+    /// Do not export assets taken with the given cameras, e.g. `iPhone 15 Pro`. May be given multiple times.
+    #[arg(long = "exclude-camera", value_name = "CAMERA")]
+    exclude_cameras: Vec<String>,
+
+    /// Exclude assets taken within the given range of days, e.g. 2024-03-01..2024-03-07. May be given multiple times.
+    #[arg(long = "exclude-date-range", value_name = "FROM..TO")]
+    exclude_date_ranges: Vec<DateRange>,
+
+    /// Only export assets created within the given duration before now, e.g. 24h, 7d or 3w
+    #[arg(long = "newer-than", value_name = "DURATION", value_parser = parse_duration)]
+    newer_than: Option<TimeDelta>,
+
+    /// Only export the given part of the assets, e.g. 3/8 for the third of eight parts, to split the export of a large
+    /// library across multiple runs. Assets are assigned to parts by their id.
+    #[arg(long = "shard", value_name = "K/N")]
+    shard: Option<Shard>,
+
     /// Restore original filenames
     #[arg(short = 'r', long = "restore-original-filenames")]
     restore_original_filenames: bool,
 
+    /// Name exported files after the title of their asset, falling back to the original filename for assets without
+    /// title. Characters not allowed in filenames are replaced with an underscore.
+    #[arg(long = "use-titles", conflicts_with_all = ["restore_original_filenames", "stable_names", "flat_frame_mode"])]
+    use_titles: bool,
+
+    /// Always name exported files after the UUID of their asset, optionally prefixed with its date, so that each
+    /// asset is exported to the same filename in every run
+    #[arg(
+        long = "stable-names",
+        value_name = "NAMING",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "uuid",
+        conflicts_with_all = ["restore_original_filenames", "flat_frame_mode", "preserve_album_order"]
+    )]
+    stable_names: Option<StableNaming>,
+
     /// Flatten album structure
-    #[arg(short = 'f', long = "flatten-albums")]
+    #[arg(short = 'f', long = "flatten-albums", requires = "album_strategy")]
     flatten_albums: bool,
 
-    /// Include edited versions of the assets if available
-    #[arg(short = 'e', long = "include-edited", group = "edited")]
-    include_edited: bool,
+    /// Export albums to the folder names given in a TOML file mapping album ids or UUIDs to names, e.g. `42 = "Trips"`
+    #[arg(long = "album-names", value_name = "FILE", value_parser = parse_album_name_overrides, requires = "album_strategy")]
+    album_names: Option<AlbumNameOverrides>,
+
+    /// Name album folders after a template, e.g. `{year} – {name}` (placeholders: name, year, month, day of the album's
+    /// start date)
+    #[arg(
+        long = "album-folder-template",
+        value_name = "TEMPLATE",
+        value_parser = parse_album_folder_template,
+        requires = "album_strategy"
+    )]
+    album_folder_template: Option<AlbumFolderTemplate>,
+
+    /// Tell apart albums with the same name in the same folder by appending their id or start date
+    #[arg(long = "disambiguate-album-names", value_name = "MODE", requires = "album_strategy")]
+    disambiguate_album_names: Option<AlbumNameDisambiguation>,
+
+    /// Include edited versions of the assets of the given media types if available
+    #[arg(
+        short = 'e',
+        long = "include-edited",
+        group = "edited",
+        value_name = "MEDIA_TYPES",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all"
+    )]
+    include_edited: Option<EditedMediaTypes>,
+
+    /// Export the edited version instead of the original for the given media types if available
+    #[arg(
+        short = 'E',
+        long = "prefer-edited",
+        group = "edited",
+        value_name = "MEDIA_TYPES",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all"
+    )]
+    prefer_edited: Option<EditedMediaTypes>,
+
+    /// Versions of slow-motion and time-lapse videos to export, overriding the edited-version flags
+    #[arg(long = "slomo", value_name = "VERSIONS")]
+    slomo: Option<RenderedVideoVersions>,
+
+    /// Also export companion resources stored next to the originals, e.g. HDR gain maps
+    #[arg(long = "include-companion-resources")]
+    include_companion_resources: bool,
+
+    /// Convert proprietary RAW files to DNG while exporting them. Only Fujifilm RAF files are recognized so far, as
+    /// the library's identifiers of other RAW formats like CR2 or NEF are not known yet.
+    #[arg(long = "convert-raw-to-dng")]
+    convert_raw_to_dng: bool,
+
+    /// Transcode videos to the given codec while exporting them, e.g. for TVs or digital photo
+    /// frames unable to play HEVC. Videos failing to transcode are copied unchanged
+    #[arg(long = "transcode-video", value_name = "CODEC")]
+    transcode_video: Option<VideoCodec>,
+
+    /// Preserve the manual sort order of albums by prefixing filenames with their position or by
+    /// writing an index file to each album folder
+    #[arg(long = "preserve-album-order", value_name = "MODE", requires = "album_strategy")]
+    preserve_album_order: Option<AlbumOrderPreservation>,
+
+    /// Shorten output paths (relative to the output directory) to at most the given number of characters
+    #[arg(long = "max-path-length", value_name = "N")]
+    max_path_length: Option<usize>,
+
+    /// Do not disambiguate output paths only differing in case, e.g. when exporting to a case-sensitive file system
+    #[arg(long = "case-sensitive-target")]
+    case_sensitive_target: bool,
+
+    /// Query a copy of each library's database including its write-ahead log instead of the database itself, e.g.
+    /// if the library is open in Photos
+    #[arg(long = "immutable-db")]
+    immutable_db: bool,
+
+    /// Query a consistent snapshot of each library's database taken using SQLite's `VACUUM INTO`, which is removed
+    /// after the export
+    #[arg(long = "db-snapshot", conflicts_with = "immutable_db")]
+    db_snapshot: bool,
+
+    /// Do not use cached query results
+    #[arg(long = "no-cache")]
+    no_cache: bool,
+
+    #[command(flatten)]
+    version_check: VersionCheckArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+
+    #[command(flatten)]
+    planning: PlanningArgs,
+
+    /// Output directory, which may only be omitted along with --repeat-last
+    #[arg(value_parser = parse_output_path, required_unless_present = "repeat_last")]
+    output_dir: Option<String>,
+
+    /// Command converting a RAW file to DNG, called with the source and destination path appended
+    #[arg(long = "dng-converter", value_name = "COMMAND", default_value = "dnglab convert", requires = "convert_raw_to_dng")]
+    dng_converter: String,
+
+    /// Command used to transcode videos
+    #[arg(long = "ffmpeg", value_name = "COMMAND", default_value = "ffmpeg", requires = "transcode_video")]
+    ffmpeg: String,
+
+    /// Time each stage of planning the export and print a breakdown before starting it
+    #[arg(long = "bench-planning", hide = true)]
+    bench_planning: bool,
+
+    /// Skip assets whose source file is empty instead of exporting them as empty files
+    #[arg(long = "skip-empty-sources")]
+    skip_empty_sources: bool,
+
+    /// Skip assets whose content is identical to an asset already exported in the same run
+    #[arg(long = "skip-duplicate-content")]
+    skip_duplicate_content: bool,
+
+    /// Write the locations of all exported assets to a GeoJSON file in the output directory
+    #[arg(long = "write-locations")]
+    write_locations: bool,
+
+    /// Write a manifest listing all exported files and the assets they have been exported from to
+    /// the output directory
+    #[arg(long = "write-manifest")]
+    write_manifest: bool,
+
+    /// Record the UUID of the asset each file has been exported from and the time of the export in extended
+    /// attributes of the file (user.apple-photos-export.uuid and user.apple-photos-export.exported-at)
+    #[arg(long = "write-xattrs")]
+    write_xattrs: bool,
+
+    /// Write a `<file>.missing.json` placeholder describing the asset in place of each file that can not be
+    /// exported as its original is offloaded to iCloud. Placeholders are removed once the file has been exported
+    /// by a later run, whether or not it is given this option.
+    #[arg(long = "write-placeholders")]
+    write_placeholders: bool,
+
+    /// Write the metadata of all exported assets to a CSV file that can be applied to the exported
+    /// files using `exiftool -csv=FILE -sep ", "`
+    #[arg(long = "exiftool-csv", value_name = "FILE")]
+    exiftool_csv: Option<PathBuf>,
 
-    /// Always export the edited version of an asset if available
-    #[arg(short = 'E', long = "only-edited", group = "edited")]
-    only_edited: bool,
+    /// Mode of the exported files in octal notation, e.g. 644
+    #[arg(long = "file-mode", value_name = "MODE", value_parser = parse_mode)]
+    file_mode: Option<u32>,
+
+    /// Mode of the directories created during the export in octal notation, e.g. 755
+    #[arg(long = "dir-mode", value_name = "MODE", value_parser = parse_mode)]
+    dir_mode: Option<u32>,
+
+    /// Create the output directory and its parents if they do not exist yet
+    #[arg(long = "create-output-dir")]
+    create_output_dir: bool,
+
+    /// Append a JSON line with the outcome, duration and size of each processed file to the given file
+    #[arg(long = "run-log", value_name = "FILE")]
+    run_log: Option<PathBuf>,
+
+    /// Flush each copied file and its directory to disk before counting it as exported, e.g. for unreliable network
+    /// shares
+    #[arg(long = "fsync")]
+    fsync: bool,
+
+    /// Copy files of at least 64 MiB in chunks, so that a copy stopped by an interruption is continued by the next
+    /// run instead of starting over. Each such copy is validated by its checksum.
+    #[arg(long = "resumable-copies")]
+    resumable_copies: bool,
+
+    /// Skip the files already copied by a previous, interrupted export into the same output directory
+    #[arg(long = "resume")]
+    resume: bool,
+
+    /// Export even if neither the libraries nor the options have changed since the last completed export into the
+    /// same output directory, e.g. after files have been removed from the export
+    #[arg(long = "force")]
+    force: bool,
+
+    /// Open the libraries read-only and verify that no file is written into a library bundle
+    #[arg(long = "paranoid")]
+    paranoid: bool,
+
+    /// Fail without exporting anything if fewer than N files are planned, e.g. as a filter excludes more assets than
+    /// intended
+    #[arg(long = "expect-at-least", value_name = "N")]
+    expect_at_least: Option<usize>,
+
+    /// Fail without exporting anything if more than N files are planned
+    #[arg(long = "expect-at-most", value_name = "N")]
+    expect_at_most: Option<usize>,
+
+    /// Start the export without asking for confirmation if fewer than N files are planned, e.g. for small exports of
+    /// single albums
+    #[arg(long = "auto-confirm-below", value_name = "N")]
+    auto_confirm_below: Option<usize>,
+
+    /// Answer all prompts with yes instead of waiting for input, e.g. when running from scripts. Interrupted exports
+    /// are only resumed when passing --resume.
+    #[arg(long = "non-interactive")]
+    non_interactive: bool,
+
+    /// Stop copying files once the export has been running for the given duration, e.g. 90m or 2h, so that it fits
+    /// into a backup window. The remaining files are copied when resuming the export with --resume.
+    #[arg(long = "max-duration", value_name = "DURATION", value_parser = parse_duration, conflicts_with = "metadata_only")]
+    max_duration: Option<TimeDelta>,
+
+    /// Only export the first N of the planned files, e.g. to try out options
+    #[arg(long = "limit", value_name = "N", conflicts_with = "sample")]
+    limit: Option<usize>,
+
+    /// Only export N randomly chosen files of the planned files, e.g. to try out options
+    #[arg(long = "sample", value_name = "N")]
+    sample: Option<usize>,
+
+    /// Seed of the random choice of --sample, so that the same files are chosen each time
+    #[arg(long = "seed", value_name = "SEED", requires = "sample")]
+    seed: Option<u64>,
+
+    /// Only write the metadata files of the assets without copying them, e.g. to add metadata to a
+    /// previous export
+    #[arg(long = "metadata-only", conflicts_with_all = ["dry_run", "resume"])]
+    metadata_only: bool,
 
     /// Dry run
     #[arg(short = 'd', long = "dry-run")]
     dry_run: bool,
+
+    /// Instead of exporting, print which files would be added, moved or no longer exported compared to an
+    /// existing export in DIR, e.g. to evaluate other grouping options
+    #[arg(long = "diff-against", value_name = "DIR", value_parser = parse_output_path)]
+    diff_against: Option<String>,
+
+    /// Instead of exporting, print the ordered stages building the copy operations of each asset, e.g. to see how
+    /// naming and grouping options interact
+    #[arg(long = "explain-pipeline", conflicts_with_all = ["diff_against", "plan_out", "plan_in"])]
+    explain_pipeline: bool,
+
+    /// Instead of exporting, only create the directories files would be copied to, e.g. the album or date tree, to
+    /// prepare a network share with the correct permissions (see --dir-mode) before the actual export
+    #[arg(
+        long = "structure-only",
+        conflicts_with_all = ["dry_run", "metadata_only", "diff_against", "explain_pipeline", "plan_out", "plan_in"]
+    )]
+    structure_only: bool,
+
+    /// Instead of exporting, write the planned copy operations to the given JSON file, e.g. to review them
+    /// before executing them using --plan-in
+    #[arg(long = "plan-out", value_name = "FILE", conflicts_with_all = ["diff_against", "plan_in"])]
+    plan_out: Option<PathBuf>,
+
+    /// Execute the copy operations of a plan written by --plan-out, possibly edited after reviewing it, instead of
+    /// planning them again. The assets of the plan must still be exportable with the given options.
+    #[arg(long = "plan-in", value_name = "FILE", conflicts_with = "diff_against")]
+    plan_in: Option<PathBuf>,
+
+    /// After the export, remove the directories in the output directory that are empty, but contained files listed in
+    /// the manifest of the previous export, e.g. album folders left behind after their files have been moved or
+    /// removed. Other empty directories are kept.
+    #[arg(long = "prune-empty-dirs", requires = "write_manifest", conflicts_with_all = ["dry_run", "diff_against"])]
+    prune_empty_dirs: bool,
+
+    /// Ask for a separate confirmation before --prune-empty-dirs removes more than N directories
+    #[arg(long = "confirm-deletions-above", value_name = "N", default_value_t = 10, requires = "prune_empty_dirs")]
+    confirm_deletions_above: usize,
+
+    /// Repeat the last export of the library with the same options. Only the library path and global options like
+    /// --no-color may be given along with this flag, e.g. `export <LIBRARY_PATH> --repeat-last`
+    #[arg(long = "repeat-last")]
+    repeat_last: bool,
+}
+
+impl ExportArgs {
+
+    /// Returns the output directory, which is only missing along with `--repeat-last`.
+    fn output_dir(&self) -> &str {
+        self.output_dir.as_deref().unwrap_or_default()
+    }
+}
+
+impl PlanningArgs {
+
+    /// Returns the paths of all libraries to export, starting with the primary one.
+    fn library_paths(&self) -> Vec<String> {
+        [vec![self.library_path.clone()], self.merge_libraries.clone()].concat()
+    }
+
+    /// Returns the hidden assets filter selected by `--hidden` or one of its deprecated aliases.
+    fn hidden_assets_filter(&self) -> HiddenAssetsFilter {
+        if self.include_hidden {
+            HiddenAssetsFilter::Include
+        } else if self.must_be_hidden {
+            HiddenAssetsFilter::Only
+        } else {
+            self.hidden
+        }
+    }
+}
+
+/// Filenames of exported files with `--stable-names`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum StableNaming {
+    /// `<uuid>.jpeg`
+    Uuid,
+    /// `<yyyy-mm-dd>_<uuid>.jpeg`
+    Date,
+}
+
+/// Ways of preserving the manual sort order of albums
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum AlbumOrderPreservation {
+    /// Prefix filenames with the position of the asset in its album, e.g. `0001_`
+    Prefix,
+    /// Write an `order.txt` file listing the filenames in order to each album folder
+    IndexFile,
 }
 
 
 fn main() {
-    let args = Arguments::parse();
+    let raw_args = std::env::args_os().map(|arg| arg.to_string_lossy().to_string()).collect();
+    let result = parse_arguments(raw_args).and_then(|(args, raw_args)| {
+        if args.no_color {
+            colored::control::set_override(false);
+        }
+        run_command(args, &raw_args)
+    });
+
+    // Handle uncaught errors and print them to stderr
+    // Errors requiring more complex handling may have already been handled at this point
+    if let Err(e) = result {
+        for message in &e.messages {
+            eprintln!("{} {}", "Error:".red(), message);
+        }
+        std::process::exit(1);
+    }
+}
+
+/// Parses the command line arguments. If only a library path is given along with `--repeat-last`,
+/// the arguments of the last export of that library are restored and parsed instead, keeping the
+/// global options given along with it.
+///
+/// Returns the parsed arguments along with the raw arguments they have been parsed from.
+fn parse_arguments(raw_args: Vec<String>) -> PhotosExportResult<(Arguments, Vec<String>)> {
+    let matches = Arguments::command().get_matches_from(&raw_args);
+    let args = Arguments::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    let Commands::Export(export_args) = &args.command else {
+        return Ok((args, raw_args));
+    };
+    if !export_args.repeat_last {
+        return Ok((args, raw_args));
+    }
+    if let Some((_, export_matches)) = matches.subcommand() {
+        if has_options_besides_repeat_last(export_matches) {
+            return Err(format!(
+                "{} only accepts the library path, e.g. `export <LIBRARY_PATH> {}`",
+                REPEAT_LAST_FLAG,
+                REPEAT_LAST_FLAG
+            ).into());
+        }
+    }
 
-    let result: PhotosExportResult<()> = match args.command {
+    let store = LastExportStore::in_state_dir()
+        .ok_or("Unable to determine the directory of the stored export settings".to_string())?;
+    let last_export = store.load(&export_args.planning.library_path)?;
+
+    std::env::set_current_dir(&last_export.working_dir).map_err(|e| {
+        format!("Unable to change into '{}': {}", last_export.working_dir.to_string_lossy(), e)
+    })?;
+    println!(
+        "Repeating the last export in '{}': {}",
+        last_export.working_dir.to_string_lossy(),
+        last_export.args.join(" ")
+    );
+
+    let repeated_args = [vec![raw_args[0].clone()], last_export.args].concat();
+    let repeated = Arguments::parse_from(&repeated_args);
+
+    Ok((Arguments { no_color: repeated.no_color || args.no_color, ..repeated }, repeated_args))
+}
+
+/// Whether options other than the library path, `--repeat-last` and global options have been
+/// given to the export subcommand.
+fn has_options_besides_repeat_last(export_matches: &ArgMatches) -> bool {
+    let command = Arguments::command();
+    let Some(export) = command.find_subcommand("export") else {
+        return false;
+    };
+
+    let given = export
+        .get_arguments()
+        .map(|arg| arg.get_id().as_str())
+        .filter(|id| !matches!(*id, "library_path" | "repeat_last" | "no_color"))
+        .any(|id| export_matches.value_source(id) == Some(ValueSource::CommandLine));
+
+    given
+}
+
+/// Remembers the arguments of an export so that it can be repeated with `--repeat-last`.
+fn save_last_export(export_args: &ExportArgs, raw_args: &[String]) {
+    let result = LastExportStore::in_state_dir()
+        .ok_or("Unable to determine the state directory".to_string())
+        .and_then(|store| {
+            let working_dir = std::env::current_dir().map_err(|e| e.to_string())?;
+            store.save(&export_args.planning.library_path, &LastExport { working_dir, args: raw_args[1..].to_vec() })
+        });
+
+    if let Err(e) = result {
+        eprintln!("{} Unable to remember the export settings: {}", "Warning:".yellow(), e);
+    }
+}
+
+fn run_command(args: Arguments, raw_args: &[String]) -> PhotosExportResult<()> {
+    match args.command {
         Commands::Changelog => print_changelog(),
         Commands::ListAlbums(list_args) => {
             let database_path = get_database_path(&list_args.library_path);
 
-            check_library_version(&database_path)
+            check_library_version(&database_path, list_args.version_check.allow_unsupported_version)
                 .and_then(|_| {
                     print_album_tree(
-                        get_database_path(&list_args.library_path)
+                        database_path.clone(),
+                        setup_query_cache(&database_path, list_args.no_cache),
+                        list_args.sort,
+                        list_args.kind
                     )
                 })
         },
-        Commands::Export(export_args) => {
-            let database_path = get_database_path(&export_args.library_path);
+        Commands::Stats(stats_args) => {
+            let database_path = get_database_path(&stats_args.library_path);
 
-            check_library_version(&database_path)
-                .and_then(|_| run_photos_export(&export_args))
+            check_library_version(&database_path, stats_args.version_check.allow_unsupported_version)
+                .and_then(|_| {
+                    print_library_statistics(
+                        database_path.clone(),
+                        setup_query_cache(&database_path, stats_args.no_cache),
+                        stats_args.by_camera,
+                        stats_args.timeline
+                    )
+                })
         },
-    };
+        Commands::Export(export_args) => run_export(&export_args, raw_args),
+        Commands::ExportAlbum(album_args) => {
+            let argv = compose_album_export_arguments(&album_args)?;
+            match Arguments::parse_from(&argv).command {
+                Commands::Export(export_args) => run_export(&export_args, &argv),
+                _ => unreachable!("composed arguments always use the export subcommand"),
+            }
+        },
+        Commands::VerifyExport(verify_args) => {
+            setup_database_snapshots(&verify_args.planning)
+                .and_then(|_snapshots| {
+                    verify_args.planning
+                        .library_paths()
+                        .iter()
+                        .try_for_each(|path| check_library_version(&get_database_path(path), verify_args.planning.version_check.allow_unsupported_version))
+                        .and_then(|_| check_album_references(&verify_args.planning))
+                        .and_then(|_| run_export_verification(&verify_args))
+                })
+        },
+        Commands::VerifyManifest(verify_args) => verify_export_manifest(Path::new(&verify_args.export_dir)),
+        Commands::Audit(audit_args) => {
+            setup_database_snapshots(&audit_args.planning)
+                .and_then(|_snapshots| {
+                    audit_args.planning
+                        .library_paths()
+                        .iter()
+                        .try_for_each(|path| check_library_version(&get_database_path(path), audit_args.planning.version_check.allow_unsupported_version))
+                        .and_then(|_| check_album_references(&audit_args.planning))
+                        .and_then(|_| run_export_audit(&audit_args))
+                })
+        },
+    }
+}
 
-    // Handle uncaught errors and print them to stderr
-    // Errors requiring more complex handling may have already been handled at this point
-    if let Err(e) = result {
-        for message in &e.messages {
-            eprintln!("{} {}", "Error:".red(), message);
-        }
-        std::process::exit(1);
+fn run_export(export_args: &ExportArgs, raw_args: &[String]) -> PhotosExportResult<()> {
+    if export_args.repeat_last {
+        return Err(format!("{} is not supported by export-album", REPEAT_LAST_FLAG).into());
+    }
+    if export_args.paranoid {
+        enable_read_only_connections();
+    }
+
+    validate_export_destinations(export_args)
+        .and_then(|_| setup_database_snapshots(&export_args.planning))
+        .and_then(|_snapshots| {
+            export_args.planning
+                .library_paths()
+                .iter()
+                .try_for_each(|path| check_library_version(&get_database_path(path), export_args.planning.version_check.allow_unsupported_version))
+                .and_then(|_| check_album_references(&export_args.planning))
+                .and_then(|_| match (&export_args.diff_against, &export_args.plan_out) {
+                    _ if export_args.explain_pipeline => run_pipeline_explanation(export_args),
+                    (Some(existing_dir), _) => run_export_diff(export_args, Path::new(existing_dir)),
+                    _ if export_args.structure_only => {
+                        prepare_output_dir(Path::new(export_args.output_dir()), export_args.create_output_dir, false)?;
+                        run_structure_creation(export_args)
+                    },
+                    (_, Some(plan_path)) => run_export_planning(export_args, plan_path),
+                    (None, None) => {
+                        prepare_output_dir(
+                            Path::new(export_args.output_dir()),
+                            export_args.create_output_dir,
+                            export_args.dry_run
+                        )?;
+                        if !export_args.dry_run {
+                            save_last_export(export_args, raw_args);
+                        }
+                        run_photos_export(export_args, raw_args)
+                    }
+                })
+        })
+}
+
+/// Composes the arguments of an export of a single album or folder into its own subdirectory of
+/// the output directory, followed by any additional export options.
+fn compose_album_export_arguments(album_args: &ExportAlbumArgs) -> PhotosExportResult<Vec<String>> {
+    let database_path = get_database_path(&album_args.library_path);
+    let allow_unsupported_version = album_args.export_options
+        .iter()
+        .any(|option| option == "--allow-unsupported-version");
+
+    // The export checks the version again, printing the warning if unsupported versions are allowed
+    if !allow_unsupported_version {
+        check_library_version(&database_path, false)?;
     }
+    let all_albums = AlbumRepository::new(database_path, None).get_all()?;
+    let album = find_album(&all_albums, &album_args.album)?;
+
+    let mut argv: Vec<String> = vec![
+        env!("CARGO_PKG_NAME"),
+        "export",
+        &album_args.library_path,
+        &album_args.output_dir,
+        "--by-album",
+        "--flatten-albums",
+        "--create-output-dir",
+        "--recursive-album-filter",
+        "--include-albums",
+    ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    argv.push(album.id.to_string());
+    argv.extend(album_args.export_options.iter().cloned());
+
+    Ok(argv)
 }
 
 
+/// Checks that neither the output directory nor any other file written by the export lies inside
+/// one of the exported libraries.
+fn validate_export_destinations(args: &ExportArgs) -> PhotosExportResult<()> {
+    let library_paths = args.planning.library_paths();
+
+    validate_output_outside_libraries(Path::new(args.output_dir()), &library_paths)?;
+    if let Some(csv_path) = &args.exiftool_csv {
+        validate_output_outside_libraries(csv_path, &library_paths)?;
+    }
+    if let Some(run_log_path) = &args.run_log {
+        validate_output_outside_libraries(run_log_path, &library_paths)?;
+    }
+    if let Some(plan_path) = &args.plan_out {
+        validate_output_outside_libraries(plan_path, &library_paths)?;
+    }
+
+    Ok(())
+}
+
 fn get_database_path(library_path: &str) -> String {
     PathBuf::new()
         .join(library_path)
@@ -155,110 +994,647 @@ fn get_database_path(library_path: &str) -> String {
 }
 
 
-fn run_photos_export(export_args: &ExportArgs) -> PhotosExportResult<()> {
-    let db_path = get_database_path(&export_args.library_path);
+/// Redirects all queries of the libraries' databases to snapshots if requested. The snapshots are
+/// removed once the returned handles are dropped.
+fn setup_database_snapshots(args: &PlanningArgs) -> PhotosExportResult<Vec<DatabaseSnapshot>> {
+    let take_snapshot = if args.db_snapshot {
+        DatabaseSnapshot::backup
+    } else if args.immutable_db {
+        DatabaseSnapshot::copy_with_wal
+    } else {
+        return Ok(vec![]);
+    };
 
-    let asset_repo = setup_asset_repo(db_path.clone(), export_args);
-    let copy_operation_factory = setup_copy_operation_factory(db_path.clone(), export_args)?;
-    let copy_strategy = setup_copy_strategy(export_args.dry_run);
+    let mut snapshots = vec![];
+    for library_path in args.library_paths() {
+        let db_path = get_database_path(&library_path);
+        let snapshot = take_snapshot(&db_path)?;
+        redirect_to_snapshot(&db_path, &snapshot.path());
+        snapshots.push(snapshot);
+    }
 
-    export_assets(asset_repo, copy_operation_factory, copy_strategy)
+    Ok(snapshots)
 }
 
-fn setup_asset_repo(db_path: String, args: &ExportArgs) -> AssetRepository {
-    let hidden_asset_filter = if args.include_hidden {
-        HiddenAssetsFilter::Include
-    } else if args.must_be_hidden {
-        HiddenAssetsFilter::Only
+fn setup_query_cache(db_path: &str, no_cache: bool) -> Option<QueryCache> {
+    if no_cache {
+        None
     } else {
-        HiddenAssetsFilter::Exclude
-    };
+        QueryCache::new(db_path)
+    }
+}
 
-    let album_filter = if let Some(ids) = args.include.clone() {
-        AlbumFilter::Include(ids)
-    } else if let Some(ids) = args.exclude.clone() {
-        AlbumFilter::Exclude(ids)
+
+fn run_photos_export(export_args: &ExportArgs, raw_args: &[String]) -> PhotosExportResult<()> {
+    let journal = setup_journal(export_args, raw_args);
+    if !export_args.force && journal.as_ref().is_some_and(|journal| journal.is_up_to_date()) {
+        println!(
+            "{} Neither the libraries nor the options have changed since the last export into this directory. Pass \
+            --force to export anyway.",
+            "Export already up to date.".green()
+        );
+        return Ok(());
+    }
+
+    let planning_benchmark = export_args.bench_planning.then(|| Rc::new(PlanningBenchmark::default()));
+    let sources = setup_export_sources(
+        &export_args.planning,
+        export_args.output_dir(),
+        setup_path_length_warnings(export_args),
+        &planning_benchmark
+    )?;
+    let conversion_fallbacks = Rc::new(ConversionFallbackRegistry::default());
+    let copy_strategy = setup_copy_strategy(export_args, &conversion_fallbacks);
+    let metadata_writers = setup_metadata_writers(export_args);
+    let run_log = export_args.run_log.clone().map(RunLog::open).transpose()?;
+
+    install_interrupt_handler()?;
+
+    let mut options = setup_export_options(export_args);
+    if export_args.prune_empty_dirs {
+        // Read before the export replaces the manifest
+        options = options
+            .with_pruning(read_manifest_paths(Path::new(export_args.output_dir()))?)
+            .with_confirm_deletions_above(Some(export_args.confirm_deletions_above));
+    }
+    if let Some(benchmark) = planning_benchmark {
+        options = options.with_planning_benchmark(benchmark);
+    }
+    if let Some(plan_path) = &export_args.plan_in {
+        options = options.with_plan(ExportPlan::read(plan_path)?);
+    }
+
+    let result = export_assets(
+        sources,
+        copy_strategy,
+        metadata_writers,
+        options,
+        PathBuf::from(export_args.output_dir()),
+        journal,
+        run_log,
+        setup_interaction(export_args)
+    );
+    print_conversion_fallbacks(&conversion_fallbacks);
+    result
+}
+
+fn print_conversion_fallbacks(registry: &ConversionFallbackRegistry) {
+    let fallbacks = registry.get_all();
+    if fallbacks.is_empty() {
+        return;
+    }
+
+    let mut fallbacks_by_kind = BTreeMap::<ConversionKind, Vec<(PathBuf, String)>>::new();
+    for (kind, path, error) in fallbacks {
+        fallbacks_by_kind.entry(kind).or_default().push((path, error));
+    }
+    for (kind, fallbacks) in fallbacks_by_kind {
+        println!(
+            "\n{} {} {} and have been copied unchanged:",
+            "Warning:".yellow(),
+            fallbacks.len(),
+            kind.failure_description()
+        );
+        for (path, error) in fallbacks {
+            println!("  - {} ({})", path.to_string_lossy().dimmed(), error);
+        }
+    }
+}
+
+fn run_export_verification(verify_args: &VerifyExportArgs) -> PhotosExportResult<()> {
+    let args = &verify_args.planning;
+    let sources = setup_export_sources(args, &verify_args.output_dir, None, &None)?;
+    let options = ExportOptions::new(false, false, false, None, args.case_sensitive_target, false);
+
+    verify_existing_export(sources, options, PathBuf::from(&verify_args.output_dir), verify_args.checksums)
+}
+
+fn run_export_audit(audit_args: &AuditArgs) -> PhotosExportResult<()> {
+    let args = &audit_args.planning;
+    let sources = setup_export_sources(args, &audit_args.output_dir, None, &None)?;
+    let options = ExportOptions::new(
+        false,
+        false,
+        false,
+        Some(ExportSubset::Sample(audit_args.sample, audit_args.seed)),
+        args.case_sensitive_target,
+        false
+    );
+
+    audit_existing_export(sources, options, PathBuf::from(&audit_args.output_dir))
+}
+
+fn run_export_diff(export_args: &ExportArgs, existing_dir: &Path) -> PhotosExportResult<()> {
+    let sources = setup_export_sources(
+        &export_args.planning,
+        export_args.output_dir(),
+        setup_path_length_warnings(export_args),
+        &None
+    )?;
+
+    diff_existing_export(
+        sources,
+        setup_export_options(export_args),
+        PathBuf::from(export_args.output_dir()),
+        existing_dir
+    )
+}
+
+fn run_structure_creation(export_args: &ExportArgs) -> PhotosExportResult<()> {
+    let sources = setup_export_sources(
+        &export_args.planning,
+        export_args.output_dir(),
+        setup_path_length_warnings(export_args),
+        &None
+    )?;
+
+    create_export_structure(
+        sources,
+        setup_export_options(export_args),
+        PathBuf::from(export_args.output_dir()),
+        export_args.dir_mode
+    )
+}
+
+fn run_pipeline_explanation(export_args: &ExportArgs) -> PhotosExportResult<()> {
+    // The stages only depend on the options, so any library can be used to assemble the pipeline
+    let library_path = &export_args.planning.library_paths()[0];
+
+    setup_copy_operation_pipeline(
+        library_path,
+        get_database_path(library_path),
+        &export_args.planning,
+        export_args.output_dir(),
+        &None,
+        &Rc::new(FlatOutputRegistry::default()),
+        &Rc::new(MissingDerivateRegistry::default()),
+        &Rc::new(RelocatedOriginalRegistry::default()),
+        &Rc::new(UnknownAlbumRegistry::default()),
+        &Rc::new(RenamedAlbumRegistry::default()),
+        &Rc::new(PipelineExclusionRegistry::default()),
+        &None
+    )?.print();
+
+    Ok(())
+}
+
+fn run_export_planning(export_args: &ExportArgs, plan_path: &Path) -> PhotosExportResult<()> {
+    let sources = setup_export_sources(
+        &export_args.planning,
+        export_args.output_dir(),
+        setup_path_length_warnings(export_args),
+        &None
+    )?;
+
+    write_export_plan(
+        sources,
+        setup_export_options(export_args),
+        PathBuf::from(export_args.output_dir()),
+        plan_path
+    )
+}
+
+fn setup_export_options(args: &ExportArgs) -> ExportOptions {
+    ExportOptions::new(
+        args.skip_empty_sources,
+        args.skip_duplicate_content,
+        args.metadata_only,
+        setup_export_subset(args),
+        args.planning.case_sensitive_target,
+        args.dry_run
+    )
+        .with_expected_count(ExpectedCount { at_least: args.expect_at_least, at_most: args.expect_at_most })
+        .with_auto_confirm_below(args.auto_confirm_below)
+        .with_max_duration(args.max_duration.and_then(|duration| duration.to_std().ok()))
+        // Nothing is written to the output directory in dry-run mode
+        .with_placeholders(args.write_placeholders && !args.dry_run)
+        .with_placeholder_removal(!args.dry_run)
+        .with_album_folder_dates(!args.dry_run)
+}
+
+fn setup_export_subset(args: &ExportArgs) -> Option<ExportSubset> {
+    match (args.limit, args.sample) {
+        (Some(count), _) => Some(ExportSubset::Limit(count)),
+        (_, Some(count)) => Some(ExportSubset::Sample(count, args.seed)),
+        _ => None,
+    }
+}
+
+/// Sets up the sources of all libraries to export, sharing the registry of a flat export.
+fn setup_export_sources(
+    args: &PlanningArgs,
+    output_dir: &str,
+    path_length_warnings: Option<Rc<dyn UserInteraction>>,
+    planning_benchmark: &Option<Rc<PlanningBenchmark>>
+) -> PhotosExportResult<Vec<ExportSource>> {
+    let flat_output_registry = Rc::new(FlatOutputRegistry::default());
+
+    args.library_paths()
+        .iter()
+        .map(|library_path| {
+            setup_export_source(
+                library_path,
+                args,
+                output_dir,
+                &path_length_warnings,
+                &flat_output_registry,
+                planning_benchmark
+            )
+        })
+        .collect()
+}
+
+fn setup_export_source(
+    library_path: &str,
+    args: &PlanningArgs,
+    output_dir: &str,
+    path_length_warnings: &Option<Rc<dyn UserInteraction>>,
+    flat_output_registry: &Rc<FlatOutputRegistry>,
+    planning_benchmark: &Option<Rc<PlanningBenchmark>>
+) -> PhotosExportResult<ExportSource> {
+    let db_path = get_database_path(library_path);
+
+    let name = PathBuf::from(library_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or(library_path.to_string());
+
+    warn_about_unsupported_albums(&db_path, args)?;
+
+    let missing_derivates = Rc::new(MissingDerivateRegistry::default());
+    let relocated_originals = Rc::new(RelocatedOriginalRegistry::default());
+    let unknown_albums = Rc::new(UnknownAlbumRegistry::default());
+    let renamed_albums = Rc::new(RenamedAlbumRegistry::default());
+    let excluded_assets = Rc::new(PipelineExclusionRegistry::default());
+
+    Ok(
+        ExportSource::new(
+            name,
+            PathBuf::from(library_path),
+            setup_asset_repo(db_path.clone(), args)?,
+            setup_copy_operation_pipeline(
+                library_path,
+                db_path.clone(),
+                args,
+                output_dir,
+                path_length_warnings,
+                flat_output_registry,
+                &missing_derivates,
+                &relocated_originals,
+                &unknown_albums,
+                &renamed_albums,
+                &excluded_assets,
+                planning_benchmark
+            )?.into_factory()
+        )
+            .with_missing_derivates(missing_derivates)
+            .with_relocated_originals(relocated_originals)
+            .with_unknown_albums(unknown_albums)
+            .with_renamed_albums(renamed_albums)
+            .with_pipeline_exclusions(excluded_assets)
+            .with_detached_albums(resolve_albums_by_size(&db_path, args)?)
+    )
+}
+
+fn warn_about_unsupported_albums(db_path: &str, args: &PlanningArgs) -> PhotosExportResult<()> {
+    let unsupported = AlbumRepository::new(
+        db_path.to_string(),
+        setup_query_cache(db_path, args.no_cache)
+    ).get_unsupported()?;
+
+    for album in unsupported {
+        eprintln!(
+            "{} Album '{}' has an unsupported kind ({}) and is not exported",
+            "Warning:".yellow(),
+            album.name.unwrap_or(String::from("unnamed")),
+            album.kind
+        );
+    }
+
+    Ok(())
+}
+
+fn setup_asset_repo(db_path: String, args: &PlanningArgs) -> PhotosExportResult<AssetRepository> {
+    let album_filter = if let Some(references) = &args.include {
+        AlbumFilter::Include(resolve_album_ids(&db_path, references, args)?)
+    } else if let Some(references) = &args.exclude {
+        AlbumFilter::Exclude(resolve_album_ids(&db_path, references, args)?)
+    } else if args.only_unalbumed {
+        AlbumFilter::Unalbumed
+    } else if args.albums_from.is_some() || args.albums_to.is_some() {
+        AlbumFilter::Include(resolve_albums_by_start_date(&db_path, args)?)
     } else {
         AlbumFilter::None
     };
+    let album_filter = if args.exclude_album_prefixes.is_empty() {
+        album_filter
+    } else {
+        let albums = AlbumRepository::new(db_path.clone(), setup_query_cache(&db_path, args.no_cache)).get_all()?;
+        album_filter.excluding(&with_name_prefix(&albums, &args.exclude_album_prefixes))
+    };
 
-    AssetRepository::new(db_path, hidden_asset_filter, album_filter)
+    let mut excluded_subtypes = vec![];
+    if args.exclude_screenshots {
+        excluded_subtypes.push(Subtype::Screenshot);
+    }
+    if args.exclude_screen_recordings {
+        excluded_subtypes.push(Subtype::ScreenRecording);
+    }
+
+    let cache = setup_query_cache(&db_path, args.no_cache);
+
+    let mut repo = AssetRepository::new(db_path, args.hidden_assets_filter(), album_filter, excluded_subtypes, cache);
+    if let Some(shard) = args.shard {
+        repo = repo.in_shard(shard);
+    }
+
+    match args.newer_than {
+        Some(duration) => Ok(repo.created_after(to_cocoa_timestamp(Utc::now() - duration))),
+        None => Ok(repo),
+    }
 }
 
-fn setup_copy_operation_factory(
-    db_path: String,
-    args: &ExportArgs
-) -> PhotosExportResult<Box<dyn CopyOperationFactory>> {
-    let factory: Box<dyn CopyOperationFactory> = Box::new(
-        AbsolutePathBuildingCopyOperationFactoryDecorator::new(
-            PathBuf::from(&args.library_path),
-            PathBuf::from(&args.output_dir),
-            Box::new(
-                OutputStructureCopyOperationFactoryDecorator::new(
-                    if args.include_edited {
-                        Box::new(
-                            CombiningCopyOperationFactory::new(
-                                vec![
-                                    Box::new(
-                                        SuffixSettingCopyOperationFactoryDecorator::new(
-                                            Box::new(OriginalsCopyOperationFactory::new()),
-                                            "_original".to_string()
-                                        )
-                                    ),
-                                    Box::new(DerivatesCopyOperationFactory::new())
-                                ]
-                            )
-                        )
-                    } else if args.only_edited {
-                        Box::new(DerivatesCopyOperationFactory::new())
-                    } else {
-                        Box::new(OriginalsCopyOperationFactory::new())
-                    },
-                    setup_output_strategy(db_path, args)?
-                )
-            )
-        )
-    );
+/// Checks the albums given by `-i` or `-x`, warning about UUIDs that do not match any album in any
+/// of the exported libraries.
+fn check_album_references(args: &PlanningArgs) -> PhotosExportResult<()> {
+    for uuid in find_unknown_album_uuids(args)? {
+        eprintln!("{} No album with UUID '{}' found", "Warning:".yellow(), uuid);
+    }
+    Ok(())
+}
+
+/// Returns the UUIDs given by `-i` or `-x` that do not match any album in any of the exported
+/// libraries. Fails if albums are given by id while merging libraries, as ids are only unique within
+/// a library.
+fn find_unknown_album_uuids(args: &PlanningArgs) -> PhotosExportResult<Vec<String>> {
+    let references: Vec<&AlbumReference> = args.include.iter().chain(args.exclude.iter()).flatten().collect();
+    let library_paths = args.library_paths();
+
+    if library_paths.len() > 1 {
+        if let Some(AlbumReference::Id(id)) = references.iter().find(|r| matches!(r, AlbumReference::Id(_))) {
+            return Err(format!(
+                "Album ids are specific to a library, select album {} by its UUID when merging libraries",
+                id
+            ).into());
+        }
+    }
+
+    let uuids: Vec<&String> = references
+        .iter()
+        .filter_map(|reference| match reference {
+            AlbumReference::Uuid(uuid) => Some(uuid),
+            AlbumReference::Id(_) => None,
+        })
+        .collect();
+    if uuids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut known = HashSet::new();
+    for library_path in library_paths {
+        let db_path = get_database_path(&library_path);
+        let albums = AlbumRepository::new(db_path.clone(), setup_query_cache(&db_path, args.no_cache)).get_all()?;
+        known.extend(albums.into_iter().filter_map(|album| album.uuid));
+    }
+
+    Ok(uuids.into_iter().filter(|uuid| !known.contains(*uuid)).cloned().collect())
+}
+
+/// Resolves the given album references to the ids of the albums in the given database. UUIDs that
+/// do not match any album in the database are skipped, as they may belong to another library.
+fn resolve_album_ids(
+    db_path: &str,
+    references: &[AlbumReference],
+    args: &PlanningArgs
+) -> PhotosExportResult<Vec<i32>> {
+    let albums = AlbumRepository::new(
+        db_path.to_string(),
+        setup_query_cache(db_path, args.no_cache)
+    ).get_all()?;
+
+    let ids: Vec<i32> = references
+        .iter()
+        .filter_map(|reference| match reference {
+            AlbumReference::Id(id) => Some(*id),
+            AlbumReference::Uuid(uuid) => {
+                albums.iter().find(|a| a.uuid.as_ref() == Some(uuid)).map(|a| a.id)
+            }
+        })
+        .collect();
+
+    if args.recursive_album_filter {
+        Ok(with_descendants(&albums, &ids))
+    } else {
+        Ok(ids)
+    }
+}
+
+/// Resolves the ids of the albums selected by `--albums-from` and `--albums-to`.
+fn resolve_albums_by_start_date(db_path: &str, args: &PlanningArgs) -> PhotosExportResult<Vec<i32>> {
+    if let (Some(from), Some(to)) = (args.albums_from, args.albums_to) {
+        if from > to {
+            return Err(format!("--albums-from ({}) must not be after --albums-to ({})", from, to).into());
+        }
+    }
+
+    let albums = AlbumRepository::new(
+        db_path.to_string(),
+        setup_query_cache(db_path, args.no_cache)
+    ).get_all()?;
+
+    Ok(with_start_date_between(&albums, args.albums_from, args.albums_to)?)
+}
+
+/// Resolves the ids of the albums skipped by `--min-album-size` and `--max-album-size`, whose assets
+/// are exported as if they were in no album.
+fn resolve_albums_by_size(db_path: &str, args: &PlanningArgs) -> PhotosExportResult<HashSet<i32>> {
+    if args.min_album_size.is_none() && args.max_album_size.is_none() {
+        return Ok(HashSet::new());
+    }
+    if let (Some(min), Some(max)) = (args.min_album_size, args.max_album_size) {
+        if min > max {
+            return Err(format!("--min-album-size ({}) must not be greater than --max-album-size ({})", min, max).into());
+        }
+    }
+
+    let repo = AlbumRepository::new(db_path.to_string(), setup_query_cache(db_path, args.no_cache));
 
     Ok(
-        if args.restore_original_filenames {
-            Box::new(
-                FilenameRestoringCopyOperationFactoryDecorator::new(factory)
+        with_asset_count_outside(&repo.get_all()?, &repo.get_asset_counts()?, args.min_album_size, args.max_album_size)
+            .into_iter()
+            .collect()
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup_copy_operation_pipeline(
+    library_path: &str,
+    db_path: String,
+    args: &PlanningArgs,
+    output_dir: &str,
+    path_length_warnings: &Option<Rc<dyn UserInteraction>>,
+    flat_output_registry: &Rc<FlatOutputRegistry>,
+    missing_derivates: &Rc<MissingDerivateRegistry>,
+    relocated_originals: &Rc<RelocatedOriginalRegistry>,
+    unknown_albums: &Rc<UnknownAlbumRegistry>,
+    renamed_albums: &Rc<RenamedAlbumRegistry>,
+    excluded_assets: &Rc<PipelineExclusionRegistry>,
+    planning_benchmark: &Option<Rc<PlanningBenchmark>>
+) -> PhotosExportResult<CopyOperationPipeline> {
+    let file_type_filter = if !args.include_ext.is_empty() {
+        Some(UtiFilter::Include(args.include_ext.clone()))
+    } else if !args.exclude_ext.is_empty() {
+        Some(UtiFilter::Exclude(args.exclude_ext.clone()))
+    } else {
+        None
+    };
+    let camera_filter = if !args.include_cameras.is_empty() {
+        Some(CameraFilter::Include(args.include_cameras.clone()))
+    } else if !args.exclude_cameras.is_empty() {
+        Some(CameraFilter::Exclude(args.exclude_cameras.clone()))
+    } else {
+        None
+    };
+
+    let output_strategy = if args.keep_library_structure {
+        None
+    } else {
+        Some(setup_output_strategy(db_path, args, unknown_albums, renamed_albums)?)
+    };
+
+    let date_tree_strategy: Option<Box<dyn OutputStrategy>> = args.also_by_date.then(|| {
+        Box::new(
+            HiddenAssetHandlingOutputStrategyDecorator::new(
+                Box::new(with_month_names(DateOutputStrategy::asset_date_based(DateGranularity::Month), args))
             )
-        } else {
-            factory
+        ) as Box<dyn OutputStrategy>
+    });
+
+    let options = PipelineOptions {
+        library_path: PathBuf::from(library_path),
+        output_dir: PathBuf::from(output_dir),
+        missing_derivates: missing_derivates.clone(),
+        relocated_originals: relocated_originals.clone(),
+        excluded_assets: excluded_assets.clone(),
+        include_edited: args.include_edited,
+        prefer_edited: args.prefer_edited,
+        slomo: args.slomo,
+        include_companion_resources: args.include_companion_resources,
+        skip_referenced: args.skip_referenced,
+        only_edited: args.edited_assets_only,
+        exclude_date_ranges: args.exclude_date_ranges.clone(),
+        file_type_filter,
+        camera_filter,
+        convert_raw_to_dng: args.convert_raw_to_dng,
+        transcode_video: args.transcode_video.is_some(),
+        keep_library_structure: args.keep_library_structure,
+        output_strategy,
+        date_tree_strategy,
+        group_by_keyword: args.group_by_keyword,
+        favorites_subdir: args.favorites_subdir,
+        raw_subdir: args.raw_subdir.clone(),
+        restore_original_filenames: args.restore_original_filenames,
+        use_titles: args.use_titles,
+        stable_names: args.stable_names.map(|naming| naming == StableNaming::Date),
+        album_order_prefixes: args.preserve_album_order == Some(AlbumOrderPreservation::Prefix),
+        flat_output_registry: args.flat_frame_mode.then(|| flat_output_registry.clone()),
+        max_path_length: args.max_path_length,
+        path_length_warnings: path_length_warnings.clone(),
+    };
+
+    Ok(CopyOperationPipeline::build(options, planning_benchmark))
+}
+
+/// Returns the albums used to build the output structure, with the names of albums that would be
+/// exported to the same folder disambiguated if requested.
+fn get_output_albums(
+    album_repo: &AlbumRepository,
+    args: &PlanningArgs,
+    renamed_albums: &RenamedAlbumRegistry
+) -> PhotosExportResult<Vec<AlbumDto>> {
+    let mut albums = album_repo.get_all()?;
+
+    if let Some(overrides) = &args.album_names {
+        let (renamed, unmatched) = overrides.apply(albums);
+        for reference in unmatched {
+            eprintln!("{} No album '{}' found to rename", "Warning:".yellow(), reference);
         }
-    )
+        albums = renamed;
+    }
+
+    let Some(mode) = args.disambiguate_album_names else {
+        for name in find_ambiguous_album_names(&albums, args.flatten_albums) {
+            eprintln!(
+                "{} Multiple albums named '{}' are exported to the same folder. Pass --disambiguate-album-names to \
+                tell them apart.",
+                "Warning:".yellow(),
+                name
+            );
+        }
+        return Ok(albums);
+    };
+
+    // Listed in the summary of the export
+    let (albums, renamed) = disambiguate_album_names(albums, args.flatten_albums, mode);
+    renamed_albums.record(renamed);
+
+    Ok(albums)
+}
+
+fn with_month_names(strategy: DateOutputStrategy, args: &PlanningArgs) -> DateOutputStrategy {
+    match args.locale {
+        Some(locale) => strategy.with_month_names(locale),
+        None => strategy,
+    }
+}
+
+fn setup_album_output_strategy(
+    album_repo: &AlbumRepository,
+    args: &PlanningArgs,
+    unknown_albums: &Rc<UnknownAlbumRegistry>,
+    renamed_albums: &Rc<RenamedAlbumRegistry>
+) -> PhotosExportResult<AlbumOutputStrategy> {
+    let strategy = AlbumOutputStrategy::new(args.flatten_albums, get_output_albums(album_repo, args, renamed_albums)?)
+        .with_unknown_album_fallback(unknown_albums.clone());
+
+    Ok(with_album_folder_template(strategy, args))
+}
+
+fn with_album_folder_template(strategy: AlbumOutputStrategy, args: &PlanningArgs) -> AlbumOutputStrategy {
+    match &args.album_folder_template {
+        Some(template) => strategy.with_folder_template(template.clone()),
+        None => strategy,
+    }
 }
 
 fn setup_output_strategy(
     db_path: String,
-    args: &ExportArgs
+    args: &PlanningArgs,
+    unknown_albums: &Rc<UnknownAlbumRegistry>,
+    renamed_albums: &Rc<RenamedAlbumRegistry>
 ) -> PhotosExportResult<Box<dyn OutputStrategy>> {
+    let cache = setup_query_cache(&db_path, args.no_cache);
+    let album_repo = AlbumRepository::new(db_path, cache);
 
     let strategy: Box<dyn OutputStrategy> = if args.album {
-        Box::new(
-            AlbumOutputStrategy::new(
-                args.flatten_albums,
-                AlbumRepository::new(db_path).get_all()?
-            )
-        )
+        Box::new(setup_album_output_strategy(&album_repo, args, unknown_albums, renamed_albums)?)
     } else if args.year_month {
-        Box::new(YearMonthOutputStrategy::asset_date_based())
+        Box::new(with_month_names(DateOutputStrategy::asset_date_based(DateGranularity::Month), args))
+    } else if let Some(granularity) = args.group_by_date {
+        Box::new(with_month_names(DateOutputStrategy::asset_date_based(granularity), args))
     } else if args.year_month_album {
         Box::new(
             NestingOutputStrategyDecorator::new(
                 vec![
-                    Box::new(YearMonthOutputStrategy::album_date_based()),
                     Box::new(
-                        AlbumOutputStrategy::new(
-                            args.flatten_albums,
-                            AlbumRepository::new(db_path).get_all()?
-                        )
-                    )
+                        with_month_names(DateOutputStrategy::album_date_based(DateGranularity::Month), args)
+                    ),
+                    Box::new(setup_album_output_strategy(&album_repo, args, unknown_albums, renamed_albums)?)
                 ]
             )
         )
+    } else if args.group_by_camera {
+        Box::new(CameraOutputStrategy::new())
     } else {
         Box::new(PlainOutputStrategy::new())
     };
@@ -270,10 +1646,275 @@ fn setup_output_strategy(
     )
 }
 
-fn setup_copy_strategy(dry_run: bool) -> Box<dyn AssetCopyStrategy> {
-    if dry_run {
-        Box::new(DryRunAssetCopyStrategy::new())
+fn setup_copy_strategy(
+    args: &ExportArgs,
+    conversion_fallbacks: &Rc<ConversionFallbackRegistry>
+) -> Box<dyn AssetCopyStrategy> {
+    if args.dry_run {
+        return Box::new(DryRunAssetCopyStrategy::new());
+    }
+
+    let mut strategy: Box<dyn AssetCopyStrategy> = Box::new(DefaultAssetCopyStrategy::new());
+
+    if args.resumable_copies {
+        strategy = Box::new(ResumableAssetCopyStrategyDecorator::new(strategy, RESUMABLE_COPY_MIN_SIZE));
+    }
+
+    if args.planning.convert_raw_to_dng {
+        strategy = Box::new(
+            ConvertingAssetCopyStrategyDecorator::new(
+                strategy,
+                Box::new(
+                    ExternalDngConverter::new(args.dng_converter.split_whitespace().map(String::from).collect())
+                )
+            )
+        );
+    }
+
+    if let Some(codec) = args.planning.transcode_video {
+        strategy = Box::new(
+            ConvertingAssetCopyStrategyDecorator::new(
+                strategy,
+                Box::new(FfmpegVideoTranscoder::new(args.ffmpeg.split_whitespace().map(String::from).collect(), codec))
+            ).with_fallback(conversion_fallbacks.clone())
+        );
+    }
+
+    if args.file_mode.is_some() || args.dir_mode.is_some() {
+        strategy = Box::new(
+            PermissionSettingAssetCopyStrategyDecorator::new(strategy, args.file_mode, args.dir_mode)
+        );
+    }
+
+    if args.fsync {
+        strategy = Box::new(SyncingAssetCopyStrategyDecorator::new(strategy));
+    }
+
+    if args.paranoid {
+        strategy = Box::new(
+            LibraryProtectingAssetCopyStrategyDecorator::new(strategy, args.planning.library_paths())
+        );
+    }
+
+    strategy
+}
+
+fn setup_journal(args: &ExportArgs, raw_args: &[String]) -> Option<ExportJournal> {
+    // Nothing is copied in dry-run and metadata-only mode, so there is nothing to resume
+    if args.dry_run || args.metadata_only {
+        return None;
+    }
+
+    let journal = ExportJournal::new(&PathBuf::from(args.output_dir()), args.resume);
+    // Exports of a plan or of a random sample may differ even if the libraries have not changed
+    let reproducible = args.plan_in.is_none() && (args.sample.is_none() || args.seed.is_some());
+    let db_paths: Vec<String> = args.planning.library_paths().iter().map(|path| get_database_path(path)).collect();
+    match ExportFingerprint::new(&db_paths, &raw_args[1..]).filter(|_| reproducible) {
+        Some(fingerprint) => Some(journal.with_fingerprint(fingerprint)),
+        None => Some(journal),
+    }
+}
+
+/// Returns the interaction to warn about each shortened output path on, which is only done in a
+/// dry run.
+fn setup_path_length_warnings(args: &ExportArgs) -> Option<Rc<dyn UserInteraction>> {
+    args.dry_run.then(|| setup_interaction(args))
+}
+
+fn setup_interaction(args: &ExportArgs) -> Rc<dyn UserInteraction> {
+    if args.non_interactive {
+        Rc::new(NonInteractive::new(Answer::Yes))
     } else {
-        Box::new(DefaultAssetCopyStrategy::new())
+        Rc::new(TerminalInteraction)
+    }
+}
+
+fn setup_metadata_writers(args: &ExportArgs) -> Vec<Box<dyn MetadataWriter>> {
+    let mut writers: Vec<Box<dyn MetadataWriter>> = vec![];
+
+    // Metadata files are not written in dry-run mode as nothing is written to the output directory
+    if args.dry_run {
+        return writers;
+    }
+
+    if args.write_locations {
+        writers.push(
+            Box::new(GeoJsonMetadataWriter::new(PathBuf::from(args.output_dir())))
+        );
+    }
+
+    if args.planning.preserve_album_order == Some(AlbumOrderPreservation::IndexFile) {
+        writers.push(Box::new(AlbumOrderIndexWriter::new()));
+    }
+
+    if args.write_manifest {
+        writers.push(
+            Box::new(ManifestMetadataWriter::new(PathBuf::from(args.output_dir())))
+        );
+    }
+
+    if let Some(csv_path) = &args.exiftool_csv {
+        writers.push(Box::new(ExifToolCsvMetadataWriter::new(csv_path.clone())));
+    }
+
+    if args.write_xattrs {
+        writers.push(Box::new(ProvenanceXattrWriter::new(Utc::now())));
+    }
+
+    writers
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::test_util::library::{SyntheticLibrary, TestAlbum};
+
+    fn parse_export(library: &SyntheticLibrary, flags: &[&str]) -> Result<Arguments, clap::Error> {
+        let output = library.path().parent().unwrap().join("out");
+        let library_path = library.path().to_string_lossy().to_string();
+        let output_path = output.to_string_lossy().to_string();
+
+        Arguments::try_parse_from(
+            [vec!["apple-photos-export", "export", &library_path, &output_path], flags.to_vec()].concat()
+        )
+    }
+
+    #[test]
+    fn has_consistent_arguments() {
+        Arguments::command().debug_assert();
+    }
+
+    #[test]
+    fn rejects_options_without_effect_on_the_grouping() {
+        let library = SyntheticLibrary::new();
+
+        assert!(parse_export(&library, &["-a", "-f", "--disambiguate-album-names", "id"]).is_ok());
+        assert!(parse_export(&library, &["-M", "--locale", "de-DE", "--preserve-album-order", "prefix"]).is_ok());
+        assert!(parse_export(&library, &["-x", "2", "--recursive-album-filter"]).is_ok());
+
+        assert!(parse_export(&library, &["-m", "-f"]).is_err());
+        assert!(parse_export(&library, &["--preserve-album-order", "prefix"]).is_err());
+        assert!(parse_export(&library, &["-a", "--locale", "de-DE"]).is_err());
+        assert!(parse_export(&library, &["-a", "--also-by-date", "--locale", "de-DE"]).is_ok());
+        assert!(parse_export(&library, &["-m", "--also-by-date"]).is_err());
+        assert!(parse_export(&library, &["--only-unalbumed", "--recursive-album-filter"]).is_err());
+    }
+
+    #[test]
+    fn only_accepts_the_library_path_and_global_options_along_with_repeat_last() {
+        let library = SyntheticLibrary::new();
+        let library_path = library.path().to_string_lossy().to_string();
+        let output_path = library.path().parent().unwrap().join("out").to_string_lossy().to_string();
+        let has_other_options = |args: &[&str]| {
+            let matches = Arguments::command()
+                .try_get_matches_from([vec!["apple-photos-export"], args.to_vec()].concat())
+                .unwrap();
+            has_options_besides_repeat_last(matches.subcommand().unwrap().1)
+        };
+
+        assert!(!has_other_options(&["export", &library_path, "--repeat-last"]));
+        assert!(!has_other_options(&["export", "--repeat-last", &library_path]));
+        assert!(!has_other_options(&["--no-color", "export", &library_path, "--repeat-last"]));
+        assert!(!has_other_options(&["export", &library_path, "--repeat-last", "--no-color"]));
+        assert!(has_other_options(&["export", &library_path, &output_path, "--repeat-last"]));
+        assert!(has_other_options(&["export", &library_path, "--repeat-last", "-a"]));
+        assert!(has_other_options(&["export", &library_path, "--repeat-last", "--hidden", "include"]));
+
+        assert!(Arguments::try_parse_from(["apple-photos-export", "export", &library_path]).is_err());
+    }
+
+    /// Flags only affecting how the planned files are copied, which commands not copying files reject
+    const EXPORT_ONLY_FLAGS: [&[&str]; 8] = [
+        &["--dry-run"], &["--plan-out", "plan.json"], &["--resume"], &["--fsync"], &["--write-placeholders"],
+        &["--structure-only"], &["--force"], &["--repeat-last"]
+    ];
+
+    fn parse_command(library: &SyntheticLibrary, command: &str, flags: &[&str]) -> Result<Arguments, clap::Error> {
+        let library_path = library.path().to_string_lossy().to_string();
+        let output_path = library.path().parent().unwrap().join("out").to_string_lossy().to_string();
+
+        Arguments::try_parse_from(
+            [vec!["apple-photos-export", command, &library_path, &output_path], flags.to_vec()].concat()
+        )
+    }
+
+    #[test]
+    fn only_accepts_planning_options_along_with_the_sample_size_for_audits() {
+        let library = SyntheticLibrary::new();
+        let audit = |flags: &[&str]| parse_command(&library, "audit", flags);
+
+        assert!(audit(&["--sample", "5", "--seed", "1", "-a", "--hidden", "include", "--stable-names"]).is_ok());
+        assert!(audit(&[]).is_err());
+        for flags in EXPORT_ONLY_FLAGS {
+            assert!(audit(&[&["--sample", "5"], flags].concat()).is_err(), "{:?}", flags);
+        }
+    }
+
+    #[test]
+    fn only_accepts_planning_options_for_verifying_exports() {
+        let library = SyntheticLibrary::new();
+        let verify = |flags: &[&str]| parse_command(&library, "verify-export", flags);
+
+        assert!(verify(&["--checksums", "-a", "--hidden", "include", "--stable-names"]).is_ok());
+        for flags in EXPORT_ONLY_FLAGS {
+            assert!(verify(flags).is_err(), "{:?}", flags);
+        }
+    }
+
+    #[test]
+    fn maps_hidden_options_to_the_hidden_assets_filter() {
+        let library = SyntheticLibrary::new();
+        let filter = |flags: &[&str]| match parse_export(&library, flags).unwrap().command {
+            Commands::Export(args) => args.planning.hidden_assets_filter(),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(filter(&[]), HiddenAssetsFilter::Exclude);
+        assert_eq!(filter(&["--hidden", "exclude"]), HiddenAssetsFilter::Exclude);
+        assert_eq!(filter(&["--hidden", "include"]), HiddenAssetsFilter::Include);
+        assert_eq!(filter(&["--hidden", "only"]), HiddenAssetsFilter::Only);
+        assert_eq!(filter(&["-H"]), HiddenAssetsFilter::Include);
+        assert_eq!(filter(&["--include-hidden"]), HiddenAssetsFilter::Include);
+        assert_eq!(filter(&["--must-be-hidden"]), HiddenAssetsFilter::Only);
+
+        assert!(parse_export(&library, &["--hidden", "only", "-H"]).is_err());
+        assert!(parse_export(&library, &["--hidden", "exclude", "--must-be-hidden"]).is_err());
+        assert!(parse_export(&library, &["-H", "--must-be-hidden"]).is_err());
+        assert!(parse_export(&library, &["--hidden", "sometimes"]).is_err());
+    }
+
+    #[test]
+    fn does_not_reuse_the_former_long_name_of_prefer_edited() {
+        let library = SyntheticLibrary::new();
+        let edited_assets_only = |flags: &[&str]| match parse_export(&library, flags).unwrap().command {
+            Commands::Export(args) => (args.planning.edited_assets_only, args.planning.prefer_edited.is_some()),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(edited_assets_only(&["--edited-assets-only"]), (true, false));
+        assert_eq!(edited_assets_only(&["--edited-assets-only", "-E"]), (true, true));
+        assert!(parse_export(&library, &["--only-edited"]).is_err());
+    }
+
+    #[test]
+    fn requires_album_uuids_when_merging_libraries() {
+        let library = SyntheticLibrary::new();
+        library.add_album(&TestAlbum::album(2, "Summer"));
+        let merged = SyntheticLibrary::new();
+        merged.add_album(&TestAlbum::album(3, "Winter"));
+        let merged_path = merged.path().to_string_lossy().to_string();
+        let unknown_uuids = |flags: &[&str]| match parse_export(&library, flags).unwrap().command {
+            Commands::Export(args) => find_unknown_album_uuids(&args.planning),
+            _ => unreachable!(),
+        };
+
+        assert!(unknown_uuids(&["-i", "2"]).is_ok());
+        assert!(unknown_uuids(&["-L", &merged_path, "-i", "2"]).is_err());
+        assert!(unknown_uuids(&["-L", &merged_path, "-x", "ALBUM-2", "3"]).is_err());
+        assert_eq!(
+            unknown_uuids(&["-L", &merged_path, "-i", "ALBUM-2", "ALBUM-3", "ALBUM-4"]).unwrap(),
+            ["ALBUM-4"]
+        );
     }
 }
\ No newline at end of file
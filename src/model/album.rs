@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use chrono::NaiveDateTime;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
@@ -7,11 +9,20 @@ pub enum Kind {
     Root = 3999,
     UserFolder= 4000,
     UserAlbum = 2,
+    /// Album of a project, e.g. a book or calendar, also used for projects migrated from Aperture
+    Project = 1508,
+    /// Album whose assets are determined by rules instead of being added by the user. Smart albums
+    /// are listed, but not exported.
+    SmartAlbum = 1507,
 }
 
 impl Kind {
+    /// Returns the values of the kinds whose assets are exported.
     pub fn int_values() -> Vec<i32> {
-        Kind::iter().map(|k| k as i32).collect()
+        Kind::iter()
+            .filter(|k| *k != Kind::SmartAlbum)
+            .map(|k| k as i32)
+            .collect()
     }
 }
 
@@ -23,6 +34,8 @@ impl TryFrom<i32> for Kind {
             3999 => Ok(Kind::Root),
             4000 => Ok(Kind::UserFolder),
             2 => Ok(Kind::UserAlbum),
+            1508 => Ok(Kind::Project),
+            1507 => Ok(Kind::SmartAlbum),
             _ => Err(format!("Invalid album kind: {}", value)),
         }
     }
@@ -32,9 +45,29 @@ impl TryFrom<i32> for Kind {
 #[derive(Clone)]
 pub struct Album {
     pub id: i32,
+    pub uuid: Option<String>,
     pub kind: Kind,
     pub name: Option<String>,
     pub parent_id: Option<i32>,
     pub start_date: Option<NaiveDateTime>,
     pub trashed: bool,
+}
+
+/// Reference to an album given by the user, either by its id or by its UUID.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AlbumReference {
+    Id(i32),
+    Uuid(String),
+}
+
+impl FromStr for AlbumReference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<i32>() {
+            Ok(id) => Ok(AlbumReference::Id(id)),
+            Err(_) if !s.is_empty() => Ok(AlbumReference::Uuid(s.to_string())),
+            Err(_) => Err("Album reference must not be empty".to_string()),
+        }
+    }
 }
\ No newline at end of file
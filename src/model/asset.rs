@@ -5,6 +5,162 @@ use chrono::NaiveDateTime;
 use crate::model::album::Album;
 use crate::model::uti::Uti;
 
+/// Subtype of an asset as stored in the database.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Subtype {
+    Default,
+    Panorama,
+    LivePhoto,
+    Screenshot,
+    SlowMotion,
+    TimeLapse,
+    ScreenRecording,
+    Unknown(i32),
+}
+
+impl Subtype {
+
+    /// Whether the asset is a video whose playback is rendered differently from the original
+    /// recording, i.e. a slow-motion or time-lapse video.
+    pub fn is_rendered_video_variant(&self) -> bool {
+        matches!(self, Subtype::SlowMotion | Subtype::TimeLapse)
+    }
+}
+
+impl From<i32> for Subtype {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Subtype::Default,
+            1 => Subtype::Panorama,
+            2 => Subtype::LivePhoto,
+            10 => Subtype::Screenshot,
+            101 => Subtype::SlowMotion,
+            102 => Subtype::TimeLapse,
+            103 => Subtype::ScreenRecording,
+            _ => Subtype::Unknown(value),
+        }
+    }
+}
+
+/// EXIF orientation of an asset, describing how its pixels have to be transformed to be displayed
+/// upright. Unknown values are treated as upright.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Orientation {
+    #[default]
+    Upright,
+    MirroredHorizontally,
+    Rotated180,
+    MirroredVertically,
+    Transposed,
+    Rotated90Clockwise,
+    Transversed,
+    Rotated90CounterClockwise,
+}
+
+impl Orientation {
+
+    /// The ffmpeg video filter rotating the pixels upright, `None` if they already are.
+    pub fn ffmpeg_filter(&self) -> Option<&'static str> {
+        match self {
+            Orientation::Upright => None,
+            Orientation::MirroredHorizontally => Some("hflip"),
+            Orientation::Rotated180 => Some("hflip,vflip"),
+            Orientation::MirroredVertically => Some("vflip"),
+            Orientation::Transposed => Some("transpose=cclock_flip"),
+            Orientation::Rotated90Clockwise => Some("transpose=clock"),
+            Orientation::Transversed => Some("transpose=clock_flip"),
+            Orientation::Rotated90CounterClockwise => Some("transpose=cclock"),
+        }
+    }
+}
+
+impl From<i32> for Orientation {
+    fn from(value: i32) -> Self {
+        match value {
+            2 => Orientation::MirroredHorizontally,
+            3 => Orientation::Rotated180,
+            4 => Orientation::MirroredVertically,
+            5 => Orientation::Transposed,
+            6 => Orientation::Rotated90Clockwise,
+            7 => Orientation::Transversed,
+            8 => Orientation::Rotated90CounterClockwise,
+            _ => Orientation::Upright,
+        }
+    }
+}
+
+impl From<Orientation> for i32 {
+    fn from(orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::Upright => 1,
+            Orientation::MirroredHorizontally => 2,
+            Orientation::Rotated180 => 3,
+            Orientation::MirroredVertically => 4,
+            Orientation::Transposed => 5,
+            Orientation::Rotated90Clockwise => 6,
+            Orientation::Transversed => 7,
+            Orientation::Rotated90CounterClockwise => 8,
+        }
+    }
+}
+
+/// Width and height of an asset's original in pixels as stored, i.e. before applying its
+/// orientation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Dimensions {
+
+    /// Returns the dimensions unless either of them is missing or zero, e.g. for assets imported
+    /// by older versions of Photos.
+    pub fn from_pixels(width: Option<i32>, height: Option<i32>) -> Option<Dimensions> {
+        match (width, height) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => {
+                Some(Dimensions { width: width as u32, height: height as u32 })
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Value Photos uses for both coordinates of assets without location information.
+const NO_LOCATION_COORDINATE: f64 = -180.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Location {
+
+    pub fn from_coordinates(latitude: f64, longitude: f64) -> Option<Location> {
+        if latitude == NO_LOCATION_COORDINATE && longitude == NO_LOCATION_COORDINATE {
+            None
+        } else {
+            Some(Location { latitude, longitude })
+        }
+    }
+}
+
+impl From<Subtype> for i32 {
+    fn from(value: Subtype) -> Self {
+        match value {
+            Subtype::Default => 0,
+            Subtype::Panorama => 1,
+            Subtype::LivePhoto => 2,
+            Subtype::Screenshot => 10,
+            Subtype::SlowMotion => 101,
+            Subtype::TimeLapse => 102,
+            Subtype::ScreenRecording => 103,
+            Subtype::Unknown(value) => value,
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub struct ExportAsset {
     pub id: i32,
@@ -17,20 +173,62 @@ pub struct ExportAsset {
     pub datetime: NaiveDateTime,
     pub favorite: bool,
     pub hidden: bool,
+    pub trashed: bool,
+    /// Raw visibility state of the asset, `0` for assets visible in the library
+    pub visibility_state: i32,
     pub original_filename: String,
     pub has_adjustments: bool,
+    pub subtype: Subtype,
+    /// EXIF orientation of the original. Derivates are always rendered upright.
+    pub orientation: Orientation,
+    pub location: Option<Location>,
+    /// Pixel dimensions of the original, unknown if the library does not record them
+    pub dimensions: Option<Dimensions>,
+    /// Duration of videos in seconds
+    pub duration: Option<f64>,
     pub album: Option<Album>,
+    /// 1-based position of the asset in the manual sort order of its album
+    pub album_position: Option<usize>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    /// Name of the person who contributed the asset to a shared album or library
+    pub contributor: Option<String>,
+    /// Absolute path of the original if it is referenced, i.e. stored outside the library
+    pub referenced_path: Option<PathBuf>,
 }
 
 impl ExportAsset {
 
+    /// Returns the path of the original relative to the library root, or its absolute path if the
+    /// original is referenced.
     pub fn get_path(&self) -> PathBuf {
+        if let Some(path) = &self.referenced_path {
+            return path.clone();
+        }
+
         PathBuf::new()
             .join("originals")
             .join(&self.dir)
             .join(&self.filename)
     }
 
+    /// Returns the locations the original may be stored at relative to the library root, starting
+    /// with [Self::get_path]. Libraries migrated from iPhoto or Aperture may still keep some
+    /// originals in their former `Masters` directory.
+    pub fn get_path_candidates(&self) -> Vec<PathBuf> {
+        vec![
+            self.get_path(),
+            PathBuf::new()
+                .join("Masters")
+                .join(&self.dir)
+                .join(&self.filename),
+        ]
+    }
+
     pub fn get_derivate_path(&self) -> Option<PathBuf> {
         if !self.has_adjustments {
             return None
@@ -52,4 +250,21 @@ impl ExportAsset {
 
         Some(derivate_path)
     }
+
+    /// Returns the name of the camera the asset has been taken with, e.g. `Apple iPhone 15 Pro`.
+    /// The make is omitted if the model already starts with it, e.g. `Canon EOS R5`.
+    pub fn get_camera_name(&self) -> Option<String> {
+        let make = self.camera_make.as_deref().map(str::trim).filter(|m| !m.is_empty());
+        let model = self.camera_model.as_deref().map(str::trim).filter(|m| !m.is_empty());
+
+        match (make, model) {
+            (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => {
+                Some(model.to_string())
+            },
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (None, Some(model)) => Some(model.to_string()),
+            (Some(make), None) => Some(make.to_string()),
+            (None, None) => None,
+        }
+    }
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
+use MediaType::{Photo, Video};
+
 const UTI_HEIC: &str = "public.heic";
 const UTI_JPEG: &str = "public.jpeg";
 const UTI_PNG: &str = "public.png";
@@ -35,23 +37,31 @@ const EXTENSION_MOV: &str = "mov";
 const PICTURE_DERIVATE_SUFFIX: &str = "_1_201_a";
 const VIDEO_DERIVATE_SUFFIX: &str = "_2_0_a";
 
-static HEIC: Uti = Uti::new(UTI_HEIC, COMPACT_UTI_HEIC, PICTURE_DERIVATE_SUFFIX, EXTENSION_HEIC);
-static JPEG: Uti = Uti::new(UTI_JPEG, COMPACT_UTI_JPEG, PICTURE_DERIVATE_SUFFIX, EXTENSION_JPEG);
-static JPG: Uti = Uti::new(UTI_JPEG, COMPACT_UTI_JPEG, PICTURE_DERIVATE_SUFFIX, EXTENSION_JPG);
-static PNG: Uti = Uti::new(UTI_PNG, COMPACT_UTI_PNG, PICTURE_DERIVATE_SUFFIX, EXTENSION_PNG);
-static GIF: Uti = Uti::new(UTI_GIF, COMPACT_UTI_GIF, PICTURE_DERIVATE_SUFFIX, EXTENSION_GIF);
-static BMP: Uti = Uti::new(UTI_BMP, COMPACT_UTI_BMP, PICTURE_DERIVATE_SUFFIX, EXTENSION_BMP);
-static DNG: Uti = Uti::new(UTI_DNG, COMPACT_UTI_DNG, PICTURE_DERIVATE_SUFFIX, EXTENSION_DNG);
-static RAF: Uti = Uti::new(UTI_RAF, COMPACT_UTI_RAF, PICTURE_DERIVATE_SUFFIX, EXTENSION_RAF);
-static MP4: Uti = Uti::new(UTI_MP4, COMPACT_UTI_MP4, VIDEO_DERIVATE_SUFFIX, EXTENSION_MP4);
-static MOV: Uti = Uti::new(UTI_MOV, COMPACT_UTI_MOV, VIDEO_DERIVATE_SUFFIX, EXTENSION_MOV);
-
-#[derive(PartialEq)]
+static HEIC: Uti = Uti::new(UTI_HEIC, COMPACT_UTI_HEIC, PICTURE_DERIVATE_SUFFIX, EXTENSION_HEIC, Photo);
+static JPEG: Uti = Uti::new(UTI_JPEG, COMPACT_UTI_JPEG, PICTURE_DERIVATE_SUFFIX, EXTENSION_JPEG, Photo);
+static JPG: Uti = Uti::new(UTI_JPEG, COMPACT_UTI_JPEG, PICTURE_DERIVATE_SUFFIX, EXTENSION_JPG, Photo);
+static PNG: Uti = Uti::new(UTI_PNG, COMPACT_UTI_PNG, PICTURE_DERIVATE_SUFFIX, EXTENSION_PNG, Photo);
+static GIF: Uti = Uti::new(UTI_GIF, COMPACT_UTI_GIF, PICTURE_DERIVATE_SUFFIX, EXTENSION_GIF, Photo);
+static BMP: Uti = Uti::new(UTI_BMP, COMPACT_UTI_BMP, PICTURE_DERIVATE_SUFFIX, EXTENSION_BMP, Photo);
+static DNG: Uti = Uti::new(UTI_DNG, COMPACT_UTI_DNG, PICTURE_DERIVATE_SUFFIX, EXTENSION_DNG, Photo);
+static RAF: Uti = Uti::new(UTI_RAF, COMPACT_UTI_RAF, PICTURE_DERIVATE_SUFFIX, EXTENSION_RAF, Photo);
+static MP4: Uti = Uti::new(UTI_MP4, COMPACT_UTI_MP4, VIDEO_DERIVATE_SUFFIX, EXTENSION_MP4, Video);
+static MOV: Uti = Uti::new(UTI_MOV, COMPACT_UTI_MOV, VIDEO_DERIVATE_SUFFIX, EXTENSION_MOV, Video);
+
+/// General class of media an [Uti] belongs to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaType {
+    Photo,
+    Video,
+}
+
+#[derive(PartialEq, Debug)]
 pub struct Uti {
     pub uti: &'static str,
     pub compact_uti: &'static str,
     pub uuid_suffix: &'static str,
     pub extension: &'static str,
+    pub media_type: MediaType,
 }
 
 impl Uti {
@@ -60,8 +70,25 @@ impl Uti {
         compact_uti: &'static str,
         uuid_suffix: &'static str,
         extension: &'static str,
+        media_type: MediaType,
     ) -> Self {
-        Self { uti, compact_uti, uuid_suffix, extension }
+        Self { uti, compact_uti, uuid_suffix, extension, media_type }
+    }
+
+    /// Returns the UTI of Adobe's Digital Negative, the open format proprietary RAW files can be
+    /// converted to.
+    pub fn dng() -> &'static Uti {
+        &DNG
+    }
+
+    /// Returns whether this is a camera manufacturer's proprietary RAW format.
+    pub fn is_proprietary_raw(&self) -> bool {
+        self.uti == UTI_RAF
+    }
+
+    /// Returns whether this is a RAW format, either proprietary or DNG.
+    pub fn is_raw(&self) -> bool {
+        self.is_proprietary_raw() || self.uti == UTI_DNG
     }
 
     pub fn from_name(name: &str) -> Result<&'static Uti, String> {
@@ -97,7 +124,7 @@ impl Uti {
         }
     }
 
-    pub fn from_filename(filename: &String) -> Result<&'static Uti, String> {
+    pub fn from_filename(filename: &str) -> Result<&'static Uti, String> {
         let extension = Self::extension_from_filename(filename)?;
 
         match extension {
@@ -114,10 +141,56 @@ impl Uti {
         }
     }
 
+    /// Returns the UTI of the given file extension, ignoring case and a leading dot.
+    pub fn from_extension(extension: &str) -> Result<&'static Uti, String> {
+        match extension.trim_start_matches('.').to_lowercase().as_str() {
+            EXTENSION_HEIC => Ok(&HEIC),
+            EXTENSION_JPEG => Ok(&JPEG),
+            EXTENSION_JPG => Ok(&JPG),
+            EXTENSION_PNG => Ok(&PNG),
+            EXTENSION_GIF => Ok(&GIF),
+            EXTENSION_BMP => Ok(&BMP),
+            EXTENSION_DNG => Ok(&DNG),
+            EXTENSION_RAF => Ok(&RAF),
+            EXTENSION_MP4 => Ok(&MP4),
+            EXTENSION_MOV => Ok(&MOV),
+            _ => Err(format!("Unknown extension: {}", extension))
+        }
+    }
+
     fn extension_from_filename(filename: &str) -> Result<&str, String> {
         filename
             .split('.')
-            .last()
+            .next_back()
             .ok_or(format!("File {} seems to have no extension!", filename))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_extension_of_compact_jpeg_utis() {
+        assert_eq!(Uti::from_compact_and_filename("1", "A.jpeg").unwrap().extension, "jpeg");
+        assert_eq!(Uti::from_compact_and_filename("1", "A.jpg").unwrap().extension, "jpg");
+    }
+
+    #[test]
+    fn rejects_unknown_compact_utis() {
+        assert!(Uti::from_compact_and_filename("999", "A.jpeg").is_err());
+    }
+
+    #[test]
+    fn resolves_extensions_ignoring_case() {
+        assert_eq!(Uti::from_extension("JPG").unwrap().uti, UTI_JPEG);
+        assert_eq!(Uti::from_extension(".raf").unwrap().uti, UTI_RAF);
+        assert!(Uti::from_extension("txt").is_err());
+    }
+
+    #[test]
+    fn falls_back_to_extension() {
+        assert_eq!(Uti::from_filename("A.heic").unwrap().uti, UTI_HEIC);
+        assert_eq!(Uti::from_filename("A.mov").unwrap().media_type, Video);
+    }
+}
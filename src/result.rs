@@ -1,3 +1,7 @@
+use std::fmt::{Display, Formatter};
+
+use crate::model::asset::ExportAsset;
+
 #[derive(Debug)]
 pub struct PhotosExportError {
     pub messages: Vec<String>,
@@ -20,4 +24,67 @@ where
     fn from(error: E) -> Self {
         PhotosExportError { messages: vec![error.to_string()] }
     }
+}
+
+
+/// Step of the export during which an error concerning a single asset occurred
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssetErrorStep {
+    /// Reading the asset from the database, e.g. resolving its UTI or parsing its timestamps
+    Loading,
+    /// Building the source and output paths of the files to export
+    Planning,
+    DuplicateDetection,
+    Copying,
+}
+
+impl Display for AssetErrorStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            AssetErrorStep::Loading => "loading",
+            AssetErrorStep::Planning => "planning the files of",
+            AssetErrorStep::DuplicateDetection => "checking for duplicates of",
+            AssetErrorStep::Copying => "copying",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// Error concerning a single asset, identifying the asset by its UUID and original filename so
+/// that the asset can be found in Photos.
+#[derive(Debug, PartialEq)]
+pub struct AssetError {
+    pub uuid: String,
+    pub original_filename: String,
+    pub step: AssetErrorStep,
+    pub source: String,
+}
+
+impl AssetError {
+
+    pub fn new(uuid: &str, original_filename: &str, step: AssetErrorStep, source: impl ToString) -> Self {
+        AssetError {
+            uuid: uuid.to_string(),
+            original_filename: original_filename.to_string(),
+            step,
+            source: source.to_string(),
+        }
+    }
+
+    pub fn of(asset: &ExportAsset, step: AssetErrorStep, source: impl ToString) -> Self {
+        AssetError::new(&asset.uuid, &asset.original_filename, step, source)
+    }
+}
+
+impl Display for AssetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Error while {} asset {} ({}): {}",
+            self.step,
+            self.uuid,
+            self.original_filename,
+            self.source
+        )
+    }
 }
\ No newline at end of file
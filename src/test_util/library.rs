@@ -0,0 +1,595 @@
+use std::fs::{create_dir_all, write};
+use std::path::{Path, PathBuf};
+
+use diesel::connection::SimpleConnection;
+use diesel::{Connection, SqliteConnection};
+use tempfile::TempDir;
+
+use crate::db::repo::asset::{AlbumFilter, AssetRepository, HiddenAssetsFilter};
+use crate::export::copying::{AbsolutePathBuildingCopyOperationFactoryDecorator, CopyOperation, CopyOperationFactory};
+use crate::model::asset::ExportAsset;
+use crate::model::FromDbModel;
+
+/// Model version written to the metadata table of synthetic libraries.
+pub const SUPPORTED_MODEL_VERSION: u64 = 18100;
+
+/// Id of the root album every synthetic library contains.
+pub const ROOT_ALBUM_ID: i32 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE Z_METADATA (Z_VERSION INTEGER PRIMARY KEY, Z_PLIST BLOB);
+    CREATE TABLE ZGENERICALBUM (
+        Z_PK INTEGER PRIMARY KEY, ZUUID VARCHAR, ZKIND INTEGER, ZPARENTFOLDER INTEGER,
+        ZTITLE VARCHAR, ZSTARTDATE TIMESTAMP, ZTRASHEDSTATE INTEGER
+    );
+    CREATE TABLE ZASSET (
+        Z_PK INTEGER PRIMARY KEY, ZUUID VARCHAR, ZDIRECTORY VARCHAR, ZFILENAME VARCHAR,
+        ZUNIFORMTYPEIDENTIFIER VARCHAR, ZDATECREATED TIMESTAMP, ZHIDDEN INTEGER,
+        ZFAVORITE INTEGER, ZTRASHEDSTATE INTEGER, ZVISIBILITYSTATE INTEGER,
+        ZDUPLICATEASSETVISIBILITYSTATE INTEGER, ZADJUSTMENTSSTATE INTEGER, ZKINDSUBTYPE INTEGER,
+        ZORIENTATION INTEGER, ZLATITUDE FLOAT, ZLONGITUDE FLOAT, ZCLOUDOWNERHASHEDPERSONID VARCHAR,
+        ZWIDTH INTEGER, ZHEIGHT INTEGER, ZDURATION FLOAT
+    );
+    CREATE TABLE ZPERSON (Z_PK INTEGER PRIMARY KEY, ZFULLNAME VARCHAR, ZPERSONURI VARCHAR);
+    CREATE TABLE ZADDITIONALASSETATTRIBUTES (
+        Z_PK INTEGER PRIMARY KEY, ZASSET INTEGER, ZORIGINALFILENAME VARCHAR,
+        ZMASTERFINGERPRINT VARCHAR, ZTITLE VARCHAR, ZORIGINALFILESIZE INTEGER
+    );
+    CREATE TABLE ZASSETDESCRIPTION (
+        Z_PK INTEGER PRIMARY KEY, ZASSETATTRIBUTES INTEGER, ZLONGDESCRIPTION VARCHAR
+    );
+    CREATE TABLE ZEXTENDEDATTRIBUTES (
+        Z_PK INTEGER PRIMARY KEY, ZASSET INTEGER, ZCAMERAMAKE VARCHAR, ZCAMERAMODEL VARCHAR,
+        ZLENSMODEL VARCHAR
+    );
+    CREATE TABLE ZKEYWORD (Z_PK INTEGER PRIMARY KEY, ZTITLE VARCHAR);
+    CREATE TABLE Z_1KEYWORDS (Z_1ASSETATTRIBUTES INTEGER, Z_52KEYWORDS INTEGER);
+    CREATE TABLE ZINTERNALRESOURCE (
+        Z_PK INTEGER PRIMARY KEY, ZASSET INTEGER, ZFINGERPRINT VARCHAR,
+        ZDATASTORESUBTYPE INTEGER, ZLOCALAVAILABILITY INTEGER, ZCOMPACTUTI VARCHAR,
+        ZFILESYSTEMVOLUME INTEGER
+    );
+    CREATE TABLE ZFILESYSTEMBOOKMARK (
+        Z_PK INTEGER PRIMARY KEY, ZRESOURCEFINGERPRINT VARCHAR, ZPATHRELATIVETOVOLUME VARCHAR
+    );
+    CREATE TABLE ZFILESYSTEMVOLUME (Z_PK INTEGER PRIMARY KEY, ZNAME VARCHAR);
+    CREATE TABLE Z_30ASSETS (Z_30ALBUMS INTEGER, Z_3ASSETS INTEGER, Z_FOK_3ASSETS INTEGER);
+";
+
+/// A minimal `.photoslibrary` bundle in a temporary directory, containing a database with the
+/// tables queried by this crate and dummy files for the assets.
+///
+/// The library is deleted when dropped.
+pub struct SyntheticLibrary {
+    dir: TempDir,
+}
+
+impl SyntheticLibrary {
+
+    /// Creates an empty library containing only the root album.
+    pub fn new() -> Self {
+        Self::with_model_version(SUPPORTED_MODEL_VERSION)
+    }
+
+    pub fn with_model_version(model_version: u64) -> Self {
+        let dir = tempfile::Builder::new()
+            .suffix(".photoslibrary")
+            .tempdir()
+            .expect("Unable to create temporary library directory");
+        create_dir_all(dir.path().join("database")).unwrap();
+
+        let library = SyntheticLibrary { dir };
+
+        let mut plist = Vec::new();
+        plist::to_writer_binary(
+            &mut plist,
+            &plist::Value::Dictionary(
+                [("PLModelVersion".to_string(), plist::Value::Integer(model_version.into()))]
+                    .into_iter()
+                    .collect()
+            )
+        ).unwrap();
+
+        library.execute(SCHEMA);
+        library.execute(&format!("INSERT INTO Z_METADATA VALUES (1, X'{}')", hex(&plist)));
+        library.add_album(&TestAlbum::root());
+
+        library
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn db_path(&self) -> String {
+        self.path()
+            .join("database")
+            .join("Photos.sqlite")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    pub fn execute(&self, sql: &str) {
+        let mut conn = SqliteConnection::establish(&self.db_path()).unwrap();
+        conn.batch_execute(sql).unwrap();
+    }
+
+    pub fn add_album(&self, album: &TestAlbum) {
+        self.execute(&format!(
+            "INSERT INTO ZGENERICALBUM VALUES ({}, {}, {}, {}, {}, {}, {})",
+            album.id,
+            quote(&album.uuid),
+            album.kind,
+            album.parent_id.map_or("NULL".to_string(), |id| id.to_string()),
+            quote(&album.name),
+            album.start_date.map_or("NULL".to_string(), |d| d.to_string()),
+            album.trashed as i32,
+        ));
+    }
+
+    /// Adds the asset to the database and creates dummy files for its original and render.
+    pub fn add_asset(&self, asset: &TestAsset) {
+        self.execute(&format!(
+            "INSERT INTO ZASSET VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, 0, 0, {}, {}, {}, {}, {}, {}, {}, {}, {});
+             INSERT INTO ZADDITIONALASSETATTRIBUTES VALUES ({}, {}, {}, 'fingerprint-{}', {}, {});",
+            asset.id,
+            quote(&asset.uuid),
+            quote(&asset.dir()),
+            quote(&asset.filename()),
+            quote(asset.uti),
+            asset.date,
+            asset.hidden as i32,
+            asset.favorite as i32,
+            asset.trashed as i32,
+            asset.adjusted as i32,
+            asset.subtype,
+            asset.orientation,
+            asset.location.map_or(-180.0, |(lat, _)| lat),
+            asset.location.map_or(-180.0, |(_, lon)| lon),
+            asset.contributor.as_ref().map_or("NULL".to_string(), |_| quote(&format!("person-{}", asset.id))),
+            asset.dimensions.map_or("NULL".to_string(), |(width, _)| width.to_string()),
+            asset.dimensions.map_or("NULL".to_string(), |(_, height)| height.to_string()),
+            asset.duration,
+            asset.id,
+            asset.id,
+            quote(&asset.original_filename),
+            asset.id,
+            asset.title.as_deref().map_or("NULL".to_string(), quote),
+            asset.file_size.map_or("NULL".to_string(), |size| size.to_string()),
+        ));
+
+        if let Some(description) = &asset.description {
+            self.execute(&format!(
+                "INSERT INTO ZASSETDESCRIPTION VALUES ({}, {}, {})",
+                asset.id,
+                asset.id,
+                quote(description),
+            ));
+        }
+
+        if let Some(contributor) = &asset.contributor {
+            self.execute(&format!(
+                "INSERT INTO ZPERSON VALUES ({}, {}, 'person-{}')",
+                asset.id,
+                quote(contributor),
+                asset.id,
+            ));
+        }
+
+        if let Some((make, model)) = &asset.camera {
+            self.execute(&format!(
+                "INSERT INTO ZEXTENDEDATTRIBUTES VALUES ({}, {}, {}, {}, {})",
+                asset.id,
+                asset.id,
+                quote(make),
+                quote(model),
+                asset.lens.as_deref().map_or("NULL".to_string(), quote),
+            ));
+        }
+
+        for keyword in &asset.keywords {
+            self.execute(&format!(
+                "INSERT INTO ZKEYWORD (ZTITLE) SELECT {0} WHERE NOT EXISTS (
+                     SELECT 1 FROM ZKEYWORD WHERE ZTITLE = {0}
+                 );
+                 INSERT INTO Z_1KEYWORDS SELECT {1}, Z_PK FROM ZKEYWORD WHERE ZTITLE = {0};",
+                quote(keyword),
+                asset.id,
+            ));
+        }
+
+        if let Some(compact_uti) = asset.compact_uti {
+            self.execute(&format!(
+                "INSERT INTO ZINTERNALRESOURCE VALUES ({}, {}, 'fingerprint-{}', 1, {}, {}, {})",
+                asset.id,
+                asset.id,
+                asset.id,
+                if asset.offloaded { 0 } else { 1 },
+                quote(compact_uti),
+                if asset.referenced.is_some() { asset.id.to_string() } else { "NULL".to_string() },
+            ));
+        }
+
+        if let Some((volume, path)) = &asset.referenced {
+            self.execute(&format!(
+                "INSERT INTO ZFILESYSTEMVOLUME VALUES ({}, {});
+                 INSERT INTO ZFILESYSTEMBOOKMARK VALUES ({}, 'fingerprint-{}', {});",
+                asset.id,
+                quote(volume),
+                asset.id,
+                asset.id,
+                quote(path),
+            ));
+        }
+
+        for album_id in &asset.album_ids {
+            self.execute(&format!(
+                "INSERT INTO Z_30ASSETS VALUES ({}, {}, {})",
+                album_id,
+                asset.id,
+                asset.album_sort_order.unwrap_or(asset.id as i64 * 1024),
+            ));
+        }
+
+        if !asset.offloaded {
+            // Referenced originals are stored outside the library
+            if asset.referenced.is_none() {
+                self.write_file(&asset.original_path(), &format!("original of {}", asset.uuid));
+            }
+            if asset.adjusted {
+                self.write_file(&asset.render_path(), &format!("render of {}", asset.uuid));
+            }
+        }
+    }
+
+    /// Writes a file with the given content relative to the library root.
+    pub fn write_file(&self, relative_path: &Path, content: &str) {
+        let path = self.path().join(relative_path);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        write(path, content).unwrap();
+    }
+}
+
+pub struct TestAlbum {
+    pub id: i32,
+    pub uuid: String,
+    pub kind: i32,
+    pub parent_id: Option<i32>,
+    pub name: String,
+    pub start_date: Option<f32>,
+    pub trashed: bool,
+}
+
+impl TestAlbum {
+
+    fn root() -> Self {
+        TestAlbum {
+            id: ROOT_ALBUM_ID,
+            uuid: "ROOT-UUID".to_string(),
+            kind: 3999,
+            parent_id: None,
+            name: String::new(),
+            start_date: None,
+            trashed: false,
+        }
+    }
+
+    /// Creates a regular album in the root folder.
+    pub fn album(id: i32, name: &str) -> Self {
+        TestAlbum {
+            id,
+            uuid: format!("ALBUM-{}", id),
+            kind: 2,
+            parent_id: Some(ROOT_ALBUM_ID),
+            name: name.to_string(),
+            start_date: None,
+            trashed: false,
+        }
+    }
+
+    /// Creates a folder in the root folder.
+    pub fn folder(id: i32, name: &str) -> Self {
+        TestAlbum { kind: 4000, ..Self::album(id, name) }
+    }
+
+    pub fn in_folder(self, parent_id: i32) -> Self {
+        TestAlbum { parent_id: Some(parent_id), ..self }
+    }
+
+    pub fn starting_at(self, cocoa_seconds: f32) -> Self {
+        TestAlbum { start_date: Some(cocoa_seconds), ..self }
+    }
+}
+
+pub struct TestAsset {
+    pub id: i32,
+    pub uuid: String,
+    pub extension: String,
+    pub uti: &'static str,
+    /// `None` simulates an offline library without internal resources
+    pub compact_uti: Option<&'static str>,
+    pub original_filename: String,
+    /// Creation date in seconds since 2001-01-01
+    pub date: f32,
+    pub hidden: bool,
+    pub favorite: bool,
+    pub trashed: bool,
+    pub adjusted: bool,
+    pub offloaded: bool,
+    pub subtype: i32,
+    /// EXIF orientation of the original
+    pub orientation: i32,
+    pub location: Option<(f64, f64)>,
+    pub album_ids: Vec<i32>,
+    /// Key for the manual sort order within the albums, derived from the id if `None`
+    pub album_sort_order: Option<i64>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    /// Make and model of the camera
+    pub camera: Option<(String, String)>,
+    pub lens: Option<String>,
+    /// Name of the person who shared the asset
+    pub contributor: Option<String>,
+    /// Volume name and path relative to the volume of a referenced original
+    pub referenced: Option<(String, String)>,
+    /// Size of the original in bytes as recorded in the database
+    pub file_size: Option<i64>,
+    /// Width and height of the original in pixels
+    pub dimensions: Option<(i32, i32)>,
+    /// Duration in seconds, `0` for photos
+    pub duration: f64,
+}
+
+impl TestAsset {
+
+    /// Creates a JPEG photo taken in mid-June 2024.
+    pub fn jpeg(id: i32) -> Self {
+        TestAsset {
+            id,
+            uuid: format!("ASSET-{:04}", id),
+            extension: "jpeg".to_string(),
+            uti: "public.jpeg",
+            compact_uti: Some("1"),
+            original_filename: format!("IMG_{:04}.JPG", id),
+            date: 740_000_000.0,
+            hidden: false,
+            favorite: false,
+            trashed: false,
+            adjusted: false,
+            offloaded: false,
+            subtype: 0,
+            orientation: 1,
+            location: None,
+            album_ids: vec![],
+            album_sort_order: None,
+            title: None,
+            description: None,
+            keywords: vec![],
+            camera: None,
+            lens: None,
+            contributor: None,
+            referenced: None,
+            file_size: None,
+            dimensions: Some((4032, 3024)),
+            duration: 0.0,
+        }
+    }
+
+    /// Creates a QuickTime video taken in mid-June 2024.
+    pub fn mov(id: i32) -> Self {
+        TestAsset {
+            extension: "mov".to_string(),
+            uti: "com.apple.quicktime-movie",
+            compact_uti: Some("23"),
+            original_filename: format!("IMG_{:04}.MOV", id),
+            dimensions: Some((1920, 1080)),
+            duration: 12.5,
+            ..Self::jpeg(id)
+        }
+    }
+
+    pub fn hidden(self) -> Self {
+        TestAsset { hidden: true, ..self }
+    }
+
+    pub fn favorite(self) -> Self {
+        TestAsset { favorite: true, ..self }
+    }
+
+    pub fn trashed(self) -> Self {
+        TestAsset { trashed: true, ..self }
+    }
+
+    pub fn edited(self) -> Self {
+        TestAsset { adjusted: true, ..self }
+    }
+
+    pub fn offloaded(self) -> Self {
+        TestAsset { offloaded: true, ..self }
+    }
+
+    pub fn offline_library(self) -> Self {
+        TestAsset { compact_uti: None, ..self }
+    }
+
+    pub fn with_subtype(self, subtype: i32) -> Self {
+        TestAsset { subtype, ..self }
+    }
+
+    pub fn oriented(self, orientation: i32) -> Self {
+        TestAsset { orientation, ..self }
+    }
+
+    pub fn at(self, latitude: f64, longitude: f64) -> Self {
+        TestAsset { location: Some((latitude, longitude)), ..self }
+    }
+
+    pub fn sized(self, bytes: i64) -> Self {
+        TestAsset { file_size: Some(bytes), ..self }
+    }
+
+    pub fn measuring(self, dimensions: Option<(i32, i32)>) -> Self {
+        TestAsset { dimensions, ..self }
+    }
+
+    pub fn taken_at(self, cocoa_seconds: f32) -> Self {
+        TestAsset { date: cocoa_seconds, ..self }
+    }
+
+    pub fn in_albums(self, album_ids: &[i32]) -> Self {
+        TestAsset { album_ids: album_ids.to_vec(), ..self }
+    }
+
+    pub fn sorted_at(self, sort_order: i64) -> Self {
+        TestAsset { album_sort_order: Some(sort_order), ..self }
+    }
+
+    pub fn titled(self, title: &str) -> Self {
+        TestAsset { title: Some(title.to_string()), ..self }
+    }
+
+    pub fn described(self, description: &str) -> Self {
+        TestAsset { description: Some(description.to_string()), ..self }
+    }
+
+    pub fn tagged(self, keywords: &[&str]) -> Self {
+        TestAsset { keywords: keywords.iter().map(|k| k.to_string()).collect(), ..self }
+    }
+
+    pub fn shot_with(self, make: &str, model: &str, lens: Option<&str>) -> Self {
+        TestAsset {
+            camera: Some((make.to_string(), model.to_string())),
+            lens: lens.map(String::from),
+            ..self
+        }
+    }
+
+    pub fn referenced_at(self, volume: &str, path: &str) -> Self {
+        TestAsset { referenced: Some((volume.to_string(), path.to_string())), ..self }
+    }
+
+    pub fn shared_by(self, contributor: &str) -> Self {
+        TestAsset { contributor: Some(contributor.to_string()), ..self }
+    }
+
+    pub fn dir(&self) -> String {
+        self.uuid.chars().last().unwrap().to_string()
+    }
+
+    pub fn filename(&self) -> String {
+        format!("{}.{}", self.uuid, self.extension)
+    }
+
+    pub fn original_path(&self) -> PathBuf {
+        PathBuf::from("originals").join(self.dir()).join(self.filename())
+    }
+
+    pub fn render_path(&self) -> PathBuf {
+        let suffix = if self.extension == "mov" { "_2_0_a" } else { "_1_201_a" };
+        PathBuf::from("resources")
+            .join("renders")
+            .join(self.dir())
+            .join(format!("{}{}.{}", self.uuid, suffix, self.extension))
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+
+/// Loads the exportable assets of a repository as they would be handed to the copy operation
+/// factories.
+pub fn load_exportable(repo: &AssetRepository) -> Vec<ExportAsset> {
+    repo.get_exportable()
+        .unwrap()
+        .iter()
+        .map(|dto| ExportAsset::from_db_model(dto).unwrap())
+        .collect()
+}
+
+/// Exportable assets of a library along with the copy operations a factory has built for them.
+pub struct PlannedOperations {
+    /// Assets sorted by their id
+    pub assets: Vec<ExportAsset>,
+    /// Copy operations along with the index of their asset
+    operations: Vec<(usize, CopyOperation)>,
+}
+
+impl PlannedOperations {
+
+    /// Returns each copy operation along with its asset, as handed to the metadata writers.
+    pub fn get_all(&self) -> Vec<(&ExportAsset, &CopyOperation)> {
+        self.operations
+            .iter()
+            .map(|(index, op)| (&self.assets[*index], op))
+            .collect()
+    }
+
+    /// Returns each copy operation along with its asset, as planned by the exporter.
+    pub fn to_planned(&self) -> Vec<(&ExportAsset, CopyOperation)> {
+        self.operations
+            .iter()
+            .map(|(index, op)| (&self.assets[*index], op.clone()))
+            .collect()
+    }
+}
+
+/// Loads all exportable assets of a library, including hidden ones, and builds their copy
+/// operations using the given factory, with the paths resolved against the library and the
+/// output directory.
+pub fn load_operations(
+    library: &SyntheticLibrary,
+    output_dir: &Path,
+    factory: Box<dyn CopyOperationFactory>
+) -> PlannedOperations {
+    let assets = load_exportable(&unfiltered_repo(library));
+    build_operations(library, assets, output_dir, factory)
+}
+
+/// Like [load_operations], but for the assets that are not exported as their originals are
+/// offloaded to iCloud.
+pub fn load_offloaded_operations(
+    library: &SyntheticLibrary,
+    output_dir: &Path,
+    factory: Box<dyn CopyOperationFactory>
+) -> PlannedOperations {
+    let assets = unfiltered_repo(library)
+        .get_offloaded()
+        .unwrap()
+        .iter()
+        .map(|dto| ExportAsset::from_db_model(dto).unwrap())
+        .collect();
+    build_operations(library, assets, output_dir, factory)
+}
+
+fn unfiltered_repo(library: &SyntheticLibrary) -> AssetRepository {
+    AssetRepository::new(library.db_path(), HiddenAssetsFilter::Include, AlbumFilter::None, vec![], None)
+}
+
+fn build_operations(
+    library: &SyntheticLibrary,
+    mut assets: Vec<ExportAsset>,
+    output_dir: &Path,
+    factory: Box<dyn CopyOperationFactory>
+) -> PlannedOperations {
+    assets.sort_by_key(|a| a.id);
+
+    let factory = AbsolutePathBuildingCopyOperationFactoryDecorator::new(
+        library.path().to_path_buf(),
+        output_dir.to_path_buf(),
+        factory
+    );
+    let operations = assets
+        .iter()
+        .enumerate()
+        .flat_map(|(index, asset)| factory.build(asset).unwrap().into_iter().map(move |op| (index, op)))
+        .collect();
+
+    PlannedOperations { assets, operations }
+}
@@ -0,0 +1,3 @@
+//! Utilities shared by the tests of this crate.
+
+pub mod library;
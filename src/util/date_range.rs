@@ -0,0 +1,67 @@
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// Range of days including both its first and last day, e.g. to exclude the assets taken within
+/// it from an export.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DateRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl DateRange {
+
+    pub fn contains(&self, datetime: &NaiveDateTime) -> bool {
+        (self.start..=self.end).contains(&datetime.date())
+    }
+}
+
+impl FromStr for DateRange {
+    type Err = String;
+
+    /// Parses a range like `2024-03-01..2024-03-07`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid date range '{}', expected e.g. 2024-03-01..2024-03-07", s);
+
+        let (start, end) = s.split_once("..").ok_or_else(invalid)?;
+        let start = NaiveDate::from_str(start.trim()).map_err(|_| invalid())?;
+        let end = NaiveDate::from_str(end.trim()).map_err(|_| invalid())?;
+        if start > end {
+            return Err(format!("Invalid date range '{}', the start must not be after the end", s));
+        }
+
+        Ok(DateRange { start, end })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn parses_date_ranges() {
+        assert_eq!(
+            "2024-03-01..2024-03-07".parse(),
+            Ok(DateRange { start: date("2024-03-01"), end: date("2024-03-07") })
+        );
+        assert!("2024-03-01".parse::<DateRange>().is_err());
+        assert!("2024-03-07..2024-03-01".parse::<DateRange>().is_err());
+        assert!("2024-03-01..tomorrow".parse::<DateRange>().is_err());
+    }
+
+    #[test]
+    fn includes_first_and_last_day() {
+        let range: DateRange = "2024-03-01..2024-03-07".parse().unwrap();
+
+        assert!(range.contains(&date("2024-03-01").and_hms_opt(0, 0, 0).unwrap()));
+        assert!(range.contains(&date("2024-03-07").and_hms_opt(23, 59, 59).unwrap()));
+        assert!(!range.contains(&date("2024-02-29").and_hms_opt(23, 59, 59).unwrap()));
+        assert!(!range.contains(&date("2024-03-08").and_hms_opt(0, 0, 0).unwrap()));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+use std::path::{Path, PathBuf};
+
+const APP_DIR_NAME: &str = "apple-photos-export";
+
+/// Returns the directory for cached data, e.g. query results.
+pub fn cache_dir() -> Option<PathBuf> {
+    user_dir("Caches", "XDG_CACHE_HOME", Path::new(".cache"))
+}
+
+/// Returns the directory for state persisted between runs, e.g. the settings of the last export.
+pub fn state_dir() -> Option<PathBuf> {
+    user_dir("Application Support", "XDG_STATE_HOME", &Path::new(".local").join("state"))
+}
+
+/// Returns the application's subdirectory of a per-user base directory. On macOS, this is the
+/// given directory within `~/Library`. On all other platforms, e.g. when exporting a copied
+/// library on a Linux NAS, this is the XDG base directory of the given variable, falling back to
+/// the given default relative to the home directory.
+fn user_dir(macos_dir: &str, xdg_variable: &str, xdg_default: &Path) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+
+    let base = if cfg!(target_os = "macos") {
+        home?.join("Library").join(macos_dir)
+    } else {
+        match std::env::var_os(xdg_variable) {
+            Some(dir) => PathBuf::from(dir),
+            None => home?.join(xdg_default),
+        }
+    };
+
+    Some(base.join(APP_DIR_NAME))
+}
\ No newline at end of file
@@ -0,0 +1,65 @@
+use chrono::TimeDelta;
+
+/// Argument parser for human-friendly durations consisting of a number and a unit, e.g. `90m`,
+/// `24h`, `7d` or `3w`.
+pub fn parse_duration(duration: &str) -> Result<TimeDelta, String> {
+    let invalid = || {
+        format!(
+            "'{}' is not a valid duration, expected a number followed by m (minutes), h (hours), d (days) or w (weeks)",
+            duration
+        )
+    };
+
+    let unit_index = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = duration.split_at(unit_index);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+
+    match unit {
+        "m" => TimeDelta::try_minutes(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        "w" => TimeDelta::try_weeks(amount),
+        _ => None,
+    }.ok_or_else(invalid)
+}
+
+/// Formats the given duration in hours, minutes and seconds, e.g. `2h 5m 0s`.
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let seconds = duration.as_secs();
+    match (seconds / 3600, seconds % 3600 / 60, seconds % 60) {
+        (0, 0, seconds) => format!("{}s", seconds),
+        (0, minutes, seconds) => format!("{}m {}s", minutes, seconds),
+        (hours, minutes, seconds) => format!("{}h {}m {}s", hours, minutes, seconds),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90m"), Ok(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("24h"), Ok(TimeDelta::hours(24)));
+        assert_eq!(parse_duration("7d"), Ok(TimeDelta::days(7)));
+        assert_eq!(parse_duration("3w"), Ok(TimeDelta::weeks(3)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("-7d").is_err());
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(std::time::Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(std::time::Duration::from_secs(90)), "1m 30s");
+        assert_eq!(format_duration(std::time::Duration::from_secs(7500)), "2h 5m 0s");
+    }
+}
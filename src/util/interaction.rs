@@ -0,0 +1,152 @@
+use std::io::{stdin, stdout, BufRead, IsTerminal, Write};
+
+use colored::Colorize;
+use derive_new::new;
+
+use crate::util::interaction::Answer::{No, Yes};
+
+const CHARACTER_YES: char = 'y';
+const CHARACTER_NO: char = 'n';
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Answer {
+    Yes,
+    No
+}
+
+/// Communication with the user running an export, so that prompts and messages can be answered
+/// and shown without a terminal, e.g. in tests or when embedding the exporter.
+pub trait UserInteraction {
+
+    /// Asks the given yes-no question, returning the answer.
+    fn prompt_yes_no(&self, prompt: &str) -> Answer;
+
+    /// Shows the given message as a warning.
+    fn warn(&self, message: &str);
+
+    /// Shows the given message as an error.
+    fn error(&self, message: &str);
+
+    /// Shows the given message, e.g. the progress of the export or a note.
+    fn info(&self, message: &str);
+
+    /// Returns whether prompts are answered by the user rather than automatically.
+    fn is_interactive(&self) -> bool;
+}
+
+/// Prompts on and reads answers from the terminal.
+pub struct TerminalInteraction;
+
+impl UserInteraction for TerminalInteraction {
+    fn prompt_yes_no(&self, prompt: &str) -> Answer {
+        read_answer(prompt, &mut stdin().lock(), &mut stdout())
+    }
+
+    fn warn(&self, message: &str) {
+        println!("{}", with_prefix(&"Warning:".yellow().to_string(), message));
+    }
+
+    fn error(&self, message: &str) {
+        eprintln!("{}", with_prefix(&"Error:".red().to_string(), message));
+    }
+
+    fn info(&self, message: &str) {
+        println!("{}", message);
+    }
+
+    fn is_interactive(&self) -> bool {
+        stdin().is_terminal()
+    }
+}
+
+/// Answers all prompts with the given answer without waiting for input, e.g. when running from
+/// scripts. Messages are still printed.
+#[derive(new)]
+pub struct NonInteractive {
+    answer: Answer,
+}
+
+impl UserInteraction for NonInteractive {
+    fn prompt_yes_no(&self, prompt: &str) -> Answer {
+        let answer = match self.answer {
+            Yes => "y (answered automatically)",
+            No => "n (answered automatically)",
+        };
+        println!("{} {}", prompt, answer.dimmed());
+        self.answer
+    }
+
+    fn warn(&self, message: &str) {
+        TerminalInteraction.warn(message);
+    }
+
+    fn error(&self, message: &str) {
+        TerminalInteraction.error(message);
+    }
+
+    fn info(&self, message: &str) {
+        TerminalInteraction.info(message);
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Inserts the given prefix after the leading line breaks of the given message, which separate it
+/// from previous output.
+fn with_prefix(prefix: &str, message: &str) -> String {
+    let body = message.trim_start_matches('\n');
+    format!("{}{} {}", &message[..message.len() - body.len()], prefix, body)
+}
+
+/// Asks the given question until it is answered with either yes or no. Input ending without an
+/// answer counts as no.
+fn read_answer(prompt: &str, input: &mut impl BufRead, output: &mut impl Write) -> Answer {
+    let mut line = String::new();
+
+    while line.len() != 1 || !matches!(line.chars().next(), Some(CHARACTER_YES) | Some(CHARACTER_NO)) {
+        line.clear();
+
+        let _ = write!(output, "{} [{}{}] ", prompt, CHARACTER_YES, CHARACTER_NO);
+        let _ = output.flush();
+        if input.read_line(&mut line).expect("Unable to read user input") == 0 {
+            return No;
+        }
+
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+
+    match line.chars().next_back() {
+        Some(CHARACTER_YES) => Yes,
+        Some(CHARACTER_NO) => No,
+        _ => unreachable!()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asks_until_answered_with_yes_or_no() {
+        let mut output = Vec::new();
+
+        assert_eq!(read_answer("Export?", &mut "maybe\n\ny\n".as_bytes(), &mut output), Yes);
+        assert_eq!(String::from_utf8(output).unwrap(), "Export? [yn] ".repeat(3));
+        assert_eq!(read_answer("Export?", &mut "n\r\n".as_bytes(), &mut Vec::new()), No);
+        assert_eq!(read_answer("Export?", &mut "yes\n".as_bytes(), &mut Vec::new()), No);
+    }
+
+    #[test]
+    fn prefixes_messages_after_leading_line_breaks() {
+        assert_eq!(with_prefix("Warning:", "Disk full"), "Warning: Disk full");
+        assert_eq!(with_prefix("Warning:", "\nDisk full"), "\nWarning: Disk full");
+    }
+}
\ No newline at end of file
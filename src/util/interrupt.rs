@@ -0,0 +1,22 @@
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Exit code of processes terminated by SIGINT
+const SIGINT_EXIT_CODE: i32 = 130;
+
+/// Installs a Ctrl-C handler that only marks the process as interrupted, allowing long-running
+/// operations to stop at a safe point. A second Ctrl-C terminates the process immediately.
+pub fn install_interrupt_handler() -> Result<(), String> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            exit(SIGINT_EXIT_CODE);
+        }
+        eprintln!("\nInterrupted, finishing the current file. Press Ctrl-C again to abort immediately.");
+    }).map_err(|e| format!("Unable to install Ctrl-C handler: {}", e))
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
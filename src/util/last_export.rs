@@ -0,0 +1,102 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::checksum::fnv1a_64;
+use crate::util::dirs::state_dir;
+
+pub const REPEAT_LAST_FLAG: &str = "--repeat-last";
+
+/// Arguments of an export, along with the directory it has been started in, so that relative
+/// paths are resolved the same way when repeating it.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LastExport {
+    pub working_dir: PathBuf,
+    pub args: Vec<String>,
+}
+
+/// Stores the arguments of the last export of each library in a TOML file per library.
+pub struct LastExportStore {
+    dir: PathBuf,
+}
+
+impl LastExportStore {
+
+    pub fn new(dir: PathBuf) -> Self {
+        LastExportStore { dir }
+    }
+
+    /// Returns the store in the user's state directory, or `None` if it can not be determined.
+    pub fn in_state_dir() -> Option<Self> {
+        state_dir().map(LastExportStore::new)
+    }
+
+    pub fn save(&self, library_path: &str, export: &LastExport) -> Result<(), String> {
+        let path = self.file(library_path);
+        let content = toml::to_string(export)
+            .map_err(|e| format!("Unable to serialize the export settings: {}", e))?;
+
+        create_dir_all(&self.dir)
+            .map_err(|e| format!("Unable to create '{}': {}", self.dir.to_string_lossy(), e))?;
+        write(&path, content)
+            .map_err(|e| format!("Unable to write '{}': {}", path.to_string_lossy(), e))
+    }
+
+    pub fn load(&self, library_path: &str) -> Result<LastExport, String> {
+        let path = self.file(library_path);
+        if !path.is_file() {
+            return Err(format!("No previous export of '{}' found", library_path));
+        }
+
+        let content = read_to_string(&path)
+            .map_err(|e| format!("Unable to read '{}': {}", path.to_string_lossy(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Unable to parse '{}': {}", path.to_string_lossy(), e))
+    }
+
+    fn file(&self, library_path: &str) -> PathBuf {
+        let canonical_path = std::fs::canonicalize(library_path).unwrap_or(PathBuf::from(library_path));
+        let hash = fnv1a_64(canonical_path.as_os_str().as_encoded_bytes());
+
+        self.dir.join(format!("{:016x}.toml", hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::library::SyntheticLibrary;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn stores_last_export_per_library() {
+        let library = SyntheticLibrary::new();
+        let other = SyntheticLibrary::new();
+        let state = tempfile::tempdir().unwrap();
+        let store = LastExportStore::new(state.path().to_path_buf());
+        let library_path = library.path().to_string_lossy().to_string();
+        let export = LastExport {
+            working_dir: PathBuf::from("/Users/jane"),
+            args: args(&["export", &library_path, "out", "-a"]),
+        };
+
+        store.save(&library_path, &export).unwrap();
+
+        assert_eq!(store.load(&library_path), Ok(export));
+        assert!(store.load(&other.path().to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn names_files_by_a_hash_that_is_stable_across_builds() {
+        let store = LastExportStore::new(PathBuf::from("/state"));
+
+        assert_eq!(
+            store.file("/Volumes/Photos/Missing.photoslibrary"),
+            PathBuf::from("/state/ae23c78f22db951a.toml")
+        );
+    }
+}
\ No newline at end of file
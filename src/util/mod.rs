@@ -1 +1,9 @@
-pub mod confirmation;
\ No newline at end of file
+pub mod checksum;
+pub mod date_range;
+pub mod dirs;
+pub mod duration;
+pub mod interaction;
+pub mod interrupt;
+pub mod paths;
+pub mod last_export;
+pub mod xattr;
\ No newline at end of file
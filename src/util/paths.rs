@@ -0,0 +1,411 @@
+use std::collections::BTreeSet;
+use std::fs::{create_dir_all, read_dir, remove_file, set_permissions, File, OpenOptions, Permissions};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{absolute, Path, PathBuf};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+
+/// Expands a leading `~` to the home directory of the current user and resolves relative paths
+/// against the current working directory.
+pub fn normalize_path(path: &str) -> Result<PathBuf, String> {
+    let expanded = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let home = std::env::var_os("HOME")
+                .ok_or(format!("Unable to expand '{}': HOME is not set", path))?;
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        },
+        _ => PathBuf::from(path),
+    };
+
+    absolute(&expanded)
+        .map_err(|e| format!("Unable to resolve '{}': {}", path, e))
+}
+
+/// Checks that the given path points to a Photos library bundle.
+pub fn validate_library_path(path: &Path) -> Result<(), String> {
+    let display = path.to_string_lossy();
+
+    if !path.exists() {
+        return Err(format!("Library not found: '{}' does not exist", display));
+    }
+    if !path.is_dir() {
+        return Err(format!("Not a Photos library: '{}' is not a directory", display));
+    }
+    if !path.join("database").join("Photos.sqlite").is_file() {
+        return Err(
+            format!("Not a Photos library: missing database/Photos.sqlite in '{}'", display)
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks that the given path can be used as output directory, i.e. that it either does not
+/// exist yet or is a directory.
+pub fn validate_output_path(path: &Path) -> Result<(), String> {
+    if path.exists() && !path.is_dir() {
+        return Err(
+            format!("Invalid output directory: '{}' is not a directory", path.to_string_lossy())
+        );
+    }
+
+    Ok(())
+}
+
+/// Name of the file written to the output directory to check that it is writable
+const WRITE_PROBE_FILENAME: &str = ".apple-photos-export-write-probe";
+
+/// Checks that the output directory exists, creating it first if requested, and that files can be
+/// written to it, so that an unusable output directory is reported once before the export starts.
+///
+/// During a dry run, the directory is neither created nor written to. Instead, a missing directory
+/// is only checked to be creatable, i.e. that its closest existing ancestor is a directory.
+pub fn prepare_output_dir(path: &Path, create: bool, dry_run: bool) -> Result<(), String> {
+    let display = path.to_string_lossy();
+
+    if !path.exists() {
+        if !create {
+            return Err(
+                format!(
+                    "Output directory '{}' does not exist. Pass --create-output-dir to create it.",
+                    display
+                )
+            );
+        }
+        if dry_run {
+            return match path.ancestors().find(|ancestor| ancestor.exists()) {
+                Some(ancestor) if !ancestor.is_dir() => Err(
+                    format!(
+                        "Unable to create output directory '{}': '{}' is not a directory",
+                        display,
+                        ancestor.to_string_lossy()
+                    )
+                ),
+                _ => Ok(()),
+            };
+        }
+        create_dir_all(path)
+            .map_err(|e| format!("Unable to create output directory '{}': {}", display, e))?;
+    }
+
+    validate_output_path(path)?;
+    if dry_run {
+        return Ok(());
+    }
+
+    let probe = path.join(WRITE_PROBE_FILENAME);
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .map_err(|e| format!("Output directory '{}' is not writable: {}", display, e))?;
+    remove_file(&probe)
+        .map_err(|e| format!("Unable to remove '{}': {}", probe.to_string_lossy(), e))
+}
+
+/// Finds the directories below the given directory that have contained one of the given files,
+/// relative to it, but are empty now or only contain other such directories, e.g. album folders
+/// left behind after their files have been removed. Other empty directories, e.g. ones created by
+/// the user, and the given directory itself are kept.
+///
+/// Returns the paths of the directories deepest first, so that they can be removed in order.
+pub fn find_prunable_dirs(dir: &Path, previous_files: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let candidates: BTreeSet<&Path> = previous_files
+        .iter()
+        .flat_map(|file| file.ancestors().skip(1))
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .collect();
+
+    let mut prunable = Vec::new();
+    // Deepest first, so that parents only containing prunable children are pruned as well
+    for candidate in candidates.iter().rev() {
+        let path = dir.join(candidate);
+        if !path.is_dir() {
+            continue;
+        }
+
+        let entries = read_dir(&path)
+            .map_err(|e| format!("Unable to read '{}': {}", path.to_string_lossy(), e))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()
+            .map_err(|e| format!("Unable to read '{}': {}", path.to_string_lossy(), e))?;
+        if entries.iter().all(|entry| prunable.contains(entry)) {
+            prunable.push(path);
+        }
+    }
+
+    Ok(prunable)
+}
+
+/// Creates the given directory and its missing parents, setting the given mode on each directory
+/// created. Existing directories are left untouched.
+///
+/// Returns the number of created directories.
+pub fn create_dirs(dir: &Path, mode: Option<u32>) -> Result<usize, String> {
+    let missing: Vec<&Path> = dir
+        .ancestors()
+        .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+        .collect();
+
+    create_dir_all(dir)
+        .map_err(|e| format!("Unable to create '{}': {}", dir.to_string_lossy(), e))?;
+
+    if let Some(mode) = mode {
+        for created in &missing {
+            set_permissions(created, Permissions::from_mode(mode))
+                .map_err(|e| format!("Unable to set mode of '{}': {}", created.to_string_lossy(), e))?;
+        }
+    }
+
+    Ok(missing.len())
+}
+
+/// Sets the modification time of the given file or directory to the given local date and time.
+pub fn set_modified_time(path: &Path, datetime: NaiveDateTime) -> Result<(), String> {
+    let time = Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .ok_or(format!("{} is not a valid local time", datetime))?;
+
+    File::open(path)
+        .and_then(|file| file.set_modified(time.into()))
+        .map_err(|e| format!("Unable to set the modification time of '{}': {}", path.to_string_lossy(), e))
+}
+
+/// Resolves symbolic links in the existing part of the given path, so that paths can be compared
+/// even if their last components do not exist yet.
+fn resolve_existing_prefix(path: &Path) -> PathBuf {
+    let mut missing = Vec::new();
+    let mut existing = path;
+
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return missing.iter().rev().fold(canonical, |path, component| path.join(component));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.to_os_string());
+                existing = parent;
+            },
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Whether the given path lies inside the given library bundle or is the bundle itself.
+pub fn is_inside_library(path: &Path, library_path: &Path) -> bool {
+    resolve_existing_prefix(path).starts_with(resolve_existing_prefix(library_path))
+}
+
+/// Checks that the given output path does not lie inside any of the given libraries, which
+/// would write the exported files into the library bundle.
+pub fn validate_output_outside_libraries(output_path: &Path, library_paths: &[String]) -> Result<(), String> {
+    match library_paths.iter().find(|library| is_inside_library(output_path, Path::new(library))) {
+        Some(library) => Err(
+            format!(
+                "Invalid output path: '{}' lies inside the library '{}'",
+                output_path.to_string_lossy(),
+                library
+            )
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Argument parser for library paths, normalizing and validating the given path.
+pub fn parse_library_path(path: &str) -> Result<String, String> {
+    let path = normalize_path(path)?;
+    validate_library_path(&path)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Argument parser for output directories, normalizing and validating the given path.
+pub fn parse_output_path(path: &str) -> Result<String, String> {
+    let path = normalize_path(path)?;
+    validate_output_path(&path)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Separators of path components on Unix-like systems and Windows. Both are treated as separators
+/// in user-provided names and templates, as exports may be copied to either kind of file system.
+pub const PATH_SEPARATORS: [char; 2] = ['/', '\\'];
+
+/// Turns the given name, e.g. of an album or keyword, into a single path component by replacing
+/// both Unix and Windows path separators with dashes, e.g. `AC/DC` and `AC\DC` become `AC-DC`.
+pub fn to_folder_name(name: &str) -> String {
+    name.replace(PATH_SEPARATORS, "-")
+}
+
+/// Argument parser for the names of subfolders created in output folders, which must consist of a
+/// single path component.
+pub fn parse_subfolder_name(name: &str) -> Result<String, String> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(PATH_SEPARATORS) {
+        Err(format!("'{}' is not a valid folder name", name))
+    } else {
+        Ok(name.to_string())
+    }
+}
+
+/// Argument parser for octal file modes, e.g. `644` or `0755`.
+pub fn parse_mode(mode: &str) -> Result<u32, String> {
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or(format!("'{}' is not a valid octal file mode", mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::library::SyntheticLibrary;
+
+    #[test]
+    fn expands_home_directory() {
+        let home = PathBuf::from(std::env::var_os("HOME").unwrap());
+
+        assert_eq!(normalize_path("~").unwrap(), home);
+        assert_eq!(normalize_path("~/My Pictures").unwrap(), home.join("My Pictures"));
+    }
+
+    #[test]
+    fn resolves_relative_paths() {
+        let cwd = std::env::current_dir().unwrap();
+
+        assert_eq!(normalize_path("out").unwrap(), cwd.join("out"));
+        assert_eq!(normalize_path("~other").unwrap(), cwd.join("~other"));
+        assert_eq!(normalize_path("/tmp/out").unwrap(), PathBuf::from("/tmp/out"));
+    }
+
+    #[test]
+    fn validates_library_structure() {
+        let library = SyntheticLibrary::new();
+        let empty = tempfile::tempdir().unwrap();
+
+        assert!(validate_library_path(library.path()).is_ok());
+        assert_eq!(
+            validate_library_path(empty.path()),
+            Err(
+                format!(
+                    "Not a Photos library: missing database/Photos.sqlite in '{}'",
+                    empty.path().to_string_lossy()
+                )
+            )
+        );
+        assert!(validate_library_path(&empty.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn rejects_output_directories_inside_libraries() {
+        let library = SyntheticLibrary::new();
+        let other = tempfile::tempdir().unwrap();
+        let libraries = [library.path().to_string_lossy().to_string()];
+
+        assert!(validate_output_outside_libraries(other.path(), &libraries).is_ok());
+        assert!(validate_output_outside_libraries(library.path(), &libraries).is_err());
+        assert!(
+            validate_output_outside_libraries(&library.path().join("export/new"), &libraries).is_err()
+        );
+        assert!(
+            validate_output_outside_libraries(&library.path().join("database/../../x"), &libraries)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn rejects_files_as_output_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+
+        assert!(validate_output_path(dir.path()).is_ok());
+        assert!(validate_output_path(&dir.path().join("new")).is_ok());
+        assert!(validate_output_path(&file).is_err());
+    }
+
+    #[test]
+    fn prepares_output_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("a").join("b");
+
+        assert!(prepare_output_dir(&missing, false, false).is_err());
+        assert!(prepare_output_dir(&missing, true, true).is_ok());
+        assert!(!missing.exists());
+        assert!(prepare_output_dir(&missing, true, false).is_ok());
+        assert!(missing.is_dir());
+        assert_eq!(std::fs::read_dir(&missing).unwrap().count(), 0);
+
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(prepare_output_dir(&file.join("out"), true, true).is_err());
+        assert!(prepare_output_dir(&file.join("out"), true, false).is_err());
+    }
+
+    #[test]
+    fn finds_empty_directories_to_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("Trips").join("2024").join("Summer")).unwrap();
+        std::fs::create_dir_all(dir.path().join("Family")).unwrap();
+        std::fs::create_dir_all(dir.path().join("Misc")).unwrap();
+        std::fs::write(dir.path().join("Family").join("IMG_0001.jpeg"), "").unwrap();
+
+        std::fs::create_dir_all(dir.path().join("Trips").join("Notes")).unwrap();
+        let previous_files = [
+            PathBuf::from("Trips/2024/Summer/IMG_0002.jpeg"),
+            PathBuf::from("Family/IMG_0001.jpeg"),
+            PathBuf::from("Misc/IMG_0003.jpeg"),
+            PathBuf::from("Removed/IMG_0004.jpeg"),
+        ];
+
+        let prunable = find_prunable_dirs(dir.path(), &previous_files).unwrap();
+
+        assert_eq!(
+            prunable,
+            [
+                dir.path().join("Trips").join("2024").join("Summer"),
+                dir.path().join("Trips").join("2024"),
+                dir.path().join("Misc"),
+            ]
+        );
+        // Never contained an exported file
+        assert!(!prunable.contains(&dir.path().join("Trips").join("Notes")));
+    }
+
+    #[test]
+    fn creates_missing_directories_with_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        create_dir_all(dir.path().join("Trips")).unwrap();
+        set_permissions(dir.path().join("Trips"), Permissions::from_mode(0o700)).unwrap();
+
+        assert_eq!(create_dirs(&dir.path().join("Trips/2024/Rome"), Some(0o750)), Ok(2));
+        assert_eq!(create_dirs(&dir.path().join("Trips/2024/Rome"), Some(0o750)), Ok(0));
+
+        let mode = |path: &str| std::fs::metadata(dir.path().join(path)).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode("Trips/2024"), 0o750);
+        assert_eq!(mode("Trips/2024/Rome"), 0o750);
+        assert_eq!(mode("Trips"), 0o700);
+    }
+
+    #[test]
+    fn parses_subfolder_names() {
+        assert_eq!(parse_subfolder_name("raw"), Ok("raw".to_string()));
+        assert!(parse_subfolder_name("").is_err());
+        assert!(parse_subfolder_name("..").is_err());
+        assert!(parse_subfolder_name("raw/files").is_err());
+        assert!(parse_subfolder_name("raw\\files").is_err());
+    }
+
+    #[test]
+    fn replaces_separators_in_folder_names() {
+        assert_eq!(to_folder_name("AC/DC"), "AC-DC");
+        assert_eq!(to_folder_name("AC\\DC"), "AC-DC");
+        assert_eq!(to_folder_name("Summer 2024"), "Summer 2024");
+    }
+
+    #[test]
+    fn parses_octal_modes() {
+        assert_eq!(parse_mode("644"), Ok(0o644));
+        assert_eq!(parse_mode("0755"), Ok(0o755));
+        assert!(parse_mode("888").is_err());
+        assert!(parse_mode("17777").is_err());
+    }
+}
\ No newline at end of file
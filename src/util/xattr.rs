@@ -0,0 +1,71 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Sets the extended attribute with the given name of the given file to the given value.
+pub fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    let path = to_c_string(path.as_os_str().as_bytes())?;
+    let name = to_c_string(name.as_bytes())?;
+
+    #[cfg(target_os = "macos")]
+    let result = unsafe {
+        libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0, 0)
+    };
+    #[cfg(not(target_os = "macos"))]
+    let result = unsafe {
+        libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0)
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Returns the value of the extended attribute with the given name of the given file, `None` if
+/// the file has no such attribute.
+#[cfg(test)]
+pub fn get_xattr(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let path = to_c_string(path.as_os_str().as_bytes())?;
+    let name = to_c_string(name.as_bytes())?;
+
+    let get = |buffer: &mut [u8]| {
+        #[cfg(target_os = "macos")]
+        let size = unsafe {
+            libc::getxattr(path.as_ptr(), name.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len(), 0, 0)
+        };
+        #[cfg(not(target_os = "macos"))]
+        let size = unsafe {
+            libc::getxattr(path.as_ptr(), name.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len())
+        };
+        size
+    };
+
+    // Passing an empty buffer returns the size of the value
+    let size = get(&mut []);
+    if size < 0 {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            #[cfg(target_os = "macos")]
+            Some(libc::ENOATTR) => Ok(None),
+            #[cfg(not(target_os = "macos"))]
+            Some(libc::ENODATA) => Ok(None),
+            _ => Err(error),
+        };
+    }
+
+    let mut value = vec![0; size as usize];
+    let size = get(&mut value);
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    value.truncate(size as usize);
+
+    Ok(Some(value))
+}
+
+fn to_c_string(bytes: &[u8]) -> io::Result<CString> {
+    CString::new(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
\ No newline at end of file